@@ -0,0 +1,37 @@
+//! Serial vs parallel decode of a live Kamino program account snapshot.
+//!
+//! Run with `RPC_URL=<endpoint> cargo bench --bench decode`. The accounts are
+//! fetched once up front so only the decode-and-classify pass is measured.
+
+use carbon_kamino_lending_decoder::PROGRAM_ID;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use solana_client::rpc_client::RpcClient;
+use solana_liquidation::kamino::decode_accounts;
+use solana_sdk::pubkey::Pubkey;
+
+fn bench_decode(c: &mut Criterion) {
+    let Ok(url) = std::env::var("RPC_URL") else {
+        eprintln!("RPC_URL not set, skipping decode benchmark");
+        return;
+    };
+    let accs = RpcClient::new(url).get_program_accounts(&PROGRAM_ID).expect("Failed to get Kamino program accounts");
+
+    let market: Pubkey = std::env::var("MARKET")
+        .unwrap_or_else(|_| "7u3HeHxYDLhnCoErrtycNokbQYbWGzLs6JSDqGAv5PfF".to_string())
+        .parse()
+        .expect("Invalid MARKET");
+
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut group = c.benchmark_group("decode_accounts");
+    group.sample_size(10);
+    for threads in [1, cores] {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        group.bench_with_input(BenchmarkId::new("threads", threads), &accs, |b, accs| {
+            b.iter(|| pool.install(|| decode_accounts(accs, &market)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use serde::Serialize;
+use serde_json::json;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+use tracing::{error, info, warn};
+
+use crate::kamino::LiquidationCandidate;
+
+/// Operator alerting via log and optional JSON webhook (Slack/Discord compatible).
+pub struct Alerter {
+    webhook: Option<String>,
+    http: reqwest::Client,
+}
+
+impl Alerter {
+    /// Create an alerter; without a webhook alerts are only logged.
+    pub fn new(webhook: Option<String>) -> Self {
+        Self { webhook, http: reqwest::Client::new() }
+    }
+
+    /// Emit an alert. Delivery failures are logged but never propagated.
+    pub async fn send(&self, message: &str) {
+        error!(alert = message, "ALERT");
+        self.post(json!({ "text": message, "content": message })).await;
+    }
+
+    async fn post(&self, body: serde_json::Value) {
+        let Some(url) = self.webhook.as_ref() else { return };
+        if let Err(e) = self.http.post(url).json(&body).send().await.and_then(|r| r.error_for_status()) {
+            warn!(error = %e, "Failed to deliver alert webhook");
+        }
+    }
+}
+
+/// A detected liquidation with everything needed to execute it outside the bot.
+#[derive(Debug, Serialize)]
+pub struct Opportunity {
+    pub obligation: String,
+    pub owner: String,
+    pub health: f64,
+    pub repay_reserve: String,
+    pub withdraw_reserve: String,
+    pub repay_amount: u64,
+    pub expected_profit_lamports: Option<u64>,
+    pub tip_lamports: u64,
+    /// Unsigned transactions, bincode then base64, to sign and submit in order (as a bundle
+    /// when there are several).
+    pub transactions: Vec<String>,
+    /// Keys that must sign each transaction, in signature order.
+    pub signers: Vec<Vec<String>>,
+    /// Blockhash the transactions were built on; they expire about a minute after the scan.
+    pub recent_blockhash: String,
+}
+
+impl Opportunity {
+    pub fn new(cand: &LiquidationCandidate, txs: &[VersionedTransaction], tip_lamports: u64) -> Self {
+        let encode = |tx: &VersionedTransaction| {
+            // Signatures are cleared so the signer sees exactly which slots it has to fill
+            let mut unsigned = tx.clone();
+            unsigned.signatures = vec![Signature::default(); tx.signatures.len()];
+            bincode::serialize(&unsigned)
+                .map(|b| base64::engine::general_purpose::STANDARD.encode(b))
+                .unwrap_or_default()
+        };
+        let signers = |tx: &VersionedTransaction| {
+            let required = tx.message.header().num_required_signatures as usize;
+            tx.message.static_account_keys().iter().take(required).map(Pubkey::to_string).collect()
+        };
+        Self {
+            obligation: cand.obligation.to_string(),
+            owner: cand.owner.to_string(),
+            health: cand.health,
+            repay_reserve: cand.repay_reserve.to_string(),
+            withdraw_reserve: cand.withdraw_reserve.to_string(),
+            repay_amount: cand.repay_amount,
+            expected_profit_lamports: cand.expected_profit_lamports,
+            tip_lamports,
+            transactions: txs.iter().map(encode).collect(),
+            signers: txs.iter().map(signers).collect(),
+            recent_blockhash: txs.first().map(|tx| tx.message.recent_blockhash().to_string()).unwrap_or_default(),
+        }
+    }
+}
+
+/// Posts actionable opportunities to a webhook, at most once per obligation per cooldown.
+pub struct OpportunityNotifier {
+    alerter: Alerter,
+    cooldown: Duration,
+    last_sent: HashMap<Pubkey, Instant>,
+}
+
+impl OpportunityNotifier {
+    pub fn new(webhook: String, cooldown: Duration) -> Self {
+        Self { alerter: Alerter::new(Some(webhook)), cooldown, last_sent: HashMap::new() }
+    }
+
+    /// Send the opportunity unless the obligation was notified within the cooldown.
+    pub async fn notify(&mut self, cand: &LiquidationCandidate, txs: &[VersionedTransaction], tip_lamports: u64) {
+        if self.last_sent.get(&cand.obligation).is_some_and(|t| t.elapsed() < self.cooldown) {
+            return;
+        }
+        self.last_sent.retain(|_, t| t.elapsed() < self.cooldown);
+        self.last_sent.insert(cand.obligation, Instant::now());
+
+        let opportunity = Opportunity::new(cand, txs, tip_lamports);
+        let text = format!(
+            "Liquidatable obligation {} (health {:.4}, expected profit {:?} lamports): {} transaction(s) ready to sign",
+            opportunity.obligation,
+            opportunity.health,
+            opportunity.expected_profit_lamports,
+            opportunity.transactions.len()
+        );
+        info!(obligation = %cand.obligation, transactions = txs.len(), "Sending opportunity notification");
+        self.alerter.post(json!({ "text": text, "content": text, "opportunity": opportunity })).await;
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::bundles::BundleBook;
+use crate::jito::JitoSender;
+use crate::kamino::{fetch_obligation, LiquidationCandidate};
+use crate::latency::Route;
+use crate::ratelimit::RequestClass;
+use crate::reconcile::BalanceLedger;
+use crate::rpc::Rpc;
+use crate::sender::SenderKind;
+use crate::summary::record_submission;
+use crate::tracker::SignatureTracker;
+use crate::util::{Blockhash, LiquidationTxs, TxBuilder};
+
+/// Tip escalation parameters for high-value candidates.
+#[derive(Clone, Copy, Debug)]
+pub struct AuctionConfig {
+    /// Candidates with at least this expected profit are auctioned instead of sent once.
+    pub min_profit_lamports: u64,
+    /// Tip multiplier applied each round.
+    pub tip_growth: f64,
+    /// Maximum share of expected profit that may be tipped.
+    pub max_profit_share: f64,
+    /// Maximum number of submissions per candidate.
+    pub max_rounds: u32,
+    /// Wait between rounds, roughly one slot.
+    pub round_interval: Duration,
+    /// Operator tip cap no round may exceed.
+    pub max_tip_lamports: Option<u64>,
+}
+
+impl AuctionConfig {
+    /// Whether a candidate is valuable enough to auction.
+    pub fn applies_to(&self, cand: &LiquidationCandidate) -> bool {
+        cand.expected_profit_lamports.is_some_and(|p| p >= self.min_profit_lamports)
+    }
+
+    /// Tip for the given 0-based round, capped at the profit share and the operator's tip cap.
+    pub fn tip_for_round(&self, base_tip: u64, round: u32, expected_profit: u64) -> u64 {
+        let cap = (expected_profit as f64 * self.max_profit_share) as u64;
+        let escalated = base_tip as f64 * self.tip_growth.powi(round as i32);
+        let tip = (escalated as u64).min(cap.max(base_tip));
+        self.max_tip_lamports.map_or(tip, |max| tip.min(max))
+    }
+}
+
+/// How an auction finished.
+#[derive(Debug)]
+pub enum AuctionOutcome {
+    /// One of our submissions landed.
+    Landed { round: u32, tip: u64 },
+    /// One of our submissions was included but failed.
+    Reverted { round: u32 },
+    /// Someone else repaid the obligation first.
+    Taken { rounds: u32 },
+    /// Rounds ran out without landing.
+    Exhausted { rounds: u32 },
+    /// The auction's blockhash neared expiry, so no later round could land.
+    Expired { rounds: u32 },
+}
+
+/// One submitted round, tracked on its own.
+#[derive(Clone, Debug)]
+pub struct AuctionRound {
+    pub round: u32,
+    pub signature: Signature,
+    pub bundle_id: String,
+    pub tip: u64,
+}
+
+/// Result of an auction with every round that was submitted; at most one of them lands.
+pub struct AuctionResult {
+    pub outcome: AuctionOutcome,
+    /// Reserve the rounds seize collateral from.
+    pub withdraw_reserve: Pubkey,
+    pub rounds: Vec<AuctionRound>,
+}
+
+/// A candidate's auction, signed up front so it can run off the scan loop.
+pub struct Auction {
+    pub cand: LiquidationCandidate,
+    /// Every round's tip and transactions, all on one blockhash so no round outlives it.
+    /// Rounds after the profit cap repeat the same transactions, which can only land once.
+    pub rounds: Vec<(u64, LiquidationTxs)>,
+    pub blockhash: Blockhash,
+    /// Blocks before expiry at which the auction stops.
+    pub blockhash_margin: u64,
+    /// Fees of one round's transactions.
+    pub fee_lamports: u64,
+    pub submitted_slot: Option<u64>,
+}
+
+impl Auction {
+    /// Sign every round of `cand`'s auction on `blockhash`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn build(
+        builder: &TxBuilder,
+        cfg: &AuctionConfig,
+        cand: &LiquidationCandidate,
+        blockhash: Blockhash,
+        blockhash_margin: u64,
+        prelude: Vec<Instruction>,
+        ix: Instruction,
+        base_tip: u64,
+        submitted_slot: Option<u64>,
+    ) -> Result<Self> {
+        let expected_profit = cand.expected_profit_lamports.unwrap_or(0);
+        let rounds = (0..cfg.max_rounds)
+            .map(|round| {
+                let tip = cfg.tip_for_round(base_tip, round, expected_profit);
+                let built = builder.liquidation_txs(blockhash.hash, prelude.clone(), vec![ix.clone()], tip)?;
+                Ok((tip, built))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let fee_lamports = rounds
+            .first()
+            .map_or(0, |(_, built)| built.txs.iter().map(|tx| builder.budget.fee_lamports(tx.signatures.len())).sum());
+        Ok(Self { cand: cand.clone(), rounds, blockhash, blockhash_margin, fee_lamports, submitted_slot })
+    }
+}
+
+/// Where an auction reports its rounds as they are submitted.
+pub struct AuctionSinks {
+    pub bundles: Arc<BundleBook>,
+    pub tracker: Arc<SignatureTracker>,
+    pub ledger: Option<Arc<BalanceLedger>>,
+    pub route: Route,
+}
+
+/// Auctions running in the background, at most one per obligation.
+#[derive(Default)]
+pub struct Auctions {
+    running: HashMap<Pubkey, JoinHandle<AuctionResult>>,
+}
+
+impl Auctions {
+    pub fn is_running(&self, obligation: &Pubkey) -> bool {
+        self.running.contains_key(obligation)
+    }
+
+    /// Run `auction` in the background; results are picked up with `finished`.
+    pub fn spawn(
+        &mut self,
+        auction: Auction,
+        rpc: Arc<Rpc>,
+        jito: JitoSender,
+        cfg: AuctionConfig,
+        sinks: AuctionSinks,
+    ) {
+        let obligation = auction.cand.obligation;
+        let handle = tokio::spawn(async move { run_auction(&rpc, jito, auction, &cfg, &sinks).await });
+        self.running.insert(obligation, handle);
+    }
+
+    /// Results of auctions that ended since the last call, by obligation.
+    pub async fn finished(&mut self) -> Vec<(Pubkey, AuctionResult)> {
+        let done: Vec<Pubkey> = self.running.iter().filter(|(_, h)| h.is_finished()).map(|(pk, _)| *pk).collect();
+        let mut out = Vec::with_capacity(done.len());
+        for obligation in done {
+            let Some(handle) = self.running.remove(&obligation) else { continue };
+            match handle.await {
+                Ok(result) => out.push((obligation, result)),
+                Err(e) => warn!(obligation = %obligation, error = %e, "Auction task failed"),
+            }
+        }
+        out
+    }
+}
+
+/// Submit a liquidation each round with increasing tips until it lands, is taken, its blockhash
+/// nears expiry, or rounds run out. No round is sent once an earlier one has been seen on chain,
+/// and every submitted round is tracked.
+async fn run_auction(
+    rpc: &Rpc,
+    jito: JitoSender,
+    auction: Auction,
+    cfg: &AuctionConfig,
+    sinks: &AuctionSinks,
+) -> AuctionResult {
+    let cand = &auction.cand;
+    let mut submitted: Vec<AuctionRound> = Vec::new();
+    let initial_debt = match largest_borrow_amount(rpc, &cand.obligation).await {
+        Ok(debt) => debt,
+        Err(e) => {
+            warn!(obligation = %cand.obligation, error = %e, "Failed to read obligation before auction");
+            let outcome = AuctionOutcome::Exhausted { rounds: 0 };
+            return AuctionResult { outcome, withdraw_reserve: cand.withdraw_reserve, rounds: submitted };
+        }
+    };
+
+    let outcome = 'rounds: {
+        for (round, (tip, built)) in (0..).zip(&auction.rounds) {
+            if round > 0 {
+                // Stop escalating once any earlier round is seen, so two cannot both land
+                if let Some(seen) = seen(rpc, &submitted).await {
+                    break 'rounds seen;
+                }
+                // Stop once the debt shrank without one of our transactions landing
+                match largest_borrow_amount(rpc, &cand.obligation).await {
+                    Ok(debt) if debt < initial_debt => break 'rounds AuctionOutcome::Taken { rounds: round },
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!(obligation = %cand.obligation, error = %e, "Failed to re-check obligation during auction")
+                    }
+                }
+            }
+            // A block height we cannot read counts as expiring, since rounds cannot be re-signed
+            if !matches!(auction.blockhash.is_expiring(rpc, auction.blockhash_margin).await, Ok(false)) {
+                break 'rounds AuctionOutcome::Expired { rounds: round };
+            }
+
+            match jito.send(&built.txs).await {
+                Ok(uuid) => {
+                    let signature = built.signature;
+                    info!(obligation = %cand.obligation, round, tip, jito_uuid = %uuid, "Auction bundle submitted");
+                    sinks.bundles.submitted(
+                        &uuid,
+                        &cand.obligation,
+                        &built.txs,
+                        &signature,
+                        auction.submitted_slot,
+                        *tip,
+                    );
+                    record_submission(SenderKind::Bundle, *tip, cand.expected_profit_lamports);
+                    // A repeated round resends the same signature, which is tracked already
+                    if !submitted.iter().any(|r| r.signature == signature) {
+                        if let Some(ledger) = &sinks.ledger {
+                            ledger.expect_liquidation(signature, cand, *tip, auction.fee_lamports);
+                        }
+                        sinks.tracker.spawn(
+                            cand.obligation,
+                            signature,
+                            uuid.clone(),
+                            auction.submitted_slot,
+                            sinks.route,
+                        );
+                        submitted.push(AuctionRound { round, signature, bundle_id: uuid, tip: *tip });
+                    }
+                }
+                Err(e) => warn!(obligation = %cand.obligation, round, error = %e, "Auction submission failed"),
+            }
+
+            tokio::time::sleep(cfg.round_interval).await;
+        }
+        seen(rpc, &submitted).await.unwrap_or(AuctionOutcome::Exhausted { rounds: cfg.max_rounds })
+    };
+    AuctionResult { outcome, withdraw_reserve: cand.withdraw_reserve, rounds: submitted }
+}
+
+async fn largest_borrow_amount(rpc: &Rpc, obligation: &Pubkey) -> Result<u64> {
+    let obl = fetch_obligation(rpc, obligation).await?;
+    Ok(obl.borrows.iter().map(|b| b.amount).max().unwrap_or(0))
+}
+
+/// Outcome of the first submitted round with any status, processed or later.
+async fn seen(rpc: &Rpc, submitted: &[AuctionRound]) -> Option<AuctionOutcome> {
+    if submitted.is_empty() {
+        return None;
+    }
+    rpc.throttle(RequestClass::Candidate).await;
+    let sigs: Vec<Signature> = submitted.iter().map(|r| r.signature).collect();
+    let statuses = rpc.get_signature_statuses(&sigs).ok()?.value;
+    statuses.iter().zip(submitted).find_map(|(status, round)| {
+        status.as_ref().map(|s| match s.err {
+            None => AuctionOutcome::Landed { round: round.round, tip: round.tip },
+            Some(_) => AuctionOutcome::Reverted { round: round.round },
+        })
+    })
+}
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use anyhow::{Context, Result};
+use carbon_kamino_lending_decoder::{types, KaminoLendingDecoder, PROGRAM_ID};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::health::{estimate_health, DEPOSIT_WEIGHT, PREFILTER_HEALTH_THRESHOLD};
+use crate::kamino::{
+    choose_repay_borrow, fetch_obligation, min_out_for, redeemable_repay_amount, remaining_withdrawal_cap,
+    select_candidates, DecodedAccounts, LiquidationLimits,
+};
+use crate::partial::{RESERVE_LENDING_MARKET_OFFSET, RESERVE_SIZE};
+use crate::profit::{
+    estimate_bonus_usd, estimate_withdraw_amount, protocol_fee_rate, sol_price_usd, token_price_usd, value_usd,
+};
+use crate::rpc::Rpc;
+use crate::strategy::StrategyProfile;
+use crate::swap::JupiterClient;
+use crate::util::now_millis;
+
+/// Inputs the audit needs beyond the obligation itself.
+pub struct AuditContext<'a> {
+    pub strategy: &'a StrategyProfile,
+    pub jupiter: &'a JupiterClient,
+    /// Tip floor used when the profile sets none (`--tip-lamports`).
+    pub default_tip_floor: u64,
+    /// Slippage for the collateral sale quote.
+    pub slippage_bps: u16,
+}
+
+/// Walk one obligation through the bot's decision pipeline, health to tip, and render every
+/// intermediate number so the economics can be checked by hand.
+///
+/// Every number comes from the same functions the scan loop uses, run on current chain state.
+/// Volatility multipliers and per-class minimums are not applied, since they depend on streamed
+/// prices.
+pub async fn audit(rpc: &Rpc, obligation: &Pubkey, ctx: &AuditContext<'_>) -> Result<String> {
+    let strategy = ctx.strategy;
+    let obl = fetch_obligation(rpc, obligation).await?;
+    let market = obl.lending_market;
+    let reserves = market_reserves(rpc, &market).await?;
+    let mut out = String::new();
+
+    let _ = writeln!(out, "obligation {obligation}");
+    let _ = writeln!(out, "  market   {market}");
+    let _ = writeln!(out, "  owner    {}", obl.owner);
+    if obl.referrer != Pubkey::default() {
+        let _ = writeln!(out, "  referrer {}", obl.referrer);
+    }
+
+    section(&mut out, "reserves");
+    let mut touched: Vec<Pubkey> =
+        obl.deposits.iter().map(|d| d.reserve).chain(obl.borrows.iter().map(|b| b.reserve)).collect();
+    touched.retain(|pk| *pk != Pubkey::default());
+    touched.sort();
+    touched.dedup();
+    for pk in &touched {
+        match reserves.get(pk) {
+            Some(r) => {
+                let _ = writeln!(
+                    out,
+                    "  {pk}  mint {}  price ${:.6}  decimals {}  ltv {}%  liq threshold {}%  bonus {}-{} bps  protocol fee {}%  available {}{}",
+                    r.liquidity.mint_pubkey,
+                    token_price_usd(r),
+                    r.liquidity.mint_decimals,
+                    r.config.loan_to_value_pct,
+                    r.config.liquidation_threshold_pct,
+                    r.config.min_liquidation_bonus_bps,
+                    r.config.max_liquidation_bonus_bps,
+                    r.config.protocol_liquidation_fee_pct,
+                    r.liquidity.available_amount,
+                    if strategy.is_blacklisted(pk) { "  BLACKLISTED" } else { "" },
+                );
+            }
+            None => {
+                let _ = writeln!(out, "  {pk}  not found in market, positions on it are ignored");
+            }
+        }
+    }
+
+    section(&mut out, "positions");
+    for d in obl.deposits.iter().filter(|d| d.amount > 0) {
+        let usd = reserves.get(&d.reserve).map(|r| value_usd(r, d.amount));
+        let _ = writeln!(out, "  deposit {}  amount {}  value {}", d.reserve, d.amount, usd_or_unknown(usd));
+    }
+    for b in obl.borrows.iter().filter(|b| b.amount > 0) {
+        let usd = reserves.get(&b.reserve).map(|r| value_usd(r, b.amount));
+        let _ = writeln!(out, "  borrow  {}  amount {}  value {}", b.reserve, b.amount, usd_or_unknown(usd));
+    }
+
+    // The estimator sums raw amounts; show its inputs so a mismatch with on-chain health is visible
+    section(&mut out, "health");
+    let total_deposit: f64 = obl.deposits.iter().map(|d| d.amount as f64).sum();
+    let total_borrow: f64 = obl.borrows.iter().map(|b| b.amount as f64).sum();
+    let health = estimate_health(&obl, &reserves, rpc)?;
+    let _ = writeln!(out, "  sum of deposit amounts  {total_deposit:.0}");
+    let _ = writeln!(out, "  sum of borrow amounts   {total_borrow:.0}");
+    let _ = writeln!(out, "  estimate = deposits * {DEPOSIT_WEIGHT} / borrows = {health:.6}");
+    let _ = writeln!(
+        out,
+        "  prefilter (< {PREFILTER_HEALTH_THRESHOLD}): {}   liquidatable (< 1.0): {}",
+        yes_no(health < PREFILTER_HEALTH_THRESHOLD),
+        yes_no(health < 1.0)
+    );
+
+    section(&mut out, "repay sizing");
+    let _ = writeln!(
+        out,
+        "  selection {:?}  preference {:?}  repay fraction {}",
+        strategy.repay_selection, strategy.repay_preference, strategy.repay_fraction
+    );
+    let Some((repay_reserve, borrowed)) = choose_repay_borrow(&obl, &reserves, strategy) else {
+        let _ = writeln!(out, "  no eligible borrow; the bot skips this obligation");
+        return Ok(out);
+    };
+    let requested = strategy.repay_amount(&repay_reserve, borrowed);
+    let max_repay = strategy.reserves.get(&repay_reserve).and_then(|r| r.max_repay_amount);
+    let _ = writeln!(out, "  borrow {repay_reserve}  amount {borrowed}");
+    let _ = writeln!(
+        out,
+        "  repay = min({borrowed} * {}, reserve max {}) = {requested}",
+        strategy.repay_fraction.clamp(0.0, 1.0),
+        max_repay.map_or("none".to_string(), |m| m.to_string())
+    );
+    let limits = LiquidationLimits::fetch(rpc, &market).await.ok();
+    let market_cap = limits.zip(reserves.get(&repay_reserve)).map(|(l, r)| l.max_repay_amount(r, borrowed));
+    let full_amount = requested.min(market_cap.unwrap_or(u64::MAX));
+    match limits.zip(market_cap) {
+        Some((l, cap)) => {
+            let _ = writeln!(
+                out,
+                "  market allows {cap} (close factor {}%, at most {} USD at once, full below {} USD)  repay {full_amount}",
+                l.close_factor_pct, l.max_debt_value_at_once_usd, l.min_full_liquidation_value_usd
+            );
+        }
+        None => {
+            let _ = writeln!(out, "  market liquidation limits unknown");
+        }
+    }
+
+    section(&mut out, "collateral choice");
+    for d in obl.deposits.iter().filter(|d| d.amount > 0 && d.reserve != Pubkey::default()) {
+        if strategy.is_blacklisted(&d.reserve) {
+            let _ = writeln!(out, "  {}  blacklisted", d.reserve);
+            continue;
+        }
+        let expected = estimate_withdraw_amount(&reserves, &repay_reserve, &d.reserve, full_amount);
+        let redeemable = redeemable_repay_amount(&reserves, &repay_reserve, &d.reserve, full_amount);
+        let available = reserves.get(&d.reserve).map_or(0, |r| r.liquidity.available_amount);
+        let cap = reserves.get(&d.reserve).and_then(|r| remaining_withdrawal_cap(r, now_millis() / 1_000));
+        let _ = writeln!(
+            out,
+            "  {}  deposit {}  seizes {}  available {available}  withdrawal cap left {}  supports repay {redeemable}",
+            d.reserve,
+            d.amount,
+            expected.map_or("unknown".to_string(), |e| e.to_string()),
+            cap.map_or("none".to_string(), |c| c.to_string()),
+        );
+    }
+
+    let decoded = DecodedAccounts { reserves, obligations: vec![(*obligation, obl)], limits };
+    let Some(cand) = select_candidates(&decoded, market, rpc, f64::INFINITY, strategy).into_iter().next() else {
+        let _ = writeln!(out, "  no collateral can be redeemed; the bot skips this obligation");
+        return Ok(out);
+    };
+    let reserves = &decoded.reserves;
+    let _ = writeln!(out, "  chosen {}  repay {}", cand.withdraw_reserve, cand.repay_amount);
+    if let Some(cap) = cand.repay_cap {
+        let _ =
+            writeln!(out, "  repay capped from {requested} to {cap} by market limits or withdraw reserve liquidity");
+    }
+
+    section(&mut out, "expected bonus");
+    let (Some(repay), Some(withdraw)) = (reserves.get(&cand.repay_reserve), reserves.get(&cand.withdraw_reserve))
+    else {
+        let _ = writeln!(out, "  reserve prices unknown");
+        return Ok(out);
+    };
+    let bonus_bps = withdraw.config.min_liquidation_bonus_bps;
+    let repay_usd = value_usd(repay, cand.repay_amount);
+    let gross_usd = repay_usd * f64::from(bonus_bps) / 10_000.0;
+    let fee_pct = protocol_fee_rate(withdraw) * 100.0;
+    let bonus_usd = estimate_bonus_usd(repay, withdraw, cand.repay_amount);
+    let sol_price = sol_price_usd(reserves);
+    let _ = writeln!(
+        out,
+        "  repay value   {} * ${:.6} = ${repay_usd:.4}",
+        scaled(cand.repay_amount, repay),
+        token_price_usd(repay)
+    );
+    let _ = writeln!(out, "  gross bonus   ${repay_usd:.4} * {bonus_bps} bps = ${gross_usd:.4}  (minimum bonus)");
+    let _ = writeln!(out, "  protocol fee  {fee_pct:.0}% of the bonus = ${:.4}", gross_usd - bonus_usd);
+    let _ = writeln!(out, "  bonus         ${bonus_usd:.4}");
+    let _ = writeln!(out, "  seized value  ${:.4}", repay_usd + bonus_usd);
+    let _ = writeln!(out, "  seized amount {}", amount_or_unknown(cand.expected_withdraw_amount));
+    let min_out = min_out_for(&cand, cand.repay_amount, strategy);
+    let _ = writeln!(out, "  min out       {min_out}");
+    let _ = writeln!(out, "  SOL price     {}", usd_or_unknown(sol_price));
+    let _ = writeln!(out, "  profit        {}", lamports(cand.expected_profit_lamports));
+    let _ = writeln!(out, "  seized        {}", lamports(cand.expected_seized_lamports));
+
+    section(&mut out, "swap quote");
+    match cand.expected_withdraw_amount.filter(|a| *a > 0) {
+        None => {
+            let _ = writeln!(out, "  no seized amount to quote");
+        }
+        Some(_) if withdraw.liquidity.mint_pubkey == repay.liquidity.mint_pubkey => {
+            let _ = writeln!(out, "  collateral and debt share a mint, no swap needed");
+        }
+        Some(seized) => {
+            let max_impact_bps = strategy.max_price_impact_bps(&cand.withdraw_reserve);
+            let quote = ctx
+                .jupiter
+                .best_route(
+                    &withdraw.liquidity.mint_pubkey,
+                    &repay.liquidity.mint_pubkey,
+                    seized,
+                    ctx.slippage_bps,
+                    max_impact_bps,
+                    strategy.swap_route(&cand.withdraw_reserve),
+                )
+                .await;
+            match quote {
+                Ok(Some(q)) => {
+                    let net = q.out_amount as i128 - cand.repay_amount as i128;
+                    let net_usd =
+                        net as f64 / 10f64.powi(repay.liquidity.mint_decimals as i32) * token_price_usd(repay);
+                    let _ = writeln!(
+                        out,
+                        "  sell {} -> {}  impact {} bps (limit {max_impact_bps})  hops {}",
+                        q.in_amount, q.out_amount, q.price_impact_bps, q.hops
+                    );
+                    let _ = writeln!(out, "  net after repay {net} base units (${net_usd:.4})");
+                }
+                Ok(None) => {
+                    let _ = writeln!(out, "  no route within {max_impact_bps} bps price impact");
+                }
+                Err(e) => {
+                    let _ = writeln!(out, "  quote failed: {e:#}");
+                }
+            }
+        }
+    }
+
+    section(&mut out, "tip");
+    let (floor, ceiling) = strategy.tip_bounds(ctx.default_tip_floor);
+    match strategy.tip_seized_share {
+        Some(share) => {
+            let _ = writeln!(out, "  basis  seized {} * share {share}", lamports(cand.expected_seized_lamports));
+        }
+        None => {
+            let _ = writeln!(
+                out,
+                "  basis  profit {} * share {}",
+                lamports(cand.expected_profit_lamports),
+                strategy.tip_profit_share
+            );
+        }
+    }
+    let tip =
+        strategy.tip_lamports(cand.expected_profit_lamports, cand.expected_seized_lamports, ctx.default_tip_floor, 1.0);
+    let ceiling = match ceiling {
+        u64::MAX => "none".to_string(),
+        c => c.to_string(),
+    };
+    let _ = writeln!(out, "  clamp  floor {floor}  ceiling {ceiling}");
+    let _ = writeln!(out, "  tip    {}", lamports(Some(tip)));
+
+    section(&mut out, "decision");
+    let net = cand.expected_profit_lamports.map(|p| p as i128 - tip as i128);
+    let _ = writeln!(out, "  liquidatable {}", yes_no(cand.is_liquidatable()));
+    let _ = writeln!(
+        out,
+        "  net after tip {}",
+        net.map_or("unknown".to_string(), |n| format!("{n} lamports ({:.6} SOL)", n as f64 / LAMPORTS_PER_SOL as f64))
+    );
+    Ok(out)
+}
+
+/// Every reserve of the market, so the SOL price is known even when the obligation has no SOL.
+async fn market_reserves(rpc: &Rpc, market: &Pubkey) -> Result<HashMap<Pubkey, types::Reserve>> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize(RESERVE_SIZE as u64),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(RESERVE_LENDING_MARKET_OFFSET, market.as_ref())),
+        ]),
+        account_config: RpcAccountInfoConfig { encoding: Some(UiAccountEncoding::Base64), ..Default::default() },
+        ..Default::default()
+    };
+    rpc.throttle_gpa().await;
+    let accounts =
+        rpc.get_program_accounts_with_config(&PROGRAM_ID, config).context("Failed to fetch market reserves")?;
+    let decoder = KaminoLendingDecoder::default();
+    Ok(accounts.into_iter().filter_map(|(pk, acc)| Some((pk, decoder.decode_reserve(&acc.data).ok()?))).collect())
+}
+
+fn section(out: &mut String, title: &str) {
+    let _ = writeln!(out, "\n{title}");
+}
+
+fn yes_no(b: bool) -> &'static str {
+    match b {
+        true => "yes",
+        false => "no",
+    }
+}
+
+/// Base units in whole tokens.
+fn scaled(amount: u64, reserve: &types::Reserve) -> String {
+    format!("{:.6}", amount as f64 / 10f64.powi(reserve.liquidity.mint_decimals as i32))
+}
+
+fn usd_or_unknown(usd: Option<f64>) -> String {
+    usd.map_or("unknown".to_string(), |v| format!("${v:.4}"))
+}
+
+fn amount_or_unknown(amount: Option<u64>) -> String {
+    amount.map_or("unknown".to_string(), |a| a.to_string())
+}
+
+fn lamports(value: Option<u64>) -> String {
+    value.map_or("unknown".to_string(), |l| format!("{l} lamports ({:.6} SOL)", l as f64 / LAMPORTS_PER_SOL as f64))
+}
@@ -0,0 +1,165 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{ensure, Context, Result};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use solana_sdk::message::{Message, VersionedMessage};
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+use spl_associated_token_account_client::instruction::create_associated_token_account_idempotent;
+
+use crate::kamino::{build_liquidation_ix, fetch_obligation, LiquidationCandidate};
+use crate::keeper::{fetch_reserves, referrer_init_ixs, refresh_ixs};
+use crate::metrics::metrics;
+use crate::pda::{LiquidatorAccounts, MarketAccounts};
+use crate::ratelimit::{RateLimiter, RequestClass};
+use crate::rpc::Rpc;
+use crate::strategy::StrategyProfile;
+use crate::util::{fetch_latest_blockhash, ComputeBudget};
+
+/// Compute units requested by a wallet-signed liquidation, which also refreshes every reserve.
+const BLINK_CU_LIMIT: u32 = 1_400_000;
+
+/// Transaction requests served per second across all clients, each costing several RPC calls.
+const BLINK_REQUESTS_PER_SEC: f64 = 0.5;
+
+/// Transaction requests served back to back before the rate applies.
+const BLINK_BURST: u32 = 4;
+
+/// `GET /actions/liquidate/{obligation}` response, per the Solana Actions spec.
+#[derive(Debug, Serialize)]
+pub struct ActionMetadata {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub icon: String,
+    pub title: String,
+    pub description: String,
+    pub label: String,
+    pub disabled: bool,
+}
+
+/// `POST /actions/liquidate/{obligation}` request body.
+#[derive(Debug, Deserialize)]
+pub struct ActionRequest {
+    /// Wallet that signs, pays and receives the seized collateral.
+    pub account: String,
+}
+
+/// `POST` response: the unsigned transaction for the wallet to sign and send.
+#[derive(Debug, Serialize)]
+pub struct ActionTransaction {
+    pub transaction: String,
+    pub message: String,
+}
+
+/// Serves Solana Actions (blinks) for the latest liquidatable obligations, so an operator can
+/// liquidate from a wallet without the bot holding the keys.
+///
+/// Transactions are legacy, without the bot's lookup tables, and refresh the obligation's
+/// reserves inline; obligations with many positions may not fit and are refused.
+pub struct Blinks {
+    rpc: Arc<Rpc>,
+    market: MarketAccounts,
+    strategy: StrategyProfile,
+    icon: String,
+    candidates: Mutex<HashMap<Pubkey, LiquidationCandidate>>,
+    limiter: RateLimiter,
+}
+
+impl Blinks {
+    pub fn new(rpc: Arc<Rpc>, market: MarketAccounts, strategy: StrategyProfile, icon: String) -> Self {
+        let limiter = RateLimiter::new(BLINK_REQUESTS_PER_SEC, BLINK_BURST);
+        Self { rpc, market, strategy, icon, candidates: Mutex::new(HashMap::new()), limiter }
+    }
+
+    /// Whether another transaction request fits under the blink rate limit; counted when it does.
+    pub fn admit(&self) -> bool {
+        let admitted = self.limiter.try_acquire(RequestClass::Candidate, 1).is_ok();
+        if !admitted {
+            metrics().inc("blink_requests_limited_total");
+        }
+        admitted
+    }
+
+    /// Replace the served candidates with the latest scan's liquidatable ones.
+    pub fn publish(&self, candidates: &[&LiquidationCandidate]) {
+        let latest = candidates.iter().map(|c| (c.obligation, (*c).clone())).collect();
+        *self.candidates.lock().unwrap() = latest;
+    }
+
+    fn candidate(&self, obligation: &str) -> Option<LiquidationCandidate> {
+        let pk: Pubkey = obligation.parse().ok()?;
+        self.candidates.lock().unwrap().get(&pk).cloned()
+    }
+
+    /// Action metadata, or None when the obligation is not currently liquidatable.
+    pub fn metadata(&self, obligation: &str) -> Option<ActionMetadata> {
+        let cand = self.candidate(obligation)?;
+        let profit = cand
+            .expected_profit_lamports
+            .map_or("unknown".to_string(), |p| format!("{:.4} SOL", p as f64 / LAMPORTS_PER_SOL as f64));
+        Some(ActionMetadata {
+            kind: "action",
+            icon: self.icon.clone(),
+            title: format!("Liquidate {}", cand.obligation),
+            description: format!(
+                "Health {:.4}. Repays {} of reserve {} and seizes collateral from reserve {}. Expected profit {profit}.",
+                cand.health, cand.repay_amount, cand.repay_reserve, cand.withdraw_reserve
+            ),
+            label: "Liquidate".to_string(),
+            disabled: false,
+        })
+    }
+
+    /// Build the liquidation for `request.account` to sign, refreshing the obligation in the same
+    /// transaction and creating the account's token accounts for the seized collateral.
+    pub async fn transaction(&self, obligation: &str, request: &ActionRequest) -> Result<ActionTransaction> {
+        let cand = self.candidate(obligation).context("Obligation is not currently liquidatable")?;
+        let account: Pubkey = request.account.parse().context("Invalid account")?;
+        let liquidator = LiquidatorAccounts::new(account);
+
+        let obl = fetch_obligation(&self.rpc, &cand.obligation).await?;
+        let keys: Vec<Pubkey> = obl
+            .deposits
+            .iter()
+            .map(|d| d.reserve)
+            .chain(obl.borrows.iter().map(|b| b.reserve))
+            .filter(|pk| *pk != Pubkey::default())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let reserves = fetch_reserves(&self.rpc, &keys).await?;
+        let withdraw = reserves.get(&cand.withdraw_reserve).context("Withdraw reserve missing")?;
+
+        let budget = ComputeBudget { cu_limit: BLINK_CU_LIMIT, cu_price: None, heap_frame_bytes: None };
+        let mut ixs = budget.instructions();
+        ixs.push(create_associated_token_account_idempotent(
+            &account,
+            &account,
+            &withdraw.liquidity.mint_pubkey,
+            &withdraw.liquidity.token_program,
+        ));
+        let collateral_mint = withdraw.collateral.mint_pubkey;
+        ixs.push(create_associated_token_account_idempotent(&account, &account, &collateral_mint, &spl_token::ID));
+        ixs.extend(referrer_init_ixs(&self.rpc, &self.market, &account, &obl).await?);
+        ixs.extend(refresh_ixs(&self.market, &cand.obligation, &obl, &reserves)?);
+        ixs.push(build_liquidation_ix(&self.rpc, &self.market, &liquidator, &cand, &obl, &self.strategy).await?);
+
+        let blockhash = fetch_latest_blockhash(&self.rpc).await?;
+        let message = Message::new_with_blockhash(&ixs, Some(&account), &blockhash);
+        let signatures = vec![Signature::default(); message.header.num_required_signatures as usize];
+        let tx = VersionedTransaction { signatures, message: VersionedMessage::Legacy(message) };
+        let bytes = bincode::serialize(&tx).context("Failed to serialize transaction")?;
+        ensure!(bytes.len() <= PACKET_DATA_SIZE, "Liquidation is too large for a single wallet transaction");
+        metrics().inc("blink_transactions_total");
+
+        Ok(ActionTransaction {
+            transaction: base64::engine::general_purpose::STANDARD.encode(bytes),
+            message: format!("Liquidating {} at health {:.4}", cand.obligation, cand.health),
+        })
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+use tracing::warn;
+
+use crate::metrics::metrics;
+use crate::store::Store;
+use crate::tracker::TxOutcome;
+use crate::util::now_millis;
+
+/// Store collection holding bundle submissions and their resolved outcomes.
+pub const BUNDLES: &str = "bundles";
+
+/// Bundles kept in memory; the store keeps the full history.
+const MAX_RECORDS: usize = 10_000;
+
+/// What we know about one submitted bundle. Appended on submission and again once resolved;
+/// the last line per bundle id is authoritative.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BundleRecord {
+    pub bundle_id: String,
+    pub obligation: String,
+    /// Signatures of every transaction in the bundle, in order.
+    pub signatures: Vec<String>,
+    /// Signature whose outcome decides the bundle's.
+    pub tracked_signature: String,
+    /// Unset when the slot could not be read at submission.
+    pub submitted_slot: Option<u64>,
+    pub submitted_at_ms: u64,
+    /// Tip the bundle pays if it lands.
+    #[serde(default)]
+    pub tip_lamports: u64,
+    /// Unset while the bundle is still being tracked.
+    pub outcome: Option<TxOutcome>,
+    pub landed_slot: Option<u64>,
+    pub resolved_at_ms: Option<u64>,
+}
+
+/// Bundles submitted but not yet resolved.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InFlight {
+    pub bundles: usize,
+    /// Tips owed if every one of them lands.
+    pub tip_lamports: u64,
+}
+
+/// Bundle records persisted to the store, the newest `MAX_RECORDS` of them also cached in memory.
+pub struct BundleBook {
+    store: Arc<Store>,
+    records: Mutex<HashMap<String, BundleRecord>>,
+}
+
+impl BundleBook {
+    /// Load the latest state of the newest persisted bundles.
+    pub fn load(store: Arc<Store>) -> Result<Self> {
+        let mut records =
+            store.read_all::<BundleRecord>(BUNDLES)?.into_iter().map(|r| (r.bundle_id.clone(), r)).collect();
+        prune(&mut records, MAX_RECORDS);
+        Ok(Self { store, records: Mutex::new(records) })
+    }
+
+    /// Record a bundle accepted by the block engine.
+    pub fn submitted(
+        &self,
+        bundle_id: &str,
+        obligation: &Pubkey,
+        txs: &[VersionedTransaction],
+        tracked: &Signature,
+        submitted_slot: Option<u64>,
+        tip_lamports: u64,
+    ) {
+        let record = BundleRecord {
+            bundle_id: bundle_id.to_string(),
+            obligation: obligation.to_string(),
+            signatures: txs.iter().map(|tx| tx.signatures[0].to_string()).collect(),
+            tracked_signature: tracked.to_string(),
+            submitted_slot,
+            submitted_at_ms: now_millis(),
+            tip_lamports,
+            outcome: None,
+            landed_slot: None,
+            resolved_at_ms: None,
+        };
+        self.persist(record);
+    }
+
+    /// Attach the final outcome to a known bundle; ids that are not bundles are ignored.
+    pub fn resolve(&self, bundle_id: &str, outcome: TxOutcome, landed_slot: Option<u64>) {
+        let Some(mut record) = self.get(bundle_id) else {
+            return;
+        };
+        record.outcome = Some(outcome);
+        record.landed_slot = landed_slot;
+        record.resolved_at_ms = Some(now_millis());
+        self.persist(record);
+    }
+
+    pub fn get(&self, bundle_id: &str) -> Option<BundleRecord> {
+        self.records.lock().unwrap().get(bundle_id).cloned()
+    }
+
+    /// Unresolved bundles submitted within `max_age`. Older ones are left over from a previous
+    /// run or past the tracker's timeout, and no longer count.
+    pub fn in_flight(&self, max_age: Duration) -> InFlight {
+        let since = now_millis().saturating_sub(max_age.as_millis() as u64);
+        let records = self.records.lock().unwrap();
+        let pending = records.values().filter(|r| r.outcome.is_none() && r.submitted_at_ms >= since);
+        let in_flight = pending.fold(InFlight::default(), |acc, r| InFlight {
+            bundles: acc.bundles + 1,
+            tip_lamports: acc.tip_lamports + r.tip_lamports,
+        });
+        metrics().set_gauge("bundles_in_flight", &[], in_flight.bundles as f64);
+        metrics().set_gauge("bundles_in_flight_tip_lamports", &[], in_flight.tip_lamports as f64);
+        in_flight
+    }
+
+    /// Most recently submitted bundles, newest first.
+    pub fn recent(&self, limit: usize) -> Vec<BundleRecord> {
+        let mut records: Vec<BundleRecord> = self.records.lock().unwrap().values().cloned().collect();
+        records.sort_by(|a, b| b.submitted_at_ms.cmp(&a.submitted_at_ms));
+        records.truncate(limit);
+        records
+    }
+
+    fn persist(&self, record: BundleRecord) {
+        if let Err(e) = self.store.append(BUNDLES, &record) {
+            warn!(bundle_id = %record.bundle_id, error = %e, "Failed to persist bundle record");
+        }
+        let mut records = self.records.lock().unwrap();
+        records.insert(record.bundle_id.clone(), record);
+        // Prune in batches so a full book is not sorted on every submission
+        if records.len() > MAX_RECORDS + MAX_RECORDS / 10 {
+            prune(&mut records, MAX_RECORDS);
+        }
+    }
+}
+
+/// Keep the `keep` most recently submitted records.
+fn prune(records: &mut HashMap<String, BundleRecord>, keep: usize) {
+    if records.len() <= keep {
+        return;
+    }
+    let mut submitted: Vec<u64> = records.values().map(|r| r.submitted_at_ms).collect();
+    submitted.sort_unstable_by(|a, b| b.cmp(a));
+    let cutoff = submitted[keep - 1];
+    let before = records.len();
+    records.retain(|_, r| r.submitted_at_ms >= cutoff);
+    metrics().add("bundle_book_pruned_total", &[], (before - records.len()) as u64);
+}
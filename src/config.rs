@@ -1,33 +1,625 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use dotenvy::dotenv;
-use solana_sdk::signature::{read_keypair_file, Keypair};
+use serde::de::{self, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use zeroize::Zeroizing;
+
+use crate::hooks::{memo_instruction, InstructionHook, StaticHook};
+use crate::pda::LiquidatorAccounts;
+use crate::protect::ProtectConfig;
+use crate::rpc::RpcEndpoint;
+use crate::schedule::Schedule;
+use crate::strategy::{ReserveOverride, StrategyProfile, PRESETS};
+use crate::volatility::{ClassPolicy, VolatilityConfig};
 
 /// Runtime configuration loaded from environment and CLI.
 pub struct Config {
     pub rpc_url: String,
-    pub payer_path: PathBuf,
+    pub payer_source: SecretSource,
+    /// Pays transaction fees and tips.
     pub payer: Keypair,
+    /// Owns the token accounts liquidations trade through, when kept apart from the payer.
+    pub owner: Option<Keypair>,
 }
 
 impl Config {
-    /// Load configuration from environment variables and optional CLI overrides.
-    pub fn from_env(rpc_url_cli: Option<String>, payer_cli: Option<PathBuf>) -> Result<Self> {
+    /// Load configuration from environment variables and optional CLI overrides, fetching the
+    /// keypairs from their secret sources.
+    pub async fn from_env(
+        rpc_url_cli: Option<String>,
+        payer_cli: Option<SecretSource>,
+        owner_cli: Option<SecretSource>,
+    ) -> Result<Self> {
         dotenv().ok();
 
-        let rpc_url = rpc_url_cli
-            .or_else(|| std::env::var("RPC_URL").ok())
-            .unwrap_or_else(|| "https://api.mainnet-beta.solana.com".to_string());
+        let rpc_url = resolve_rpc_url(rpc_url_cli);
+
+        let payer_source = match payer_cli {
+            Some(source) => source,
+            None => match std::env::var("PAYER") {
+                Ok(value) => value.parse()?,
+                Err(_) => {
+                    let home = std::env::var_os("HOME").context("Set PAYER, or HOME for the default keypair path")?;
+                    SecretSource::File(PathBuf::from(home).join(".config/solana/id.json"))
+                }
+            },
+        };
+
+        let payer = payer_source
+            .keypair()
+            .await
+            .with_context(|| format!("Failed to load payer keypair from {payer_source}"))?;
+
+        let owner = match owner_cli {
+            Some(source) => {
+                Some(source.keypair().await.with_context(|| format!("Failed to load owner keypair from {source}"))?)
+            }
+            None => None,
+        };
+
+        Ok(Self { rpc_url, payer_source, payer, owner })
+    }
+
+    /// Keypair owning liquidation funds: the separate owner if configured, else the payer.
+    pub fn owner(&self) -> &Keypair {
+        self.owner.as_ref().unwrap_or(&self.payer)
+    }
+}
+
+/// Where a keypair is read from: a file path, or a secret store so the key never touches disk.
+///
+/// - `/path/id.json` or `file:///path/id.json`: a `solana-keygen` keypair file
+/// - `aws-sm://{secret id or ARN}`: an AWS Secrets Manager secret
+/// - `aws-kms:///path/id.json.enc`: a keypair file encrypted with an AWS KMS key
+/// - `vault://{mount}/{path}#{field}`: a HashiCorp Vault KV v2 secret, read with `VAULT_ADDR`
+///   and `VAULT_TOKEN`; the field defaults to `keypair`
+///
+/// Secrets hold the keypair as a `solana-keygen` JSON byte array or as the raw 64 bytes. AWS
+/// sources need the `aws` feature; credentials and region come from the standard AWS
+/// environment and profile chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SecretSource {
+    File(PathBuf),
+    AwsSecretsManager(String),
+    AwsKms(PathBuf),
+    Vault { mount: String, path: String, field: String },
+}
+
+impl FromStr for SecretSource {
+    type Err = anyhow::Error;
 
-        let payer_path = payer_cli
-            .or_else(|| std::env::var("PAYER").ok().map(PathBuf::from))
-            .unwrap_or_else(|| dirs::home_dir().unwrap().join(".config/solana/id.json"));
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(id) = s.strip_prefix("aws-sm://") {
+            ensure!(!id.is_empty(), "Missing secret id in {s}");
+            return Ok(Self::AwsSecretsManager(id.to_string()));
+        }
+        if let Some(path) = s.strip_prefix("aws-kms://") {
+            return Ok(Self::AwsKms(PathBuf::from(path)));
+        }
+        if let Some(rest) = s.strip_prefix("vault://") {
+            let (location, field) = rest.split_once('#').unwrap_or((rest, "keypair"));
+            let (mount, path) =
+                location.split_once('/').with_context(|| format!("Vault secret {s} is not mount/path"))?;
+            ensure!(!mount.is_empty() && !path.is_empty(), "Vault secret {s} is not mount/path");
+            return Ok(Self::Vault { mount: mount.to_string(), path: path.to_string(), field: field.to_string() });
+        }
+        Ok(Self::File(PathBuf::from(s.strip_prefix("file://").unwrap_or(s))))
+    }
+}
+
+impl fmt::Display for SecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::AwsSecretsManager(id) => write!(f, "aws-sm://{id}"),
+            Self::AwsKms(path) => write!(f, "aws-kms://{}", path.display()),
+            Self::Vault { mount, path, field } => write!(f, "vault://{mount}/{path}#{field}"),
+        }
+    }
+}
 
-        let payer = read_keypair_file(&payer_path)
-            .with_context(|| format!("Failed to load payer keypair from {}", payer_path.display()))?;
+impl SecretSource {
+    /// Fetch and parse the keypair. Every intermediate copy of the secret is zeroed on drop, so
+    /// only the keypair itself keeps the key in memory.
+    pub async fn keypair(&self) -> Result<Keypair> {
+        let secret = self.fetch().await?;
+        parse_keypair(&secret)
+    }
 
-        Ok(Self { rpc_url, payer_path, payer })
+    async fn fetch(&self) -> Result<Zeroizing<Vec<u8>>> {
+        match self {
+            Self::File(path) => Ok(Zeroizing::new(std::fs::read(path).context("Failed to read keypair file")?)),
+            #[cfg(feature = "aws")]
+            Self::AwsSecretsManager(id) => {
+                let aws = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+                let mut out = aws_sdk_secretsmanager::Client::new(&aws)
+                    .get_secret_value()
+                    .secret_id(id)
+                    .send()
+                    .await
+                    .context("Secrets Manager request failed")?;
+                // Move both values out of the response, so neither copy outlives this call unzeroed
+                let text = out.secret_string.take().map(|text| Zeroizing::new(text.into_bytes()));
+                let binary = out.secret_binary.take().map(|blob| Zeroizing::new(blob.into_inner()));
+                text.or(binary).with_context(|| format!("Secret {id} has no value"))
+            }
+            #[cfg(feature = "aws")]
+            Self::AwsKms(path) => {
+                let ciphertext = std::fs::read(path).context("Failed to read encrypted keypair")?;
+                let aws = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+                let mut out = aws_sdk_kms::Client::new(&aws)
+                    .decrypt()
+                    .ciphertext_blob(aws_sdk_kms::primitives::Blob::new(ciphertext))
+                    .send()
+                    .await
+                    .context("KMS decrypt failed")?;
+                let plaintext = out.plaintext.take().context("KMS returned no plaintext")?;
+                Ok(Zeroizing::new(plaintext.into_inner()))
+            }
+            #[cfg(not(feature = "aws"))]
+            Self::AwsSecretsManager(_) | Self::AwsKms(_) => bail!("{self} needs a build with the `aws` feature"),
+            Self::Vault { mount, path, field } => {
+                let addr = std::env::var("VAULT_ADDR").context("VAULT_ADDR is not set")?;
+                let token = Zeroizing::new(std::env::var("VAULT_TOKEN").context("VAULT_TOKEN is not set")?);
+                let url = format!("{}/v1/{mount}/data/{path}", addr.trim_end_matches('/'));
+                let body = reqwest::Client::new()
+                    .get(&url)
+                    .header("X-Vault-Token", token.as_str())
+                    .send()
+                    .await
+                    .context("Vault request failed")?
+                    .error_for_status()
+                    .context("Vault rejected the request")?
+                    .text()
+                    .await
+                    .map(Zeroizing::new)
+                    .context("Failed to read Vault response")?;
+                let response: VaultResponse = serde_json::from_str(&body).context("Invalid Vault response")?;
+                let secret =
+                    response.data.data.into_iter().find(|(name, _)| name == field).and_then(|(_, value)| value.0);
+                secret.with_context(|| format!("Vault secret has no {field} field"))
+            }
+        }
     }
 }
 
+/// The parts of a Vault KV v2 read we need.
+#[derive(Deserialize)]
+struct VaultResponse {
+    data: VaultData,
+}
+
+#[derive(Deserialize)]
+struct VaultData {
+    data: HashMap<String, VaultField>,
+}
+
+/// A Vault secret field read straight into zeroed memory: a string as its bytes, a byte array
+/// as the raw key. Other values are skipped.
+struct VaultField(Option<Zeroizing<Vec<u8>>>);
+
+impl<'de> Deserialize<'de> for VaultField {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct FieldVisitor;
+
+        impl<'de> Visitor<'de> for FieldVisitor {
+            type Value = VaultField;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a Vault secret value")
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> std::result::Result<VaultField, E> {
+                Ok(VaultField(Some(Zeroizing::new(text.as_bytes().to_vec()))))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<VaultField, A::Error> {
+                // Sized for a keypair up front, so pushing never leaves a reallocated copy behind
+                let mut bytes = Zeroizing::new(Vec::with_capacity(seq.size_hint().unwrap_or(0).max(64)));
+                while let Some(byte) = seq.next_element::<u8>()? {
+                    bytes.push(byte);
+                }
+                Ok(VaultField(Some(bytes)))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<VaultField, A::Error> {
+                while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+                Ok(VaultField(None))
+            }
+
+            fn visit_bool<E: de::Error>(self, _: bool) -> std::result::Result<VaultField, E> {
+                Ok(VaultField(None))
+            }
+
+            fn visit_i64<E: de::Error>(self, _: i64) -> std::result::Result<VaultField, E> {
+                Ok(VaultField(None))
+            }
+
+            fn visit_u64<E: de::Error>(self, _: u64) -> std::result::Result<VaultField, E> {
+                Ok(VaultField(None))
+            }
+
+            fn visit_f64<E: de::Error>(self, _: f64) -> std::result::Result<VaultField, E> {
+                Ok(VaultField(None))
+            }
+
+            fn visit_unit<E: de::Error>(self) -> std::result::Result<VaultField, E> {
+                Ok(VaultField(None))
+            }
+        }
+
+        deserializer.deserialize_any(FieldVisitor)
+    }
+}
+
+/// A keypair from a `solana-keygen` JSON byte array or its raw 64 bytes.
+fn parse_keypair(secret: &[u8]) -> Result<Keypair> {
+    let bytes = match secret.trim_ascii_start().first() {
+        Some(b'[') if secret.len() != 64 => {
+            Zeroizing::new(serde_json::from_slice::<Vec<u8>>(secret).context("Invalid keypair JSON")?)
+        }
+        _ => Zeroizing::new(secret.to_vec()),
+    };
+    Keypair::try_from(bytes.as_slice()).map_err(|e| anyhow!("Invalid keypair: {e}"))
+}
+
+/// Optional TOML configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    /// Profile used by markets without an explicit strategy.
+    pub default_strategy: Option<String>,
+    /// Custom strategy profiles by name (`[strategies.<name>]`).
+    pub strategies: HashMap<String, StrategyProfile>,
+    /// Per-market settings keyed by lending market pubkey.
+    pub markets: HashMap<String, MarketConfig>,
+    /// Per-reserve overrides keyed by reserve pubkey.
+    pub reserves: HashMap<String, ReserveOverride>,
+    /// RPC endpoints (`[[rpc]]`); when set they replace the CLI/env RPC URL.
+    pub rpc: Vec<RpcEndpointConfig>,
+    /// Wallet and token accounts used by liquidations (`[liquidator]`).
+    pub liquidator: LiquidatorConfig,
+    /// Wallets repaid instead of liquidated (`[protect]`).
+    pub protect: ProtectSection,
+    /// Per-asset volatility classes from streamed reserve prices (`[volatility]`).
+    pub volatility: VolatilitySection,
+    /// UTC windows during which liquidations are submitted (`[schedule]`).
+    pub schedule: ScheduleSection,
+    /// Instructions added to every transaction before signing (`[[hooks]]`).
+    pub hooks: Vec<HookSection>,
+}
+
+/// One instruction hook. Either `memo` for an SPL memo, or `program` with base64 `data` and
+/// `accounts` written as `"<pubkey>[:flags]"`, where flags are `s` for signer and `w` for
+/// writable and `payer` or `owner` stand for the bot's keys, e.g. `"payer:sw"`. Only the payer
+/// and owner can sign. With `only_with` the hook applies only to transactions calling one of
+/// those programs, e.g. Kamino's for liquidations alone.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HookSection {
+    pub name: Option<String>,
+    pub memo: Option<String>,
+    pub program: Option<String>,
+    pub data: String,
+    pub accounts: Vec<String>,
+    pub only_with: Vec<String>,
+}
+
+/// Active windows such as `"Mon-Fri 13:30-20:00"` or `"* 22:00-02:00"`, all in UTC. Outside
+/// every window the bot keeps scanning but submits nothing; no windows means always active.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScheduleSection {
+    pub windows: Vec<String>,
+}
+
+/// Volatility thresholds and how each class is treated. A class table, e.g.
+/// `[volatility.volatile]`, replaces that class's defaults and must set every field.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VolatilitySection {
+    pub enabled: bool,
+    pub window_secs: u64,
+    /// Price range over the window at or below which an asset is calm, in basis points.
+    pub calm_bps: f64,
+    /// Price range at or above which an asset is volatile, in basis points.
+    pub volatile_bps: f64,
+    pub calm: ClassPolicy,
+    pub normal: ClassPolicy,
+    pub volatile: ClassPolicy,
+}
+
+impl Default for VolatilitySection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_secs: 900,
+            calm_bps: 50.0,
+            volatile_bps: 300.0,
+            calm: ClassPolicy { scan_delay_ms: 1_500, min_profit_lamports: 0, tip_multiplier: 0.8 },
+            normal: ClassPolicy { scan_delay_ms: 800, min_profit_lamports: 0, tip_multiplier: 1.0 },
+            volatile: ClassPolicy { scan_delay_ms: 200, min_profit_lamports: 0, tip_multiplier: 1.5 },
+        }
+    }
+}
+
+/// Obligation owners to keep clear of liquidation.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProtectSection {
+    pub owners: Vec<String>,
+    /// Health below which a protected obligation gets a repayment.
+    pub trigger_health: f64,
+    /// Share of the largest borrow repaid per intervention, in basis points.
+    pub repay_bps: u16,
+    pub cooldown_secs: u64,
+}
+
+impl Default for ProtectSection {
+    fn default() -> Self {
+        Self { owners: Vec::new(), trigger_health: 1.05, repay_bps: 1_000, cooldown_secs: 60 }
+    }
+}
+
+/// Token accounts liquidations move funds through.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LiquidatorConfig {
+    /// Wallet whose associated token accounts are used; defaults to the owner keypair. Must be
+    /// the owner or payer keypair, since it signs the liquidation.
+    pub owner: Option<String>,
+    /// Token account per mint, overriding the owner's associated token account
+    /// (`[liquidator.token_accounts]`).
+    pub token_accounts: HashMap<String, String>,
+}
+
+/// One RPC endpoint in the config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcEndpointConfig {
+    pub url: String,
+    /// Extra HTTP headers; `${VAR}` in a value is replaced from the environment so tokens
+    /// stay out of the file.
+    pub headers: HashMap<String, String>,
+    /// Lower is preferred.
+    pub priority: u32,
+}
+
+/// Settings for one lending market.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarketConfig {
+    /// Strategy profile name: conservative, aggressive, or a custom profile.
+    pub strategy: Option<String>,
+}
+
+impl FileConfig {
+    /// Load from a TOML file, or return an empty config when no path is given.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let Some(path) = path else { return Ok(Self::default()) };
+        let raw =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&raw).with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// RPC endpoints with environment references in header values resolved.
+    pub fn rpc_endpoints(&self) -> Result<Vec<RpcEndpoint>> {
+        dotenv().ok();
+        self.rpc
+            .iter()
+            .map(|e| {
+                let headers = e
+                    .headers
+                    .iter()
+                    .map(|(name, value)| {
+                        Ok((name.clone(), expand_env(value).with_context(|| format!("RPC header {name}"))?))
+                    })
+                    .collect::<Result<_>>()?;
+                Ok(RpcEndpoint { url: e.url.clone(), headers, priority: e.priority })
+            })
+            .collect()
+    }
+
+    /// Protect mode settings, or None when no wallets are protected.
+    pub fn protect_config(&self) -> Result<Option<ProtectConfig>> {
+        let p = &self.protect;
+        if p.owners.is_empty() {
+            return Ok(None);
+        }
+        if !(1.0..=2.0).contains(&p.trigger_health) || p.repay_bps == 0 || p.repay_bps > 10_000 {
+            bail!("Protect trigger health must be within [1.0, 2.0] and repay bps within (0, 10000]");
+        }
+        let owners = p
+            .owners
+            .iter()
+            .map(|o| o.parse().with_context(|| format!("Invalid protected owner: {o}")))
+            .collect::<Result<_>>()?;
+        Ok(Some(ProtectConfig {
+            owners,
+            trigger_health: p.trigger_health,
+            repay_bps: p.repay_bps,
+            cooldown: std::time::Duration::from_secs(p.cooldown_secs),
+        }))
+    }
+
+    /// Volatility classification settings, or None when disabled.
+    pub fn volatility_config(&self) -> Result<Option<VolatilityConfig>> {
+        let v = &self.volatility;
+        if !v.enabled {
+            return Ok(None);
+        }
+        if v.window_secs == 0 || v.calm_bps < 0.0 || v.volatile_bps <= v.calm_bps {
+            bail!("Volatility window must be positive and volatile bps above calm bps");
+        }
+        if [v.calm, v.normal, v.volatile].iter().any(|p| p.tip_multiplier <= 0.0) {
+            bail!("Volatility tip multipliers must be positive");
+        }
+        Ok(Some(VolatilityConfig {
+            window: std::time::Duration::from_secs(v.window_secs),
+            calm_bps: v.calm_bps,
+            volatile_bps: v.volatile_bps,
+            calm: v.calm,
+            normal: v.normal,
+            volatile: v.volatile,
+        }))
+    }
+
+    /// Active windows from `[schedule]`.
+    pub fn schedule(&self) -> Result<Schedule> {
+        Schedule::parse(&self.schedule.windows).context("Invalid schedule window")
+    }
+
+    /// Hooks from `[[hooks]]`, with `payer` and `owner` in account lists resolved to these keys.
+    pub fn instruction_hooks(&self, payer: Pubkey, owner: Pubkey) -> Result<Vec<Box<dyn InstructionHook>>> {
+        self.hooks
+            .iter()
+            .enumerate()
+            .map(|(i, h)| {
+                let name = h.name.clone().unwrap_or_else(|| format!("hooks[{i}]"));
+                let hook =
+                    parse_hook(h, &name, payer, owner).with_context(|| format!("Invalid instruction hook {name}"))?;
+                Ok(Box::new(hook) as Box<dyn InstructionHook>)
+            })
+            .collect()
+    }
+
+    /// Liquidator wallet and token account overrides. The owner defaults to `signers[0]` and
+    /// must be one of `signers`.
+    pub fn liquidator_accounts(&self, signers: &[Pubkey]) -> Result<LiquidatorAccounts> {
+        let owner = match self.liquidator.owner.as_deref() {
+            Some(owner) => owner.parse().context("Invalid liquidator owner in config")?,
+            None => *signers.first().context("No signer for liquidations")?,
+        };
+        if !signers.contains(&owner) {
+            bail!("Liquidator owner {owner} is neither the owner nor the payer keypair");
+        }
+        let mut accounts = LiquidatorAccounts::new(owner);
+        for (mint, account) in &self.liquidator.token_accounts {
+            let mint: Pubkey =
+                mint.parse().with_context(|| format!("Invalid mint in liquidator token accounts: {mint}"))?;
+            let account = account.parse().with_context(|| format!("Invalid token account for mint {mint}"))?;
+            accounts.overrides.insert(mint, account);
+        }
+        Ok(accounts)
+    }
+
+    /// Every profile a market can run: the presets and the custom `[strategies]`.
+    pub fn strategy_names(&self) -> Vec<String> {
+        let mut names: Vec<String> =
+            PRESETS.iter().map(|p| p.to_string()).chain(self.strategies.keys().cloned()).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Resolve the strategy for a market: CLI override, then market entry, then default.
+    /// Reserve overrides are attached to the returned profile.
+    pub fn strategy_for(&self, market: &str, override_name: Option<&str>) -> Result<(String, StrategyProfile)> {
+        let name = override_name
+            .or_else(|| self.markets.get(market).and_then(|m| m.strategy.as_deref()))
+            .or(self.default_strategy.as_deref())
+            .unwrap_or("conservative");
+
+        let mut profile = self
+            .strategies
+            .get(name)
+            .cloned()
+            .or_else(|| StrategyProfile::preset(name))
+            .ok_or_else(|| anyhow!("Unknown strategy profile: {name}"))?;
+        for (key, reserve) in &self.reserves {
+            let pk: Pubkey = key.parse().with_context(|| format!("Invalid reserve key in config: {key}"))?;
+            profile.reserves.insert(pk, reserve.clone());
+        }
+        Ok((name.to_string(), profile))
+    }
+}
+
+/// Resolve the RPC URL from CLI, environment, or the public mainnet default.
+pub fn resolve_rpc_url(rpc_url_cli: Option<String>) -> String {
+    dotenv().ok();
+    rpc_url_cli
+        .or_else(|| std::env::var("RPC_URL").ok())
+        .unwrap_or_else(|| "https://api.mainnet-beta.solana.com".to_string())
+}
+
+/// Build a `[[hooks]]` entry's instruction; `payer` and `owner` stand in for the accounts
+/// named so.
+fn parse_hook(h: &HookSection, name: &str, payer: Pubkey, owner: Pubkey) -> Result<StaticHook> {
+    let instruction = match (&h.memo, &h.program) {
+        (Some(text), None) => {
+            ensure!(h.data.is_empty() && h.accounts.is_empty(), "A memo hook takes no data or accounts");
+            memo_instruction(text)
+        }
+        (None, Some(program)) => {
+            let program_id = program.parse().with_context(|| format!("Invalid program {program}"))?;
+            let data = STANDARD.decode(&h.data).context("Hook data is not base64")?;
+            let accounts = h
+                .accounts
+                .iter()
+                .map(|a| parse_hook_account(a, payer, owner).with_context(|| format!("Invalid hook account {a:?}")))
+                .collect::<Result<_>>()?;
+            Instruction { program_id, accounts, data }
+        }
+        _ => bail!("Set exactly one of memo or program"),
+    };
+    let only_with = h
+        .only_with
+        .iter()
+        .map(|p| p.parse().with_context(|| format!("Invalid only_with program {p}")))
+        .collect::<Result<_>>()?;
+    Ok(StaticHook { name: name.to_string(), instruction, only_with })
+}
+
+/// An account spec `key[:flags]`, where the key may be `payer` or `owner` and the flags are
+/// `s` for signer and `w` for writable.
+fn parse_hook_account(spec: &str, payer: Pubkey, owner: Pubkey) -> Result<AccountMeta> {
+    let (key, flags) = spec.split_once(':').unwrap_or((spec, ""));
+    let pubkey = match key {
+        "payer" => payer,
+        "owner" => owner,
+        key => key.parse()?,
+    };
+    ensure!(flags.chars().all(|c| c == 's' || c == 'w'), "Unknown account flags {flags:?}, expected s and/or w");
+    let signer = flags.contains('s');
+    ensure!(!signer || pubkey == payer || pubkey == owner, "Only the payer or owner can sign");
+    Ok(match flags.contains('w') {
+        true => AccountMeta::new(pubkey, signer),
+        false => AccountMeta::new_readonly(pubkey, signer),
+    })
+}
+
+/// Replace each `${VAR}` with the environment variable's value.
+fn expand_env(value: &str) -> Result<String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find('}').context("Unterminated ${ in value")? + start;
+        let var = &rest[start + 2..end];
+        out.push_str(&std::env::var(var).with_context(|| format!("Environment variable {var} is not set"))?);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Derive the websocket endpoint from an HTTP RPC URL.
+pub fn derive_ws_url(rpc_url: &str) -> String {
+    if let Some(rest) = rpc_url.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = rpc_url.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        rpc_url.to_string()
+    }
+}
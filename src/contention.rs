@@ -0,0 +1,120 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::message::{AddressLookupTableAccount, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::VersionedTransaction;
+
+use crate::kamino::LiquidationCandidate;
+use crate::metrics::metrics;
+use crate::util::now_millis;
+
+/// Store collection holding one contention report per built liquidation.
+pub const CONTENTION: &str = "contention";
+
+/// Accounts a transaction write-locks, including those loaded through lookup tables.
+/// Lookups into tables missing from `lookup_tables` are skipped.
+pub fn writable_accounts(tx: &VersionedTransaction, lookup_tables: &[AddressLookupTableAccount]) -> Vec<Pubkey> {
+    let header = tx.message.header();
+    let keys = tx.message.static_account_keys();
+    let signed = header.num_required_signatures as usize;
+    let writable_signed = signed.saturating_sub(header.num_readonly_signed_accounts as usize);
+    let writable_unsigned = keys.len().saturating_sub(header.num_readonly_unsigned_accounts as usize);
+
+    let mut writable: Vec<Pubkey> = keys
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i < writable_signed || (signed..writable_unsigned).contains(i))
+        .map(|(_, pk)| *pk)
+        .collect();
+
+    if let VersionedMessage::V0(msg) = &tx.message {
+        for lookup in &msg.address_table_lookups {
+            let Some(table) = lookup_tables.iter().find(|t| t.key == lookup.account_key) else {
+                continue;
+            };
+            writable.extend(lookup.writable_indexes.iter().filter_map(|i| table.addresses.get(*i as usize)));
+        }
+    }
+    writable
+}
+
+/// Writable-account contention of one candidate's transactions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ContentionReport {
+    pub obligation: String,
+    pub writable: Vec<String>,
+    /// Accounts already write-locked by an earlier candidate in the same pass.
+    pub shared: Vec<String>,
+    /// Reserves this candidate touches that several candidates in the pass also target.
+    pub popular: Vec<String>,
+    pub recorded_at_ms: u64,
+}
+
+impl ContentionReport {
+    pub fn is_contended(&self) -> bool {
+        !self.shared.is_empty() || !self.popular.is_empty()
+    }
+}
+
+/// Writable accounts claimed by our own submissions during one scan pass.
+pub struct ContentionMap {
+    /// Reserves targeted by at least the threshold number of candidates.
+    popular: HashSet<Pubkey>,
+    /// First obligation to write-lock each account.
+    claimed: HashMap<Pubkey, Pubkey>,
+    /// Locked by every transaction (payer, tip account), so never informative.
+    ignored: HashSet<Pubkey>,
+}
+
+impl ContentionMap {
+    /// Start a pass over `candidates`; a reserve is popular once `threshold` of them touch it.
+    pub fn new(candidates: &[&LiquidationCandidate], threshold: usize, ignored: &[Pubkey]) -> Self {
+        let mut counts: HashMap<Pubkey, usize> = HashMap::new();
+        for cand in candidates {
+            let touched: BTreeSet<Pubkey> = [cand.repay_reserve, cand.withdraw_reserve].into();
+            for reserve in touched {
+                *counts.entry(reserve).or_default() += 1;
+            }
+        }
+        let popular = counts.into_iter().filter(|(_, n)| *n >= threshold.max(2)).map(|(pk, _)| pk).collect();
+        Self { popular, claimed: HashMap::new(), ignored: ignored.iter().copied().collect() }
+    }
+
+    /// Record the writable accounts of a candidate's transactions and report conflicts with
+    /// earlier candidates and popular reserves.
+    pub fn observe(&mut self, obligation: &Pubkey, writable: &[Pubkey]) -> ContentionReport {
+        let writable: BTreeSet<Pubkey> = writable.iter().filter(|pk| !self.ignored.contains(pk)).copied().collect();
+        let mut shared = Vec::new();
+        for pk in &writable {
+            match self.claimed.get(pk) {
+                Some(owner) if owner != obligation => shared.push(pk.to_string()),
+                Some(_) => {}
+                None => {
+                    self.claimed.insert(*pk, *obligation);
+                }
+            }
+        }
+        let popular: Vec<String> =
+            writable.iter().filter(|pk| self.popular.contains(pk)).map(Pubkey::to_string).collect();
+
+        let level = match (shared.is_empty(), popular.is_empty()) {
+            (false, _) => "shared",
+            (true, false) => "popular",
+            (true, true) => "clear",
+        };
+        metrics().inc_labeled("contention_candidates_total", &[("level", level)]);
+        metrics().add("contention_shared_accounts_total", &[], shared.len() as u64);
+
+        ContentionReport {
+            obligation: obligation.to_string(),
+            writable: writable.iter().map(Pubkey::to_string).collect(),
+            shared,
+            popular,
+            recorded_at_ms: now_millis(),
+        }
+    }
+}
+
+/// Most transactions the block engine accepts in one bundle.
+pub const MAX_BUNDLE_TXS: usize = 5;
@@ -0,0 +1,262 @@
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Error, Result};
+use serde::Serialize;
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, info, warn};
+
+use crate::metrics::metrics;
+use crate::schedule::Activity;
+use crate::strategy::StrategyProfile;
+
+/// How long a Telegram `getUpdates` call waits for a message before returning empty.
+const TELEGRAM_POLL_SECS: u64 = 30;
+
+/// A runtime change an operator can make over the control API or Telegram.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlCommand {
+    /// Switch to another named strategy profile.
+    Strategy(String),
+    DryRun(bool),
+    /// Cap tips in lamports, or go back to the profile's own ceiling with None.
+    MaxTip(Option<u64>),
+    /// Stop submitting liquidations on a market.
+    Pause(Pubkey),
+    Resume(Pubkey),
+    Maintenance(bool),
+}
+
+/// Parses the form both channels take, e.g. `/strategy aggressive`, `/dryrun on`,
+/// `/maxtip 500000`, `/maxtip off`, `/pause <market>`, `/resume <market>` or `/maintenance off`.
+/// The leading slash is optional.
+impl FromStr for ControlCommand {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut words = s.split_whitespace();
+        // Telegram appends the bot's name to commands in group chats, e.g. /pause@liq_bot
+        let name = words.next().context("Empty command")?.trim_start_matches('/');
+        let name = name.split('@').next().unwrap_or_default().to_ascii_lowercase();
+        let arg = words.next().context("Missing argument")?;
+        let switch = |arg: &str| match arg.to_ascii_lowercase().as_str() {
+            "on" | "true" | "1" => Ok(true),
+            "off" | "false" | "0" => Ok(false),
+            _ => Err(anyhow!("Expected on or off, got {arg:?}")),
+        };
+        match name.as_str() {
+            "strategy" => Ok(Self::Strategy(arg.to_string())),
+            "dryrun" | "dry_run" => Ok(Self::DryRun(switch(arg)?)),
+            "maxtip" | "max_tip" => match arg {
+                "off" | "none" => Ok(Self::MaxTip(None)),
+                lamports => Ok(Self::MaxTip(Some(lamports.parse().context("Invalid tip in lamports")?))),
+            },
+            "pause" => Ok(Self::Pause(arg.parse().context("Invalid market address")?)),
+            "resume" => Ok(Self::Resume(arg.parse().context("Invalid market address")?)),
+            "maintenance" => Ok(Self::Maintenance(switch(arg)?)),
+            _ => bail!("Unknown command /{name}"),
+        }
+    }
+}
+
+/// Everything operators may change while the bot runs. The main loop reads it at the start of
+/// every iteration, so changes apply from the next scan on.
+pub struct RuntimeControls {
+    activity: Arc<Activity>,
+    /// The market this process trades, the only one that can be paused.
+    market: Pubkey,
+    /// Profiles that can be switched to, already resolved for this market.
+    profiles: HashMap<String, StrategyProfile>,
+    strategy: RwLock<String>,
+    dry_run: AtomicBool,
+    max_tip: Mutex<Option<u64>>,
+    paused_markets: Mutex<BTreeSet<Pubkey>>,
+}
+
+/// `GET /control` response and the reply to Telegram's `/status`.
+#[derive(Debug, Serialize)]
+pub struct ControlStatus {
+    pub strategy: String,
+    pub strategies: Vec<String>,
+    pub dry_run: bool,
+    pub max_tip_lamports: Option<u64>,
+    pub paused_markets: Vec<String>,
+    pub maintenance: bool,
+    pub paused: Option<&'static str>,
+}
+
+impl RuntimeControls {
+    pub fn new(
+        activity: Arc<Activity>,
+        market: Pubkey,
+        profiles: HashMap<String, StrategyProfile>,
+        strategy: String,
+        dry_run: bool,
+    ) -> Self {
+        Self {
+            activity,
+            market,
+            profiles,
+            strategy: RwLock::new(strategy),
+            dry_run: AtomicBool::new(dry_run),
+            max_tip: Mutex::new(None),
+            paused_markets: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    pub fn activity(&self) -> &Activity {
+        &self.activity
+    }
+
+    /// The active profile by name, with any operator tip cap applied over its ceiling.
+    pub fn strategy(&self) -> (String, StrategyProfile) {
+        let name = self.strategy.read().unwrap().clone();
+        let mut profile = self.profiles[&name].clone();
+        if let Some(max) = self.max_tip() {
+            profile.tip_ceiling_lamports = Some(profile.tip_ceiling_lamports.map_or(max, |c| c.min(max)));
+            profile.tip_floor_lamports = Some(profile.tip_floor_lamports.map_or(max, |f| f.min(max)));
+        }
+        (name, profile)
+    }
+
+    /// Operator tip cap, which auction rounds are clamped to as well.
+    pub fn max_tip(&self) -> Option<u64> {
+        *self.max_tip.lock().unwrap()
+    }
+
+    pub fn dry_run(&self) -> bool {
+        self.dry_run.load(Ordering::Relaxed)
+    }
+
+    pub fn market_paused(&self, market: &Pubkey) -> bool {
+        self.paused_markets.lock().unwrap().contains(market)
+    }
+
+    /// Apply a command, returning a confirmation for the operator.
+    pub fn apply(&self, command: ControlCommand, source: &'static str) -> Result<String> {
+        let reply = match &command {
+            ControlCommand::Strategy(name) => {
+                if !self.profiles.contains_key(name) {
+                    let mut known: Vec<_> = self.profiles.keys().cloned().collect();
+                    known.sort();
+                    bail!("Unknown strategy profile {name:?}, expected one of {}", known.join(", "));
+                }
+                *self.strategy.write().unwrap() = name.clone();
+                format!("Strategy switched to {name}")
+            }
+            ControlCommand::DryRun(enabled) => {
+                self.dry_run.store(*enabled, Ordering::Relaxed);
+                format!("Dry run {}", if *enabled { "enabled" } else { "disabled" })
+            }
+            ControlCommand::MaxTip(max) => {
+                *self.max_tip.lock().unwrap() = *max;
+                max.map_or("Tip cap removed".to_string(), |m| format!("Tips capped at {m} lamports"))
+            }
+            ControlCommand::Pause(market) | ControlCommand::Resume(market) if *market != self.market => {
+                bail!("Unknown market {market}, this instance trades {}", self.market);
+            }
+            ControlCommand::Pause(market) => {
+                self.paused_markets.lock().unwrap().insert(*market);
+                format!("Market {market} paused")
+            }
+            ControlCommand::Resume(market) => {
+                self.paused_markets.lock().unwrap().remove(market);
+                format!("Market {market} resumed")
+            }
+            ControlCommand::Maintenance(enabled) => {
+                self.activity.set_maintenance(*enabled);
+                format!("Maintenance {}", if *enabled { "enabled" } else { "disabled" })
+            }
+        };
+        info!(source, command = ?command, "Runtime control applied");
+        metrics().inc_labeled("control_commands_total", &[("source", source)]);
+        Ok(reply)
+    }
+
+    pub fn status(&self) -> ControlStatus {
+        let mut strategies: Vec<_> = self.profiles.keys().cloned().collect();
+        strategies.sort();
+        ControlStatus {
+            strategy: self.strategy.read().unwrap().clone(),
+            strategies,
+            dry_run: self.dry_run(),
+            max_tip_lamports: self.max_tip(),
+            paused_markets: self.paused_markets.lock().unwrap().iter().map(Pubkey::to_string).collect(),
+            maintenance: self.activity.maintenance(),
+            paused: self.activity.paused_reason(),
+        }
+    }
+}
+
+/// Takes control commands from Telegram chats. Messages from any other chat are ignored, so
+/// the chat list is what authorizes an operator.
+pub struct TelegramControl {
+    token: String,
+    chats: Vec<i64>,
+    controls: Arc<RuntimeControls>,
+    http: reqwest::Client,
+}
+
+impl TelegramControl {
+    pub fn new(token: String, chats: Vec<i64>, controls: Arc<RuntimeControls>) -> Self {
+        Self { token, chats, controls, http: reqwest::Client::new() }
+    }
+
+    /// Long-poll the bot's updates and answer each command; returns only on a request failure.
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        let mut offset = 0i64;
+        loop {
+            let updates =
+                self.call("getUpdates", serde_json::json!({ "offset": offset, "timeout": TELEGRAM_POLL_SECS })).await?;
+            for update in updates.as_array().into_iter().flatten() {
+                offset = offset.max(update["update_id"].as_i64().unwrap_or_default() + 1);
+                let message = &update["message"];
+                let (Some(chat), Some(text)) = (message["chat"]["id"].as_i64(), message["text"].as_str()) else {
+                    continue;
+                };
+                if !self.chats.contains(&chat) {
+                    warn!(chat, "Ignoring Telegram command from an unauthorized chat");
+                    metrics().inc_labeled("control_requests_total", &[("result", "unauthorized")]);
+                    continue;
+                }
+                let reply = self.handle(text);
+                if let Err(e) = self.call("sendMessage", serde_json::json!({ "chat_id": chat, "text": reply })).await {
+                    debug!(chat, error = %e, "Failed to answer Telegram command");
+                }
+            }
+        }
+    }
+
+    fn handle(&self, text: &str) -> String {
+        if text.split_whitespace().next().is_some_and(|c| c.trim_start_matches('/').starts_with("status")) {
+            return serde_json::to_string_pretty(&self.controls.status()).unwrap_or_default();
+        }
+        match text.parse().and_then(|command| self.controls.apply(command, "telegram")) {
+            Ok(reply) => reply,
+            Err(e) => format!("{e:#}"),
+        }
+    }
+
+    async fn call(&self, method: &str, body: Value) -> Result<Value> {
+        let url = format!("https://api.telegram.org/bot{}/{method}", self.token);
+        let resp: Value = self
+            .http
+            .post(&url)
+            .json(&body)
+            .timeout(Duration::from_secs(TELEGRAM_POLL_SECS + 10))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            // Request URLs carry the bot token
+            .map_err(reqwest::Error::without_url)
+            .with_context(|| format!("Telegram {method} failed"))?
+            .json()
+            .await
+            .with_context(|| format!("Telegram {method} returned invalid JSON"))?;
+        Ok(resp["result"].clone())
+    }
+}
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use solana_sdk::pubkey::Pubkey;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tracing::{debug, warn};
+
+use crate::metrics::metrics;
+
+/// Budget for one Redis round trip; a slow lock server must not stall submissions.
+const REDIS_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Key prefix of per-obligation leases.
+const LEASE_PREFIX: &str = "kamino-liq:lease:";
+
+/// Take the lease, or extend it when already ours, atomically; returns 1 when held.
+const ACQUIRE_SCRIPT: &str = "if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then return 1 end \
+if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('PEXPIRE', KEYS[1], ARGV[2]) end \
+return 0";
+
+/// Delete the lease only while it is still ours.
+const RELEASE_SCRIPT: &str = "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) end \
+return 0";
+
+/// Redis reply, reduced to the shapes the lease commands return; only integers carry a value
+/// we read.
+#[derive(Debug)]
+enum Reply {
+    Simple,
+    Bulk,
+    Integer(i64),
+}
+
+/// Per-obligation leases in Redis so redundant instances never submit for the same obligation
+/// at once; whoever holds the lease submits, the rest stay hot standby.
+///
+/// Fails open: when Redis is unreachable every instance proceeds, since a duplicate
+/// revert-protected submission is cheaper than missing the liquidation.
+pub struct RedisLease {
+    addr: String,
+    /// AUTH arguments: a password, or a user and password.
+    auth: Vec<String>,
+    instance_id: String,
+    ttl: Duration,
+    conn: Option<BufReader<TcpStream>>,
+}
+
+impl RedisLease {
+    /// `url` is `redis://[[user]:password@]host:port`; without a user, AUTH uses the password alone.
+    pub fn new(url: &str, instance_id: String, ttl: Duration) -> Result<Self> {
+        let rest = url.strip_prefix("redis://").context("Redis URL must start with redis://")?;
+        let (auth, addr) = match rest.rsplit_once('@') {
+            Some((credentials, addr)) => match credentials.split_once(':') {
+                Some(("", password)) => (vec![password.to_string()], addr),
+                Some((user, password)) => (vec![user.to_string(), password.to_string()], addr),
+                None => (vec![credentials.to_string()], addr),
+            },
+            None => (Vec::new(), rest),
+        };
+        let addr = addr.trim_end_matches('/').to_string();
+        if addr.is_empty() {
+            bail!("Redis URL is missing a host");
+        }
+        Ok(Self { addr, auth, instance_id, ttl, conn: None })
+    }
+
+    /// Take or extend the lease on the obligation; false when another instance holds it.
+    pub async fn acquire(&mut self, obligation: &Pubkey) -> bool {
+        match tokio::time::timeout(REDIS_TIMEOUT, self.try_acquire(obligation)).await {
+            Ok(Ok(held)) => {
+                metrics()
+                    .inc_labeled("coordination_leases_total", &[("result", if held { "acquired" } else { "standby" })]);
+                held
+            }
+            Ok(Err(e)) => self.fail_open(obligation, &e.to_string()),
+            Err(_) => self.fail_open(obligation, "timed out"),
+        }
+    }
+
+    /// Hand the lease back early, e.g. when nothing was submitted, so a standby can take the
+    /// obligation without waiting for expiry. Leaves a lease taken over by another instance alone.
+    pub async fn release(&mut self, obligation: &Pubkey) {
+        let key = format!("{LEASE_PREFIX}{obligation}");
+        let id = self.instance_id.clone();
+        let released =
+            tokio::time::timeout(REDIS_TIMEOUT, self.command(&["EVAL", RELEASE_SCRIPT, "1", &key, &id])).await;
+        match released {
+            Ok(Ok(Reply::Integer(1))) => metrics().inc_labeled("coordination_leases_total", &[("result", "released")]),
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                debug!(obligation = %obligation, error = %e, "Failed to release lease, leaving it to expire");
+                self.conn = None;
+            }
+            Err(_) => {
+                debug!(obligation = %obligation, "Lease release timed out, leaving it to expire");
+                self.conn = None;
+            }
+        }
+    }
+
+    fn fail_open(&mut self, obligation: &Pubkey, reason: &str) -> bool {
+        warn!(obligation = %obligation, reason, "Lease backend unavailable, proceeding without lock");
+        metrics().inc_labeled("coordination_leases_total", &[("result", "unavailable")]);
+        self.conn = None;
+        true
+    }
+
+    async fn try_acquire(&mut self, obligation: &Pubkey) -> Result<bool> {
+        let key = format!("{LEASE_PREFIX}{obligation}");
+        let ttl_ms = self.ttl.as_millis().to_string();
+        let id = self.instance_id.clone();
+        // Already ours (e.g. a retry of the same candidate) extends it
+        match self.command(&["EVAL", ACQUIRE_SCRIPT, "1", &key, &id, &ttl_ms]).await? {
+            Reply::Integer(1) => Ok(true),
+            Reply::Integer(_) => {
+                debug!(obligation = %obligation, "Obligation leased by another instance");
+                Ok(false)
+            }
+            other => bail!("Unexpected lease reply: {other:?}"),
+        }
+    }
+
+    async fn command(&mut self, args: &[&str]) -> Result<Reply> {
+        if self.conn.is_none() {
+            let stream = TcpStream::connect(&self.addr)
+                .await
+                .with_context(|| format!("Failed to connect to Redis at {}", self.addr))?;
+            let mut conn = BufReader::new(stream);
+            if !self.auth.is_empty() {
+                let args: Vec<&str> = std::iter::once("AUTH").chain(self.auth.iter().map(String::as_str)).collect();
+                send(&mut conn, &args).await.context("Redis AUTH failed")?;
+            }
+            self.conn = Some(conn);
+        }
+        let conn = self.conn.as_mut().context("Redis connection missing")?;
+        send(conn, args).await
+    }
+}
+
+async fn send(conn: &mut BufReader<TcpStream>, args: &[&str]) -> Result<Reply> {
+    let mut encoded = format!("*{}\r\n", args.len());
+    for arg in args {
+        encoded.push_str(&format!("${}\r\n{arg}\r\n", arg.len()));
+    }
+    conn.get_mut().write_all(encoded.as_bytes()).await.context("Redis write failed")?;
+    read_reply(conn).await
+}
+
+async fn read_reply(conn: &mut BufReader<TcpStream>) -> Result<Reply> {
+    let mut line = String::new();
+    conn.read_line(&mut line).await.context("Redis read failed")?;
+    let line = line.trim_end_matches("\r\n");
+    let (kind, body) = line.split_at_checked(1).context("Empty Redis reply")?;
+    match kind {
+        "+" => Ok(Reply::Simple),
+        "-" => Err(anyhow!("Redis error: {body}")),
+        ":" => Ok(Reply::Integer(body.parse().context("Invalid Redis integer")?)),
+        "$" => {
+            let len: i64 = body.parse().context("Invalid Redis bulk length")?;
+            if len >= 0 {
+                let mut buf = vec![0u8; len as usize + 2];
+                conn.read_exact(&mut buf).await.context("Redis read failed")?;
+            }
+            Ok(Reply::Bulk)
+        }
+        other => bail!("Unsupported Redis reply type {other}"),
+    }
+}
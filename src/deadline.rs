@@ -0,0 +1,76 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use tracing::debug;
+
+use crate::metrics::metrics;
+
+/// Per-stage caps within a candidate's latency budget.
+#[derive(Clone, Copy, Debug)]
+pub struct StageTimeouts {
+    /// Account fetches and instruction building.
+    pub fetch: Duration,
+    /// Swap quoting.
+    pub quote: Duration,
+    /// Transaction simulation.
+    pub simulate: Duration,
+}
+
+/// Latency budget for one candidate, from detection to submission.
+///
+/// `stage` cuts off at await points (rate-limiter waits, HTTP calls) only; stages making
+/// blocking RPC calls go through `blocking_stage` so the cap holds for them too.
+pub struct Deadline {
+    started: Instant,
+    budget: Duration,
+}
+
+impl Deadline {
+    pub fn new(budget: Duration) -> Self {
+        Self { started: Instant::now(), budget }
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.budget.saturating_sub(self.started.elapsed())
+    }
+
+    /// Whether an optional stage that may take up to `needed` still fits the budget.
+    /// Records a skip when it does not.
+    pub fn allows(&self, stage: &str, needed: Duration) -> bool {
+        let ok = self.remaining() >= needed;
+        if !ok {
+            debug!(
+                stage,
+                remaining_ms = self.remaining().as_millis() as u64,
+                "Skipping stage, latency budget nearly spent"
+            );
+            metrics().inc_labeled("deadline_stage_skipped_total", &[("stage", stage)]);
+        }
+        ok
+    }
+
+    /// Run a stage capped at `cap` and never past the remaining budget.
+    pub async fn stage<T>(&self, stage: &str, cap: Duration, fut: impl Future<Output = Result<T>>) -> Result<T> {
+        let limit = cap.min(self.remaining());
+        match tokio::time::timeout(limit, fut).await {
+            Ok(result) => result,
+            Err(_) => {
+                metrics().inc_labeled("deadline_stage_timeouts_total", &[("stage", stage)]);
+                Err(anyhow!("{stage} stage timed out after {}ms", limit.as_millis()))
+            }
+        }
+    }
+
+    /// Like `stage`, but runs `fut` on the blocking pool, so blocking RPC calls inside it cannot
+    /// hold the caller past the cap. A stage cut off finishes in the background, unobserved.
+    pub async fn blocking_stage<T, F>(&self, stage: &str, cap: Duration, fut: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: Future<Output = Result<T>> + Send + 'static,
+    {
+        let runtime = tokio::runtime::Handle::current();
+        let task = tokio::task::spawn_blocking(move || runtime.block_on(fut));
+        self.stage(stage, cap, async { task.await.with_context(|| format!("{stage} stage panicked"))? }).await
+    }
+}
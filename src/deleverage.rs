@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use carbon_kamino_lending_decoder::{types, KaminoLendingDecoder};
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::kamino::{
+    choose_repay_borrow, choose_withdraw_reserve, redeemable_repay_amount, reserve_vaults, DecodedAccounts,
+    LiquidationCandidate,
+};
+use crate::partial::ObligationView;
+use crate::profit::{
+    estimate_profit_lamports, estimate_seized_lamports, estimate_withdraw_amount, sf_to_f64, sol_price_usd,
+};
+use crate::strategy::StrategyProfile;
+
+/// Which reserve cap was exceeded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeleverageKind {
+    /// Deposits exceed the deposit limit; depositors' collateral is unwound.
+    DepositLimit,
+    /// Borrows exceed the borrow limit; borrowers' debt is repaid.
+    BorrowLimit,
+}
+
+/// A reserve over one of its caps and how far its liquidation threshold has decayed.
+#[derive(Clone, Copy, Debug)]
+pub struct CrossedReserve {
+    pub kind: DeleverageKind,
+    /// Liquidation threshold reduction accrued since the cap was crossed.
+    pub reduction_bps: u64,
+}
+
+/// Reserves in `market` whose deposit or borrow cap was crossed, as of `slot`. The program
+/// lowers the liquidation threshold of affected positions by one bps every
+/// `deleveraging_threshold_slots_per_bps` slots until they are deleveraged.
+pub fn crossed_reserves(
+    reserves: &HashMap<Pubkey, types::Reserve>,
+    market: &Pubkey,
+    slot: u64,
+) -> HashMap<Pubkey, CrossedReserve> {
+    reserves
+        .iter()
+        .filter(|(_, r)| r.lending_market == *market && r.config.deleveraging_threshold_slots_per_bps > 0)
+        .filter_map(|(pk, r)| {
+            let decay = |crossed: u64| slot.saturating_sub(crossed) / r.config.deleveraging_threshold_slots_per_bps;
+            let crossed = match (r.liquidity.deposit_limit_crossed_slot, r.liquidity.borrow_limit_crossed_slot) {
+                (0, 0) => return None,
+                (d, _) if d != 0 => CrossedReserve { kind: DeleverageKind::DepositLimit, reduction_bps: decay(d) },
+                (_, b) => CrossedReserve { kind: DeleverageKind::BorrowLimit, reduction_bps: decay(b) },
+            };
+            Some((*pk, crossed))
+        })
+        .collect()
+}
+
+/// Fully decode obligations that touch a crossed reserve. These are skipped by the health
+/// prefilter because they can be deleveraged while otherwise healthy.
+pub fn decode_affected(
+    accs: &[(Pubkey, Account)],
+    market: &Pubkey,
+    crossed: &HashMap<Pubkey, CrossedReserve>,
+) -> Vec<(Pubkey, types::Obligation)> {
+    if crossed.is_empty() {
+        return Vec::new();
+    }
+    let decoder = KaminoLendingDecoder::default();
+    accs.iter()
+        .filter(|(_, acc)| {
+            ObligationView::new(&acc.data).is_some_and(|view| {
+                view.lending_market() == *market
+                    && view.deposits().chain(view.borrows()).any(|p| crossed.contains_key(&p.reserve))
+            })
+        })
+        .filter_map(|(pk, acc)| decoder.decode_obligation(&acc.data).ok().map(|o| (*pk, o)))
+        .collect()
+}
+
+/// Obligations whose loan-to-value exceeds the decayed liquidation threshold of a crossed
+/// reserve they touch. They are liquidated through the regular instruction, so they come
+/// back as ordinary candidates with a health below 1.0.
+pub fn select_deleverage_candidates(
+    decoded: &DecodedAccounts,
+    obligations: &[(Pubkey, types::Obligation)],
+    market: Pubkey,
+    crossed: &HashMap<Pubkey, CrossedReserve>,
+    strategy: &StrategyProfile,
+) -> Vec<LiquidationCandidate> {
+    let mut candidates = Vec::new();
+    for (pk, obl) in obligations {
+        let deposited = sf_to_f64(obl.deposited_value_sf);
+        if deposited <= 0.0 {
+            continue;
+        }
+        let ltv_bps = sf_to_f64(obl.borrow_factor_adjusted_debt_value_sf) / deposited * 10_000.0;
+        let threshold_bps = (obl.lowest_reserve_deposit_liquidation_ltv * 100) as f64;
+
+        let deposit_hit = obl.deposits.iter().filter(|d| d.amount > 0).find_map(|d| {
+            crossed.get(&d.reserve).filter(|c| c.kind == DeleverageKind::DepositLimit).map(|c| (d.reserve, c))
+        });
+        let borrow_hit = obl.borrows.iter().filter(|b| b.amount > 0).find_map(|b| {
+            crossed.get(&b.reserve).filter(|c| c.kind == DeleverageKind::BorrowLimit).map(|c| (b.reserve, c))
+        });
+        let Some((reserve, hit)) = deposit_hit.or(borrow_hit) else {
+            continue;
+        };
+        if strategy.is_blacklisted(&reserve) {
+            continue;
+        }
+        let decayed_bps = (threshold_bps - hit.reduction_bps as f64).max(0.0);
+        if ltv_bps <= 0.0 || ltv_bps <= decayed_bps {
+            continue;
+        }
+
+        // Unwind the capped side; take the largest redeemable position on the other
+        let repay_reserve = match hit.kind {
+            DeleverageKind::DepositLimit => {
+                choose_repay_borrow(obl, &decoded.reserves, strategy).map(|(reserve, _)| reserve)
+            }
+            DeleverageKind::BorrowLimit => Some(reserve),
+        };
+        let Some(borrow) = repay_reserve.and_then(|r| obl.borrows.iter().find(|b| b.reserve == r && b.amount > 0))
+        else {
+            continue;
+        };
+        let repay_reserve = borrow.reserve;
+        let full_amount = strategy.repay_amount(&repay_reserve, borrow.amount);
+        let withdraw = match hit.kind {
+            DeleverageKind::DepositLimit => {
+                Some((reserve, redeemable_repay_amount(&decoded.reserves, &repay_reserve, &reserve, full_amount)))
+                    .filter(|(_, amount)| *amount > 0)
+            }
+            DeleverageKind::BorrowLimit => {
+                choose_withdraw_reserve(obl, &decoded.reserves, &repay_reserve, full_amount, strategy)
+            }
+        };
+        let Some((withdraw_reserve, amount)) = withdraw else {
+            continue;
+        };
+        candidates.push(LiquidationCandidate {
+            obligation: *pk,
+            market,
+            owner: obl.owner,
+            referrer: (obl.referrer != Pubkey::default()).then_some(obl.referrer),
+            repay_reserve,
+            withdraw_reserve,
+            health: decayed_bps / ltv_bps,
+            repay_amount: amount,
+            expected_withdraw_amount: estimate_withdraw_amount(
+                &decoded.reserves,
+                &repay_reserve,
+                &withdraw_reserve,
+                amount,
+            ),
+            expected_profit_lamports: estimate_profit_lamports(
+                &decoded.reserves,
+                &repay_reserve,
+                &withdraw_reserve,
+                amount,
+            ),
+            expected_seized_lamports: estimate_seized_lamports(
+                &decoded.reserves,
+                &repay_reserve,
+                &withdraw_reserve,
+                amount,
+            ),
+            sol_price_usd: sol_price_usd(&decoded.reserves),
+            repay_cap: (amount < full_amount).then_some(amount),
+            deleveraging: true,
+            vaults: reserve_vaults(&decoded.reserves, &repay_reserve, &withdraw_reserve),
+        });
+    }
+    candidates
+}
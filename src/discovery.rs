@@ -0,0 +1,176 @@
+use std::collections::BTreeSet;
+
+use anyhow::{bail, Context, Result};
+use carbon_kamino_lending_decoder::PROGRAM_ID;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::CompiledInstruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::UiTransactionEncoding;
+use tracing::debug;
+
+use crate::metrics::metrics;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+
+/// Anchor discriminator of InitObligation.
+const INIT_OBLIGATION_DISCRIMINATOR: [u8; 8] = [0xfb, 0x0a, 0xe7, 0x4c, 0x1b, 0x0b, 0x9f, 0x60];
+
+/// Position of the new obligation among InitObligation's accounts.
+const INIT_OBLIGATION_OBLIGATION_INDEX: usize = 2;
+
+/// Most signatures one getSignaturesForAddress call returns.
+const SIGNATURE_PAGE: usize = 1000;
+
+/// Pages read per poll before giving up; a longer backlog needs a full rescan instead.
+const MAX_PAGES: usize = 5;
+
+/// Transactions fetched and parsed per poll, so a busy market cannot stall a scan pass. The
+/// rest of the backlog is left for the following polls.
+const MAX_PARSED_PER_POLL: usize = 100;
+
+/// Market transactions that landed since the previous poll.
+#[derive(Debug, Default)]
+pub struct MarketActivity {
+    /// Obligations created by InitObligation.
+    pub created: Vec<Pubkey>,
+    /// Every account passed to a Kamino instruction, so changed obligations can be re-fetched.
+    pub touched: BTreeSet<Pubkey>,
+}
+
+/// Tails a market's transaction history to find obligations created between full scans.
+pub struct ObligationDiscovery {
+    market: Pubkey,
+    /// Newest signature already processed.
+    newest: Option<Signature>,
+}
+
+impl ObligationDiscovery {
+    pub fn new(market: Pubkey) -> Self {
+        Self { market, newest: None }
+    }
+
+    /// Continue after `newest`, e.g. from another instance's cursor.
+    pub fn resume(market: Pubkey, newest: Option<Signature>) -> Self {
+        Self { market, newest }
+    }
+
+    /// Newest signature already processed.
+    pub fn newest(&self) -> Option<Signature> {
+        self.newest
+    }
+
+    /// Start from the market's newest transaction, so the next poll only covers what lands after.
+    pub async fn mark(&mut self, rpc: &Rpc) -> Result<()> {
+        let page = self.signatures(rpc, None, None, 1).await?;
+        self.newest = page.first().map(|(sig, _)| *sig);
+        Ok(())
+    }
+
+    /// Parse successful market transactions since the previous poll, oldest first and at most
+    /// `MAX_PARSED_PER_POLL` of them. Fails when the backlog exceeds several pages, in which case
+    /// the caller should rescan.
+    pub async fn poll(&mut self, rpc: &Rpc) -> Result<MarketActivity> {
+        let mut pending = Vec::new();
+        let mut before = None;
+        for _ in 0..MAX_PAGES {
+            let page = self.signatures(rpc, before, self.newest, SIGNATURE_PAGE).await?;
+            let full = page.len() == SIGNATURE_PAGE;
+            before = page.last().map(|(sig, _)| *sig);
+            pending.extend(page);
+            if !full {
+                break;
+            }
+        }
+        if pending.len() >= SIGNATURE_PAGE * MAX_PAGES {
+            bail!("More than {} market transactions since the last poll", pending.len());
+        }
+
+        // Failed transactions changed nothing and cost no fetch
+        let mut activity = MarketActivity::default();
+        let (mut parsed, mut covered) = (0, 0);
+        for (signature, failed) in pending.iter().rev() {
+            if !failed {
+                if parsed == MAX_PARSED_PER_POLL {
+                    break;
+                }
+                self.parse(rpc, signature, &mut activity).await?;
+                parsed += 1;
+            }
+            covered += 1;
+            self.newest = Some(*signature);
+        }
+        let backlog = pending.len() - covered;
+        metrics().add("discovery_transactions_total", &[], covered as u64);
+        metrics().add("discovery_obligations_total", &[], activity.created.len() as u64);
+        metrics().set_gauge("discovery_backlog_transactions", &[], backlog as f64);
+        debug!(
+            transactions = covered,
+            parsed,
+            backlog,
+            created = activity.created.len(),
+            touched = activity.touched.len(),
+            "Polled market transaction history"
+        );
+        Ok(activity)
+    }
+
+    /// Signatures newest first, each with whether the transaction failed.
+    async fn signatures(
+        &self,
+        rpc: &Rpc,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> Result<Vec<(Signature, bool)>> {
+        rpc.throttle(RequestClass::Scan).await;
+        let config = GetConfirmedSignaturesForAddress2Config {
+            before,
+            until,
+            limit: Some(limit),
+            commitment: Some(CommitmentConfig::confirmed()),
+        };
+        rpc.get_signatures_for_address_with_config(&self.market, config)
+            .context("Failed to fetch market signatures")?
+            .into_iter()
+            .map(|s| anyhow::Ok((s.signature.parse().context("Invalid signature in history")?, s.err.is_some())))
+            .collect()
+    }
+
+    async fn parse(&self, rpc: &Rpc, signature: &Signature, activity: &mut MarketActivity) -> Result<()> {
+        rpc.throttle(RequestClass::Candidate).await;
+        let fetched = rpc
+            .get_transaction_with_config(
+                signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .with_context(|| format!("Failed to fetch transaction {signature}"))?;
+        let Some(tx) = fetched.transaction.transaction.decode() else {
+            return Ok(());
+        };
+
+        let mut keys: Vec<Pubkey> = tx.message.static_account_keys().to_vec();
+        if let Some(OptionSerializer::Some(loaded)) = fetched.transaction.meta.map(|m| m.loaded_addresses) {
+            keys.extend(loaded.writable.iter().chain(&loaded.readonly).filter_map(|k| k.parse::<Pubkey>().ok()));
+        }
+
+        let program = |ix: &&CompiledInstruction| keys.get(ix.program_id_index as usize) == Some(&PROGRAM_ID);
+        for ix in tx.message.instructions().iter().filter(program) {
+            let accounts: Vec<Pubkey> = ix.accounts.iter().filter_map(|i| keys.get(*i as usize).copied()).collect();
+            if ix.data.starts_with(&INIT_OBLIGATION_DISCRIMINATOR) {
+                if let Some(obligation) = accounts.get(INIT_OBLIGATION_OBLIGATION_INDEX) {
+                    activity.created.push(*obligation);
+                }
+            }
+            activity.touched.extend(accounts);
+        }
+        Ok(())
+    }
+}
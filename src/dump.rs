@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use serde::Serialize;
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::VersionedTransaction;
+use tracing::{info, warn};
+
+use crate::metrics::metrics;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::util::now_millis;
+
+/// One transaction of a failed submission, ready to paste into an explorer simulator.
+#[derive(Debug, Serialize)]
+struct DumpedTx {
+    signature: String,
+    base64: String,
+    simulation_error: Option<String>,
+    logs: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Dump<'a> {
+    obligation: String,
+    /// Where the liquidation failed, e.g. `simulate` or `submit`.
+    stage: &'a str,
+    error: &'a str,
+    transactions: Vec<DumpedTx>,
+    written_at_ms: u64,
+}
+
+/// Writes the exact bytes and simulation logs of failed liquidations to a debug directory.
+pub struct FailureDump {
+    dir: PathBuf,
+}
+
+impl FailureDump {
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create dump directory {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    /// Re-simulate each transaction for its logs and write everything to one JSON file. Failures
+    /// are logged and never propagated.
+    pub async fn record(&self, rpc: &Rpc, obligation: &Pubkey, stage: &str, error: &str, txs: &[VersionedTransaction]) {
+        let mut transactions = Vec::with_capacity(txs.len());
+        for tx in txs {
+            transactions.push(dump_tx(rpc, tx).await);
+        }
+        let dump = Dump { obligation: obligation.to_string(), stage, error, transactions, written_at_ms: now_millis() };
+        let path = self.dir.join(format!("{}-{stage}-{obligation}.json", dump.written_at_ms));
+        let written = serde_json::to_vec_pretty(&dump)
+            .context("Failed to serialize dump")
+            .and_then(|bytes| std::fs::write(&path, bytes).context("Failed to write dump"));
+        match written {
+            Ok(()) => {
+                metrics().inc_labeled("failure_dumps_total", &[("stage", stage)]);
+                info!(obligation = %obligation, path = %path.display(), "Dumped failed liquidation");
+            }
+            Err(e) => warn!(obligation = %obligation, error = %e, "Failed to dump failed liquidation"),
+        }
+    }
+}
+
+async fn dump_tx(rpc: &Rpc, tx: &VersionedTransaction) -> DumpedTx {
+    let base64 =
+        bincode::serialize(tx).map(|bytes| base64::engine::general_purpose::STANDARD.encode(bytes)).unwrap_or_default();
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(CommitmentConfig::processed()),
+        ..Default::default()
+    };
+    rpc.throttle(RequestClass::Candidate).await;
+    let (simulation_error, logs) = match rpc.simulate_transaction_with_config(tx, config) {
+        Ok(result) => (result.value.err.map(|e| e.to_string()), result.value.logs.unwrap_or_default()),
+        Err(e) => (Some(format!("Simulation request failed: {e}")), Vec::new()),
+    };
+    DumpedTx { signature: tx.signatures[0].to_string(), base64, simulation_error, logs }
+}
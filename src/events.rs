@@ -0,0 +1,298 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use tracing::{info, warn};
+
+use crate::kamino::LiquidationCandidate;
+use crate::metrics::metrics;
+use crate::opportunity::SkipReason;
+use crate::tracker::TxOutcome;
+
+/// Written at the start of every event log.
+const MAGIC: &[u8; 8] = b"LIQEVT02";
+
+/// Events buffered between the hot path and the writer thread; further events are dropped.
+const CHANNEL_CAPACITY: usize = 65_536;
+
+/// How long the writer waits for an event before checking whether it should stop.
+const IDLE_POLL: Duration = Duration::from_millis(100);
+
+/// Largest frame the reader accepts, guarding against reading garbage as a length.
+const MAX_FRAME_BYTES: usize = 64 * 1024;
+
+/// Something the scanner or submission path did. Stored by variant index, so new variants go
+/// at the end to keep old logs readable.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Event {
+    /// One scan pass finished.
+    Scan { liquidatable: u32, duration_us: u64 },
+    /// A liquidatable obligation came out of a scan.
+    Candidate { obligation: Pubkey, health: f64, repay_amount: u64, expected_profit_lamports: Option<u64> },
+    /// A liquidation was held back.
+    Skipped { obligation: Pubkey, reason: SkipReason },
+    /// A sender accepted a liquidation.
+    Submitted { obligation: Pubkey, signature: Signature, sender: String, region: String, slot: Option<u64> },
+    /// A tracked signature resolved.
+    Outcome { signature: Signature, outcome: TxOutcome, landed_slot: Option<u64>, elapsed_ms: u64 },
+}
+
+/// One event with the wall-clock time it was recorded at.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventRecord {
+    /// Microseconds since the Unix epoch.
+    pub ts_us: u64,
+    pub event: Event,
+}
+
+/// CSV header matching `EventRecord::csv_row`.
+pub const CSV_HEADER: &str = "ts_us,event,obligation,signature,slot,value,detail";
+
+impl EventRecord {
+    pub fn name(&self) -> &'static str {
+        match self.event {
+            Event::Scan { .. } => "scan",
+            Event::Candidate { .. } => "candidate",
+            Event::Skipped { .. } => "skipped",
+            Event::Submitted { .. } => "submitted",
+            Event::Outcome { .. } => "outcome",
+        }
+    }
+
+    /// Flat JSON object with addresses and signatures in base58.
+    pub fn to_json(&self) -> Value {
+        let mut value = match &self.event {
+            Event::Scan { liquidatable, duration_us } => {
+                json!({ "liquidatable": liquidatable, "duration_us": duration_us })
+            }
+            Event::Candidate { obligation, health, repay_amount, expected_profit_lamports } => json!({
+                "obligation": obligation.to_string(),
+                "health": health,
+                "repay_amount": repay_amount,
+                "expected_profit_lamports": expected_profit_lamports,
+            }),
+            Event::Skipped { obligation, reason } => json!({ "obligation": obligation.to_string(), "reason": reason }),
+            Event::Submitted { obligation, signature, sender, region, slot } => json!({
+                "obligation": obligation.to_string(),
+                "signature": signature.to_string(),
+                "sender": sender,
+                "region": region,
+                "slot": slot,
+            }),
+            Event::Outcome { signature, outcome, landed_slot, elapsed_ms } => json!({
+                "signature": signature.to_string(),
+                "outcome": outcome,
+                "landed_slot": landed_slot,
+                "elapsed_ms": elapsed_ms,
+            }),
+        };
+        value["ts_us"] = json!(self.ts_us);
+        value["event"] = json!(self.name());
+        value
+    }
+
+    /// One CSV line under `CSV_HEADER`; `value` holds each event's main number.
+    pub fn csv_row(&self) -> String {
+        let (obligation, signature, slot, value, detail) = match &self.event {
+            Event::Scan { liquidatable, duration_us } => {
+                (None, None, None, duration_us.to_string(), liquidatable.to_string())
+            }
+            Event::Candidate { obligation, health, expected_profit_lamports, .. } => (
+                Some(obligation),
+                None,
+                None,
+                health.to_string(),
+                expected_profit_lamports.map(|p| p.to_string()).unwrap_or_default(),
+            ),
+            Event::Skipped { obligation, reason } => {
+                (Some(obligation), None, None, String::new(), json!(reason).as_str().unwrap_or_default().to_string())
+            }
+            Event::Submitted { obligation, signature, sender, region, slot } => {
+                (Some(obligation), Some(signature), *slot, String::new(), format!("{sender}/{region}"))
+            }
+            Event::Outcome { signature, outcome, landed_slot, elapsed_ms } => {
+                (None, Some(signature), *landed_slot, elapsed_ms.to_string(), outcome.as_str().to_string())
+            }
+        };
+        format!(
+            "{},{},{},{},{},{value},{detail}",
+            self.ts_us,
+            self.name(),
+            obligation.map(Pubkey::to_string).unwrap_or_default(),
+            signature.map(Signature::to_string).unwrap_or_default(),
+            slot.map(|s| s.to_string()).unwrap_or_default(),
+        )
+    }
+}
+
+/// Appends events to a binary log from a background thread, so recording costs the hot path
+/// one channel send. Each frame is a little-endian u32 length followed by a bincode
+/// `EventRecord`. Events are dropped, and counted, when the writer falls behind.
+///
+/// The writer stops, and recording becomes a no-op, at `shutdown_recorder` or the first
+/// failed write.
+pub struct EventRecorder {
+    tx: SyncSender<EventRecord>,
+    stop: AtomicBool,
+    writer: Mutex<Option<JoinHandle<()>>>,
+}
+
+static RECORDER: OnceLock<EventRecorder> = OnceLock::new();
+
+/// Start recording to `path`, appending when the log already exists.
+pub fn install_recorder(path: &Path) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open event log {}", path.display()))?;
+    if file.metadata()?.len() == 0 {
+        file.write_all(MAGIC).context("Failed to write event log header")?;
+    }
+    let (tx, rx) = mpsc::sync_channel::<EventRecord>(CHANNEL_CAPACITY);
+    if RECORDER.set(EventRecorder { tx, stop: AtomicBool::new(false), writer: Mutex::new(None) }).is_err() {
+        bail!("Event recorder already installed");
+    }
+    let writer = std::thread::Builder::new()
+        .name("event-recorder".to_string())
+        .spawn(move || {
+            if let Err(e) = write_events(file, &rx) {
+                metrics().inc("events_write_errors_total");
+                warn!(error = %e, "Failed to write event log, no longer recording events");
+            }
+        })
+        .context("Failed to start event recorder")?;
+    if let Some(recorder) = RECORDER.get() {
+        *recorder.writer.lock().unwrap() = Some(writer);
+    }
+    info!(path = %path.display(), "Recording events");
+    Ok(())
+}
+
+/// Write queued events until the recorder is shut down or the channel closes, flushing after
+/// each batch. Returns at the first failed write; dropping `rx` then turns `record` into a no-op.
+fn write_events(file: File, rx: &Receiver<EventRecord>) -> io::Result<()> {
+    let mut out = BufWriter::new(file);
+    let mut frame = Vec::with_capacity(256);
+    let stopping = || RECORDER.get().is_some_and(|r| r.stop.load(Ordering::Relaxed));
+    loop {
+        match rx.recv_timeout(IDLE_POLL) {
+            Ok(record) => {
+                write_frame(&mut out, &mut frame, &record)?;
+                while let Ok(record) = rx.try_recv() {
+                    write_frame(&mut out, &mut frame, &record)?;
+                }
+                out.flush()?;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if stopping() {
+            break;
+        }
+    }
+    while let Ok(record) = rx.try_recv() {
+        write_frame(&mut out, &mut frame, &record)?;
+    }
+    out.flush()
+}
+
+fn write_frame(out: &mut impl Write, frame: &mut Vec<u8>, record: &EventRecord) -> io::Result<()> {
+    frame.clear();
+    frame.extend_from_slice(&[0; 4]);
+    if bincode::serialize_into(&mut *frame, record).is_err() {
+        return Ok(());
+    }
+    let len = (frame.len() - 4) as u32;
+    frame[..4].copy_from_slice(&len.to_le_bytes());
+    out.write_all(frame)
+}
+
+/// Stop recording: the writer writes out what is queued, flushes, and exits. Later events are
+/// dropped. Safe to call without a recorder or more than once.
+pub fn shutdown_recorder() {
+    let Some(recorder) = RECORDER.get() else { return };
+    recorder.stop.store(true, Ordering::Relaxed);
+    let writer = recorder.writer.lock().unwrap().take();
+    if let Some(writer) = writer {
+        if writer.join().is_err() {
+            warn!("Event recorder thread panicked");
+        }
+    }
+}
+
+/// Record `event` if a recorder is installed; a no-op otherwise.
+pub fn record(event: Event) {
+    let Some(recorder) = RECORDER.get() else { return };
+    let ts_us = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_micros() as u64).unwrap_or(0);
+    match recorder.tx.try_send(EventRecord { ts_us, event }) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => metrics().inc("events_dropped_total"),
+        Err(TrySendError::Disconnected(_)) => {}
+    }
+}
+
+/// Record the liquidatable candidates out of one scan.
+pub fn record_candidates(candidates: &[&LiquidationCandidate]) {
+    if RECORDER.get().is_none() {
+        return;
+    }
+    for cand in candidates {
+        record(Event::Candidate {
+            obligation: cand.obligation,
+            health: cand.health,
+            repay_amount: cand.repay_amount,
+            expected_profit_lamports: cand.expected_profit_lamports,
+        });
+    }
+}
+
+/// Reads an event log frame by frame. A frame cut short at the end of the file, e.g. by a
+/// crash mid-write, ends the log without an error.
+pub struct EventReader {
+    input: BufReader<File>,
+}
+
+impl EventReader {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to open event log {}", path.display()))?;
+        let mut input = BufReader::new(file);
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic).context("Event log is empty")?;
+        ensure!(&magic == MAGIC, "{} is not an event log", path.display());
+        Ok(Self { input })
+    }
+}
+
+impl Iterator for EventReader {
+    type Item = Result<EventRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut len = [0; 4];
+        match self.input.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return None,
+            Err(e) => return Some(Err(e).context("Failed to read event log")),
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_FRAME_BYTES {
+            return Some(Err(anyhow::anyhow!("Corrupt event log: {len}-byte frame")));
+        }
+        let mut frame = vec![0; len];
+        match self.input.read_exact(&mut frame) {
+            Ok(()) => Some(bincode::deserialize(&frame).context("Corrupt event log frame")),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(e) => Some(Err(e).context("Failed to read event log")),
+        }
+    }
+}
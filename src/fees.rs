@@ -0,0 +1,69 @@
+use std::collections::BTreeSet;
+
+use anyhow::{Context, Result};
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, warn};
+
+use crate::kamino::LiquidationCandidate;
+use crate::metrics::metrics;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::strategy::QuietCuPrice;
+
+/// Most accounts getRecentPrioritizationFees accepts.
+const MAX_FEE_ACCOUNTS: usize = 128;
+
+/// Watches the priority fees paid to write-lock the reserves we liquidate against, and drops our
+/// own compute unit price while nobody else is paying one.
+pub struct FeeMarket {
+    mode: QuietCuPrice,
+    /// Median recent fee, in micro-lamports per compute unit, at or below which the market is quiet.
+    threshold: u64,
+}
+
+impl FeeMarket {
+    pub fn new(mode: QuietCuPrice, threshold: u64) -> Self {
+        Self { mode, threshold }
+    }
+
+    /// Compute unit price for this pass: `configured` unless the fee market around the candidates'
+    /// reserves is quiet. `None` omits the instruction. Falls back to `configured` on RPC errors.
+    pub async fn cu_price(&self, rpc: &Rpc, candidates: &[&LiquidationCandidate], configured: u64) -> Option<u64> {
+        let reserves: BTreeSet<Pubkey> =
+            candidates.iter().flat_map(|c| [c.repay_reserve, c.withdraw_reserve]).collect();
+        let accounts: Vec<Pubkey> = reserves.into_iter().take(MAX_FEE_ACCOUNTS).collect();
+        if accounts.is_empty() {
+            return Some(configured);
+        }
+        let recent = match median_recent_fee(rpc, &accounts).await {
+            Ok(fee) => fee,
+            Err(e) => {
+                warn!(error = %e, "Failed to fetch recent priority fees, paying the configured price");
+                return Some(configured);
+            }
+        };
+        metrics().set_gauge("priority_fee_recent_micro_lamports", &[], recent as f64);
+
+        let (price, label) = match (recent <= self.threshold, self.mode) {
+            (false, _) => (Some(configured), "paid"),
+            (true, QuietCuPrice::Omit) => (None, "omitted"),
+            (true, QuietCuPrice::Zero) => (Some(0), "zero"),
+        };
+        metrics().inc_labeled("cu_price_passes_total", &[("price", label)]);
+        debug!(recent_fee = recent, threshold = self.threshold, price = ?price, "Chose compute unit price");
+        price
+    }
+}
+
+/// Median per-slot priority fee over the recent window for transactions locking `accounts`.
+async fn median_recent_fee(rpc: &Rpc, accounts: &[Pubkey]) -> Result<u64> {
+    rpc.throttle(RequestClass::Candidate).await;
+    let mut fees: Vec<u64> = rpc
+        .get_recent_prioritization_fees(accounts)
+        .context("Failed to get recent prioritization fees")?
+        .into_iter()
+        .map(|f| f.prioritization_fee)
+        .collect();
+    fees.sort_unstable();
+    Ok(fees.get(fees.len() / 2).copied().unwrap_or(0))
+}
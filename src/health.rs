@@ -4,6 +4,15 @@ use solana_client::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
 use std::collections::HashMap;
 
+use crate::partial::ObligationView;
+
+/// Coarse health threshold below which an obligation is fully decoded.
+/// Kept above 1.0 so the raw-byte pass never drops something the full estimate would flag.
+pub const PREFILTER_HEALTH_THRESHOLD: f64 = 1.1;
+
+/// Share of deposits the estimates count against borrows.
+pub const DEPOSIT_WEIGHT: f64 = 0.75;
+
 /// Estimate health factor of an obligation.
 /// Returns a value < 1.0 for liquidatable positions.
 /// Note: This is a simplified off-chain approximation intended to act as a pre-filter.
@@ -21,12 +30,18 @@ pub fn estimate_health(
     }
 
     // Otherwise compute a naive ratio; assume equal prices for rough filtering
-    let hf = if total_borrow == 0.0 {
-        f64::INFINITY
-    } else {
-        (total_deposit * 0.75) / total_borrow
-    };
+    let hf = if total_borrow == 0.0 { f64::INFINITY } else { (total_deposit * DEPOSIT_WEIGHT) / total_borrow };
 
     Ok(hf)
 }
 
+/// Same approximation as `estimate_health`, computed from raw bytes without a full decode.
+pub fn estimate_health_coarse(view: &ObligationView) -> f64 {
+    let total_borrow = view.borrows().map(|b| b.amount as f64).sum::<f64>();
+    let total_deposit = view.deposits().map(|d| d.amount as f64).sum::<f64>();
+
+    if total_borrow == 0.0 {
+        return f64::INFINITY;
+    }
+    (total_deposit * DEPOSIT_WEIGHT) / total_borrow
+}
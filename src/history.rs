@@ -0,0 +1,209 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, info, warn};
+
+use crate::kamino::{decode_accounts, fetch_program_accounts, select_candidates};
+use crate::partial::{ObligationView, RESERVE_DISCRIMINATOR, RESERVE_LENDING_MARKET_OFFSET};
+use crate::rpc::Rpc;
+use crate::strategy::StrategyProfile;
+use crate::util::fetch_slot;
+
+/// Market state saved by the scanner at intervals, one bincode file per slot, so past scans
+/// can be re-run after the fact. Only the market's reserves and obligations with borrows are
+/// kept, the accounts a liquidation decision depends on.
+pub struct SnapshotStore {
+    dir: PathBuf,
+    interval: Duration,
+    /// Newest snapshots kept; older ones are deleted as new ones are written.
+    keep: usize,
+    last: Mutex<Option<Instant>>,
+}
+
+impl SnapshotStore {
+    pub fn open(dir: &Path, interval: Duration, keep: usize) -> Result<Self> {
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create snapshot dir {}", dir.display()))?;
+        Ok(Self { dir: dir.to_path_buf(), interval, keep, last: Mutex::new(None) })
+    }
+
+    /// Whether the interval has passed since the last snapshot was written.
+    pub fn due(&self) -> bool {
+        self.last.lock().unwrap().is_none_or(|at| at.elapsed() >= self.interval)
+    }
+
+    /// Save `market`'s accounts out of a program scan taken at `slot`.
+    pub fn record(&self, slot: u64, market: &Pubkey, accs: &[(Pubkey, Account)]) -> Result<()> {
+        *self.last.lock().unwrap() = Some(Instant::now());
+        let kept: Vec<&(Pubkey, Account)> = accs.iter().filter(|(_, acc)| keep_account(market, &acc.data)).collect();
+        // Written aside and renamed into place, so a crash never leaves a truncated snapshot
+        let path = self.dir.join(format!("{slot}.bin"));
+        let partial = self.dir.join(format!("{slot}.bin.tmp"));
+        let file =
+            File::create(&partial).with_context(|| format!("Failed to create snapshot {}", partial.display()))?;
+        let mut writer = BufWriter::new(file);
+        bincode::serialize_into(&mut writer, &kept).context("Failed to write snapshot")?;
+        writer.flush().context("Failed to write snapshot")?;
+        std::fs::rename(&partial, &path).with_context(|| format!("Failed to move snapshot to {}", path.display()))?;
+        debug!(slot, accounts = kept.len(), "Recorded market snapshot");
+
+        let slots = self.slots()?;
+        for old in slots.iter().take(slots.len().saturating_sub(self.keep)) {
+            if let Err(e) = std::fs::remove_file(self.dir.join(format!("{old}.bin"))) {
+                warn!(slot = old, error = %e, "Failed to delete old snapshot");
+            }
+        }
+        Ok(())
+    }
+
+    /// Slots with a snapshot, oldest first.
+    pub fn slots(&self) -> Result<Vec<u64>> {
+        let entries = std::fs::read_dir(&self.dir).with_context(|| format!("Failed to read {}", self.dir.display()))?;
+        let mut slots: Vec<u64> = entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.path().file_name()?.to_str()?.strip_suffix(".bin")?.parse().ok())
+            .collect();
+        slots.sort_unstable();
+        Ok(slots)
+    }
+
+    /// The newest snapshot at or before `slot`, with the slot it was taken at.
+    pub fn load_at(&self, slot: u64) -> Result<Option<(u64, Vec<(Pubkey, Account)>)>> {
+        let Some(found) = self.slots()?.into_iter().rev().find(|s| *s <= slot) else {
+            return Ok(None);
+        };
+        let path = self.dir.join(format!("{found}.bin"));
+        let file = File::open(&path).with_context(|| format!("Failed to open snapshot {}", path.display()))?;
+        let accs = bincode::deserialize_from(BufReader::new(file))
+            .with_context(|| format!("Failed to parse snapshot {}", path.display()))?;
+        Ok(Some((found, accs)))
+    }
+}
+
+fn keep_account(market: &Pubkey, data: &[u8]) -> bool {
+    match ObligationView::new(data) {
+        Some(view) => view.lending_market() == *market && view.borrows().next().is_some(),
+        None => {
+            data.starts_with(&RESERVE_DISCRIMINATOR)
+                && data.get(RESERVE_LENDING_MARKET_OFFSET..RESERVE_LENDING_MARKET_OFFSET + 32) == Some(market.as_ref())
+        }
+    }
+}
+
+/// Where the historical state came from.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateSource {
+    /// An RPC node whose state is at the requested slot, e.g. one started from its snapshot.
+    ArchiveRpc,
+    SnapshotStore,
+}
+
+/// What the market looked like at a slot, per our own selection.
+#[derive(Debug, Serialize)]
+pub struct SlotReport {
+    pub requested_slot: u64,
+    /// Slot of the account state the selection ran against.
+    pub state_slot: u64,
+    pub source: StateSource,
+    pub obligations: usize,
+    pub liquidatable: Vec<SlotCandidate>,
+    /// Obligations below the watch health that were not yet liquidatable.
+    pub watched: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SlotCandidate {
+    pub obligation: String,
+    pub health: f64,
+    pub repay_reserve: String,
+    pub withdraw_reserve: String,
+    pub repay_amount: u64,
+    pub expected_profit_lamports: Option<u64>,
+}
+
+/// Re-run candidate selection on `market` as of `slot`.
+///
+/// Standard RPC only serves current state, so `archive` is used only when the node reports
+/// its state at exactly `slot`. Otherwise the newest snapshot at or before `slot` is used and
+/// the gap shows in `state_slot`.
+pub async fn scan_at_slot(
+    archive: Option<&Rpc>,
+    snapshots: Option<&SnapshotStore>,
+    market: Pubkey,
+    slot: u64,
+    watch_health: f64,
+    strategy: &StrategyProfile,
+) -> Result<SlotReport> {
+    let mut state = None;
+    if let Some(rpc) = archive {
+        // A program scan is expensive, so only run it on a node that is at the slot
+        match fetch_slot(rpc).await {
+            Ok(at) if at == slot => match fetch_program_accounts(rpc, None, Some(slot)).await {
+                Ok((at, accs)) if at == slot => state = Some((at, accs, StateSource::ArchiveRpc)),
+                Ok((at, _)) => {
+                    info!(node_slot = at, slot, "Archive RPC moved past the requested slot, using snapshots")
+                }
+                Err(e) => warn!(error = %e, "Archive RPC scan failed, using snapshots"),
+            },
+            Ok(at) => info!(node_slot = at, slot, "Archive RPC is not at the requested slot, using snapshots"),
+            Err(e) => warn!(error = %e, "Archive RPC is unreachable, using snapshots"),
+        }
+    }
+    let (state_slot, accs, source) = match state {
+        Some(state) => state,
+        None => {
+            let Some(store) = snapshots else {
+                bail!("No state at slot {slot}: the archive RPC is not at that slot and no snapshot dir is set");
+            };
+            let Some((at, accs)) = store.load_at(slot)? else {
+                let oldest = store.slots()?.first().copied();
+                bail!("No snapshot at or before slot {slot} (oldest is {oldest:?})");
+            };
+            (at, accs, StateSource::SnapshotStore)
+        }
+    };
+
+    // Selection takes a client but reads only the accounts given to it
+    let offline = RpcClient::new_mock("fails".to_string());
+    let decoded = decode_accounts(&accs, &market);
+    let selected = select_candidates(&decoded, market, &offline, watch_health.max(1.0), strategy);
+    let (liquidatable, watched): (Vec<_>, Vec<_>) = selected.into_iter().partition(|c| c.is_liquidatable());
+    let mut liquidatable: Vec<SlotCandidate> = liquidatable
+        .into_iter()
+        .map(|c| SlotCandidate {
+            obligation: c.obligation.to_string(),
+            health: c.health,
+            repay_reserve: c.repay_reserve.to_string(),
+            withdraw_reserve: c.withdraw_reserve.to_string(),
+            repay_amount: c.repay_amount,
+            expected_profit_lamports: c.expected_profit_lamports,
+        })
+        .collect();
+    liquidatable.sort_by_key(|c| std::cmp::Reverse(c.expected_profit_lamports.unwrap_or(0)));
+    Ok(SlotReport {
+        requested_slot: slot,
+        state_slot,
+        source,
+        obligations: decoded.obligations.len(),
+        liquidatable,
+        watched: watched.len(),
+    })
+}
+
+/// Save a snapshot out of `accs` when one is due, stamped with the slot the scan read them at.
+pub fn record_if_due(store: &SnapshotStore, slot: u64, market: &Pubkey, accs: &[(Pubkey, Account)]) {
+    if !store.due() {
+        return;
+    }
+    if let Err(e) = store.record(slot, market, accs) {
+        warn!(error = %e, "Failed to record market snapshot");
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::Result;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+
+/// SPL Memo program v2.
+pub const MEMO_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+/// What a hook sees of the transaction it is adding to.
+pub struct HookContext<'a> {
+    pub payer: Pubkey,
+    /// The transaction's own instructions, without compute budget or tip.
+    pub instructions: &'a [Instruction],
+}
+
+impl HookContext<'_> {
+    /// Whether any of the transaction's instructions calls `program`.
+    pub fn calls(&self, program: &Pubkey) -> bool {
+        self.instructions.iter().any(|ix| ix.program_id == *program)
+    }
+}
+
+/// Adds instructions to transactions as they are built, e.g. an accounting memo, a guard
+/// program that checks balances after the liquidation, or a CPI into a vault. They land after
+/// the transaction's own instructions and before the tip. An error fails the build, so a guard
+/// that cannot be added never lets a transaction go out without it.
+pub trait InstructionHook: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn instructions(&self, ctx: &HookContext) -> Result<Vec<Instruction>>;
+}
+
+/// A fixed instruction, optionally only added to transactions calling one of `only_with`.
+pub struct StaticHook {
+    pub name: String,
+    pub instruction: Instruction,
+    /// Programs the transaction must call for the hook to apply; empty applies it everywhere.
+    pub only_with: Vec<Pubkey>,
+}
+
+impl InstructionHook for StaticHook {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn instructions(&self, ctx: &HookContext) -> Result<Vec<Instruction>> {
+        let applies = self.only_with.is_empty() || self.only_with.iter().any(|p| ctx.calls(p));
+        Ok(applies.then(|| self.instruction.clone()).into_iter().collect())
+    }
+}
+
+/// Memo instruction carrying `text`, with no required signers.
+pub fn memo_instruction(text: &str) -> Instruction {
+    Instruction { program_id: MEMO_PROGRAM_ID, accounts: Vec::new(), data: text.as_bytes().to_vec() }
+}
+
+/// Instructions from every hook for one transaction, in hook order.
+pub fn run_hooks(hooks: &[Box<dyn InstructionHook>], ctx: &HookContext) -> Result<Vec<Instruction>> {
+    let mut out = Vec::new();
+    for hook in hooks {
+        let ixs = hook.instructions(ctx).map_err(|e| e.context(format!("Instruction hook {} failed", hook.name())))?;
+        out.extend(ixs);
+    }
+    Ok(out)
+}
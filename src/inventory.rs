@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use solana_account_decoder::UiAccountData;
+use solana_client::rpc_request::TokenAccountsFilter;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, warn};
+
+use crate::pda::LiquidatorAccounts;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+
+/// SPL Token-2022 program, which some reserve mints use.
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// One token account held by a wallet.
+#[derive(Clone, Debug)]
+pub struct OwnedTokenAccount {
+    pub address: Pubkey,
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub amount: u64,
+}
+
+/// Every SPL Token and Token-2022 account `owner` holds, with the newest slot they were read at.
+pub async fn owned_token_accounts(rpc: &Rpc, owner: &Pubkey) -> Result<(u64, Vec<OwnedTokenAccount>)> {
+    let mut slot = 0;
+    let mut out = Vec::new();
+    for token_program in [spl_token::ID, TOKEN_2022_PROGRAM_ID] {
+        rpc.throttle(RequestClass::Candidate).await;
+        let accounts = rpc
+            .get_token_accounts_by_owner_with_commitment(
+                owner,
+                TokenAccountsFilter::ProgramId(token_program),
+                CommitmentConfig::confirmed(),
+            )
+            .with_context(|| format!("Failed to fetch token accounts of {owner}"))?;
+        slot = slot.max(accounts.context.slot);
+        for keyed in accounts.value {
+            let UiAccountData::Json(parsed) = keyed.account.data else { continue };
+            let info = &parsed.parsed["info"];
+            let address = keyed.pubkey.parse().ok();
+            let mint = info["mint"].as_str().and_then(|m| m.parse().ok());
+            let amount = info["tokenAmount"]["amount"].as_str().and_then(|a| a.parse().ok());
+            if let (Some(address), Some(mint), Some(amount)) = (address, mint, amount) {
+                out.push(OwnedTokenAccount { address, mint, token_program, amount });
+            }
+        }
+    }
+    Ok((slot, out))
+}
+
+/// Balances of the token accounts liquidations repay from, by mint, refreshed at intervals so
+/// repay selection can favour debt we can cover without acquiring the asset first.
+pub struct Inventory {
+    interval: Duration,
+    held: HashMap<Pubkey, u64>,
+    last_refresh: Option<Instant>,
+}
+
+impl Inventory {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, held: HashMap::new(), last_refresh: None }
+    }
+
+    /// Re-read balances if the interval has elapsed; on failure the previous balances are kept.
+    pub async fn refresh_if_due(&mut self, rpc: &Rpc, liquidator: &LiquidatorAccounts) {
+        if self.last_refresh.is_some_and(|t| t.elapsed() < self.interval) {
+            return;
+        }
+        self.last_refresh = Some(Instant::now());
+        match owned_token_accounts(rpc, &liquidator.owner).await {
+            Ok((_, accounts)) => {
+                // Only the account a liquidation would actually repay from counts for its mint
+                self.held = accounts
+                    .into_iter()
+                    .filter(|a| a.amount > 0 && liquidator.token_account(&a.mint, &a.token_program) == a.address)
+                    .map(|a| (a.mint, a.amount))
+                    .collect();
+                debug!(mints = self.held.len(), "Refreshed liquidator inventory");
+            }
+            Err(e) => warn!(error = %e, "Failed to refresh liquidator inventory"),
+        }
+    }
+
+    pub fn held(&self) -> &HashMap<Pubkey, u64> {
+        &self.held
+    }
+}
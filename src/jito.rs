@@ -1,8 +1,25 @@
-use anyhow::{Context, Result};
+use std::time::{Duration, Instant};
+
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context, Result};
+use hyper_util::rt::TokioIo;
+use jito_grpc_client::grpc::bundle::Bundle;
+use jito_grpc_client::grpc::packet::{Meta, Packet};
+use jito_grpc_client::grpc::searcher::searcher_service_client::SearcherServiceClient;
+use jito_grpc_client::grpc::searcher::{NextScheduledLeaderRequest, SendBundleRequest};
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use solana_sdk::signature::Signature;
 use solana_sdk::transaction::VersionedTransaction;
+use tokio::net::TcpStream;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Uri};
+use tracing::{debug, info, warn};
+
+use crate::contention::MAX_BUNDLE_TXS;
+use crate::metrics::metrics;
+use crate::proxy::Proxy;
 
 /// Known Jito tip accounts (mainnet-beta).
 pub const JITO_TIP_ACCOUNTS: [&str; 8] = [
@@ -16,6 +33,55 @@ pub const JITO_TIP_ACCOUNTS: [&str; 8] = [
     "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
 ];
 
+/// Known mainnet block-engine regions and their endpoints.
+pub const JITO_REGIONS: [(&str, &str); 8] = [
+    ("amsterdam", "https://amsterdam.mainnet.block-engine.jito.wtf"),
+    ("dublin", "https://dublin.mainnet.block-engine.jito.wtf"),
+    ("frankfurt", "https://frankfurt.mainnet.block-engine.jito.wtf"),
+    ("london", "https://london.mainnet.block-engine.jito.wtf"),
+    ("ny", "https://ny.mainnet.block-engine.jito.wtf"),
+    ("slc", "https://slc.mainnet.block-engine.jito.wtf"),
+    ("singapore", "https://singapore.mainnet.block-engine.jito.wtf"),
+    ("tokyo", "https://tokyo.mainnet.block-engine.jito.wtf"),
+];
+
+/// Request and connect timeout when none is configured.
+const DEFAULT_TIMEOUT_SECS: u64 = 2;
+
+/// A region only replaces the current one when it is at least this much faster.
+const MIGRATION_MARGIN: f64 = 0.8;
+
+/// Round-trip probe result for one region; `rtt` is none when unreachable.
+#[derive(Clone, Debug)]
+pub struct RegionProbe {
+    pub region: &'static str,
+    pub endpoint: &'static str,
+    pub rtt: Option<Duration>,
+}
+
+/// Measure TCP connect time to every known region concurrently.
+pub async fn probe_regions(timeout: Duration) -> Vec<RegionProbe> {
+    let probes = JITO_REGIONS.iter().map(|(region, endpoint)| async move {
+        let host = endpoint.trim_start_matches("https://");
+        let started = Instant::now();
+        let rtt = match tokio::time::timeout(timeout, TcpStream::connect((host, 443))).await {
+            Ok(Ok(_)) => Some(started.elapsed()),
+            _ => None,
+        };
+        RegionProbe { region, endpoint, rtt }
+    });
+    let results = futures::future::join_all(probes).await;
+    for probe in &results {
+        match probe.rtt {
+            Some(rtt) => {
+                metrics().set_gauge("jito_region_rtt_ms", &[("region", probe.region)], rtt.as_secs_f64() * 1000.0)
+            }
+            None => metrics().set_gauge("jito_region_rtt_ms", &[("region", probe.region)], -1.0),
+        }
+    }
+    results
+}
+
 /// Simple holder for selected tip account.
 pub struct TipAccount {
     pub pubkey: solana_sdk::pubkey::Pubkey,
@@ -35,30 +101,192 @@ impl TipAccount {
     }
 }
 
-/// Wrapper around jito-grpc-client to submit bundles and get UUIDs.
+/// How to reach the block engine from restricted or co-located networks.
+#[derive(Clone, Debug, Default)]
+pub struct JitoConnectOptions {
+    /// PEM CA bundle trusted instead of the system roots.
+    pub ca_cert: Option<PathBuf>,
+    /// TLS server name to verify, for endpoints addressed by IP.
+    pub tls_domain: Option<String>,
+    /// HTTP CONNECT or SOCKS5 proxy the connection is tunnelled through.
+    pub proxy: Option<Proxy>,
+    /// Local address to connect from; also selects IPv4 or IPv6.
+    pub local_address: Option<IpAddr>,
+    /// HTTP/2 keep-alive ping interval.
+    pub keepalive_interval: Option<Duration>,
+    /// How long to wait for a keep-alive ping acknowledgement.
+    pub keepalive_timeout: Option<Duration>,
+}
+
+/// Slot the block engine is on and the next slot led by a Jito-connected validator.
+#[derive(Clone, Copy, Debug)]
+pub struct NextLeader {
+    pub current_slot: u64,
+    pub leader_slot: u64,
+}
+
+/// Block-engine gRPC client submitting bundles and returning their UUIDs. Clones share the
+/// connection.
+#[derive(Clone)]
 pub struct JitoSender {
-    client: jito_grpc_client::JitoClient,
+    client: SearcherServiceClient<Channel>,
+    timeout: Duration,
+    options: JitoConnectOptions,
+    /// Region currently connected to, once known; pinned endpoints never migrate.
+    region: Option<&'static str>,
+    pinned: bool,
 }
 
 impl JitoSender {
-    /// Create with dynamic region selection or explicit endpoint.
-    pub async fn new(endpoint: Option<String>, timeout_secs: Option<u64>) -> Result<Self> {
-        let client = if let Some(ep) = endpoint {
-            jito_grpc_client::JitoClient::new(Box::leak(ep.into_boxed_str()), timeout_secs)
-                .await
-                .context("Failed to initialize Jito client")?
-        } else {
-            jito_grpc_client::JitoClient::new_dynamic_region(timeout_secs)
-                .await
-                .context("Failed to initialize Jito dynamic client")?
+    /// Connect to an explicit endpoint, or to the fastest reachable region.
+    pub async fn new(endpoint: Option<String>, timeout_secs: Option<u64>, options: JitoConnectOptions) -> Result<Self> {
+        let timeout = Duration::from_secs(timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+        let pinned = endpoint.is_some();
+        let (endpoint, region) = match endpoint {
+            Some(ep) => (ep, None),
+            None => {
+                // Probes connect directly, so behind a proxy they may all fail; fall back to the first region
+                let probes = probe_regions(timeout).await;
+                let (region, ep) = probes
+                    .iter()
+                    .filter(|p| p.rtt.is_some())
+                    .min_by_key(|p| p.rtt)
+                    .map_or(JITO_REGIONS[0], |p| (p.region, p.endpoint));
+                (ep.to_string(), Some(region))
+            }
         };
-        Ok(Self { client })
+        let client = connect(&endpoint, timeout, &options)
+            .await
+            .with_context(|| format!("Failed to initialize Jito client for {endpoint}"))?;
+        Ok(Self { client, timeout, options, region, pinned })
+    }
+
+    /// Region currently in use, if selected by probing.
+    pub fn region(&self) -> Option<&'static str> {
+        self.region
+    }
+
+    /// Probe all regions and reconnect to the fastest reachable one when it clearly beats the
+    /// current region. No-op for an explicitly configured endpoint.
+    pub async fn probe_and_migrate(&mut self, probe_timeout: Duration) -> Result<()> {
+        if self.pinned {
+            return Ok(());
+        }
+        let probes = probe_regions(probe_timeout).await;
+        let Some(best) = probes.iter().filter(|p| p.rtt.is_some()).min_by_key(|p| p.rtt) else {
+            warn!("No Jito region reachable during probe");
+            return Ok(());
+        };
+        let best_rtt = best.rtt.unwrap_or_default();
+
+        let current_rtt = self.region.and_then(|r| probes.iter().find(|p| p.region == r)).and_then(|p| p.rtt);
+        let migrate = match current_rtt {
+            Some(rtt) => {
+                best.region != self.region.unwrap_or_default()
+                    && best_rtt.as_secs_f64() < rtt.as_secs_f64() * MIGRATION_MARGIN
+            }
+            // Unknown or unreachable current region
+            None => self.region != Some(best.region),
+        };
+        debug!(best = best.region, best_rtt_ms = best_rtt.as_millis() as u64, current = ?self.region, "Probed Jito regions");
+        if !migrate {
+            return Ok(());
+        }
+
+        let client = connect(best.endpoint, self.timeout, &self.options)
+            .await
+            .with_context(|| format!("Failed to connect to Jito region {}", best.region))?;
+        info!(from = ?self.region, to = best.region, rtt_ms = best_rtt.as_millis() as u64, "Migrating Jito region");
+        metrics().inc_labeled("jito_region_migrations_total", &[("region", best.region)]);
+        self.client = client;
+        self.region = Some(best.region);
+        Ok(())
     }
 
     /// Send bundle and return UUID string.
-    pub async fn send(&mut self, txs: &[VersionedTransaction]) -> Result<String> {
-        let uuid = self.client.send(txs).await.context("Jito send failed")?;
-        Ok(uuid)
+    pub async fn send(&self, txs: &[VersionedTransaction]) -> Result<String> {
+        ensure!(
+            txs.len() <= MAX_BUNDLE_TXS,
+            "Bundle has {} transactions, over the limit of {MAX_BUNDLE_TXS}",
+            txs.len()
+        );
+        let packets = txs
+            .iter()
+            .map(|tx| {
+                let data = bincode::serialize(tx).context("Failed to serialize bundle transaction")?;
+                let meta = Meta {
+                    size: data.len() as u64,
+                    addr: "0.0.0.0".to_string(),
+                    port: 0,
+                    flags: None,
+                    sender_stake: 0,
+                };
+                Ok(Packet { data, meta: Some(meta) })
+            })
+            .collect::<Result<_>>()?;
+        let request = SendBundleRequest { bundle: Some(Bundle { header: None, packets }) };
+        // Clones share the channel, so sending needs no exclusive borrow
+        let response = self.client.clone().send_bundle(request).await.context("Jito send failed")?;
+        Ok(response.into_inner().uuid)
+    }
+
+    /// Next scheduled Jito leader in the connected region.
+    pub async fn next_leader(&mut self) -> Result<NextLeader> {
+        let request = NextScheduledLeaderRequest { regions: Vec::new() };
+        let response = self.client.get_next_scheduled_leader(request).await.context("Jito next leader query failed")?;
+        let response = response.into_inner();
+        Ok(NextLeader { current_slot: response.current_slot, leader_slot: response.next_leader_slot })
     }
 }
 
+/// Open a gRPC channel to the block engine with the configured TLS, proxy and keep-alive settings.
+async fn connect(
+    endpoint: &str,
+    timeout: Duration,
+    options: &JitoConnectOptions,
+) -> Result<SearcherServiceClient<Channel>> {
+    let mut tls = ClientTlsConfig::new();
+    tls = match &options.ca_cert {
+        Some(path) => {
+            let pem =
+                std::fs::read(path).with_context(|| format!("Failed to read Jito CA bundle {}", path.display()))?;
+            tls.ca_certificate(Certificate::from_pem(pem))
+        }
+        None => tls.with_native_roots(),
+    };
+    if let Some(domain) = &options.tls_domain {
+        tls = tls.domain_name(domain.clone());
+    }
+
+    let mut channel = Endpoint::from_shared(endpoint.to_string())
+        .context("Invalid Jito endpoint")?
+        .tls_config(tls)
+        .context("Invalid Jito TLS configuration")?
+        .tcp_nodelay(true)
+        .timeout(timeout)
+        .connect_timeout(timeout)
+        .local_address(options.local_address);
+    if let Some(interval) = options.keepalive_interval {
+        channel = channel.http2_keep_alive_interval(interval).keep_alive_while_idle(true).tcp_keepalive(Some(interval));
+    }
+    if let Some(keepalive_timeout) = options.keepalive_timeout {
+        channel = channel.keep_alive_timeout(keepalive_timeout);
+    }
+
+    let channel = match options.proxy.clone() {
+        None => channel.connect().await?,
+        Some(proxy) => {
+            let local_address = options.local_address;
+            let connector = tower::service_fn(move |uri: Uri| {
+                let proxy = proxy.clone();
+                async move {
+                    let host = uri.host().unwrap_or_default().trim_matches(['[', ']']).to_string();
+                    let port = uri.port_u16().unwrap_or(443);
+                    proxy.tunnel(&host, port, local_address).await.map(TokioIo::new)
+                }
+            });
+            channel.connect_with_connector(connector).await?
+        }
+    };
+    Ok(SearcherServiceClient::new(channel))
+}
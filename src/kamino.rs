@@ -1,107 +1,560 @@
-use anyhow::{Context, Result};
-use carbon_kamino_lending_decoder::{KaminoLendingDecoder, PROGRAM_ID};
+use std::collections::HashMap;
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+use carbon_kamino_lending_decoder::{types, KaminoLendingDecoder, PROGRAM_ID};
+use rayon::prelude::*;
+use serde_json::json;
+use solana_account_decoder::UiAccountEncoding;
 use solana_client::rpc_client::RpcClient;
-use solana_sdk::pubkey::Pubkey;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::RpcFilterType;
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_response::{OptionalContext, RpcKeyedAccount};
+use solana_sdk::account::Account;
 use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use tracing::debug;
 
-use crate::health::estimate_health;
+use crate::deleverage::{crossed_reserves, decode_affected, select_deleverage_candidates};
+use crate::health::{estimate_health, estimate_health_coarse, PREFILTER_HEALTH_THRESHOLD};
+use crate::metrics::metrics;
+use crate::partial::ObligationView;
+use crate::pda::{LiquidatorAccounts, MarketAccounts, ReserveVaults};
+use crate::profit::{
+    estimate_profit_lamports, estimate_seized_lamports, estimate_withdraw_amount, sol_price_usd, token_price_usd,
+    value_usd,
+};
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::scan::ProgramScanner;
+use crate::strategy::{RepayPreference, RepaySelection, StrategyProfile};
+use crate::util::now_millis;
 
 /// Minimal liquidation candidate data needed for instruction building.
+#[derive(Clone, Debug)]
 pub struct LiquidationCandidate {
     pub obligation: Pubkey,
     pub market: Pubkey,
+    /// Wallet owning the obligation.
+    pub owner: Pubkey,
+    /// Referrer the obligation was opened through, whose token states refresh and liquidation take.
+    pub referrer: Option<Pubkey>,
     pub repay_reserve: Pubkey,
     pub withdraw_reserve: Pubkey,
+    /// Estimated health factor at scan time.
+    pub health: f64,
+    /// Liquidity amount to repay, sized from the scanned borrow.
+    pub repay_amount: u64,
+    /// Expected withdraw-reserve liquidity received, when reserve prices are known.
+    pub expected_withdraw_amount: Option<u64>,
+    /// Estimated profit before tips and fees, when reserve prices are known.
+    pub expected_profit_lamports: Option<u64>,
+    /// Estimated value of the seized collateral in lamports, when reserve prices are known.
+    pub expected_seized_lamports: Option<u64>,
+    /// SOL price the lamport estimates were made at, from the market's wrapped-SOL reserve.
+    pub sol_price_usd: Option<f64>,
+    /// Upper bound on the repay amount when the withdraw reserve's available liquidity
+    /// cannot redeem the full seizure.
+    pub repay_cap: Option<u64>,
+    /// Liquidatable because a reserve it touches is over its cap, not because of its health.
+    pub deleveraging: bool,
+    /// The two reserves' vaults, from the scan that found the candidate.
+    pub vaults: Option<ReserveVaults>,
 }
 
-/// Scan Kamino program accounts and return liquidatable obligations for a given market.
-pub async fn find_liquidation_candidates(rpc: &RpcClient, market_addr: &str) -> Result<Vec<LiquidationCandidate>> {
-    let market: Pubkey = market_addr.parse()?;
-    let decoder = KaminoLendingDecoder::default();
+impl LiquidationCandidate {
+    /// Whether the obligation can be liquidated now (as opposed to only being watched).
+    pub fn is_liquidatable(&self) -> bool {
+        self.health < 1.0
+    }
+}
 
-    // Fetch all accounts owned by the program and filter obligations
-    let accs = rpc
-        .get_program_accounts(&PROGRAM_ID)
-        .context("Failed to get Kamino program accounts")?;
+/// Program accounts decoded and split by type.
+pub struct DecodedAccounts {
+    pub reserves: HashMap<Pubkey, types::Reserve>,
+    pub obligations: Vec<(Pubkey, types::Obligation)>,
+    /// The market's liquidation limits, when its lending market account was among the accounts.
+    pub limits: Option<LiquidationLimits>,
+}
 
-    let mut reserves = Vec::new();
-    let mut obligations = Vec::new();
+enum DecodedAccount {
+    Reserve(types::Reserve),
+    Obligation(types::Obligation),
+    Market(LiquidationLimits),
+}
 
-    for (pk, acc) in accs.iter() {
-        // Try decode reserve first
-        if let Ok(reserve) = decoder.decode_reserve(&acc.data) {
-            reserves.push((pk, reserve));
-            continue;
-        }
-        // Try decode obligation
-        if let Ok(obligation) = decoder.decode_obligation(&acc.data) {
-            obligations.push((pk, obligation));
-            continue;
+/// Market-wide bounds on how much of a borrow one liquidation may repay.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LiquidationLimits {
+    /// Share of a borrow one liquidation may repay, in percent; 0 when unset.
+    pub close_factor_pct: u8,
+    /// Most debt value in USD one liquidation may repay; 0 when unset.
+    pub max_debt_value_at_once_usd: u64,
+    /// Borrows worth less than this in USD may be repaid in full.
+    pub min_full_liquidation_value_usd: u64,
+}
+
+impl LiquidationLimits {
+    /// Read the limits from a lending market account.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let market = KaminoLendingDecoder::default().decode_lending_market(data).ok()?;
+        Some(Self {
+            close_factor_pct: market.liquidation_max_debt_close_factor_pct,
+            max_debt_value_at_once_usd: market.max_liquidatable_debt_market_value_at_once,
+            min_full_liquidation_value_usd: market.min_full_liquidation_value_threshold,
+        })
+    }
+
+    /// Fetch the limits of `market`.
+    pub async fn fetch(rpc: &Rpc, market: &Pubkey) -> Result<Self> {
+        rpc.throttle(RequestClass::Candidate).await;
+        let acc = rpc.get_account(market).context("Failed to fetch lending market")?;
+        Self::decode(&acc.data).context("Failed to decode lending market")
+    }
+
+    /// Largest part of `borrow_amount` of `repay` one liquidation may repay.
+    pub fn max_repay_amount(&self, repay: &types::Reserve, borrow_amount: u64) -> u64 {
+        if value_usd(repay, borrow_amount) < self.min_full_liquidation_value_usd as f64 {
+            return borrow_amount;
         }
+        let by_factor = match self.close_factor_pct {
+            0 => borrow_amount,
+            pct => (borrow_amount as u128 * u128::from(pct.min(100)) / 100) as u64,
+        };
+        let price = token_price_usd(repay);
+        let by_value = match self.max_debt_value_at_once_usd > 0 && price > 0.0 {
+            true => {
+                (self.max_debt_value_at_once_usd as f64 / price * 10f64.powi(repay.liquidity.mint_decimals as i32))
+                    as u64
+            }
+            false => u64::MAX,
+        };
+        by_factor.min(by_value)
     }
+}
+
+/// Decode and classify raw program accounts in parallel on the current rayon pool.
+/// Obligations are pre-filtered from raw bytes and only fully decoded when they belong
+/// to `market` and look close to liquidatable.
+pub fn decode_accounts(accs: &[(Pubkey, Account)], market: &Pubkey) -> DecodedAccounts {
+    let decoder = KaminoLendingDecoder::default();
+
+    let decoded: Vec<(Pubkey, DecodedAccount)> = accs
+        .par_iter()
+        .filter_map(|(pk, acc)| {
+            // Cheap raw-byte pass for obligations
+            if let Some(view) = ObligationView::new(&acc.data) {
+                if view.lending_market() != *market || estimate_health_coarse(&view) >= PREFILTER_HEALTH_THRESHOLD {
+                    return None;
+                }
+                return decoder
+                    .decode_obligation(&acc.data)
+                    .ok()
+                    .map(|obligation| (*pk, DecodedAccount::Obligation(obligation)));
+            }
+            if pk == market {
+                return LiquidationLimits::decode(&acc.data).map(|limits| (*pk, DecodedAccount::Market(limits)));
+            }
+            // Everything else we care about is a reserve
+            decoder.decode_reserve(&acc.data).ok().map(|reserve| (*pk, DecodedAccount::Reserve(reserve)))
+        })
+        .collect();
 
-    // Index reserves by pubkey for health computation
-    use std::collections::HashMap;
-    let mut reserve_map = HashMap::new();
-    for (pk, r) in reserves.into_iter() {
-        reserve_map.insert(*pk, r);
+    let mut out = DecodedAccounts { reserves: HashMap::new(), obligations: Vec::new(), limits: None };
+    for (pk, acc) in decoded {
+        match acc {
+            DecodedAccount::Reserve(r) => {
+                out.reserves.insert(pk, r);
+            }
+            DecodedAccount::Obligation(o) => out.obligations.push((pk, o)),
+            DecodedAccount::Market(limits) => out.limits = Some(limits),
+        }
     }
+    out
+}
 
+/// Fetch the Kamino lending program's accounts matching `filters`, all of them without, with the
+/// slot the node served them at. With `min_context_slot` the node refuses to answer from an
+/// older state.
+pub async fn fetch_program_accounts(
+    rpc: &Rpc,
+    filters: Option<Vec<RpcFilterType>>,
+    min_context_slot: Option<u64>,
+) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+    let config = RpcProgramAccountsConfig {
+        filters,
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            min_context_slot,
+            ..Default::default()
+        },
+        with_context: Some(true),
+        ..Default::default()
+    };
+    rpc.throttle_gpa().await;
+    let response: OptionalContext<Vec<RpcKeyedAccount>> = rpc
+        .send(RpcRequest::GetProgramAccounts, json!([PROGRAM_ID.to_string(), config]))
+        .context("Failed to get Kamino program accounts")?;
+    let (slot, keyed) = match response {
+        OptionalContext::Context(response) => (response.context.slot, response.value),
+        // Without a context there is no telling which slot the accounts are from
+        OptionalContext::NoContext(_) => bail!("RPC does not report a context slot for program accounts"),
+    };
+    let accs = keyed.into_iter().filter_map(|k| Some((k.pubkey.parse().ok()?, k.account.decode()?))).collect();
+    Ok((slot, accs))
+}
+
+/// Pick obligations in `market` with health below `max_health` out of already decoded accounts.
+/// Passing 1.0 returns only liquidatable positions; higher values include the watchlist.
+pub fn select_candidates(
+    decoded: &DecodedAccounts,
+    market: Pubkey,
+    rpc: &RpcClient,
+    max_health: f64,
+    strategy: &StrategyProfile,
+) -> Vec<LiquidationCandidate> {
     let mut candidates = Vec::new();
-    for (pk, obl) in obligations.into_iter() {
+    for (pk, obl) in decoded.obligations.iter() {
         // Filter by market
-        if obl.lending_market != market { continue; }
+        if obl.lending_market != market {
+            continue;
+        }
 
         // Estimate health
-        if let Ok(h) = estimate_health(&obl, &reserve_map, rpc) {
-            if h < 1.0 {
-                // Choose largest borrow and largest collateral
-                let repay_reserve = obl.borrows.iter().max_by_key(|b| b.amount).map(|b| b.reserve).unwrap_or_default();
-                let withdraw_reserve = obl.deposits.iter().max_by_key(|d| d.amount).map(|d| d.reserve).unwrap_or_default();
+        if let Ok(h) = estimate_health(obl, &decoded.reserves, rpc) {
+            if h < max_health {
+                // Choose the borrow per the profile's repay selection, skipping blacklisted reserves
+                let borrow = choose_repay_borrow(obl, &decoded.reserves, strategy);
+                let repay_reserve = borrow.map(|(reserve, _)| reserve).unwrap_or_default();
+                let requested = borrow.map(|(reserve, amount)| strategy.repay_amount(&reserve, amount)).unwrap_or(0);
+                // Klend rejects repays over the market's close factor or per-liquidation value cap
+                let market_cap = borrow.zip(decoded.limits).and_then(|((reserve, amount), limits)| {
+                    decoded.reserves.get(&reserve).map(|r| limits.max_repay_amount(r, amount))
+                });
+                let full_amount = requested.min(market_cap.unwrap_or(u64::MAX));
+                // Seized collateral is redeemed in the same instruction, so the reserve must hold enough liquidity
+                let (withdraw_reserve, amount) =
+                    choose_withdraw_reserve(obl, &decoded.reserves, &repay_reserve, full_amount, strategy)
+                        .unwrap_or_default();
                 if repay_reserve != Pubkey::default() && withdraw_reserve != Pubkey::default() {
+                    let repay_cap = (amount < requested).then_some(amount);
+                    if repay_cap.is_some() {
+                        debug!(
+                            obligation = %pk,
+                            withdraw_reserve = %withdraw_reserve,
+                            amount,
+                            requested,
+                            "Repay capped by market limits, withdraw reserve liquidity or withdrawal cap"
+                        );
+                        metrics().inc("candidates_liquidity_capped_total");
+                    }
+                    let expected_profit_lamports =
+                        estimate_profit_lamports(&decoded.reserves, &repay_reserve, &withdraw_reserve, amount);
+                    let expected_seized_lamports =
+                        estimate_seized_lamports(&decoded.reserves, &repay_reserve, &withdraw_reserve, amount);
+                    let expected_withdraw_amount =
+                        estimate_withdraw_amount(&decoded.reserves, &repay_reserve, &withdraw_reserve, amount);
                     candidates.push(LiquidationCandidate {
                         obligation: *pk,
                         market,
+                        owner: obl.owner,
+                        referrer: (obl.referrer != Pubkey::default()).then_some(obl.referrer),
                         repay_reserve,
                         withdraw_reserve,
+                        health: h,
+                        repay_amount: amount,
+                        expected_withdraw_amount,
+                        expected_profit_lamports,
+                        expected_seized_lamports,
+                        sol_price_usd: sol_price_usd(&decoded.reserves),
+                        repay_cap,
+                        deleveraging: false,
+                        vaults: reserve_vaults(&decoded.reserves, &repay_reserve, &withdraw_reserve),
                     });
                 }
             }
         }
     }
+    candidates
+}
+
+/// Borrow to repay as `(reserve, amount)`. With a repay preference the borrow matching the
+/// earliest rules wins, then the larger USD value; otherwise the largest, or under
+/// `PreferStable` the largest stablecoin borrow when there is one.
+pub fn choose_repay_borrow(
+    obl: &types::Obligation,
+    reserves: &HashMap<Pubkey, types::Reserve>,
+    strategy: &StrategyProfile,
+) -> Option<(Pubkey, u64)> {
+    let borrows = || {
+        obl.borrows
+            .iter()
+            .filter(|b| b.amount > 0 && !strategy.is_blacklisted(&b.reserve))
+            .map(|b| (b.reserve, b.amount))
+    };
+    if !strategy.repay_preference.is_empty() {
+        let rank = |(reserve, amount): &(Pubkey, u64)| {
+            let matches: Vec<bool> = strategy
+                .repay_preference
+                .iter()
+                .map(|rule| match rule {
+                    RepayPreference::Held => reserves.get(reserve).is_some_and(|r| {
+                        strategy.holds(&r.liquidity.mint_pubkey, strategy.repay_amount(reserve, *amount))
+                    }),
+                    RepayPreference::Stable => is_stable_reserve(reserves, reserve, strategy),
+                })
+                .collect();
+            let value = reserves.get(reserve).map_or(0.0, |r| value_usd(r, *amount));
+            (matches, value)
+        };
+        return borrows()
+            .map(|b| (rank(&b), b))
+            .max_by(|(a, _), (b, _)| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .map(|(_, b)| b);
+    }
+    let largest = borrows().max_by_key(|(_, amount)| *amount);
+    match strategy.repay_selection {
+        RepaySelection::Largest => largest,
+        RepaySelection::PreferStable => borrows()
+            .filter(|(reserve, _)| is_stable_reserve(reserves, reserve, strategy))
+            .max_by_key(|(_, amount)| *amount)
+            .or(largest),
+    }
+}
+
+fn is_stable_reserve(reserves: &HashMap<Pubkey, types::Reserve>, reserve: &Pubkey, strategy: &StrategyProfile) -> bool {
+    reserves.get(reserve).is_some_and(|r| strategy.is_stable(reserve, &r.liquidity.mint_pubkey))
+}
 
+/// Liquidity the reserve can still pay out this interval under its deposit withdrawal cap,
+/// which redeeming seized collateral counts against. None when the reserve has no cap.
+pub fn remaining_withdrawal_cap(reserve: &types::Reserve, now_secs: u64) -> Option<u64> {
+    withdrawal_cap_left(&reserve.config.deposit_withdrawal_cap, now_secs)
+}
+
+fn withdrawal_cap_left(cap: &types::WithdrawalCaps, now_secs: u64) -> Option<u64> {
+    if cap.config_interval_length_seconds == 0 {
+        return None;
+    }
+    // The running total resets once the interval has passed
+    let used = match now_secs >= cap.last_interval_start_timestamp.saturating_add(cap.config_interval_length_seconds) {
+        true => 0,
+        false => cap.current_total.max(0),
+    };
+    Some(cap.config_capacity.saturating_sub(used).max(0) as u64)
+}
+
+/// Liquidity the reserve can pay out for a liquidation right now: its available liquidity,
+/// bounded by what remains of its withdrawal cap.
+pub fn redeemable_liquidity(reserve: &types::Reserve) -> u64 {
+    let available = reserve.liquidity.available_amount;
+    remaining_withdrawal_cap(reserve, now_millis() / 1_000).map_or(available, |cap| available.min(cap))
+}
+
+/// Largest part of `repay_amount` whose seized collateral the withdraw reserve can redeem from
+/// its available liquidity and withdrawal cap. The full amount when prices are unknown.
+pub fn redeemable_repay_amount(
+    reserves: &HashMap<Pubkey, types::Reserve>,
+    repay_reserve: &Pubkey,
+    withdraw_reserve: &Pubkey,
+    repay_amount: u64,
+) -> u64 {
+    let (Some(expected), Some(withdraw)) = (
+        estimate_withdraw_amount(reserves, repay_reserve, withdraw_reserve, repay_amount),
+        reserves.get(withdraw_reserve),
+    ) else {
+        return repay_amount;
+    };
+    let available = redeemable_liquidity(withdraw);
+    if expected <= available {
+        return repay_amount;
+    }
+    (repay_amount as u128 * available as u128 / expected as u128) as u64
+}
+
+/// Collateral to seize: the largest deposit whose reserve can redeem the full repay, otherwise
+/// the one that allows the largest partial repay. Returns the reserve and the repay it supports.
+pub fn choose_withdraw_reserve(
+    obl: &types::Obligation,
+    reserves: &HashMap<Pubkey, types::Reserve>,
+    repay_reserve: &Pubkey,
+    repay_amount: u64,
+    strategy: &StrategyProfile,
+) -> Option<(Pubkey, u64)> {
+    let mut deposits: Vec<_> = obl
+        .deposits
+        .iter()
+        .filter(|d| d.amount > 0 && d.reserve != Pubkey::default() && !strategy.is_blacklisted(&d.reserve))
+        .collect();
+    // Volatile collateral first when preferring stable debt, largest first within each group
+    let prefer_volatile = strategy.repay_selection == RepaySelection::PreferStable;
+    deposits.sort_by_key(|d| {
+        let stable_last = prefer_volatile && is_stable_reserve(reserves, &d.reserve, strategy);
+        (stable_last, std::cmp::Reverse(d.amount))
+    });
+    let options: Vec<(Pubkey, u64)> = deposits
+        .iter()
+        .map(|d| (d.reserve, redeemable_repay_amount(reserves, repay_reserve, &d.reserve, repay_amount)))
+        .collect();
+    options
+        .iter()
+        .find(|(_, amount)| *amount == repay_amount)
+        .or_else(|| options.iter().max_by_key(|(_, amount)| *amount))
+        .copied()
+        .filter(|(_, amount)| *amount > 0)
+}
+
+/// Scan Kamino program accounts and return obligations below `max_health` for a given market.
+/// With `deleverage_slot` set, obligations eligible for auto-deleveraging as of that slot are
+/// included as well.
+pub async fn find_liquidation_candidates(
+    rpc: &Rpc,
+    scanner: &ProgramScanner,
+    market_addr: &str,
+    max_health: f64,
+    strategy: &StrategyProfile,
+    deleverage_slot: Option<u64>,
+) -> Result<Vec<LiquidationCandidate>> {
+    let market: Pubkey = market_addr.parse()?;
+
+    // Fetch all accounts owned by the program and filter obligations
+    let accs = scanner.fetch(rpc).await?;
+
+    let started = Instant::now();
+    let decoded = decode_accounts(&accs, &market);
+    debug!(
+        accounts = accs.len(),
+        reserves = decoded.reserves.len(),
+        obligations = decoded.obligations.len(),
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "Decoded program accounts"
+    );
+
+    let mut candidates = select_candidates(&decoded, market, rpc, max_health, strategy);
+    if let Some(slot) = deleverage_slot {
+        let crossed = crossed_reserves(&decoded.reserves, &market, slot);
+        let affected = decode_affected(&accs, &market, &crossed);
+        for cand in select_deleverage_candidates(&decoded, &affected, market, &crossed, strategy) {
+            // A health-based liquidation of the same obligation takes precedence
+            match candidates.iter_mut().find(|c| c.obligation == cand.obligation) {
+                Some(existing) if existing.is_liquidatable() => {}
+                Some(existing) => *existing = cand,
+                None => candidates.push(cand),
+            }
+        }
+        if !crossed.is_empty() {
+            debug!(crossed_reserves = crossed.len(), affected = affected.len(), "Checked auto-deleverage eligibility");
+        }
+    }
     Ok(candidates)
 }
 
-/// Build a liquidation instruction for the given candidate.
-pub async fn build_liquidation_ix(rpc: &RpcClient, cand: &LiquidationCandidate) -> Result<Instruction> {
+/// Vaults of a liquidation between two already decoded reserves.
+pub fn reserve_vaults(
+    reserves: &HashMap<Pubkey, types::Reserve>,
+    repay_reserve: &Pubkey,
+    withdraw_reserve: &Pubkey,
+) -> Option<ReserveVaults> {
+    let (repay, withdraw) = reserves.get(repay_reserve).zip(reserves.get(withdraw_reserve))?;
+    Some(ReserveVaults::from_reserves(repay, withdraw))
+}
+
+/// Fetch and decode a single obligation account.
+pub async fn fetch_obligation(rpc: &Rpc, obligation: &Pubkey) -> Result<types::Obligation> {
     let decoder = KaminoLendingDecoder::default();
+    rpc.throttle(RequestClass::Candidate).await;
+    let obl_acc = rpc.get_account(obligation).context("Failed to fetch obligation")?;
+    decoder.decode_obligation(&obl_acc.data).context("Failed to decode obligation")
+}
 
-    // Fetch obligation account data to determine amounts
-    let obl_acc = rpc.get_account(&cand.obligation).context("Failed to fetch obligation")?;
-    let obl = decoder.decode_obligation(&obl_acc.data).context("Failed to decode obligation")?;
+/// The obligation, or None when it was closed or no longer decodes; fails only when RPC does.
+pub async fn fetch_open_obligation(rpc: &Rpc, obligation: &Pubkey) -> Result<Option<types::Obligation>> {
+    let decoder = KaminoLendingDecoder::default();
+    rpc.throttle(RequestClass::Candidate).await;
+    let response =
+        rpc.get_account_with_commitment(obligation, rpc.commitment()).context("Failed to fetch obligation")?;
+    Ok(response.value.and_then(|acc| decoder.decode_obligation(&acc.data).ok()))
+}
 
-    // Repay 20% of largest borrow
-    let largest_borrow = obl.borrows.iter().max_by_key(|b| b.amount).context("No borrows")?;
-    let repay_amount = largest_borrow.amount / 5; // 20%
+/// Build a liquidation instruction for the given candidate from its freshly fetched obligation.
+pub async fn build_liquidation_ix(
+    rpc: &Rpc,
+    market: &MarketAccounts,
+    liquidator: &LiquidatorAccounts,
+    cand: &LiquidationCandidate,
+    obl: &types::Obligation,
+    strategy: &StrategyProfile,
+) -> Result<Instruction> {
+    // Vault addresses never change, so the scan's copy of the reserves serves
+    let vaults = match &cand.vaults {
+        Some(vaults) => vaults.clone(),
+        None => ReserveVaults::fetch(rpc, &cand.repay_reserve, &cand.withdraw_reserve).await?,
+    };
 
-    // Withdraw reserve is chosen from largest deposit
-    let largest_deposit = obl.deposits.iter().max_by_key(|d| d.amount).context("No deposits")?;
+    // Repay the profile's fraction of the chosen borrow
+    let borrow = obl.borrows.iter().find(|b| b.reserve == cand.repay_reserve).context("Borrow no longer present")?;
+    let repay_amount = strategy.repay_amount(&borrow.reserve, borrow.amount).min(cand.repay_cap.unwrap_or(u64::MAX));
+
+    // There must be collateral left to seize
+    obl.deposits.iter().find(|d| d.reserve == cand.withdraw_reserve && d.amount > 0).context("No deposits")?;
+
+    liquidation_ix_for(cand, obl, market, liquidator, &vaults, repay_amount, min_out_for(cand, repay_amount, strategy))
+}
+
+/// Minimum acceptable withdraw amount, scaled to the actual repay amount. Zero when prices are unknown.
+pub fn min_out_for(cand: &LiquidationCandidate, repay_amount: u64, strategy: &StrategyProfile) -> u64 {
+    match cand.expected_withdraw_amount {
+        Some(expected) if cand.repay_amount > 0 => {
+            let scaled = expected as u128 * repay_amount as u128 / cand.repay_amount as u128;
+            strategy.min_out(&cand.withdraw_reserve, scaled.min(u64::MAX as u128) as u64)
+        }
+        _ => 0,
+    }
+}
 
+/// Build the liquidation instruction from an already fetched obligation.
+pub fn liquidation_ix_for(
+    cand: &LiquidationCandidate,
+    obl: &types::Obligation,
+    market: &MarketAccounts,
+    liquidator: &LiquidatorAccounts,
+    vaults: &ReserveVaults,
+    repay_amount: u64,
+    min_out: u64,
+) -> Result<Instruction> {
     // Construct instruction using decoder-generated builders
-    let accounts = carbon_kamino_lending_decoder::instructions::liquidate_obligation::LiquidateObligationInstructionAccounts {
-        lending_market: cand.market,
-        obligation: cand.obligation,
-        repay_reserve: cand.repay_reserve,
-        withdraw_reserve: cand.withdraw_reserve,
-        // Placeholder accounts which decoder resolves internally if optional; they may be filled below if required
-        owner: obl.owner,
-        token_program: spl_token::ID,
-    };
+    let accounts =
+        carbon_kamino_lending_decoder::instructions::liquidate_obligation::LiquidateObligationInstructionAccounts {
+            liquidator: liquidator.owner,
+            lending_market: cand.market,
+            lending_market_authority: market.authority,
+            obligation: cand.obligation,
+            repay_reserve: cand.repay_reserve,
+            repay_reserve_liquidity_mint: vaults.repay_liquidity_mint,
+            repay_reserve_liquidity_supply: vaults.repay_liquidity_supply,
+            withdraw_reserve: cand.withdraw_reserve,
+            withdraw_reserve_liquidity_mint: vaults.withdraw_liquidity_mint,
+            withdraw_reserve_collateral_mint: vaults.withdraw_collateral_mint,
+            withdraw_reserve_collateral_supply: vaults.withdraw_collateral_supply,
+            withdraw_reserve_liquidity_supply: vaults.withdraw_liquidity_supply,
+            withdraw_reserve_liquidity_fee_receiver: vaults.withdraw_fee_receiver,
+            user_source_liquidity: liquidator.token_account(&vaults.repay_liquidity_mint, &vaults.repay_token_program),
+            user_destination_collateral: liquidator.token_account(&vaults.withdraw_collateral_mint, &spl_token::ID),
+            user_destination_liquidity: liquidator
+                .token_account(&vaults.withdraw_liquidity_mint, &vaults.withdraw_token_program),
+            owner: obl.owner,
+            collateral_token_program: spl_token::ID,
+            repay_liquidity_token_program: vaults.repay_token_program,
+            withdraw_liquidity_token_program: vaults.withdraw_token_program,
+            // Referral fees accrue on the repaid reserve; only present when the obligation has a referrer
+            referrer_token_state: market.referrer_token_state(&obl.referrer, &cand.repay_reserve),
+            risk_council: market.risk_council,
+        };
 
     let args = carbon_kamino_lending_decoder::instructions::liquidate_obligation::LiquidateObligationInstructionArgs {
         liquidity_amount: repay_amount,
-        min_out: 0u64, // accept any collateral out, price protected by HF check
+        min_out,
     };
 
     let ix = carbon_kamino_lending_decoder::instructions::liquidate_obligation::build(accounts, args)?;
@@ -109,3 +562,31 @@ pub async fn build_liquidation_ix(rpc: &RpcClient, cand: &LiquidationCandidate)
     Ok(ix)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caps(current_total: i64) -> types::WithdrawalCaps {
+        types::WithdrawalCaps {
+            config_capacity: 1_000,
+            current_total,
+            last_interval_start_timestamp: 10_000,
+            config_interval_length_seconds: 3_600,
+        }
+    }
+
+    #[test]
+    fn withdrawal_cap_resets_once_the_interval_passes() {
+        assert_eq!(withdrawal_cap_left(&caps(400), 10_000), Some(600));
+        assert_eq!(withdrawal_cap_left(&caps(400), 13_599), Some(600));
+        assert_eq!(withdrawal_cap_left(&caps(400), 13_600), Some(1_000));
+        assert_eq!(withdrawal_cap_left(&caps(1_500), 11_000), Some(0));
+        assert_eq!(withdrawal_cap_left(&caps(-50), 11_000), Some(1_000));
+    }
+
+    #[test]
+    fn withdrawal_cap_unset_without_an_interval() {
+        let cap = types::WithdrawalCaps { config_interval_length_seconds: 0, ..caps(400) };
+        assert_eq!(withdrawal_cap_left(&cap, 10_000), None);
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, warn};
+
+use crate::kamino::LiquidationCandidate;
+use crate::metrics::metrics;
+
+pub const DEFAULT_KAMINO_API_URL: &str = "https://api.kamino.finance";
+
+/// Budget for one API request, so a hung connection does not pile up cross-check tasks.
+const API_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Minimal client for Kamino's public obligation API.
+pub struct KaminoApi {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl KaminoApi {
+    pub fn new(base_url: String) -> Result<Self> {
+        let http = reqwest::Client::builder().timeout(API_TIMEOUT).build().context("Failed to build HTTP client")?;
+        Ok(Self { base_url, http })
+    }
+
+    /// Health factor of `obligation` as Kamino computes it: the liquidation limit over the
+    /// borrow-factor-adjusted debt, liquidatable below 1.0.
+    pub async fn health(&self, market: &Pubkey, obligation: &Pubkey) -> Result<f64> {
+        let url = format!("{}/kamino-market/{market}/obligations/{obligation}", self.base_url);
+        let body: Value = self
+            .http
+            .get(&url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .context("Kamino API request failed")?
+            .json()
+            .await
+            .context("Kamino API returned invalid JSON")?;
+        let stats = &body["refreshedStats"];
+        // Values arrive as decimal strings
+        let number = |field: &str| {
+            let value = &stats[field];
+            value
+                .as_f64()
+                .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+                .with_context(|| format!("Kamino API response has no {field}"))
+        };
+        let debt = number("userTotalBorrowBorrowFactorAdjusted")?;
+        Ok(match debt > 0.0 {
+            true => number("borrowLiquidationLimit")? / debt,
+            false => f64::INFINITY,
+        })
+    }
+}
+
+/// Compares our health estimate for detected candidates with Kamino's and logs where they
+/// disagree, while the in-house model matures. Checks run in the background and never hold
+/// up a submission.
+pub struct HealthCrossCheck {
+    api: KaminoApi,
+    /// Relative difference logged as a discrepancy.
+    tolerance: f64,
+    /// Shortest time between checks of one obligation.
+    interval: Duration,
+    checked: Mutex<HashMap<Pubkey, Instant>>,
+}
+
+impl HealthCrossCheck {
+    pub fn new(api: KaminoApi, tolerance: f64, interval: Duration) -> Arc<Self> {
+        Arc::new(Self { api, tolerance, interval, checked: Mutex::new(HashMap::new()) })
+    }
+
+    /// Queue a comparison for each candidate not checked within the interval.
+    pub fn observe(self: &Arc<Self>, candidates: &[&LiquidationCandidate]) {
+        let due: Vec<_> = {
+            let mut checked = self.checked.lock().unwrap();
+            checked.retain(|_, at| at.elapsed() < self.interval);
+            let due: Vec<_> = candidates
+                .iter()
+                .filter(|c| !checked.contains_key(&c.obligation))
+                .map(|c| (c.market, c.obligation, c.health))
+                .collect();
+            checked.extend(due.iter().map(|(_, obligation, _)| (*obligation, Instant::now())));
+            due
+        };
+        for (market, obligation, ours) in due {
+            let check = Arc::clone(self);
+            tokio::spawn(async move { check.compare(market, obligation, ours).await });
+        }
+    }
+
+    async fn compare(&self, market: Pubkey, obligation: Pubkey, ours: f64) {
+        let theirs = match self.api.health(&market, &obligation).await {
+            Ok(h) => h,
+            Err(e) => {
+                debug!(obligation = %obligation, error = %e, "Kamino health cross-check failed");
+                metrics().inc_labeled("health_cross_checks_total", &[("result", "error")]);
+                return;
+            }
+        };
+        let diff = (ours - theirs).abs() / theirs;
+        // Disagreeing on which side of 1.0 the obligation sits matters more than the size of the gap
+        let flipped = (ours < 1.0) != (theirs < 1.0);
+        match diff > self.tolerance || flipped {
+            true => {
+                warn!(
+                    obligation = %obligation,
+                    ours,
+                    kamino = theirs,
+                    diff_pct = diff * 100.0,
+                    flipped,
+                    "Health estimate disagrees with Kamino"
+                );
+                metrics().inc_labeled("health_cross_checks_total", &[("result", "mismatch")]);
+            }
+            false => {
+                debug!(obligation = %obligation, ours, kamino = theirs, "Health estimate matches Kamino");
+                metrics().inc_labeled("health_cross_checks_total", &[("result", "match")]);
+            }
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use carbon_kamino_lending_decoder::{types, KaminoLendingDecoder, PROGRAM_ID};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::Signer;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::kamino::{fetch_obligation, LiquidationCandidate};
+use crate::metrics::metrics;
+use crate::pda::{referrer_token_state, MarketAccounts};
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::util::{fetch_latest_blockhash, BackgroundSigner, ComputeBudget, TxBuilder};
+
+/// Anchor discriminators of the klend refresh instructions.
+const REFRESH_RESERVE_DISCRIMINATOR: [u8; 8] = [0x02, 0xda, 0x8a, 0xeb, 0x4f, 0xc9, 0x19, 0x66];
+const REFRESH_OBLIGATION_DISCRIMINATOR: [u8; 8] = [0x21, 0x84, 0x93, 0xe4, 0x97, 0xc0, 0x48, 0x59];
+const INIT_REFERRER_TOKEN_STATE_DISCRIMINATOR: [u8; 8] = [0x74, 0x2d, 0x42, 0x94, 0x3a, 0x0d, 0xda, 0x73];
+
+/// Referrer token states seen on chain. They are never closed, so each is only fetched until
+/// it is found once.
+static KNOWN_REFERRER_STATES: Mutex<BTreeSet<Pubkey>> = Mutex::new(BTreeSet::new());
+
+/// RefreshReserve for each reserve the obligation touches, then RefreshObligation.
+pub fn refresh_ixs(
+    market: &MarketAccounts,
+    obligation: &Pubkey,
+    obl: &types::Obligation,
+    reserves: &HashMap<Pubkey, types::Reserve>,
+) -> Result<Vec<Instruction>> {
+    let deposits: Vec<Pubkey> = obl.deposits.iter().filter(|d| d.amount > 0).map(|d| d.reserve).collect();
+    let borrows: Vec<Pubkey> = obl.borrows.iter().filter(|b| b.amount > 0).map(|b| b.reserve).collect();
+    let touched: BTreeSet<Pubkey> = deposits.iter().chain(&borrows).copied().collect();
+
+    let mut ixs = Vec::with_capacity(touched.len() + 1);
+    for reserve_pk in &touched {
+        let reserve = reserves.get(reserve_pk).context("Reserve missing for refresh")?;
+        ixs.push(refresh_reserve_ix(market, reserve_pk, reserve));
+    }
+    ixs.push(refresh_obligation_ix(market, obligation, obl));
+    Ok(ixs)
+}
+
+/// `refresh_ixs`, fetching the reserves the obligation touches.
+pub async fn fetch_refresh_ixs(
+    rpc: &Rpc,
+    market: &MarketAccounts,
+    obligation: &Pubkey,
+    obl: &types::Obligation,
+) -> Result<Vec<Instruction>> {
+    let keys: Vec<Pubkey> = obl
+        .deposits
+        .iter()
+        .map(|d| d.reserve)
+        .chain(obl.borrows.iter().map(|b| b.reserve))
+        .filter(|pk| *pk != Pubkey::default())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let reserves = fetch_reserves(rpc, &keys).await?;
+    refresh_ixs(market, obligation, obl, &reserves)
+}
+
+/// InitReferrerTokenState, paid by `payer`, for each borrow reserve whose referrer token state
+/// does not exist yet. RefreshObligation and the liquidation of an obligation opened through a
+/// referrer take these accounts and fail on a missing one; anyone may create them.
+pub async fn referrer_init_ixs(
+    rpc: &Rpc,
+    market: &MarketAccounts,
+    payer: &Pubkey,
+    obl: &types::Obligation,
+) -> Result<Vec<Instruction>> {
+    if obl.referrer == Pubkey::default() {
+        return Ok(Vec::new());
+    }
+    let known = KNOWN_REFERRER_STATES.lock().unwrap().clone();
+    let (reserves, states): (Vec<Pubkey>, Vec<Pubkey>) = obl
+        .borrows
+        .iter()
+        .filter(|b| b.amount > 0)
+        .map(|b| b.reserve)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|r| (r, referrer_token_state(&obl.referrer, &r)))
+        .filter(|(_, state)| !known.contains(state))
+        .unzip();
+    if states.is_empty() {
+        return Ok(Vec::new());
+    }
+    rpc.throttle(RequestClass::Candidate).await;
+    let existing = rpc.get_multiple_accounts(&states).context("Failed to fetch referrer token states")?;
+    KNOWN_REFERRER_STATES
+        .lock()
+        .unwrap()
+        .extend(states.iter().zip(&existing).filter(|(_, acc)| acc.is_some()).map(|(state, _)| *state));
+
+    let ixs: Vec<Instruction> = reserves
+        .iter()
+        .zip(&states)
+        .zip(existing)
+        .filter(|(_, acc)| acc.is_none())
+        .map(|((reserve, state), _)| {
+            let accounts = vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new_readonly(market.market, false),
+                AccountMeta::new_readonly(*reserve, false),
+                AccountMeta::new_readonly(obl.referrer, false),
+                AccountMeta::new(*state, false),
+                AccountMeta::new_readonly(solana_sdk::sysvar::rent::ID, false),
+                AccountMeta::new_readonly(solana_sdk::system_program::ID, false),
+            ];
+            Instruction::new_with_bytes(PROGRAM_ID, &INIT_REFERRER_TOKEN_STATE_DISCRIMINATOR, accounts)
+        })
+        .collect();
+    if !ixs.is_empty() {
+        info!(referrer = %obl.referrer, missing = ixs.len(), "Initializing referrer token states");
+        metrics().add("referrer_token_state_inits_total", &[], ixs.len() as u64);
+    }
+    Ok(ixs)
+}
+
+/// Referrer token state initializations the candidate's liquidation needs first; empty without
+/// a referrer. Uses `obl` when the caller already fetched it, otherwise fetches the obligation.
+pub async fn candidate_referrer_ixs(
+    rpc: &Rpc,
+    market: &MarketAccounts,
+    payer: &Pubkey,
+    cand: &LiquidationCandidate,
+    obl: Option<&types::Obligation>,
+) -> Result<Vec<Instruction>> {
+    if cand.referrer.is_none() {
+        return Ok(Vec::new());
+    }
+    match obl {
+        Some(obl) => referrer_init_ixs(rpc, market, payer, obl).await,
+        None => referrer_init_ixs(rpc, market, payer, &fetch_obligation(rpc, &cand.obligation).await?).await,
+    }
+}
+
+/// Refresh instructions shared by several obligations: every reserve any of them touches is
+/// refreshed once, followed by each obligation's RefreshObligation.
+pub async fn batch_refresh_ixs(rpc: &Rpc, market: &MarketAccounts, obligations: &[Pubkey]) -> Result<Vec<Instruction>> {
+    let decoder = KaminoLendingDecoder::default();
+    rpc.throttle(RequestClass::Candidate).await;
+    let accounts = rpc.get_multiple_accounts(obligations).context("Failed to fetch obligations for refresh")?;
+    let decoded: Vec<(Pubkey, types::Obligation)> = obligations
+        .iter()
+        .zip(accounts)
+        .map(|(pk, acc)| {
+            let acc = acc.with_context(|| format!("Obligation {pk} no longer exists"))?;
+            Ok((*pk, decoder.decode_obligation(&acc.data).context("Failed to decode obligation")?))
+        })
+        .collect::<Result<_>>()?;
+
+    let per_obligation: Vec<BTreeSet<Pubkey>> = decoded
+        .iter()
+        .map(|(_, obl)| {
+            let deposits = obl.deposits.iter().filter(|d| d.amount > 0).map(|d| d.reserve);
+            deposits.chain(obl.borrows.iter().filter(|b| b.amount > 0).map(|b| b.reserve)).collect()
+        })
+        .collect();
+    let touched: BTreeSet<Pubkey> = per_obligation.iter().flatten().copied().collect();
+    let keys: Vec<Pubkey> = touched.iter().copied().collect();
+    let reserves = fetch_reserves(rpc, &keys).await?;
+
+    let mut ixs = Vec::with_capacity(touched.len() + decoded.len());
+    for reserve_pk in &touched {
+        let reserve = reserves.get(reserve_pk).context("Reserve missing for refresh")?;
+        ixs.push(refresh_reserve_ix(market, reserve_pk, reserve));
+    }
+    for (pk, obl) in &decoded {
+        ixs.push(refresh_obligation_ix(market, pk, obl));
+    }
+    let repeated: usize = per_obligation.iter().map(BTreeSet::len).sum();
+    metrics().add("refresh_batch_reserves_deduplicated_total", &[], (repeated - touched.len()) as u64);
+    Ok(ixs)
+}
+
+fn refresh_reserve_ix(market: &MarketAccounts, reserve_pk: &Pubkey, reserve: &types::Reserve) -> Instruction {
+    let info = &reserve.config.token_info;
+    let accounts = vec![
+        AccountMeta::new(*reserve_pk, false),
+        AccountMeta::new_readonly(market.market, false),
+        optional(info.pyth_configuration.price),
+        optional(info.switchboard_configuration.price_aggregator),
+        optional(info.switchboard_configuration.twap_aggregator),
+        optional(info.scope_configuration.price_feed),
+    ];
+    Instruction::new_with_bytes(PROGRAM_ID, &REFRESH_RESERVE_DISCRIMINATOR, accounts)
+}
+
+fn refresh_obligation_ix(market: &MarketAccounts, obligation: &Pubkey, obl: &types::Obligation) -> Instruction {
+    let deposits: Vec<Pubkey> = obl.deposits.iter().filter(|d| d.amount > 0).map(|d| d.reserve).collect();
+    let borrows: Vec<Pubkey> = obl.borrows.iter().filter(|b| b.amount > 0).map(|b| b.reserve).collect();
+
+    // Remaining accounts: deposit reserves, then borrow reserves, then referrer token states
+    let mut accounts = vec![AccountMeta::new_readonly(market.market, false), AccountMeta::new(*obligation, false)];
+    accounts.extend(deposits.iter().map(|r| AccountMeta::new_readonly(*r, false)));
+    accounts.extend(borrows.iter().map(|r| AccountMeta::new_readonly(*r, false)));
+    accounts.extend(
+        borrows
+            .iter()
+            .filter_map(|r| market.referrer_token_state(&obl.referrer, r))
+            .map(|pk| AccountMeta::new(pk, false)),
+    );
+    Instruction::new_with_bytes(PROGRAM_ID, &REFRESH_OBLIGATION_DISCRIMINATOR, accounts)
+}
+
+/// Unset Anchor optional accounts are passed as the program id.
+fn optional(pk: Pubkey) -> AccountMeta {
+    let pk = if pk == Pubkey::default() { PROGRAM_ID } else { pk };
+    AccountMeta::new_readonly(pk, false)
+}
+
+/// Fetch and decode the given reserves.
+pub async fn fetch_reserves(rpc: &Rpc, keys: &[Pubkey]) -> Result<HashMap<Pubkey, types::Reserve>> {
+    let decoder = KaminoLendingDecoder::default();
+    rpc.throttle(RequestClass::Candidate).await;
+    let accounts = rpc.get_multiple_accounts(keys).context("Failed to fetch reserves")?;
+    Ok(keys
+        .iter()
+        .zip(accounts)
+        .filter_map(|(pk, acc)| Some((*pk, decoder.decode_reserve(&acc?.data).ok()?)))
+        .collect())
+}
+
+/// Send refresh transactions for each obligation; returns how many were submitted.
+pub async fn crank(rpc: &Rpc, builder: &TxBuilder<'_>, market: &MarketAccounts, obligations: &[Pubkey]) -> usize {
+    let mut sent = 0;
+    for obligation in obligations {
+        match crank_one(rpc, builder, market, obligation).await {
+            Ok(sig) => {
+                debug!(obligation = %obligation, signature = %sig, "Refresh submitted");
+                metrics().inc_labeled("keeper_refreshes_total", &[("result", "sent")]);
+                sent += 1;
+            }
+            Err(e) => {
+                warn!(obligation = %obligation, error = %e, "Refresh failed");
+                metrics().inc_labeled("keeper_refreshes_total", &[("result", "failed")]);
+            }
+        }
+    }
+    sent
+}
+
+async fn crank_one(rpc: &Rpc, builder: &TxBuilder<'_>, market: &MarketAccounts, obligation: &Pubkey) -> Result<String> {
+    let obl = fetch_obligation(rpc, obligation).await?;
+    let mut ixs = referrer_init_ixs(rpc, market, &builder.payer.pubkey(), &obl).await?;
+    ixs.extend(fetch_refresh_ixs(rpc, market, obligation, &obl).await?);
+
+    let blockhash = fetch_latest_blockhash(rpc).await?;
+    let tx = builder.tx(blockhash, ixs)?;
+    rpc.throttle(RequestClass::Candidate).await;
+    let sig = rpc.send_transaction(&tx).context("Failed to send refresh transaction")?;
+    Ok(sig.to_string())
+}
+
+/// Runs the crank for watched obligations at a fixed interval, in the background so a pass
+/// never holds up scanning.
+pub struct Keeper {
+    interval: Duration,
+    last_run: Option<Instant>,
+    running: Option<JoinHandle<()>>,
+}
+
+impl Keeper {
+    /// A zero interval disables the keeper.
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, last_run: None, running: None }
+    }
+
+    /// Start cranking `obligations` if the interval has elapsed and the previous pass finished.
+    pub fn tick(
+        &mut self,
+        rpc: &Arc<Rpc>,
+        signer: &BackgroundSigner,
+        budget: ComputeBudget,
+        market: &MarketAccounts,
+        obligations: Vec<Pubkey>,
+    ) {
+        if self.interval.is_zero() || self.last_run.is_some_and(|t| t.elapsed() < self.interval) {
+            return;
+        }
+        if self.running.as_ref().is_some_and(|pass| !pass.is_finished()) {
+            debug!("Previous keeper pass still running");
+            return;
+        }
+        self.last_run = Some(Instant::now());
+        if obligations.is_empty() {
+            return;
+        }
+        let (rpc, signer, market) = (Arc::clone(rpc), signer.clone(), market.clone());
+        self.running = Some(tokio::spawn(async move {
+            let sent = crank(&rpc, &signer.builder(budget), &market, &obligations).await;
+            info!(watched = obligations.len(), sent, "Keeper refreshed watched obligations");
+        }));
+    }
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use solana_sdk::signature::Signature;
+
+use crate::metrics::metrics;
+use crate::sender::SenderKind;
+
+/// Histogram holding every pipeline stage, split by the `stage` label.
+const STAGE_HISTOGRAM: &str = "pipeline_stage_seconds";
+
+/// Timed segments of a liquidation's path from scan to chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Scan result available until the signed transactions are built.
+    DetectToBuild,
+    /// Transactions built until the sender accepts them.
+    BuildToSubmit,
+    /// Sender accepted until the signature confirms.
+    SubmitToLand,
+}
+
+impl Stage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Stage::DetectToBuild => "detect_to_build",
+            Stage::BuildToSubmit => "build_to_submit",
+            Stage::SubmitToLand => "submit_to_land",
+        }
+    }
+}
+
+/// Where a submission went, so lost races can be attributed to a backend and region.
+#[derive(Clone, Copy, Debug)]
+pub struct Route {
+    pub sender: SenderKind,
+    pub region: &'static str,
+}
+
+/// Record one stage of a submission, with its liquidation signature as the exemplar trace id.
+pub fn observe_stage(stage: Stage, route: Route, elapsed: Duration, signature: &Signature) {
+    let labels = [("stage", stage.as_str()), ("sender", route.sender.as_str()), ("region", route.region)];
+    metrics().observe(STAGE_HISTOGRAM, &labels, elapsed.as_secs_f64(), Some(&signature.to_string()));
+}
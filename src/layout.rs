@@ -0,0 +1,173 @@
+use anyhow::{bail, Context, Result};
+use carbon_kamino_lending_decoder::{KaminoLendingDecoder, PROGRAM_ID};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::account::Account;
+use solana_sdk::bpf_loader_upgradeable::UpgradeableLoaderState;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{info, warn};
+
+use crate::metrics::metrics;
+use crate::partial::{
+    ObligationView, OBLIGATION_DISCRIMINATOR, OBLIGATION_LENDING_MARKET_OFFSET, OBLIGATION_OWNER_OFFSET,
+    OBLIGATION_SIZE, RESERVE_DISCRIMINATOR, RESERVE_LENDING_MARKET_OFFSET, RESERVE_SIZE,
+};
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+
+/// Obligations sampled are those whose owner starts with this byte, about 1/256 of the market.
+const SAMPLE_OWNER_PREFIX: u8 = 0;
+
+/// Problems reported per account kind before the rest are only counted.
+const MAX_REPORTED: usize = 5;
+
+/// Outcome of checking live accounts against the layouts this build decodes.
+#[derive(Debug, Default)]
+pub struct LayoutReport {
+    /// Slot the Kamino program was last deployed at, when readable.
+    pub deploy_slot: Option<u64>,
+    pub reserves_checked: usize,
+    pub obligations_checked: usize,
+    pub problems: Vec<String>,
+}
+
+impl LayoutReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Fetch the market's reserves and a sample of its obligations with only the discriminator and
+/// market as filters, so accounts of an unexpected size are seen rather than filtered out, then
+/// check that each has the expected size, decodes, and agrees with the raw-offset view.
+pub async fn check_layouts(rpc: &Rpc, market: &Pubkey) -> Result<LayoutReport> {
+    let mut report = LayoutReport { deploy_slot: deploy_slot(rpc).await.ok(), ..Default::default() };
+    let decoder = KaminoLendingDecoder::default();
+
+    let reserves = fetch_filtered(rpc, &RESERVE_DISCRIMINATOR, RESERVE_LENDING_MARKET_OFFSET, market, None).await?;
+    let mut reserve_problems = Vec::new();
+    for (pk, acc) in &reserves {
+        if acc.data.len() != RESERVE_SIZE {
+            reserve_problems.push(format!("reserve {pk} is {} bytes, expected {RESERVE_SIZE}", acc.data.len()));
+            continue;
+        }
+        match decoder.decode_reserve(&acc.data) {
+            Ok(reserve) if reserve.lending_market != *market => {
+                reserve_problems.push(format!("reserve {pk} decodes to market {}", reserve.lending_market))
+            }
+            Ok(reserve) if reserve.liquidity.mint_decimals > 18 => reserve_problems
+                .push(format!("reserve {pk} decodes to {} mint decimals", reserve.liquidity.mint_decimals)),
+            Ok(_) => {}
+            Err(e) => reserve_problems.push(format!("reserve {pk} fails to decode: {e}")),
+        }
+    }
+    report.reserves_checked = reserves.len();
+    if reserves.is_empty() {
+        reserve_problems.push(format!("no reserves found for market {market}"));
+    }
+
+    let obligations = fetch_filtered(
+        rpc,
+        &OBLIGATION_DISCRIMINATOR,
+        OBLIGATION_LENDING_MARKET_OFFSET,
+        market,
+        Some((OBLIGATION_OWNER_OFFSET, SAMPLE_OWNER_PREFIX)),
+    )
+    .await?;
+    let mut obligation_problems = Vec::new();
+    for (pk, acc) in &obligations {
+        if let Some(problem) = check_obligation(&decoder, pk, acc, market) {
+            obligation_problems.push(problem);
+        }
+    }
+    report.obligations_checked = obligations.len();
+
+    for (kind, problems) in [("reserve", reserve_problems), ("obligation", obligation_problems)] {
+        let total = problems.len();
+        report.problems.extend(problems.into_iter().take(MAX_REPORTED));
+        if total > MAX_REPORTED {
+            report.problems.push(format!("{} more {kind} problems", total - MAX_REPORTED));
+        }
+    }
+
+    metrics().set_gauge("layout_check_problems", &[], report.problems.len() as f64);
+    if let Some(slot) = report.deploy_slot {
+        metrics().set_gauge("kamino_program_deploy_slot", &[], slot as f64);
+    }
+    match report.is_ok() {
+        true => info!(
+            deploy_slot = ?report.deploy_slot,
+            reserves = report.reserves_checked,
+            obligations = report.obligations_checked,
+            "Live account layouts match the decoder"
+        ),
+        false => {
+            warn!(deploy_slot = ?report.deploy_slot, problems = report.problems.len(), "Account layout drift detected")
+        }
+    }
+    Ok(report)
+}
+
+/// Size, decode and raw-offset checks for one obligation.
+fn check_obligation(decoder: &KaminoLendingDecoder, pk: &Pubkey, acc: &Account, market: &Pubkey) -> Option<String> {
+    let Some(view) = ObligationView::new(&acc.data) else {
+        return Some(format!("obligation {pk} is {} bytes, expected {OBLIGATION_SIZE}", acc.data.len()));
+    };
+    let obl = match decoder.decode_obligation(&acc.data) {
+        Ok(obl) => obl,
+        Err(e) => return Some(format!("obligation {pk} fails to decode: {e}")),
+    };
+    if obl.lending_market != *market || view.lending_market() != *market {
+        return Some(format!("obligation {pk} decodes to market {}", obl.lending_market));
+    }
+    // The scan pre-filter reads positions at fixed offsets; they must agree with the decoder
+    let decoded: Vec<(Pubkey, u64)> = obl
+        .deposits
+        .iter()
+        .filter(|d| d.reserve != Pubkey::default() && d.amount > 0)
+        .map(|d| (d.reserve, d.amount))
+        .collect();
+    let raw: Vec<(Pubkey, u64)> = view.deposits().map(|d| (d.reserve, d.amount)).collect();
+    (decoded != raw).then(|| format!("obligation {pk} deposits differ between decoder and raw offsets"))
+}
+
+async fn fetch_filtered(
+    rpc: &Rpc,
+    discriminator: &[u8; 8],
+    market_offset: usize,
+    market: &Pubkey,
+    prefix: Option<(usize, u8)>,
+) -> Result<Vec<(Pubkey, Account)>> {
+    let mut filters = vec![
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, discriminator)),
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(market_offset, market.as_ref())),
+    ];
+    if let Some((offset, byte)) = prefix {
+        filters.push(RpcFilterType::Memcmp(Memcmp::new_base58_encoded(offset, &[byte])));
+    }
+    let config = RpcProgramAccountsConfig {
+        filters: Some(filters),
+        account_config: RpcAccountInfoConfig { encoding: Some(UiAccountEncoding::Base64), ..Default::default() },
+        ..Default::default()
+    };
+    rpc.throttle_gpa().await;
+    rpc.get_program_accounts_with_config(&PROGRAM_ID, config).context("Failed to fetch accounts for layout check")
+}
+
+/// Slot of the Kamino program's last deployment, read from its program data account.
+async fn deploy_slot(rpc: &Rpc) -> Result<u64> {
+    rpc.throttle(RequestClass::Candidate).await;
+    let program = rpc.get_account(&PROGRAM_ID).context("Failed to fetch Kamino program account")?;
+    let UpgradeableLoaderState::Program { programdata_address } = bincode::deserialize(&program.data)? else {
+        bail!("Kamino program is not upgradeable");
+    };
+    rpc.throttle(RequestClass::Candidate).await;
+    let data = rpc.get_account(&programdata_address).context("Failed to fetch Kamino program data")?;
+    let metadata =
+        data.data.get(..UpgradeableLoaderState::size_of_programdata_metadata()).context("Program data too short")?;
+    match bincode::deserialize(metadata)? {
+        UpgradeableLoaderState::ProgramData { slot, .. } => Ok(slot),
+        _ => bail!("Unexpected Kamino program data account"),
+    }
+}
@@ -0,0 +1,62 @@
+//! Kamino liquidation bot: account scanning, health estimation and Jito submission.
+
+pub mod alert;
+pub mod auction;
+pub mod audit;
+pub mod blink;
+pub mod bundles;
+pub mod config;
+pub mod contention;
+pub mod control;
+pub mod coordination;
+pub mod deadline;
+pub mod deleverage;
+pub mod discovery;
+pub mod dump;
+pub mod events;
+pub mod fees;
+pub mod health;
+pub mod history;
+pub mod hooks;
+pub mod inventory;
+pub mod jito;
+pub mod kamino;
+pub mod kamino_api;
+pub mod keeper;
+pub mod latency;
+pub mod layout;
+pub mod lut;
+pub mod metrics;
+pub mod opportunity;
+pub mod oracle;
+pub mod partial;
+pub mod pda;
+pub mod profit;
+pub mod protect;
+pub mod proxy;
+pub mod races;
+pub mod ratelimit;
+pub mod reconcile;
+pub mod replay;
+pub mod resubmit;
+pub mod retry;
+pub mod rpc;
+pub mod scan;
+pub mod scan_bench;
+pub mod scan_state;
+pub mod schedule;
+pub mod sender;
+pub mod simulate;
+pub mod skew;
+pub mod status;
+pub mod store;
+pub mod strategy;
+pub mod summary;
+pub mod supervisor;
+pub mod swap;
+pub mod template;
+pub mod tracker;
+pub mod treasury;
+pub mod unwind;
+pub mod util;
+pub mod volatility;
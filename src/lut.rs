@@ -0,0 +1,134 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{ensure, Context, Result};
+use carbon_kamino_lending_decoder::{types, PROGRAM_ID};
+use serde::{Deserialize, Serialize};
+use solana_address_lookup_table_interface::instruction::{create_lookup_table, extend_lookup_table};
+use solana_address_lookup_table_interface::state::AddressLookupTable;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::AddressLookupTableAccount;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+use spl_associated_token_account_client::address::get_associated_token_address_with_program_id;
+use tracing::info;
+
+use crate::jito::JITO_TIP_ACCOUNTS;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::store::Store;
+use crate::util::now_millis;
+
+/// Store collection holding the lookup tables created by the bot.
+pub const LOOKUP_TABLES: &str = "lookup_tables";
+
+/// Lookup tables hold at most this many addresses.
+const MAX_TABLE_ADDRESSES: usize = 256;
+
+/// Addresses appended per extend transaction, to stay under the packet limit.
+const EXTEND_CHUNK: usize = 20;
+
+/// Persisted record of a bot-owned lookup table.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LookupTableRecord {
+    pub address: String,
+    pub market: String,
+    pub created_at_ms: u64,
+}
+
+/// Most recently created lookup table for the market, if any.
+pub fn latest_table(store: &Store, market: &Pubkey) -> Result<Option<Pubkey>> {
+    let records: Vec<LookupTableRecord> = store.read_all(LOOKUP_TABLES)?;
+    records
+        .iter()
+        .rev()
+        .find(|r| r.market == market.to_string())
+        .map(|r| r.address.parse().context("Invalid lookup table address in store"))
+        .transpose()
+}
+
+/// Accounts that appear in most liquidations on the market: program, market, sysvars, tip
+/// accounts, and every reserve's mints, vaults, oracles and our token accounts for them.
+pub fn frequent_accounts(reserves: &HashMap<Pubkey, types::Reserve>, market: &Pubkey, owner: &Pubkey) -> Vec<Pubkey> {
+    let mut accounts = BTreeSet::new();
+    accounts.insert(PROGRAM_ID);
+    accounts.insert(*market);
+    accounts.insert(spl_token::ID);
+    accounts.insert(solana_sdk::sysvar::instructions::ID);
+    accounts.extend(JITO_TIP_ACCOUNTS.iter().filter_map(|a| a.parse::<Pubkey>().ok()));
+
+    for (pk, reserve) in reserves.iter().filter(|(_, r)| r.lending_market == *market) {
+        let token_program = reserve.liquidity.token_program;
+        let info = &reserve.config.token_info;
+        accounts.extend([
+            *pk,
+            token_program,
+            reserve.liquidity.mint_pubkey,
+            reserve.liquidity.supply_vault,
+            reserve.liquidity.fee_vault,
+            reserve.collateral.mint_pubkey,
+            reserve.collateral.supply_vault,
+            info.pyth_configuration.price,
+            info.switchboard_configuration.price_aggregator,
+            info.switchboard_configuration.twap_aggregator,
+            info.scope_configuration.price_feed,
+            get_associated_token_address_with_program_id(owner, &reserve.liquidity.mint_pubkey, &token_program),
+            get_associated_token_address_with_program_id(owner, &reserve.collateral.mint_pubkey, &spl_token::ID),
+        ]);
+    }
+    accounts.remove(&Pubkey::default());
+    accounts.into_iter().collect()
+}
+
+/// Create a new lookup table owned by `payer` and record it in the store.
+pub async fn create(rpc: &Rpc, payer: &Keypair, market: &Pubkey, store: &Store) -> Result<Pubkey> {
+    rpc.throttle(RequestClass::Candidate).await;
+    let recent_slot =
+        rpc.get_slot_with_commitment(CommitmentConfig::finalized()).context("Failed to fetch recent slot")?;
+    let (ix, table) = create_lookup_table(payer.pubkey(), payer.pubkey(), recent_slot);
+    send_and_confirm(rpc, payer, &[ix]).await.context("Failed to create lookup table")?;
+
+    store.append(
+        LOOKUP_TABLES,
+        &LookupTableRecord { address: table.to_string(), market: market.to_string(), created_at_ms: now_millis() },
+    )?;
+    info!(table = %table, "Created lookup table");
+    Ok(table)
+}
+
+/// Append the accounts missing from the table; returns how many were added.
+pub async fn extend(rpc: &Rpc, payer: &Keypair, table: &Pubkey, accounts: &[Pubkey]) -> Result<usize> {
+    let existing = load(rpc, table).await?.addresses;
+    let missing: Vec<Pubkey> = accounts.iter().filter(|a| !existing.contains(a)).copied().collect();
+    ensure!(
+        existing.len() + missing.len() <= MAX_TABLE_ADDRESSES,
+        "Lookup table would exceed {MAX_TABLE_ADDRESSES} addresses ({} present, {} to add)",
+        existing.len(),
+        missing.len()
+    );
+
+    for chunk in missing.chunks(EXTEND_CHUNK) {
+        let ix = extend_lookup_table(*table, payer.pubkey(), Some(payer.pubkey()), chunk.to_vec());
+        send_and_confirm(rpc, payer, &[ix]).await.context("Failed to extend lookup table")?;
+        info!(table = %table, added = chunk.len(), "Extended lookup table");
+    }
+    Ok(missing.len())
+}
+
+/// Fetch a lookup table for use in v0 message compilation.
+pub async fn load(rpc: &Rpc, table: &Pubkey) -> Result<AddressLookupTableAccount> {
+    rpc.throttle(RequestClass::Candidate).await;
+    let acc = rpc.get_account(table).context("Failed to fetch lookup table")?;
+    let state = AddressLookupTable::deserialize(&acc.data)
+        .map_err(|e| anyhow::anyhow!("Failed to decode lookup table: {e}"))?;
+    Ok(AddressLookupTableAccount { key: *table, addresses: state.addresses.to_vec() })
+}
+
+async fn send_and_confirm(rpc: &Rpc, payer: &Keypair, ixs: &[Instruction]) -> Result<()> {
+    rpc.throttle(RequestClass::Blockhash).await;
+    let blockhash = rpc.get_latest_blockhash().context("Failed to fetch blockhash")?;
+    let tx = Transaction::new_signed_with_payer(ixs, Some(&payer.pubkey()), &[payer], blockhash);
+    rpc.send_and_confirm_transaction(&tx).context("Transaction failed")?;
+    Ok(())
+}
@@ -1,40 +1,349 @@
+use std::collections::HashMap;
+use std::io::Write;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::{ArgAction, Parser, Subcommand};
-use tracing::{error, info, warn};
+use futures::FutureExt;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::Signer;
+use tracing::{debug, error, info, warn};
 
-mod config;
-mod kamino;
-mod health;
-mod jito;
-mod util;
-
-use crate::config::Config;
-use crate::kamino::{find_liquidation_candidates, build_liquidation_ix};
-use crate::jito::{JitoSender, TipAccount};
-use crate::util::{build_tx_with_tip, fetch_latest_blockhash};
+use solana_liquidation::alert::{Alerter, OpportunityNotifier};
+use solana_liquidation::auction::{Auction, AuctionConfig, AuctionSinks, Auctions};
+use solana_liquidation::audit::{audit, AuditContext};
+use solana_liquidation::blink::Blinks;
+use solana_liquidation::bundles::BundleBook;
+use solana_liquidation::config::{derive_ws_url, resolve_rpc_url, Config, FileConfig, SecretSource};
+use solana_liquidation::contention::{writable_accounts, ContentionMap, CONTENTION};
+use solana_liquidation::control::{RuntimeControls, TelegramControl};
+use solana_liquidation::coordination::RedisLease;
+use solana_liquidation::deadline::{Deadline, StageTimeouts};
+use solana_liquidation::dump::FailureDump;
+use solana_liquidation::events::{install_recorder, record_candidates, shutdown_recorder, EventReader, CSV_HEADER};
+use solana_liquidation::fees::FeeMarket;
+use solana_liquidation::health::PREFILTER_HEALTH_THRESHOLD;
+use solana_liquidation::history::{scan_at_slot, SnapshotStore};
+use solana_liquidation::inventory::Inventory;
+use solana_liquidation::jito::{JitoConnectOptions, JitoSender, TipAccount};
+use solana_liquidation::kamino::{
+    build_liquidation_ix, decode_accounts, fetch_obligation, find_liquidation_candidates,
+};
+use solana_liquidation::kamino_api::{HealthCrossCheck, KaminoApi, DEFAULT_KAMINO_API_URL};
+use solana_liquidation::keeper::{batch_refresh_ixs, candidate_referrer_ixs, crank, fetch_refresh_ixs, Keeper};
+use solana_liquidation::latency::{observe_stage, Stage};
+use solana_liquidation::layout::check_layouts;
+use solana_liquidation::lut;
+use solana_liquidation::opportunity::{OpportunityLog, SkipReason};
+use solana_liquidation::oracle::{OracleGuard, PriceQuorum};
+use solana_liquidation::pda::MarketAccounts;
+use solana_liquidation::protect::Protector;
+use solana_liquidation::proxy::Proxy;
+use solana_liquidation::races::RaceReport;
+use solana_liquidation::reconcile::{BalanceLedger, ReconcileConfig, Reconciler};
+use solana_liquidation::replay::replay_tx;
+use solana_liquidation::resubmit::BundleResubmitter;
+use solana_liquidation::retry::{retry, CircuitBreaker, RetryPolicy};
+use solana_liquidation::rpc::{Rpc, RpcLimits};
+use solana_liquidation::scan::{fetch_peer_cache, ObligationType, ProgramScanner, ScanStrategy};
+use solana_liquidation::scan_bench::{bench_scan, ScanSource};
+use solana_liquidation::scan_state::ScanState;
+use solana_liquidation::schedule::Activity;
+use solana_liquidation::sender::{JitoTxSender, SenderKind, SenderPolicy, Senders, DEFAULT_JITO_TX_URL};
+use solana_liquidation::simulate::{confirm_profit, simulate_liquidation, SIMULATIONS};
+use solana_liquidation::skew::{SkewConfig, SkewMonitor};
+use solana_liquidation::status::StatusServer;
+use solana_liquidation::store::Store;
+use solana_liquidation::strategy::RepayPreference;
+use solana_liquidation::summary::{record_scan, record_submission, Summary};
+use solana_liquidation::supervisor::{
+    install_panic_hook, panic_message, record_restart, restart_policy, spawn_supervised,
+};
+use solana_liquidation::swap::{JupiterClient, DEFAULT_JUPITER_URL};
+use solana_liquidation::template::TemplateCache;
+use solana_liquidation::tracker::SignatureTracker;
+use solana_liquidation::treasury::{SweepConfig, Sweeper, TopUpConfig, Treasury, USDC_MINT};
+use solana_liquidation::unwind::{UnwindConfig, Unwinder};
+use solana_liquidation::util::{fetch_blockhash, fetch_slot, now_millis, BackgroundSigner, ComputeBudget, TxBuilder};
+use solana_liquidation::volatility::PriceWatch;
 
 /// Kamino liquidation bot entrypoint.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// RPC URL for Solana cluster
     #[arg(long, env = "RPC_URL")]
     rpc_url: Option<String>,
 
-    /// Path to payer keypair
-    #[arg(long, env = "PAYER", value_name = "FILE")]
-    payer: Option<PathBuf>,
+    /// Payer keypair: a file path, `aws-sm://{secret}`, `aws-kms://{encrypted file}` or
+    /// `vault://{mount}/{path}#{field}`
+    #[arg(long, env = "PAYER", value_name = "SOURCE")]
+    payer: Option<SecretSource>,
+
+    /// Keypair owning the liquidity, when fees and tips are paid by a separate payer; same
+    /// sources as the payer
+    #[arg(long, env = "OWNER", value_name = "SOURCE")]
+    owner: Option<SecretSource>,
 
     /// Kamino Lending market address
     #[arg(long, env = "MARKET", default_value = "7u3HeHxYDLhnCoErrtycNokbQYbWGzLs6JSDqGAv5PfF")]
     market: String,
 
+    /// Websocket URL for subscriptions (derived from the RPC URL if unset)
+    #[arg(long, env = "WS_URL")]
+    ws_url: Option<String>,
+
+    /// Optional TOML config file with strategy profiles and per-market settings
+    #[arg(long, env = "CONFIG", value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Strategy profile name, overriding the market's configured profile
+    #[arg(long, env = "STRATEGY")]
+    strategy: Option<String>,
+
+    /// Directory for persisted bot records
+    #[arg(long, env = "DATA_DIR", default_value = "data")]
+    data_dir: PathBuf,
+
+    /// Address for the status API (metrics and bundle lookups); disabled when unset
+    #[arg(long, env = "STATUS_ADDR")]
+    status_addr: Option<std::net::SocketAddr>,
+
+    /// Bearer token for the status API's `/control` routes (strategy, dry-run, tip cap, market
+    /// pauses and maintenance mode); disabled when unset
+    #[arg(long, env = "CONTROL_TOKEN")]
+    control_token: Option<String>,
+
+    /// Bearer token for the status API's bundle and cache routes; the control token also works.
+    /// Both are refused when neither is set. Also sent to the --warm-start-from peer
+    #[arg(long, env = "STATUS_TOKEN")]
+    status_token: Option<String>,
+
+    /// Report what the market looked like at this slot and exit, from an RPC whose state is at
+    /// that slot or else the newest snapshot at or before it
+    #[arg(long, value_name = "SLOT")]
+    at_slot: Option<u64>,
+
+    /// Append scanner and submission events to this compact binary log; read it back with `events`
+    #[arg(long, env = "EVENT_LOG")]
+    event_log: Option<PathBuf>,
+
+    /// Directory of market snapshots recorded while scanning, read back by `--at-slot`
+    #[arg(long, env = "SNAPSHOT_DIR")]
+    snapshot_dir: Option<PathBuf>,
+
+    /// Seconds between recorded market snapshots
+    #[arg(long, env = "SNAPSHOT_INTERVAL_SECS", default_value_t = 300)]
+    snapshot_interval_secs: u64,
+
+    /// Newest market snapshots kept on disk
+    #[arg(long, env = "SNAPSHOT_KEEP", default_value_t = 288)]
+    snapshot_keep: usize,
+
+    /// Telegram bot token to take runtime control commands from, e.g. `/dryrun on`
+    #[arg(long, env = "TELEGRAM_BOT_TOKEN", requires = "telegram_chats")]
+    telegram_token: Option<String>,
+
+    /// Telegram chat IDs allowed to send control commands
+    #[arg(long, env = "TELEGRAM_CHAT_IDS", value_delimiter = ',')]
+    telegram_chats: Vec<i64>,
+
+    /// Seconds to wait for a submitted signature before counting it as dropped
+    #[arg(long, env = "TRACK_TIMEOUT_SECS", default_value_t = 60)]
+    track_timeout_secs: u64,
+
+    /// Most Jito bundles awaiting an outcome at once, bounding tip exposure; candidates beyond it
+    /// wait for the next scan (0 disables)
+    #[arg(long, env = "MAX_INFLIGHT_BUNDLES", default_value_t = 0)]
+    max_inflight_bundles: usize,
+
+    /// Sustained RPC request budget (credits per second)
+    #[arg(long, env = "RPC_RPS", default_value_t = 10.0)]
+    rpc_rps: f64,
+
+    /// Maximum RPC credits that may be spent in a burst
+    #[arg(long, env = "RPC_BURST", default_value_t = 40)]
+    rpc_burst: u32,
+
+    /// Credits charged per getProgramAccounts call
+    #[arg(long, env = "RPC_GPA_COST", default_value_t = 10)]
+    rpc_gpa_cost: u32,
+
+    /// Program scan strategy: full, chunked, or auto (full with chunked fallback)
+    #[arg(long, env = "SCAN_STRATEGY", default_value = "auto")]
+    scan_strategy: ScanStrategy,
+
+    /// Seconds between full program scans (0 scans fully every pass); passes in between refresh
+    /// reserves and catch up on obligations from market transaction history
+    #[arg(long, env = "RESCAN_SECS", default_value_t = 0)]
+    rescan_secs: u64,
+
+    /// Seconds an obligation without borrows stays in the incremental scan cache
+    #[arg(long, env = "CACHE_IDLE_TTL_SECS", default_value_t = 600)]
+    cache_idle_ttl_secs: u64,
+
+    /// Status API URL of a running instance to copy the incremental scan cache from at startup,
+    /// instead of a full scan; needs --rescan-secs
+    #[arg(long, env = "WARM_START_FROM", value_name = "URL")]
+    warm_start_from: Option<String>,
+
+    /// Obligation product types to scan (vanilla, multiply, lending, leverage); all when unset
+    #[arg(long, env = "OBLIGATION_TYPES", value_delimiter = ',')]
+    obligation_types: Vec<ObligationType>,
+
+    /// Obligations below this health are watched and get pre-built liquidation templates (max 1.1)
+    #[arg(long, env = "WATCH_HEALTH", default_value_t = 1.05)]
+    watch_health: f64,
+
+    /// Seconds before a watchlist template is rebuilt
+    #[arg(long, env = "TEMPLATE_MAX_AGE_SECS", default_value_t = 30)]
+    template_max_age_secs: u64,
+
+    /// Seconds between keeper refreshes of watched obligations (0 disables)
+    #[arg(long, env = "KEEPER_INTERVAL_SECS", default_value_t = 0)]
+    keeper_interval_secs: u64,
+
+    /// Seconds between template cache reconciliation passes (0 disables)
+    #[arg(long, env = "RECONCILE_INTERVAL_SECS", default_value_t = 60)]
+    reconcile_interval_secs: u64,
+
+    /// Cached templates re-verified against RPC per reconciliation pass
+    #[arg(long, env = "RECONCILE_SAMPLE", default_value_t = 8)]
+    reconcile_sample: usize,
+
+    /// Worker threads for account decoding (0 = one per core)
+    #[arg(long, env = "DECODE_THREADS", default_value_t = 0)]
+    decode_threads: usize,
+
+    /// Consecutive failed iterations before the circuit breaker opens
+    #[arg(long, env = "BREAKER_THRESHOLD", default_value_t = 5)]
+    breaker_threshold: u32,
+
+    /// Seconds to pause once the circuit breaker opens
+    #[arg(long, env = "BREAKER_COOLDOWN_SECS", default_value_t = 30)]
+    breaker_cooldown_secs: u64,
+
+    /// Optional webhook URL for operator alerts
+    #[arg(long, env = "ALERT_WEBHOOK")]
+    alert_webhook: Option<String>,
+
+    /// Pause submissions and alert when the local clock is this far off the cluster's block time
+    #[arg(long, env = "MAX_CLOCK_SKEW_MS")]
+    max_clock_skew_ms: Option<u64>,
+
+    /// Pause submissions and alert when RPC and websocket slots, or RPC slots and elapsed time,
+    /// disagree by more than this many slots
+    #[arg(long, env = "MAX_SLOT_LAG")]
+    max_slot_lag: Option<u64>,
+
+    /// Seconds between clock and slot skew checks
+    #[arg(long, env = "SKEW_CHECK_SECS", default_value_t = 5)]
+    skew_check_secs: u64,
+
+    /// In dry-run, post each liquidation found with its unsigned transactions to this webhook,
+    /// for manual execution or a separate signer
+    #[arg(long, env = "OPPORTUNITY_WEBHOOK")]
+    opportunity_webhook: Option<String>,
+
+    /// Serve Solana Actions (blinks) for liquidatable obligations on the status API, shown with
+    /// this icon URL; wallets sign the liquidation themselves
+    #[arg(long, env = "BLINK_ICON_URL")]
+    blink_icon_url: Option<String>,
+
+    /// Seconds before the same obligation is posted to the opportunity webhook again
+    #[arg(long, env = "OPPORTUNITY_COOLDOWN_SECS", default_value_t = 60)]
+    opportunity_cooldown_secs: u64,
+
+    /// Write the base64 transactions and simulation logs of reverted or failed submissions here
+    #[arg(long, env = "DUMP_FAILED_DIR", value_name = "DIR")]
+    dump_failed_dir: Option<PathBuf>,
+
+    /// Start even when live Kamino accounts no longer match the decoder's layouts
+    #[arg(long, env = "SKIP_LAYOUT_CHECK", action = ArgAction::SetTrue)]
+    skip_layout_check: bool,
+
     /// Tip lamports to include per liquidation tx
     #[arg(long, env = "TIP_LAMPORTS", default_value_t = 5_000)]
     tip_lamports: u64,
 
+    /// Pay the tip in a separate last bundle transaction (forces the bundle sender)
+    #[arg(long, env = "SEPARATE_TIP", action = ArgAction::SetTrue)]
+    separate_tip: bool,
+
+    /// Auction candidates with at least this expected profit (lamports); unset disables auctions
+    #[arg(long, env = "AUCTION_MIN_PROFIT_LAMPORTS")]
+    auction_min_profit_lamports: Option<u64>,
+
+    /// Tip multiplier per auction round
+    #[arg(long, env = "AUCTION_TIP_GROWTH", default_value_t = 1.5)]
+    auction_tip_growth: f64,
+
+    /// Maximum share of expected profit an auction may tip
+    #[arg(long, env = "AUCTION_MAX_PROFIT_SHARE", default_value_t = 0.5)]
+    auction_max_profit_share: f64,
+
+    /// Maximum auction rounds per candidate
+    #[arg(long, env = "AUCTION_MAX_ROUNDS", default_value_t = 8)]
+    auction_max_rounds: u32,
+
+    /// Milliseconds between auction rounds
+    #[arg(long, env = "AUCTION_ROUND_MS", default_value_t = 400)]
+    auction_round_ms: u64,
+
+    /// Resend a bundle unchanged to up to this many following Jito leaders when it misses its
+    /// target slot, while its blockhash is valid (0 disables)
+    #[arg(long, env = "BUNDLE_RESUBMIT_ATTEMPTS", default_value_t = 0)]
+    bundle_resubmit_attempts: u32,
+
+    /// Also liquidate positions eligible for auto-deleveraging in reserves over their caps
+    #[arg(long, env = "DELEVERAGE", action = ArgAction::SetTrue)]
+    deleverage: bool,
+
+    /// Replace the blockhash once this few blocks remain before it expires
+    #[arg(long, env = "BLOCKHASH_MARGIN_BLOCKS", default_value_t = 20)]
+    blockhash_margin_blocks: u64,
+
+    /// Report writable-account contention per candidate, flagging reserves targeted by at least this many candidates in a pass
+    #[arg(long, env = "CONTENTION_THRESHOLD")]
+    contention_threshold: Option<usize>,
+
+    /// Minutes between periodic summary log lines (0 disables; a lifetime summary is always printed on exit)
+    #[arg(long, env = "SUMMARY_INTERVAL_MINS", default_value_t = 15)]
+    summary_interval_mins: u64,
+
+    /// Redis URL (redis://[[user]:password@]host:port) for per-obligation leases shared with redundant instances
+    #[arg(long, env = "REDIS_URL")]
+    redis_url: Option<String>,
+
+    /// Identifier of this instance in coordination leases (defaults to host name and pid)
+    #[arg(long, env = "INSTANCE_ID")]
+    instance_id: Option<String>,
+
+    /// How long a lease on an obligation is held after submitting for it, in milliseconds
+    #[arg(long, env = "LEASE_MS", default_value_t = 10_000)]
+    lease_ms: u64,
+
+    /// Latency budget per candidate from detection to submission, in milliseconds
+    #[arg(long, env = "CANDIDATE_BUDGET_MS", default_value_t = 2_000)]
+    candidate_budget_ms: u64,
+
+    /// Cap on account fetches while building a candidate's instruction, in milliseconds
+    #[arg(long, env = "FETCH_TIMEOUT_MS", default_value_t = 500)]
+    fetch_timeout_ms: u64,
+
+    /// Cap on swap quoting, in milliseconds
+    #[arg(long, env = "QUOTE_TIMEOUT_MS", default_value_t = 600)]
+    quote_timeout_ms: u64,
+
+    /// Cap on simulation; skipped when less than this remains of the budget, in milliseconds
+    #[arg(long, env = "SIMULATE_TIMEOUT_MS", default_value_t = 1_000)]
+    simulate_timeout_ms: u64,
+
     /// Compute unit price (micro-lamports per CU)
     #[arg(long, env = "CU_PRICE", default_value_t = 2_000)]
     cu_price: u64,
@@ -43,14 +352,27 @@ struct Cli {
     #[arg(long, env = "CU_LIMIT", default_value_t = 300_000)]
     cu_limit: u32,
 
-    /// Use dry-run (simulate only, no send)
+    /// Request a larger heap frame in bytes (multiple of 1024, 32768..=262144)
+    #[arg(long, env = "HEAP_FRAME_BYTES")]
+    heap_frame_bytes: Option<u32>,
+
+    /// Use dry-run (simulate and record each liquidation, no send)
     #[arg(long, action = ArgAction::SetTrue)]
     dry_run: bool,
 
+    /// Only submit liquidations whose simulation succeeds with token balance changes worth more
+    /// than the tip and fees
+    #[arg(long, env = "STRICT_SIM")]
+    strict_sim: bool,
+
     /// Run one iteration then exit
     #[arg(long, action = ArgAction::SetTrue)]
     once: bool,
 
+    /// Log every scan's result, not only what changed since the previous scan
+    #[arg(long, action = ArgAction::SetTrue)]
+    verbose: bool,
+
     /// Jito gRPC timeout in seconds
     #[arg(long, env = "JITO_TIMEOUT", default_value_t = 2)]
     jito_timeout: u64,
@@ -59,9 +381,236 @@ struct Cli {
     #[arg(long, env = "JITO_ENDPOINT")]
     jito_endpoint: Option<String>,
 
+    /// Seconds between Jito region latency probes (0 disables; ignored with --jito-endpoint)
+    #[arg(long, env = "JITO_PROBE_INTERVAL_SECS", default_value_t = 300)]
+    jito_probe_interval_secs: u64,
+
+    /// PEM CA bundle trusted for the Jito block engine instead of the system roots
+    #[arg(long, env = "JITO_CA_CERT", value_name = "FILE")]
+    jito_ca_cert: Option<PathBuf>,
+
+    /// TLS server name to verify when the Jito endpoint is addressed by IP
+    #[arg(long, env = "JITO_TLS_DOMAIN")]
+    jito_tls_domain: Option<String>,
+
+    /// Proxy for the Jito connection: http://[user:pass@]host:port or socks5://[user:pass@]host:port
+    #[arg(long, env = "JITO_PROXY")]
+    jito_proxy: Option<String>,
+
+    /// Local address to connect to Jito from (an IPv6 address forces IPv6)
+    #[arg(long, env = "JITO_BIND")]
+    jito_bind: Option<std::net::IpAddr>,
+
+    /// Seconds between HTTP/2 keep-alive pings on the Jito connection
+    #[arg(long, env = "JITO_KEEPALIVE_SECS")]
+    jito_keepalive_secs: Option<u64>,
+
+    /// Seconds to wait for a Jito keep-alive acknowledgement before dropping the connection
+    #[arg(long, env = "JITO_KEEPALIVE_TIMEOUT_SECS")]
+    jito_keepalive_timeout_secs: Option<u64>,
+
+    /// Submission backend: bundle, jito-tx (revert-protected), rpc, or auto
+    #[arg(long, env = "SENDER", default_value = "bundle")]
+    sender: SenderPolicy,
+
+    /// In auto sender mode, candidates expected to earn at least this many lamports are sent as bundles
+    #[arg(long, env = "CONTENTION_PROFIT_LAMPORTS", default_value_t = 10_000_000)]
+    contention_profit_lamports: u64,
+
+    /// Jito sendTransaction endpoint
+    #[arg(long, env = "JITO_TX_URL", default_value = DEFAULT_JITO_TX_URL)]
+    jito_tx_url: String,
+
     /// Optional explicit tip account to use
     #[arg(long, env = "TIP_ACCOUNT")]
     tip_account: Option<String>,
+
+    /// Top up the payer once its balance drops below this many lamports
+    #[arg(long, env = "MIN_SOL_LAMPORTS", default_value_t = 100_000_000)]
+    min_sol_lamports: u64,
+
+    /// Balance to refill the payer to, in lamports
+    #[arg(long, env = "TOP_UP_TARGET_LAMPORTS", default_value_t = 500_000_000)]
+    top_up_target_lamports: u64,
+
+    /// Mint swapped into SOL for top-ups
+    #[arg(long, env = "TOP_UP_MINT", default_value = USDC_MINT)]
+    top_up_mint: String,
+
+    /// Swap profit into SOL automatically when low (otherwise only alert)
+    #[arg(long, env = "AUTO_TOP_UP", action = ArgAction::SetTrue)]
+    auto_top_up: bool,
+
+    /// Cold wallet receiving profit sweeps; unset disables sweeping
+    #[arg(long, env = "SWEEP_TO")]
+    sweep_to: Option<String>,
+
+    /// SOL kept on the payer after a sweep, in lamports (never below the top-up threshold)
+    #[arg(long, env = "SWEEP_SOL_FLOAT_LAMPORTS", default_value_t = 1_000_000_000)]
+    sweep_sol_float_lamports: u64,
+
+    /// Top-up mint base units kept on the payer after a sweep
+    #[arg(long, env = "SWEEP_TOKEN_FLOAT", default_value_t = 100_000_000)]
+    sweep_token_float: u64,
+
+    /// Seconds between profit sweeps
+    #[arg(long, env = "SWEEP_INTERVAL_SECS", default_value_t = 3_600)]
+    sweep_interval_secs: u64,
+
+    /// Sell seized collateral into the unwind mint after liquidations
+    #[arg(long, env = "UNWIND", action = ArgAction::SetTrue)]
+    unwind: bool,
+
+    /// Token seized collateral is sold into
+    #[arg(long, env = "UNWIND_MINT", default_value = USDC_MINT)]
+    unwind_mint: String,
+
+    /// Slippage accepted on collateral sales, in basis points
+    #[arg(long, env = "UNWIND_SLIPPAGE_BPS", default_value_t = 50)]
+    unwind_slippage_bps: u16,
+
+    /// Seconds between attempts to sell seized collateral
+    #[arg(long, env = "UNWIND_INTERVAL_SECS", default_value_t = 30)]
+    unwind_interval_secs: u64,
+
+    /// Seconds between refreshes of the token balances the `held` repay preference checks
+    #[arg(long, env = "INVENTORY_REFRESH_SECS", default_value_t = 30)]
+    inventory_refresh_secs: u64,
+
+    /// Seconds between balance reconciliations against recorded liquidations and swaps; 0 disables
+    #[arg(long, env = "BALANCE_RECONCILE_INTERVAL_SECS", default_value_t = 0)]
+    balance_reconcile_interval_secs: u64,
+
+    /// Balance difference accepted relative to the expected change, in basis points
+    #[arg(long, env = "BALANCE_RECONCILE_TOLERANCE_BPS", default_value_t = 100)]
+    balance_reconcile_tolerance_bps: u16,
+
+    /// SOL difference always accepted, covering fees of cranks and other unrecorded transactions
+    #[arg(long, env = "BALANCE_RECONCILE_SOL_DUST_LAMPORTS", default_value_t = 10_000_000)]
+    balance_reconcile_sol_dust_lamports: u64,
+
+    /// Token difference always accepted, in base units
+    #[arg(long, env = "BALANCE_RECONCILE_TOKEN_DUST", default_value_t = 1_000)]
+    balance_reconcile_token_dust: u64,
+
+    /// Skip candidates whose oracle price diverges from the Jupiter quote midpoint by more than this,
+    /// in basis points
+    #[arg(long, env = "MAX_ORACLE_DIVERGENCE_BPS")]
+    max_oracle_divergence_bps: Option<f64>,
+
+    /// Before liquidations seizing at least this many lamports, require the reserves' Scope and
+    /// Pyth feeds to agree with their cached prices; disagreements are skipped and alerted
+    #[arg(long, env = "PRICE_QUORUM_MIN_SEIZED_LAMPORTS")]
+    price_quorum_min_seized_lamports: Option<u64>,
+
+    /// Largest divergence of a feed from the cached price accepted by the price quorum, in basis points
+    #[arg(long, env = "PRICE_QUORUM_MAX_DIVERGENCE_BPS", default_value_t = 100.0)]
+    price_quorum_max_divergence_bps: f64,
+
+    /// Jupiter swap API base URL
+    #[arg(long, env = "JUPITER_URL", default_value = DEFAULT_JUPITER_URL)]
+    jupiter_url: String,
+
+    /// Compare the health of detected candidates with Kamino's API and log where they disagree
+    #[arg(long, env = "CROSS_CHECK_HEALTH", action = ArgAction::SetTrue)]
+    cross_check_health: bool,
+
+    /// Relative health difference from Kamino's figure logged as a discrepancy
+    #[arg(long, env = "CROSS_CHECK_TOLERANCE", default_value_t = 0.02)]
+    cross_check_tolerance: f64,
+
+    /// Shortest time between health cross-checks of one obligation, in seconds
+    #[arg(long, env = "CROSS_CHECK_INTERVAL_SECS", default_value_t = 60)]
+    cross_check_interval_secs: u64,
+
+    /// Kamino API base URL, for the health cross-check
+    #[arg(long, env = "KAMINO_API_URL", default_value = DEFAULT_KAMINO_API_URL)]
+    kamino_api_url: String,
+
+    /// Address lookup table used to compress transactions (defaults to the latest one created by `lut create`)
+    #[arg(long, env = "LOOKUP_TABLE")]
+    lookup_table: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Walk one obligation through the decision pipeline, printing every intermediate number
+    Audit {
+        /// Obligation account to audit
+        obligation: String,
+    },
+
+    /// Time the fetch+decode+health scan pipeline and report per-stage latency
+    BenchScan {
+        /// Number of scan iterations
+        #[arg(long, default_value_t = 10)]
+        iterations: usize,
+
+        /// Read program accounts from a snapshot file instead of live RPC
+        #[arg(long, value_name = "FILE")]
+        snapshot: Option<PathBuf>,
+
+        /// Write the first fetched account set to a snapshot file
+        #[arg(long, value_name = "FILE", conflicts_with = "snapshot")]
+        record: Option<PathBuf>,
+    },
+
+    /// Crank RefreshReserve/RefreshObligation for watched positions without liquidating
+    Crank {
+        /// Seconds between crank passes
+        #[arg(long, default_value_t = 30)]
+        interval_secs: u64,
+    },
+
+    /// Replay a landed liquidation and diff our health and profit decision against it
+    ReplayTx {
+        /// Signature of the liquidation transaction
+        signature: String,
+
+        /// RPC serving the transaction, and account state when it is still before the liquidation
+        /// (defaults to --rpc-url); otherwise the pre-state comes from --snapshot-dir
+        #[arg(long, env = "ARCHIVE_RPC_URL")]
+        archive_rpc_url: Option<String>,
+    },
+
+    /// Manage the bot-owned address lookup table
+    Lut {
+        #[command(subcommand)]
+        action: LutCommand,
+    },
+
+    /// Convert a binary event log written under --event-log to JSON lines or CSV on stdout
+    Events {
+        /// Event log to read
+        path: PathBuf,
+
+        #[arg(long, default_value = "json", value_parser = ["json", "csv"])]
+        format: String,
+    },
+
+    /// Summarize recorded liquidation windows
+    Report {
+        #[command(subcommand)]
+        report: ReportCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ReportCommand {
+    /// Attribute each closed window to a cause: won, detected too late, tip too low, simulation gate, RPC error
+    Races {
+        /// Only windows closed within this many hours
+        #[arg(long, default_value_t = 24)]
+        hours: u64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum LutCommand {
+    /// Create a lookup table and fill it with the market's frequently used accounts
+    Create,
+    /// Add any missing frequently used accounts to the lookup table
+    Extend,
 }
 
 #[tokio::main]
@@ -72,86 +621,1034 @@ async fn main() -> Result<()> {
         .with_target(false)
         .compact()
         .init();
+    install_panic_hook();
 
     let cli = Cli::parse();
-    let cfg = Config::from_env(cli.rpc_url.clone(), cli.payer.clone())?;
 
-    info!(rpc = %cfg.rpc_url, payer = %cfg.payer_path.display(), "Starting Kamino liquidation bot");
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(cli.decode_threads)
+        .build_global()
+        .context("Failed to initialize decode thread pool")?;
 
-    // Initialize RPC client and jito sender
-    let rpc = solana_client::rpc_client::RpcClient::new(cfg.rpc_url.clone());
-    let mut jito = JitoSender::new(cli.jito_endpoint.clone(), Some(cli.jito_timeout)).await?;
+    let rpc_limits = RpcLimits { rps: cli.rpc_rps, burst: cli.rpc_burst, gpa_cost: cli.rpc_gpa_cost };
+
+    if let Some(Command::BenchScan { iterations, snapshot, record }) = cli.command.as_ref() {
+        let rpc = Rpc::new(resolve_rpc_url(cli.rpc_url.clone()), rpc_limits);
+        let market = cli.market.parse().context("Invalid market address")?;
+        let scanner =
+            ProgramScanner::new(cli.scan_strategy, market).with_obligation_types(cli.obligation_types.clone());
+        let source = snapshot.as_deref().map_or(ScanSource::Live(&scanner), ScanSource::Snapshot);
+        return bench_scan(&rpc, market, *iterations, source, record.as_deref()).await;
+    }
+
+    if let Some(Command::Events { path, format }) = cli.command.as_ref() {
+        let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+        if format == "csv" {
+            writeln!(out, "{CSV_HEADER}")?;
+        }
+        for record in EventReader::open(path)? {
+            let record = record?;
+            match format.as_str() {
+                "csv" => writeln!(out, "{}", record.csv_row())?,
+                _ => writeln!(out, "{}", record.to_json())?,
+            }
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    if let Some(Command::Report { report: ReportCommand::Races { hours } }) = cli.command.as_ref() {
+        let store = Store::open(&cli.data_dir)?;
+        let since_ms = now_millis().saturating_sub(hours * 3_600_000);
+        print!("{}", RaceReport::build(&store, since_ms)?.render());
+        return Ok(());
+    }
+
+    if let Some(Command::Audit { obligation }) = cli.command.as_ref() {
+        let rpc = Rpc::new(resolve_rpc_url(cli.rpc_url.clone()), rpc_limits);
+        let (_, strategy) =
+            FileConfig::load(cli.config.as_deref())?.strategy_for(&cli.market, cli.strategy.as_deref())?;
+        let obligation = obligation.parse().context("Invalid obligation address")?;
+        let ctx = AuditContext {
+            strategy: &strategy,
+            jupiter: &JupiterClient::new(cli.jupiter_url.clone()),
+            default_tip_floor: cli.tip_lamports,
+            slippage_bps: cli.unwind_slippage_bps,
+        };
+        print!("{}", audit(&rpc, &obligation, &ctx).await?);
+        return Ok(());
+    }
+
+    let snapshots = cli
+        .snapshot_dir
+        .as_deref()
+        .map(|dir| {
+            SnapshotStore::open(
+                dir,
+                std::time::Duration::from_secs(cli.snapshot_interval_secs),
+                cli.snapshot_keep.max(1),
+            )
+        })
+        .transpose()?
+        .map(Arc::new);
+
+    if let Some(slot) = cli.at_slot {
+        let rpc = Rpc::new(resolve_rpc_url(cli.rpc_url.clone()), rpc_limits);
+        let (_, strategy) =
+            FileConfig::load(cli.config.as_deref())?.strategy_for(&cli.market, cli.strategy.as_deref())?;
+        let market = cli.market.parse().context("Invalid market address")?;
+        let watch_health = cli.watch_health.clamp(1.0, PREFILTER_HEALTH_THRESHOLD);
+        let report = scan_at_slot(Some(&rpc), snapshots.as_deref(), market, slot, watch_health, &strategy).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    if let Some(Command::ReplayTx { signature, archive_rpc_url }) = cli.command.as_ref() {
+        let url = archive_rpc_url.clone().unwrap_or_else(|| resolve_rpc_url(cli.rpc_url.clone()));
+        let rpc = Rpc::new(url, rpc_limits);
+        let (_, strategy) =
+            FileConfig::load(cli.config.as_deref())?.strategy_for(&cli.market, cli.strategy.as_deref())?;
+        let signature = signature.parse().context("Invalid transaction signature")?;
+        let report = replay_tx(&rpc, snapshots.as_deref(), &signature, &strategy).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let cfg = Config::from_env(cli.rpc_url.clone(), cli.payer.clone(), cli.owner.clone()).await?;
+    let file_cfg = FileConfig::load(cli.config.as_deref())?;
+    let (strategy_name, strategy) = file_cfg.strategy_for(&cli.market, cli.strategy.as_deref())?;
+    let liquidator = file_cfg.liquidator_accounts(&[cfg.owner().pubkey(), cfg.payer.pubkey()])?;
+
+    // Initialize RPC client and jito sender; config-file endpoints carry provider auth headers
+    let endpoints = file_cfg.rpc_endpoints()?;
+    let rpc = Arc::new(match endpoints.is_empty() {
+        true => Rpc::new(cfg.rpc_url.clone(), rpc_limits),
+        false => Rpc::with_endpoints(endpoints, rpc_limits)?,
+    });
+
+    info!(
+        rpc = %rpc.url(),
+        payer = %cfg.payer_source,
+        liquidator = %liquidator.owner,
+        strategy = %strategy_name,
+        "Starting Kamino liquidation bot"
+    );
+    let store = Arc::new(Store::open(&cli.data_dir)?);
+
+    if let Some(Command::Lut { action }) = cli.command.as_ref() {
+        return lut_command(&cli, &rpc, &cfg, &store, action).await;
+    }
 
     // Select tip account
-    let tip_acc = if let Some(acc) = cli.tip_account.as_ref() {
-        TipAccount::from_str(acc)?
-    } else {
-        TipAccount::random()
+    let tip_acc =
+        if let Some(acc) = cli.tip_account.as_ref() { TipAccount::from_str(acc)? } else { TipAccount::random() };
+
+    let market = cli.market.parse().context("Invalid market address")?;
+    let mut scanner = ProgramScanner::new(cli.scan_strategy, market)
+        .with_obligation_types(cli.obligation_types.clone())
+        .with_rescan_interval(std::time::Duration::from_secs(cli.rescan_secs))
+        .with_idle_ttl(std::time::Duration::from_secs(cli.cache_idle_ttl_secs));
+    if let Some(store) = snapshots.as_ref() {
+        info!(interval_secs = cli.snapshot_interval_secs, keep = cli.snapshot_keep, "Recording market snapshots");
+        scanner = scanner.with_snapshots(Arc::clone(store));
+    }
+    let scanner = Arc::new(scanner);
+    // A peer's cache replaces the first full scan; on failure the first pass simply scans
+    if let Some(peer) = cli.warm_start_from.as_deref() {
+        let started = std::time::Instant::now();
+        let warmed =
+            retry("warm_start", &RetryPolicy::default(), || fetch_peer_cache(peer, cli.status_token.as_deref()))
+                .await
+                .and_then(|snapshot| scanner.import(snapshot));
+        match warmed {
+            Ok(accounts) => {
+                info!(peer, accounts, elapsed_ms = started.elapsed().as_millis() as u64, "Warm-started scan cache")
+            }
+            Err(e) => warn!(peer, error = %e, "Warm start failed, running a full scan"),
+        }
+    }
+    let market_accounts = MarketAccounts::resolve(&rpc, market).await?;
+    info!(
+        market = %market,
+        risk_council = ?market_accounts.risk_council,
+        autodeleverage = market_accounts.autodeleverage_enabled,
+        "Resolved market accounts"
+    );
+    let deleverage = cli.deleverage && market_accounts.autodeleverage_enabled;
+    if cli.deleverage && !deleverage {
+        warn!(market = %market, "Auto-deleverage requested but disabled on this market");
+    }
+
+    let ws_url = cli.ws_url.clone().unwrap_or_else(|| derive_ws_url(rpc.url()));
+    let bundles = Arc::new(BundleBook::load(Arc::clone(&store))?);
+    let blinks = cli
+        .blink_icon_url
+        .clone()
+        .map(|icon| Arc::new(Blinks::new(Arc::clone(&rpc), market_accounts.clone(), strategy.clone(), icon)));
+    // Submissions pause outside the configured windows or while an operator enables maintenance
+    let activity = Arc::new(Activity::new(file_cfg.schedule()?));
+    if cli.max_clock_skew_ms.is_some() || cli.max_slot_lag.is_some() {
+        let cfg = SkewConfig {
+            interval: std::time::Duration::from_secs(cli.skew_check_secs.max(1)),
+            max_clock_skew: cli.max_clock_skew_ms.map(std::time::Duration::from_millis),
+            max_slot_lag: cli.max_slot_lag,
+        };
+        let monitor =
+            SkewMonitor::new(cfg, Arc::clone(&rpc), Arc::clone(&activity), Alerter::new(cli.alert_webhook.clone()));
+        let (watcher, ws_url) = (Arc::clone(&monitor), ws_url.clone());
+        spawn_supervised("slot_watch", restart_policy(), move || Arc::clone(&watcher).watch_slots(ws_url.clone()));
+        spawn_supervised("skew_monitor", restart_policy(), move || Arc::clone(&monitor).run());
+    }
+    // Operators may switch between every profile the config file knows
+    let profiles = file_cfg
+        .strategy_names()
+        .iter()
+        .map(|name| file_cfg.strategy_for(&cli.market, Some(name)))
+        .collect::<Result<HashMap<_, _>>>()?;
+    let controls =
+        Arc::new(RuntimeControls::new(Arc::clone(&activity), market, profiles, strategy_name.clone(), cli.dry_run));
+    if let Some(token) = cli.telegram_token.clone() {
+        info!(chats = cli.telegram_chats.len(), "Taking control commands from Telegram");
+        let telegram = Arc::new(TelegramControl::new(token, cli.telegram_chats.clone(), Arc::clone(&controls)));
+        spawn_supervised("telegram_control", restart_policy(), move || Arc::clone(&telegram).run());
+    }
+    if let Some(addr) = cli.status_addr {
+        let mut server = StatusServer::new(Arc::clone(&bundles));
+        if let Some(blinks) = blinks.as_ref() {
+            info!(path = "/actions/liquidate/{obligation}", "Serving liquidation blinks");
+            server = server.with_blinks(Arc::clone(blinks));
+        }
+        if cli.rescan_secs > 0 {
+            server = server.with_cache(Arc::clone(&scanner));
+        }
+        if let Some(token) = cli.status_token.clone() {
+            server = server.with_read_token(token);
+        }
+        if let Some(token) = cli.control_token.clone() {
+            info!(path = "/control", "Serving operator controls");
+            server = server.with_control(token, Arc::clone(&controls));
+        }
+        let server = Arc::new(server);
+        spawn_supervised("status_api", restart_policy(), move || Arc::clone(&server).serve(addr));
+    }
+    // Streamed reserve prices classify each collateral asset by how much it has moved lately
+    let price_watch = file_cfg.volatility_config()?.map(PriceWatch::new);
+    if let Some(watch) = price_watch.as_ref() {
+        let (watch, ws_url) = (Arc::clone(watch), ws_url.clone());
+        spawn_supervised("price_watch", restart_policy(), move || Arc::clone(&watch).watch(ws_url.clone(), market));
+    }
+    let tracker = Arc::new(SignatureTracker::new(
+        ws_url,
+        std::time::Duration::from_secs(cli.track_timeout_secs),
+        Arc::clone(&rpc),
+        Arc::clone(&store),
+        Arc::clone(&bundles),
+    ));
+
+    let mut opportunities = OpportunityLog::new(cfg.payer.pubkey(), Arc::clone(&store));
+
+    let mut auctions = Auctions::default();
+    let auction_cfg = cli.auction_min_profit_lamports.map(|min_profit_lamports| AuctionConfig {
+        min_profit_lamports,
+        tip_growth: cli.auction_tip_growth,
+        max_profit_share: cli.auction_max_profit_share,
+        max_rounds: cli.auction_max_rounds,
+        round_interval: std::time::Duration::from_millis(cli.auction_round_ms),
+        max_tip_lamports: None,
+    });
+
+    let budget =
+        ComputeBudget { cu_limit: cli.cu_limit, cu_price: Some(cli.cu_price), heap_frame_bytes: cli.heap_frame_bytes };
+    budget.validate()?;
+
+    let lookup_table = match cli.lookup_table.as_deref() {
+        Some(table) => Some(table.parse().context("Invalid lookup table address")?),
+        None => lut::latest_table(&store, &market)?,
+    };
+    let lookup_tables = Arc::new(match lookup_table {
+        Some(table) => {
+            let account = lut::load(&rpc, &table).await?;
+            info!(table = %table, addresses = account.addresses.len(), "Using address lookup table");
+            vec![account]
+        }
+        None => Vec::new(),
+    });
+    let hooks = Arc::new(file_cfg.instruction_hooks(cfg.payer.pubkey(), cfg.owner().pubkey())?);
+    if !hooks.is_empty() {
+        info!(hooks = ?hooks.iter().map(|h| h.name()).collect::<Vec<_>>(), "Instruction hooks loaded");
+    }
+    let candidate_budget = std::time::Duration::from_millis(cli.candidate_budget_ms);
+    let stage_timeouts = StageTimeouts {
+        fetch: std::time::Duration::from_millis(cli.fetch_timeout_ms),
+        quote: std::time::Duration::from_millis(cli.quote_timeout_ms),
+        simulate: std::time::Duration::from_millis(cli.simulate_timeout_ms),
+    };
+
+    let watch_health = cli.watch_health.clamp(1.0, PREFILTER_HEALTH_THRESHOLD);
+    let mut tx_builder = TxBuilder {
+        payer: &cfg.payer,
+        owner: cfg.owner.as_ref(),
+        budget,
+        lookup_tables: &lookup_tables,
+        tip_account: tip_acc.pubkey,
+        separate_tip: cli.separate_tip,
+        hooks: &hooks,
+    };
+    // Keeper, treasury and sweeper transactions are built and confirmed off the scan loop
+    let background_signer = BackgroundSigner {
+        payer: Arc::new(cfg.payer.insecure_clone()),
+        lookup_tables: Arc::clone(&lookup_tables),
+        hooks: Arc::clone(&hooks),
+        tip_account: tip_acc.pubkey,
+    };
+
+    if let Some(Command::Crank { interval_secs }) = cli.command.as_ref() {
+        loop {
+            let watched =
+                find_liquidation_candidates(&rpc, &scanner, &cli.market, watch_health, &strategy, None).await?;
+            let obligations: Vec<_> = watched.iter().map(|c| c.obligation).collect();
+            let sent = crank(&rpc, &tx_builder, &market_accounts, &obligations).await;
+            info!(watched = obligations.len(), sent, "Crank pass finished");
+            if cli.once {
+                return Ok(());
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(*interval_secs)).await;
+        }
+    }
+
+    let jito_options = JitoConnectOptions {
+        ca_cert: cli.jito_ca_cert.clone(),
+        tls_domain: cli.jito_tls_domain.clone(),
+        proxy: cli.jito_proxy.as_deref().map(Proxy::parse).transpose().context("Invalid --jito-proxy")?,
+        local_address: cli.jito_bind,
+        keepalive_interval: cli.jito_keepalive_secs.map(std::time::Duration::from_secs),
+        keepalive_timeout: cli.jito_keepalive_timeout_secs.map(std::time::Duration::from_secs),
+    };
+    let mut senders = Senders {
+        bundle: JitoSender::new(cli.jito_endpoint.clone(), Some(cli.jito_timeout), jito_options).await?,
+        jito_tx: JitoTxSender::new(cli.jito_tx_url.clone()),
+    };
+    let mut resubmitter = BundleResubmitter::new(cli.bundle_resubmit_attempts);
+    let mut keeper = Keeper::new(std::time::Duration::from_secs(cli.keeper_interval_secs));
+
+    let mut templates = TemplateCache::new(std::time::Duration::from_secs(cli.template_max_age_secs));
+    let reconcile_interval = std::time::Duration::from_secs(cli.reconcile_interval_secs);
+    let mut last_reconcile = std::time::Instant::now();
+
+    let alerter = Alerter::new(cli.alert_webhook.clone());
+    let mut notifier = match (cli.opportunity_webhook.clone(), cli.dry_run) {
+        (Some(url), true) => {
+            Some(OpportunityNotifier::new(url, std::time::Duration::from_secs(cli.opportunity_cooldown_secs)))
+        }
+        (Some(_), false) => {
+            warn!("Opportunity webhook only applies in dry-run; ignoring it");
+            None
+        }
+        (None, _) => None,
+    };
+    let mut protector = match file_cfg.protect_config()? {
+        Some(protect) => {
+            if protect.trigger_health > watch_health {
+                warn!(
+                    trigger_health = protect.trigger_health,
+                    watch_health,
+                    "Protect trigger is above the watch health; protected obligations are only seen below the latter"
+                );
+            }
+            info!(owners = protect.owners.len(), trigger_health = protect.trigger_health, "Protecting wallets");
+            Some(Protector::new(protect))
+        }
+        None => None,
+    };
+    let failure_dump = cli.dump_failed_dir.clone().map(FailureDump::new).transpose()?;
+    if !cli.skip_layout_check {
+        let report = check_layouts(&rpc, &market).await?;
+        if !report.is_ok() {
+            let message = format!(
+                "Kamino account layouts do not match the decoder (program deployed at slot {:?}): {}",
+                report.deploy_slot,
+                report.problems.join("; ")
+            );
+            alerter.send(&message).await;
+            bail!("Decoder is out of date with the Kamino program; update carbon-kamino-lending-decoder or pass --skip-layout-check");
+        }
+    }
+    let retry_policy = RetryPolicy::default();
+    let mut breaker =
+        CircuitBreaker::new(cli.breaker_threshold, std::time::Duration::from_secs(cli.breaker_cooldown_secs));
+
+    let mut inventory = Inventory::new(std::time::Duration::from_secs(cli.inventory_refresh_secs));
+    let ledger = (cli.balance_reconcile_interval_secs > 0).then(BalanceLedger::new);
+    if let Some(ledger) = ledger.as_ref() {
+        let mut wallets = vec![cfg.payer.pubkey(), liquidator.owner];
+        wallets.dedup();
+        info!(
+            interval_secs = cli.balance_reconcile_interval_secs,
+            wallets = wallets.len(),
+            "Reconciling wallet balances"
+        );
+        let reconcile_cfg = ReconcileConfig {
+            wallets,
+            interval: std::time::Duration::from_secs(cli.balance_reconcile_interval_secs),
+            tolerance_bps: cli.balance_reconcile_tolerance_bps,
+            sol_dust_lamports: cli.balance_reconcile_sol_dust_lamports,
+            token_dust: cli.balance_reconcile_token_dust,
+        };
+        let reconciler = Arc::new(Reconciler::new(reconcile_cfg, Arc::clone(ledger), Arc::clone(&store)));
+        let (reconcile_rpc, alerter) = (Arc::clone(&rpc), Arc::new(Alerter::new(cli.alert_webhook.clone())));
+        spawn_supervised("reconciler", restart_policy(), move || {
+            Arc::clone(&reconciler).run(Arc::clone(&reconcile_rpc), Arc::clone(&alerter))
+        });
+    }
+
+    let treasury_active = Arc::new(AtomicBool::new(false));
+    let sweep_active = Arc::new(AtomicBool::new(false));
+    let mut treasury = Treasury::new(
+        TopUpConfig {
+            min_lamports: cli.min_sol_lamports,
+            target_lamports: cli.top_up_target_lamports.max(cli.min_sol_lamports),
+            source_mint: cli.top_up_mint.parse().context("Invalid top-up mint")?,
+            auto: cli.auto_top_up && !cli.dry_run,
+            check_interval: std::time::Duration::from_secs(60),
+        },
+        JupiterClient::new(cli.jupiter_url.clone()),
+    );
+
+    let mut sweeper = match cli.sweep_to.as_deref() {
+        Some(cold) => Some(Sweeper::new(SweepConfig {
+            cold_wallet: cold.parse().context("Invalid sweep wallet address")?,
+            sol_float_lamports: cli.sweep_sol_float_lamports.max(cli.top_up_target_lamports),
+            token_mint: cli.top_up_mint.parse().context("Invalid top-up mint")?,
+            token_float: cli.sweep_token_float,
+            interval: std::time::Duration::from_secs(cli.sweep_interval_secs.max(1)),
+        })),
+        None => None,
+    };
+
+    let mut unwinder = match cli.unwind {
+        true => Some(Unwinder::new(
+            UnwindConfig {
+                output_mint: cli.unwind_mint.parse().context("Invalid unwind mint")?,
+                slippage_bps: cli.unwind_slippage_bps,
+                interval: std::time::Duration::from_secs(cli.unwind_interval_secs),
+            },
+            JupiterClient::new(cli.jupiter_url.clone()),
+        )),
+        false => None,
+    };
+    if let Some(ledger) = &ledger {
+        treasury = treasury.with_ledger(Arc::clone(ledger));
+        sweeper = sweeper.map(|s| s.with_ledger(Arc::clone(ledger)));
+        unwinder = unwinder.map(|u| u.with_ledger(Arc::clone(ledger)));
+    }
+    // Never stall on fees: refill SOL from profit or alert
+    let payer = Arc::clone(&background_signer.payer);
+    let treasury = Arc::new(treasury);
+    let (treasury_rpc, treasury_payer) = (Arc::clone(&rpc), Arc::clone(&payer));
+    let (treasury_alerter, active) = (Arc::new(Alerter::new(cli.alert_webhook.clone())), Arc::clone(&treasury_active));
+    spawn_supervised("treasury", restart_policy(), move || {
+        Arc::clone(&treasury).run(
+            Arc::clone(&treasury_rpc),
+            Arc::clone(&treasury_payer),
+            Arc::clone(&treasury_alerter),
+            Arc::clone(&active),
+        )
+    });
+    if let Some(sweeper) = sweeper.map(Arc::new) {
+        let (sweep_rpc, active) = (Arc::clone(&rpc), Arc::clone(&sweep_active));
+        spawn_supervised("sweeper", restart_policy(), move || {
+            Arc::clone(&sweeper).run(Arc::clone(&sweep_rpc), Arc::clone(&payer), Arc::clone(&active))
+        });
+    }
+
+    let cross_check = match cli.cross_check_health {
+        true => {
+            info!(tolerance = cli.cross_check_tolerance, "Cross-checking candidate health against Kamino's API");
+            Some(HealthCrossCheck::new(
+                KaminoApi::new(cli.kamino_api_url.clone())?,
+                cli.cross_check_tolerance,
+                std::time::Duration::from_secs(cli.cross_check_interval_secs),
+            ))
+        }
+        false => None,
     };
 
-    // Main loop
-    loop {
-        // Fetch latest blockhash for transaction building
-        let blockhash = fetch_latest_blockhash(&rpc)?;
-
-        // Find candidates
-        let candidates = find_liquidation_candidates(&rpc, &cli.market).await?;
-        if candidates.is_empty() {
-            info!("No liquidatable obligations found");
-        }
-
-        for cand in candidates.iter() {
-            match build_liquidation_ix(&rpc, cand).await {
-                Ok(ix) => {
-                    // Build and optionally send transaction via Jito
-                    match build_tx_with_tip(
-                        &cfg.payer,
-                        blockhash,
-                        vec![ix],
-                        cli.cu_limit,
-                        cli.cu_price,
-                        tip_acc.pubkey,
-                        cli.tip_lamports,
-                    ) {
-                        Ok(versioned_tx) => {
-                            if cli.dry_run {
-                                info!(
-                                    obligation = %cand.obligation.to_string(),
-                                    "Dry-run: built liquidation tx"
-                                );
-                            } else {
-                                match jito.send(&[versioned_tx]).await {
-                                    Ok(uuid) => {
+    let oracle_guard =
+        cli.max_oracle_divergence_bps.map(|bps| OracleGuard::new(JupiterClient::new(cli.jupiter_url.clone()), bps));
+    let price_quorum =
+        cli.price_quorum_min_seized_lamports.map(|min| PriceQuorum::new(min, cli.price_quorum_max_divergence_bps));
+
+    let mut lease = match cli.redis_url.as_deref() {
+        Some(url) => {
+            let instance_id = cli.instance_id.clone().unwrap_or_else(|| {
+                let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "bot".to_string());
+                format!("{host}-{}", std::process::id())
+            });
+            info!(instance_id = %instance_id, "Coordinating submissions through Redis leases");
+            Some(RedisLease::new(url, instance_id, std::time::Duration::from_millis(cli.lease_ms))?)
+        }
+        None => None,
+    };
+
+    let jito_probe_interval = std::time::Duration::from_secs(cli.jito_probe_interval_secs);
+    let mut last_jito_probe: Option<std::time::Instant> = None;
+
+    let fee_market = strategy.quiet_cu_price.map(|mode| FeeMarket::new(mode, strategy.quiet_fee_threshold));
+    let mut summary = Summary::new(std::time::Duration::from_secs(cli.summary_interval_mins * 60));
+    // Listen from the start so Ctrl-C mid-iteration still ends the loop at the next pause
+    let mut shutdown = tokio::spawn(tokio::signal::ctrl_c());
+
+    if let Some(path) = cli.event_log.as_deref() {
+        install_recorder(path)?;
+    }
+
+    // Main loop; a panicking iteration is logged and retried after a backoff
+    let loop_restart = restart_policy();
+    let mut loop_failures = 0;
+    let mut scan_delay = std::time::Duration::from_millis(800);
+    let mut scan_state = ScanState::default();
+    let mut was_paused = None;
+    let outcome = loop {
+        let iteration = std::panic::AssertUnwindSafe(async {
+            // Operators may have switched the profile, dry-run or tip cap since the last iteration
+            let (_, mut strategy) = controls.strategy();
+            if strategy.repay_preference.contains(&RepayPreference::Held) {
+                inventory.refresh_if_due(&rpc, &liquidator).await;
+                strategy.held = inventory.held().clone();
+            }
+            let dry_run = controls.dry_run();
+            let auction_cfg = auction_cfg.map(|a| AuctionConfig { max_tip_lamports: controls.max_tip(), ..a });
+            let send_policy = strategy.retry_policy();
+            if let Some(wait) = breaker.open_for() {
+                warn!(wait_secs = wait.as_secs(), "Circuit breaker open, pausing");
+                tokio::time::sleep(wait).await;
+            }
+
+            // Fetch latest blockhash and candidates, retrying transient RPC errors
+            let scan = async {
+                let blockhash = retry("fetch_blockhash", &retry_policy, || fetch_blockhash(&rpc)).await?;
+                let deleverage_slot = match deleverage {
+                    true => Some(fetch_slot(&rpc).await?),
+                    false => None,
+                };
+                let candidates = retry("find_liquidation_candidates", &retry_policy, || {
+                    find_liquidation_candidates(&rpc, &scanner, &cli.market, watch_health, &strategy, deleverage_slot)
+                })
+                .await?;
+                anyhow::Ok((blockhash, candidates))
+            };
+            let scan_started = std::time::Instant::now();
+            let (mut blockhash, scanned) = match scan.await {
+                Ok(v) => {
+                    breaker.record_success();
+                    record_scan(scan_started.elapsed(), v.1.iter().filter(|c| c.is_liquidatable()).count());
+                    v
+                }
+                Err(e) => {
+                    error!(error = %e, "Scan iteration failed");
+                    rpc.fail_over();
+                    if breaker.record_failure() {
+                        alerter
+                            .send(&format!(
+                                "RPC failing repeatedly ({} consecutive iterations), pausing {}s: {e:#}",
+                                cli.breaker_threshold, cli.breaker_cooldown_secs
+                            ))
+                            .await;
+                    }
+                    if cli.once {
+                        return Err(e);
+                    }
+                    return Ok(false);
+                }
+            };
+            let detected_at = std::time::Instant::now();
+            if let Some(watch) = price_watch.as_ref() {
+                scan_delay = watch.scan_delay(&scanned);
+            }
+            // While paused, keep scanning and caches warm but submit nothing
+            let paused = activity.paused_reason().or(controls.market_paused(&market).then_some("market paused"));
+            if paused != was_paused {
+                match paused {
+                    Some(reason) => info!(reason, "Submissions paused"),
+                    None => info!("Submissions resumed"),
+                }
+                was_paused = paused;
+            }
+            let active = paused.is_none();
+            treasury_active.store(active, Ordering::Relaxed);
+            sweep_active.store(active && !dry_run, Ordering::Relaxed);
+            // Protected wallets get a repayment instead of a liquidation
+            if let Some(protector) = protector.as_mut().filter(|_| active) {
+                let all: Vec<_> = scanned.iter().collect();
+                protector.tick(&rpc, &tx_builder, &market_accounts, &liquidator, &all, dry_run).await;
+            }
+            let (candidates, watchlist): (Vec<_>, Vec<_>) = scanned
+                .iter()
+                .filter(|c| !protector.as_ref().is_some_and(|p| p.is_protected(c)))
+                .partition(|c| c.is_liquidatable());
+            scan_state.update(&scanned).log();
+            if let Some(blinks) = blinks.as_ref() {
+                blinks.publish(&candidates);
+            }
+            if let Some(check) = cross_check.as_ref() {
+                check.observe(&candidates);
+            }
+            record_candidates(&candidates);
+            if cli.verbose {
+                match candidates.is_empty() {
+                    true => info!(watched = watchlist.len(), "No liquidatable obligations found"),
+                    false => info!(liquidatable = candidates.len(), watched = watchlist.len(), "Scan complete"),
+                }
+            }
+            if let Some(fees) = fee_market.as_ref() {
+                tx_builder.budget.cu_price = fees.cu_price(&rpc, &candidates, cli.cu_price).await;
+            }
+            // Record every HF<1 window, including dry runs, for latency and tip analysis
+            let scan_slot = fetch_slot(&rpc).await.unwrap_or_default();
+            opportunities.observe(&rpc, &candidates, scan_slot);
+            let mut contention = cli
+                .contention_threshold
+                .map(|threshold| ContentionMap::new(&candidates, threshold, &[cfg.payer.pubkey(), liquidator.owner, tip_acc.pubkey]));
+            let mut capped = false;
+            // A lease taken for a candidate that was then not submitted goes back to the standbys
+            let mut unsubmitted: Option<Pubkey> = None;
+
+            for cand in candidates.iter().copied().filter(|_| active) {
+                if let Some((lease, obligation)) = lease.as_mut().zip(unsubmitted.take()) {
+                    lease.release(&obligation).await;
+                }
+                if cli.max_inflight_bundles > 0 {
+                    let in_flight = bundles.in_flight(std::time::Duration::from_secs(cli.track_timeout_secs));
+                    if in_flight.bundles >= cli.max_inflight_bundles {
+                        if !capped {
+                            info!(
+                                in_flight = in_flight.bundles,
+                                tip_lamports = in_flight.tip_lamports,
+                                max = cli.max_inflight_bundles,
+                                "In-flight bundle cap reached, holding candidates until the next scan"
+                            );
+                            capped = true;
+                        }
+                        opportunities.mark_skipped(&cand.obligation, SkipReason::InFlightCap);
+                        continue;
+                    }
+                }
+                let class = price_watch.as_ref().map(|w| w.candidate_class(cand));
+                let policy = price_watch.as_ref().zip(class).map(|(w, c)| *w.config().policy(c));
+                if let Some(policy) = policy.filter(|p| !p.clears_min_profit(cand)) {
+                    debug!(
+                        obligation = %cand.obligation,
+                        volatility = class.map(|c| c.as_str()),
+                        expected_profit_lamports = ?cand.expected_profit_lamports,
+                        min_profit_lamports = policy.min_profit_lamports,
+                        "Expected profit below the volatility class minimum, skipping"
+                    );
+                    continue;
+                }
+                // Redundant instances stay on standby while another holds the obligation's lease
+                if let Some(lease) = lease.as_mut().filter(|_| !dry_run) {
+                    if !lease.acquire(&cand.obligation).await {
+                        continue;
+                    }
+                    unsubmitted = Some(cand.obligation);
+                }
+                // Watchlist obligations that crossed 1.0 already have their accounts resolved
+                let deadline = Deadline::new(candidate_budget);
+                // Never act on an oracle print the swap market disagrees with
+                if let Some(guard) = oracle_guard.as_ref() {
+                    match deadline.stage("quote", stage_timeouts.quote, guard.check(&rpc, cand)).await {
+                        Ok(check) if guard.rejects(&check) => {
+                            warn!(
+                                obligation = %cand.obligation,
+                                oracle_price = check.oracle_price,
+                                market_price = check.market_price,
+                                divergence_bps = format!("{:.0}", check.divergence_bps),
+                                "Oracle price diverges from market, skipping"
+                            );
+                            continue;
+                        }
+                        Ok(_) => {}
+                        Err(e) => debug!(obligation = %cand.obligation, error = %e, "Oracle cross-check unavailable"),
+                    }
+                }
+                // The largest liquidations also need independent feeds to confirm the price; fails closed
+                if let Some(quorum) = price_quorum.as_ref().filter(|q| q.applies_to(cand)) {
+                    match deadline.stage("quote", stage_timeouts.quote, quorum.check(&rpc, cand)).await {
+                        Ok(check) if check.confirmed() => {}
+                        Ok(check) => {
+                            warn!(
+                                obligation = %cand.obligation,
+                                expected_seized_lamports = ?cand.expected_seized_lamports,
+                                prices = %check.describe(),
+                                "Price feeds do not confirm a large liquidation, holding it for review"
+                            );
+                            quorum.alert(&alerter, cand, &check).await;
+                            continue;
+                        }
+                        Err(e) => {
+                            warn!(obligation = %cand.obligation, error = %e, "Price quorum unavailable, skipping");
+                            continue;
+                        }
+                    }
+                }
+                // Templates skip the obligation fetch; otherwise the fetched obligation also serves the prelude
+                let ix = match templates.instruction_for(cand, &strategy) {
+                    Some(ix) => Ok((ix, None)),
+                    None => {
+                        let (rpc, market, liquidator, cand, strategy) =
+                            (Arc::clone(&rpc), market_accounts.clone(), liquidator.clone(), cand.clone(), strategy.clone());
+                        deadline
+                            .blocking_stage("fetch", stage_timeouts.fetch, async move {
+                                let obl = fetch_obligation(&rpc, &cand.obligation).await?;
+                                let ix = build_liquidation_ix(&rpc, &market, &liquidator, &cand, &obl, &strategy).await?;
+                                Ok((ix, Some(obl)))
+                            })
+                            .await
+                    }
+                };
+                let tip_multiplier = policy.map_or(1.0, |p| p.tip_multiplier);
+                let tip = strategy.tip_lamports(
+                    cand.expected_profit_lamports,
+                    cand.expected_seized_lamports,
+                    cli.tip_lamports,
+                    tip_multiplier,
+                );
+                match ix {
+                    Ok((ix, obl)) => {
+                        // Referred obligations fail on a missing referrer token state until someone creates it
+                        let referrers = candidate_referrer_ixs(&rpc, &market_accounts, &cfg.payer.pubkey(), cand, obl.as_ref());
+                        let mut prelude = match referrers.await {
+                            Ok(ixs) => ixs,
+                            Err(e) => {
+                                warn!(obligation = %cand.obligation, error = %e, "Failed to check referrer token states");
+                                Vec::new()
+                            }
+                        };
+                        // Liquidation rejects stale reserves; an oversized prelude splits into a refresh transaction
+                        let refresh = match obl.as_ref() {
+                            Some(obl) => fetch_refresh_ixs(&rpc, &market_accounts, &cand.obligation, obl).await,
+                            None => batch_refresh_ixs(&rpc, &market_accounts, &[cand.obligation]).await,
+                        };
+                        match refresh {
+                            Ok(ixs) => prelude.extend(ixs),
+                            Err(e) => {
+                                warn!(obligation = %cand.obligation, error = %e, "Failed to build refresh, skipping");
+                                continue;
+                            }
+                        }
+                        // High-value candidates get an escalating-tip auction in the background instead of a single send
+                        if let Some(auction_cfg) = auction_cfg.filter(|a| !dry_run && a.applies_to(cand)) {
+                            if auctions.is_running(&cand.obligation) {
+                                continue;
+                            }
+                            let submitted_slot = fetch_slot(&rpc).await.ok();
+                            if let Err(e) = blockhash.refresh_if_expiring(&rpc, cli.blockhash_margin_blocks).await {
+                                warn!(error = %e, "Failed to refresh expiring blockhash");
+                                continue;
+                            }
+                            let built = Auction::build(
+                                &tx_builder,
+                                &auction_cfg,
+                                cand,
+                                blockhash,
+                                cli.blockhash_margin_blocks,
+                                prelude,
+                                ix,
+                                tip,
+                                submitted_slot,
+                            );
+                            match built {
+                                Ok(auction) => {
+                                    // The last round carries the highest tip, so it must still pay for itself
+                                    if let Some((top_tip, top)) = auction.rounds.last().filter(|_| cli.strict_sim) {
+                                        let checked = {
+                                            let (rpc, payer, liquidator, cand, top) =
+                                                (Arc::clone(&rpc), cfg.payer.pubkey(), liquidator.clone(), cand.clone(), top.clone());
+                                            let cost = top_tip + auction.fee_lamports;
+                                            deadline
+                                                .blocking_stage("simulate", stage_timeouts.simulate, async move {
+                                                    confirm_profit(&rpc, &payer, &liquidator, &cand, &top, cost).await
+                                                })
+                                                .await
+                                        };
+                                        if let Err(e) = checked {
+                                            info!(obligation = %cand.obligation, error = %e, "Strict mode: not starting auction");
+                                            opportunities.mark_skipped(&cand.obligation, SkipReason::SimulationGate);
+                                            if let Some(dump) = failure_dump.as_ref() {
+                                                dump.record(&rpc, &cand.obligation, "strict_sim", &e.to_string(), &top.txs).await;
+                                            }
+                                            continue;
+                                        }
+                                    }
+                                    let sinks = AuctionSinks {
+                                        bundles: Arc::clone(&bundles),
+                                        tracker: Arc::clone(&tracker),
+                                        ledger: ledger.clone(),
+                                        route: senders.route(SenderKind::Bundle),
+                                    };
+                                    auctions.spawn(auction, Arc::clone(&rpc), senders.bundle.clone(), auction_cfg, sinks);
+                                    opportunities.mark_submitted(&cand.obligation);
+                                    unsubmitted = None;
+                                }
+                                Err(e) => warn!(obligation = %cand.obligation, error = %e, "Failed to build auction"),
+                            }
+                            continue;
+                        }
+
+                        // Build and optionally send transaction via Jito; oversized liquidations split into a bundle
+                        // Never sign on a blockhash that may expire before the transaction lands
+                        if let Err(e) = blockhash.refresh_if_expiring(&rpc, cli.blockhash_margin_blocks).await {
+                            warn!(error = %e, "Failed to refresh expiring blockhash");
+                            continue;
+                        }
+                        match tx_builder.liquidation_txs(blockhash.hash, prelude, vec![ix], tip) {
+                            Ok(built) => {
+                                let built_at = std::time::Instant::now();
+                                if let Some(map) = contention.as_mut() {
+                                    let writable: Vec<_> =
+                                        built.txs.iter().flat_map(|tx| writable_accounts(tx, &lookup_tables)).collect();
+                                    let report = map.observe(&cand.obligation, &writable);
+                                    if report.is_contended() {
                                         info!(
-                                            obligation = %cand.obligation.to_string(),
-                                            jito_uuid = %uuid,
-                                            tip = cli.tip_lamports,
-                                            "Bundle submitted"
+                                            obligation = %cand.obligation,
+                                            shared = ?report.shared,
+                                            popular = ?report.popular,
+                                            "Liquidation write-locks contended accounts"
                                         );
                                     }
-                                    Err(e) => {
-                                        warn!(
-                                            obligation = %cand.obligation.to_string(),
-                                            error = %e,
-                                            "Failed to submit bundle"
-                                        );
+                                    if let Err(e) = store.append(CONTENTION, &report) {
+                                        warn!(error = %e, "Failed to persist contention report");
+                                    }
+                                }
+                                if dry_run {
+                                    if let Some(notifier) = notifier.as_mut() {
+                                        notifier.notify(cand, &built.txs, tip).await;
+                                    }
+                                    let liquidation_tx = built.txs.iter().find(|tx| tx.signatures[0] == built.signature);
+                                    match liquidation_tx.filter(|_| deadline.allows("simulate", stage_timeouts.simulate)) {
+                                        Some(tx) => match {
+                                            let (rpc, payer, liquidator, cand, tx) =
+                                                (Arc::clone(&rpc), cfg.payer.pubkey(), liquidator.clone(), cand.clone(), tx.clone());
+                                            deadline
+                                                .blocking_stage("simulate", stage_timeouts.simulate, async move {
+                                                    simulate_liquidation(&rpc, &payer, &liquidator, &cand, &tx).await
+                                                })
+                                                .await
+                                        } {
+                                            Ok(record) => {
+                                                info!(
+                                                    obligation = %cand.obligation.to_string(),
+                                                    success = record.success,
+                                                    profit_usd = ?record.profit_usd,
+                                                    sol_delta_lamports = record.sol_delta_lamports,
+                                                    units = ?record.units_consumed,
+                                                    "Dry-run: simulated liquidation"
+                                                );
+                                                if let Err(e) = store.append(SIMULATIONS, &record) {
+                                                    warn!(error = %e, "Failed to persist simulation record");
+                                                }
+                                                if let Some(dump) = failure_dump.as_ref().filter(|_| !record.success) {
+                                                    let error = record.error.as_deref().unwrap_or("unknown error");
+                                                    dump.record(&rpc, &cand.obligation, "simulate", error, &built.txs).await;
+                                                }
+                                            }
+                                            Err(e) => warn!(obligation = %cand.obligation, error = %e, "Dry-run simulation failed"),
+                                        },
+                                        None => info!(obligation = %cand.obligation, "Dry-run: built liquidation tx, simulation skipped"),
+                                    }
+                                } else {
+                                    if cli.strict_sim {
+                                        let fees: u64 =
+                                            built.txs.iter().map(|tx| tx_builder.budget.fee_lamports(tx.signatures.len())).sum();
+                                        let checked = {
+                                            let (rpc, payer, liquidator, cand, built) =
+                                                (Arc::clone(&rpc), cfg.payer.pubkey(), liquidator.clone(), cand.clone(), built.clone());
+                                            deadline
+                                                .blocking_stage("simulate", stage_timeouts.simulate, async move {
+                                                    confirm_profit(&rpc, &payer, &liquidator, &cand, &built, tip + fees).await
+                                                })
+                                                .await
+                                        };
+                                        if let Err(e) = checked {
+                                            info!(obligation = %cand.obligation, error = %e, "Strict mode: not submitting liquidation");
+                                            opportunities.mark_skipped(&cand.obligation, SkipReason::SimulationGate);
+                                            if let Some(dump) = failure_dump.as_ref() {
+                                                dump.record(&rpc, &cand.obligation, "strict_sim", &e.to_string(), &built.txs).await;
+                                            }
+                                            continue;
+                                        }
+                                    }
+                                    let signature = built.signature;
+                                    let submitted_slot = fetch_slot(&rpc).await.ok();
+                                    let kind = match built.txs.len() {
+                                        1 => cli.sender.choose(cand, cli.contention_profit_lamports),
+                                        _ => SenderKind::Bundle,
+                                    };
+                                    match senders.send_with_retry(kind, &rpc, &built.txs, &send_policy).await {
+                                        Ok(uuid) => {
+                                            record_submission(kind, tip, cand.expected_profit_lamports);
+                                            let route = senders.route(kind);
+                                            observe_stage(Stage::DetectToBuild, route, built_at - detected_at, &signature);
+                                            observe_stage(Stage::BuildToSubmit, route, built_at.elapsed(), &signature);
+                                            info!(
+                                                obligation = %cand.obligation.to_string(),
+                                                submission_id = %uuid,
+                                                sender = kind.as_str(),
+                                                tip,
+                                                deleveraging = cand.deleveraging,
+                                                swap_route = strategy.swap_route(&cand.withdraw_reserve),
+                                                "Liquidation submitted"
+                                            );
+                                            if kind == SenderKind::Bundle {
+                                                bundles.submitted(&uuid, &cand.obligation, &built.txs, &signature, submitted_slot, tip);
+                                                resubmitter.track(&mut senders.bundle, cand.obligation, &built.txs, signature).await;
+                                            }
+                                            opportunities.mark_submitted(&cand.obligation);
+                                            unsubmitted = None;
+                                            if let Some(unwinder) = unwinder.as_mut() {
+                                                unwinder.track(cand.withdraw_reserve, signature);
+                                            }
+                                            if let Some(ledger) = &ledger {
+                                                let fee = built.txs.iter().map(|tx| budget.fee_lamports(tx.signatures.len())).sum();
+                                                ledger.expect_liquidation(signature, cand, tip, fee);
+                                            }
+                                            tracker.spawn(cand.obligation, signature, uuid, submitted_slot, route);
+                                        }
+                                        Err(e) => {
+                                            warn!(
+                                                obligation = %cand.obligation.to_string(),
+                                                sender = kind.as_str(),
+                                                error = %e,
+                                                "Failed to submit liquidation"
+                                            );
+                                            opportunities.mark_skipped(&cand.obligation, SkipReason::RpcError);
+                                            if let Some(dump) = failure_dump.as_ref() {
+                                                dump.record(&rpc, &cand.obligation, "submit", &e.to_string(), &built.txs).await;
+                                            }
+                                        }
                                     }
                                 }
                             }
+                            Err(e) => warn!(error = %e, "Failed to build liquidation transaction"),
                         }
-                        Err(e) => warn!(error = %e, "Failed to build liquidation transaction"),
                     }
+                    Err(e) => {
+                        warn!(error = %e, "Failed to build liquidation instruction");
+                        opportunities.mark_skipped(&cand.obligation, SkipReason::RpcError);
+                    }
+                }
+            }
+            if let Some((lease, obligation)) = lease.as_mut().zip(unsubmitted.take()) {
+                lease.release(&obligation).await;
+            }
+
+            // Background auctions report their rounds once they end
+            for (obligation, result) in auctions.finished().await {
+                info!(obligation = %obligation, outcome = ?result.outcome, rounds = result.rounds.len(), "Auction finished");
+                if let Some(unwinder) = unwinder.as_mut() {
+                    for round in &result.rounds {
+                        unwinder.track(result.withdraw_reserve, round.signature);
+                    }
+                }
+            }
+
+            // Give bundles that missed their leader another slot before rescanning
+            if active {
+                resubmitter.tick(&rpc, &mut senders.bundle).await;
+            }
+
+            // Sell seized collateral first so the proceeds fund top-ups and sweeps
+            if let Some(unwinder) = unwinder.as_mut().filter(|_| !dry_run && active) {
+                unwinder.tick(&rpc, cfg.owner(), &strategy, &alerter).await;
+            }
+
+            // Follow the fastest block-engine region as network conditions change
+            if !jito_probe_interval.is_zero() && last_jito_probe.is_none_or(|t| t.elapsed() >= jito_probe_interval) {
+                if let Err(e) = senders.bundle.probe_and_migrate(std::time::Duration::from_secs(cli.jito_timeout)).await {
+                    warn!(error = %e, "Jito region migration failed");
                 }
-                Err(e) => warn!(error = %e, "Failed to build liquidation instruction"),
+                last_jito_probe = Some(std::time::Instant::now());
             }
+
+            // Catch cached templates that drifted from chain before refreshing, so drift gets rebuilt
+            if !reconcile_interval.is_zero() && last_reconcile.elapsed() >= reconcile_interval {
+                templates.reconcile(&rpc, cli.reconcile_sample).await;
+                last_reconcile = std::time::Instant::now();
+            }
+
+            // Keep templates warm for positions close to liquidation, off the hot path
+            templates.refresh(&rpc, &market_accounts, &liquidator, &watchlist).await;
+
+            // Keep watched obligations' on-chain health current
+            if active {
+                let watched: Vec<_> = watchlist.iter().map(|c| c.obligation).collect();
+                keeper.tick(&rpc, &background_signer, tx_builder.budget, &market_accounts, watched);
+            }
+
+            anyhow::Ok(true)
+        })
+        .catch_unwind()
+        .await;
+        // Wait the scan delay before the next pass, or back off after a failure
+        let pause = match iteration {
+            Ok(Ok(true)) => {
+                loop_failures = 0;
+                summary.tick();
+                scan_delay
+            }
+            // Scan failed
+            Ok(Ok(false)) => std::time::Duration::from_millis(800),
+            Ok(Err(e)) => break Err(e),
+            Err(payload) => {
+                loop_failures += 1;
+                record_restart("main_loop", "panic");
+                let delay = loop_restart.delay(loop_failures);
+                error!(
+                    panic = %panic_message(payload.as_ref()),
+                    delay_ms = delay.as_millis() as u64,
+                    "Main loop iteration panicked, restarting"
+                );
+                if cli.once {
+                    break Err(anyhow::anyhow!("Main loop panicked"));
+                }
+                delay
+            }
+        };
+        if cli.once {
+            break Ok(());
         }
 
-        if cli.once { break; }
+        // Every pause listens for Ctrl-C, so failing or panicking passes can still be stopped
+        tokio::select! {
+            _ = &mut shutdown => {
+                info!("Received Ctrl-C, shutting down");
+                break Ok(());
+            }
+            _ = tokio::time::sleep(pause) => {}
+        }
+    };
 
-        // Sleep briefly before next scan
-        tokio::time::sleep(std::time::Duration::from_millis(800)).await;
-    }
+    // Every exit flushes buffered events and prints the lifetime summary
+    shutdown_recorder();
+    print!("{}", summary.render());
+    outcome
+}
+
+/// Create or extend the lookup table with the market's frequently used accounts.
+async fn lut_command(cli: &Cli, rpc: &Rpc, cfg: &Config, store: &Store, action: &LutCommand) -> Result<()> {
+    let market = cli.market.parse().context("Invalid market address")?;
+    let scanner = ProgramScanner::new(cli.scan_strategy, market).with_obligation_types(cli.obligation_types.clone());
+    let decoded = decode_accounts(&scanner.fetch(rpc).await?, &market);
+    let accounts = lut::frequent_accounts(&decoded.reserves, &market, &cfg.owner().pubkey());
 
+    let table = match action {
+        LutCommand::Create => lut::create(rpc, &cfg.payer, &market, store).await?,
+        LutCommand::Extend => match cli.lookup_table.as_deref() {
+            Some(table) => table.parse().context("Invalid lookup table address")?,
+            None => lut::latest_table(store, &market)?.context("No lookup table found; run `lut create` first")?,
+        },
+    };
+    let added = lut::extend(rpc, &cfg.payer, &table, &accounts).await?;
+    info!(table = %table, added, total = accounts.len(), "Lookup table up to date");
     Ok(())
 }
-
@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 14] =
+    [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+/// Process-wide registry of counters, gauges and latency histograms.
+#[derive(Default)]
+pub struct Metrics {
+    counters: Mutex<BTreeMap<String, u64>>,
+    gauges: Mutex<BTreeMap<String, f64>>,
+    histograms: Mutex<BTreeMap<(String, Vec<(String, String)>), Histogram>>,
+}
+
+/// Observation counts over `LATENCY_BUCKETS` plus an overflow bucket, with the latest exemplar
+/// seen in each.
+#[derive(Default)]
+struct Histogram {
+    counts: [u64; LATENCY_BUCKETS.len() + 1],
+    exemplars: [Option<(String, f64)>; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+/// Global metrics registry.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::default)
+}
+
+/// Render a series key in Prometheus form, e.g. `name{k="v"}`.
+fn series_key(name: &str, labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return name.to_string();
+    }
+    let mut key = format!("{name}{{");
+    for (i, (k, v)) in labels.iter().enumerate() {
+        if i > 0 {
+            key.push(',');
+        }
+        let _ = write!(key, "{k}=\"{v}\"");
+    }
+    key.push('}');
+    key
+}
+
+impl Metrics {
+    pub fn inc(&self, name: &str) {
+        self.add(name, &[], 1);
+    }
+
+    pub fn inc_labeled(&self, name: &str, labels: &[(&str, &str)]) {
+        self.add(name, labels, 1);
+    }
+
+    pub fn add(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        *self.counters.lock().unwrap().entry(series_key(name, labels)).or_default() += value;
+    }
+
+    pub fn set_gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.gauges.lock().unwrap().insert(series_key(name, labels), value);
+    }
+
+    /// Record one latency observation in seconds. `trace_id` is kept as the bucket's exemplar so
+    /// a slow bucket can be followed back to a concrete submission.
+    pub fn observe(&self, name: &str, labels: &[(&str, &str)], seconds: f64, trace_id: Option<&str>) {
+        let labels = labels.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let mut histograms = self.histograms.lock().unwrap();
+        let h = histograms.entry((name.to_string(), labels)).or_default();
+        let bucket = LATENCY_BUCKETS.iter().position(|le| seconds <= *le).unwrap_or(LATENCY_BUCKETS.len());
+        h.counts[bucket] += 1;
+        if let Some(trace_id) = trace_id {
+            h.exemplars[bucket] = Some((trace_id.to_string(), seconds));
+        }
+        h.sum += seconds;
+        h.count += 1;
+    }
+
+    /// Current value of a counter series, zero if never incremented.
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+        self.counters.lock().unwrap().get(&series_key(name, labels)).copied().unwrap_or(0)
+    }
+
+    /// Current value of a gauge series, if set.
+    pub fn gauge(&self, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        self.gauges.lock().unwrap().get(&series_key(name, labels)).copied()
+    }
+
+    /// Render all series in Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        self.render_with(false)
+    }
+
+    /// Render all series in OpenMetrics format, with histogram exemplars.
+    pub fn render_openmetrics(&self) -> String {
+        let mut out = self.render_with(true);
+        out.push_str("# EOF\n");
+        out
+    }
+
+    fn render_with(&self, exemplars: bool) -> String {
+        let mut out = String::new();
+        for (key, value) in self.counters.lock().unwrap().iter() {
+            let _ = writeln!(out, "{key} {value}");
+        }
+        for (key, value) in self.gauges.lock().unwrap().iter() {
+            let _ = writeln!(out, "{key} {value}");
+        }
+        let mut typed = None;
+        for ((name, labels), h) in self.histograms.lock().unwrap().iter() {
+            if typed != Some(name) {
+                let _ = writeln!(out, "# TYPE {name} histogram");
+                typed = Some(name);
+            }
+            let labels: Vec<(&str, &str)> = labels.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+            let mut cumulative = 0;
+            for (i, count) in h.counts.iter().enumerate() {
+                cumulative += count;
+                let le = LATENCY_BUCKETS.get(i).map_or("+Inf".to_string(), |le| le.to_string());
+                let mut bucket_labels = labels.clone();
+                bucket_labels.push(("le", &le));
+                let _ = write!(out, "{} {cumulative}", series_key(&format!("{name}_bucket"), &bucket_labels));
+                if let Some((trace_id, value)) = h.exemplars[i].as_ref().filter(|_| exemplars) {
+                    let _ = write!(out, " # {{trace_id=\"{trace_id}\"}} {value}");
+                }
+                out.push('\n');
+            }
+            let _ = writeln!(out, "{} {}", series_key(&format!("{name}_sum"), &labels), h.sum);
+            let _ = writeln!(out, "{} {}", series_key(&format!("{name}_count"), &labels), h.count);
+        }
+        out
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use carbon_kamino_lending_decoder::PROGRAM_ID;
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::UiTransactionEncoding;
+use tracing::{debug, info, warn};
+
+use crate::events::{self, Event};
+use crate::kamino::LiquidationCandidate;
+use crate::metrics::metrics;
+use crate::ratelimit::RequestClass;
+use crate::replay::{LIQUIDATE_DISCRIMINATOR, OBLIGATION_INDEX};
+use crate::rpc::Rpc;
+use crate::store::Store;
+use crate::util::now_millis;
+
+/// Store collection holding one record per closed liquidation window.
+pub const OPPORTUNITIES: &str = "opportunities";
+
+/// Signatures inspected when attributing a closed window.
+const ATTRIBUTION_SIGNATURES: usize = 20;
+
+/// Why we did not submit for a window we saw.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Strict simulation rejected the liquidation.
+    SimulationGate,
+    /// Building or submitting failed on an RPC or sender error.
+    RpcError,
+    /// Held back while the in-flight bundle cap was reached.
+    InFlightCap,
+}
+
+/// Persisted HF<1 window for one obligation.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OpportunityRecord {
+    pub obligation: String,
+    pub repay_reserve: String,
+    pub withdraw_reserve: String,
+    pub opened_at_ms: u64,
+    pub closed_at_ms: u64,
+    pub duration_ms: u64,
+    pub opened_slot: u64,
+    pub closed_slot: u64,
+    pub min_health: f64,
+    pub expected_profit_lamports: Option<u64>,
+    /// Whether we submitted a liquidation during the window.
+    pub submitted: bool,
+    /// Fee payer of the first Kamino liquidation of the obligation inside the window.
+    pub closed_by: Option<String>,
+    pub closing_signature: Option<String>,
+    #[serde(default)]
+    pub closing_slot: Option<u64>,
+    /// Whether `closed_by` is our payer.
+    pub ours: bool,
+    /// Latest reason a liquidation was held back during the window.
+    #[serde(default)]
+    pub skipped: Option<SkipReason>,
+}
+
+struct OpenWindow {
+    repay_reserve: Pubkey,
+    withdraw_reserve: Pubkey,
+    opened_at_ms: u64,
+    opened_slot: u64,
+    min_health: f64,
+    expected_profit_lamports: Option<u64>,
+    submitted: bool,
+    skipped: Option<SkipReason>,
+}
+
+/// Tracks liquidatable windows across scans and records them once they close, submitted or not.
+pub struct OpportunityLog {
+    open: HashMap<Pubkey, OpenWindow>,
+    payer: Pubkey,
+    store: Arc<Store>,
+}
+
+impl OpportunityLog {
+    pub fn new(payer: Pubkey, store: Arc<Store>) -> Self {
+        Self { open: HashMap::new(), payer, store }
+    }
+
+    /// Update windows from the liquidatable candidates seen at `slot`. Windows that disappeared
+    /// are attributed and recorded in the background, since that takes several RPC calls each.
+    pub fn observe(&mut self, rpc: &Arc<Rpc>, candidates: &[&LiquidationCandidate], slot: u64) {
+        let now = now_millis();
+        for cand in candidates {
+            let window = self.open.entry(cand.obligation).or_insert_with(|| OpenWindow {
+                repay_reserve: cand.repay_reserve,
+                withdraw_reserve: cand.withdraw_reserve,
+                opened_at_ms: now,
+                opened_slot: slot,
+                min_health: cand.health,
+                expected_profit_lamports: cand.expected_profit_lamports,
+                submitted: false,
+                skipped: None,
+            });
+            window.min_health = window.min_health.min(cand.health);
+            window.expected_profit_lamports = window.expected_profit_lamports.max(cand.expected_profit_lamports);
+        }
+
+        let closed: Vec<Pubkey> =
+            self.open.keys().filter(|pk| !candidates.iter().any(|c| c.obligation == **pk)).copied().collect();
+        for obligation in closed {
+            if let Some(window) = self.open.remove(&obligation) {
+                let (rpc, store, payer) = (Arc::clone(rpc), Arc::clone(&self.store), self.payer);
+                tokio::spawn(async move { close(&rpc, &store, payer, obligation, window, now, slot).await });
+            }
+        }
+        metrics().set_gauge("opportunity_windows_open", &[], self.open.len() as f64);
+    }
+
+    /// Note that we submitted a liquidation for an open window.
+    pub fn mark_submitted(&mut self, obligation: &Pubkey) {
+        if let Some(window) = self.open.get_mut(obligation) {
+            window.submitted = true;
+        }
+    }
+
+    /// Note why a liquidation for an open window was not submitted.
+    pub fn mark_skipped(&mut self, obligation: &Pubkey, reason: SkipReason) {
+        events::record(Event::Skipped { obligation: *obligation, reason });
+        if let Some(window) = self.open.get_mut(obligation) {
+            window.skipped = Some(reason);
+        }
+    }
+}
+
+/// Attribute a closed window to whoever closed it and persist its record.
+async fn close(
+    rpc: &Rpc,
+    store: &Store,
+    payer: Pubkey,
+    obligation: Pubkey,
+    window: OpenWindow,
+    closed_at_ms: u64,
+    closed_slot: u64,
+) {
+    let closer = match find_closer(rpc, &obligation, window.opened_slot).await {
+        Ok(closer) => closer,
+        Err(e) => {
+            debug!(obligation = %obligation, error = %e, "Failed to attribute closed window");
+            None
+        }
+    };
+    let ours = closer.as_ref().is_some_and(|(_, _, closed_by)| *closed_by == payer);
+
+    let record = OpportunityRecord {
+        obligation: obligation.to_string(),
+        repay_reserve: window.repay_reserve.to_string(),
+        withdraw_reserve: window.withdraw_reserve.to_string(),
+        opened_at_ms: window.opened_at_ms,
+        closed_at_ms,
+        duration_ms: closed_at_ms.saturating_sub(window.opened_at_ms),
+        opened_slot: window.opened_slot,
+        closed_slot,
+        min_health: window.min_health,
+        expected_profit_lamports: window.expected_profit_lamports,
+        submitted: window.submitted,
+        closed_by: closer.as_ref().map(|(_, _, payer)| payer.to_string()),
+        closing_signature: closer.as_ref().map(|(sig, _, _)| sig.to_string()),
+        closing_slot: closer.as_ref().map(|(_, slot, _)| *slot),
+        ours,
+        skipped: window.skipped,
+    };
+
+    let result = if ours {
+        "won"
+    } else if window.submitted {
+        "lost"
+    } else {
+        "missed"
+    };
+    metrics().inc_labeled("opportunity_windows_closed_total", &[("result", result)]);
+    info!(
+        obligation = %obligation,
+        duration_ms = record.duration_ms,
+        closed_by = ?record.closed_by,
+        result,
+        "Liquidation window closed"
+    );
+    if let Err(e) = store.append(OPPORTUNITIES, &record) {
+        warn!(error = %e, "Failed to persist opportunity record");
+    }
+}
+
+/// Earliest successful liquidation of the obligation since `since_slot`, with its slot and fee payer.
+async fn find_closer(rpc: &Rpc, obligation: &Pubkey, since_slot: u64) -> Result<Option<(Signature, u64, Pubkey)>> {
+    rpc.throttle(RequestClass::Candidate).await;
+    let config = GetConfirmedSignaturesForAddress2Config {
+        limit: Some(ATTRIBUTION_SIGNATURES),
+        commitment: Some(CommitmentConfig::confirmed()),
+        ..Default::default()
+    };
+    let sigs = rpc
+        .get_signatures_for_address_with_config(obligation, config)
+        .context("Failed to fetch obligation signatures")?;
+
+    // Newest first; walk backwards past refreshes, deposits and repays to the first liquidation
+    for sig in sigs.iter().rev().filter(|s| s.slot >= since_slot && s.err.is_none()) {
+        let signature: Signature = sig.signature.parse().context("Invalid signature")?;
+        if let Some(payer) = liquidation_payer(rpc, &signature, obligation).await? {
+            return Ok(Some((signature, sig.slot, payer)));
+        }
+    }
+    Ok(None)
+}
+
+/// Fee payer of the transaction when it carries a Kamino liquidation of `obligation`.
+async fn liquidation_payer(rpc: &Rpc, signature: &Signature, obligation: &Pubkey) -> Result<Option<Pubkey>> {
+    rpc.throttle(RequestClass::Candidate).await;
+    let fetched = rpc
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .context("Failed to fetch obligation transaction")?;
+    let tx = fetched.transaction.transaction.decode().context("Failed to decode obligation transaction")?;
+
+    // The obligation may come from a lookup table, so resolve loaded addresses too
+    let mut keys: Vec<Pubkey> = tx.message.static_account_keys().to_vec();
+    if let Some(OptionSerializer::Some(loaded)) = fetched.transaction.meta.map(|m| m.loaded_addresses) {
+        keys.extend(loaded.writable.iter().chain(&loaded.readonly).filter_map(|k| k.parse::<Pubkey>().ok()));
+    }
+    let liquidates = tx.message.instructions().iter().any(|ix| {
+        keys.get(ix.program_id_index as usize) == Some(&PROGRAM_ID)
+            && ix.data.starts_with(&LIQUIDATE_DISCRIMINATOR)
+            && ix.accounts.get(OBLIGATION_INDEX).and_then(|i| keys.get(*i as usize)) == Some(obligation)
+    });
+    Ok(match liquidates {
+        true => keys.first().copied(),
+        false => None,
+    })
+}
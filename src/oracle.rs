@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Context, Result};
+use carbon_kamino_lending_decoder::types;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::alert::Alerter;
+use crate::kamino::LiquidationCandidate;
+use crate::keeper::fetch_reserves;
+use crate::metrics::metrics;
+use crate::profit::token_price_usd;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::swap::{JupiterClient, SwapMode};
+use crate::util::now_millis;
+
+/// Slippage passed with cross-check quotes; only the quoted amounts are used.
+const QUOTE_SLIPPAGE_BPS: u16 = 50;
+
+/// Collateral priced in debt, by the reserves' oracles and by the swap market.
+#[derive(Clone, Copy, Debug)]
+pub struct PriceCheck {
+    /// Whole repay tokens per whole withdraw token from the reserves' cached oracle prices.
+    pub oracle_price: f64,
+    /// The same price from the midpoint of Jupiter quotes in both directions.
+    pub market_price: f64,
+    pub divergence_bps: f64,
+}
+
+/// Cross-checks oracle prices against Jupiter before a candidate is acted on.
+pub struct OracleGuard {
+    jupiter: JupiterClient,
+    /// Largest accepted divergence between oracle and market price, in basis points.
+    pub max_divergence_bps: f64,
+}
+
+impl OracleGuard {
+    pub fn new(jupiter: JupiterClient, max_divergence_bps: f64) -> Self {
+        Self { jupiter, max_divergence_bps }
+    }
+
+    /// Whether the check shows a depegged or manipulated oracle print.
+    pub fn rejects(&self, check: &PriceCheck) -> bool {
+        let rejected = check.divergence_bps > self.max_divergence_bps;
+        let result = match rejected {
+            true => "rejected",
+            false => "passed",
+        };
+        metrics().inc_labeled("oracle_checks_total", &[("result", result)]);
+        rejected
+    }
+
+    /// Price the candidate's collateral in its debt both ways. Quotes are sized to the
+    /// liquidation itself, and their midpoint cancels out most of the price impact.
+    pub async fn check(&self, rpc: &Rpc, cand: &LiquidationCandidate) -> Result<PriceCheck> {
+        let reserves = fetch_reserves(rpc, &[cand.repay_reserve, cand.withdraw_reserve]).await?;
+        let repay = reserves.get(&cand.repay_reserve).context("Repay reserve missing")?;
+        let withdraw = reserves.get(&cand.withdraw_reserve).context("Withdraw reserve missing")?;
+        let repay_mint = repay.liquidity.mint_pubkey;
+        let withdraw_mint = withdraw.liquidity.mint_pubkey;
+        let withdraw_amount = cand.expected_withdraw_amount.context("Candidate has no expected withdraw amount")?;
+
+        let sell =
+            self.jupiter.quote(&withdraw_mint, &repay_mint, withdraw_amount, SwapMode::ExactIn, QUOTE_SLIPPAGE_BPS);
+        let buy =
+            self.jupiter.quote(&repay_mint, &withdraw_mint, cand.repay_amount, SwapMode::ExactIn, QUOTE_SLIPPAGE_BPS);
+        let (sell, buy) = tokio::try_join!(sell, buy)?;
+        ensure!(sell.in_amount > 0 && buy.out_amount > 0, "Jupiter returned an empty quote");
+
+        // Repay base units per withdraw base unit, then scaled to whole tokens
+        let sell_rate = sell.out_amount as f64 / sell.in_amount as f64;
+        let buy_rate = buy.in_amount as f64 / buy.out_amount as f64;
+        let decimals = withdraw.liquidity.mint_decimals as i32 - repay.liquidity.mint_decimals as i32;
+        let market_price = (sell_rate + buy_rate) / 2.0 * 10f64.powi(decimals);
+
+        let repay_price = token_price_usd(repay);
+        ensure!(repay_price > 0.0 && market_price > 0.0, "Price unavailable");
+        let oracle_price = token_price_usd(withdraw) / repay_price;
+        let divergence_bps = (oracle_price - market_price).abs() / market_price * 10_000.0;
+        Ok(PriceCheck { oracle_price, market_price, divergence_bps })
+    }
+}
+
+/// Independent feeds that must agree before a large liquidation is sent.
+const MIN_AGREEING_SOURCES: usize = 2;
+
+/// Feed prints older than this are left out of the quorum.
+const MAX_FEED_AGE_SECS: u64 = 60;
+
+/// Shortest time between review alerts for one obligation.
+const ALERT_COOLDOWN: Duration = Duration::from_secs(600);
+
+/// Offset of the price message in a Pyth `PriceUpdateV2` account, after the discriminator,
+/// write authority and a `Full` verification level. `Partial` adds a signature count byte.
+const PYTH_MESSAGE_OFFSET: usize = 8 + 32 + 1;
+
+/// Offset of the price array in a Scope `OraclePrices` account and the size of each entry.
+const SCOPE_PRICES_OFFSET: usize = 8 + 32;
+const SCOPE_PRICE_LEN: usize = 56;
+
+/// Marks the end of a Scope price chain.
+const SCOPE_CHAIN_END: u16 = u16::MAX;
+
+/// A price feed a reserve configures alongside its cached price.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceSource {
+    Scope,
+    Pyth,
+}
+
+impl PriceSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Scope => "scope",
+            Self::Pyth => "pyth",
+        }
+    }
+}
+
+/// Collateral priced in debt by the reserves' cached prices and by each feed both reserves
+/// configure.
+#[derive(Clone, Debug)]
+pub struct QuorumCheck {
+    /// Whole repay tokens per whole withdraw token, as the profit estimate used.
+    pub cached_price: f64,
+    pub sources: Vec<(PriceSource, f64)>,
+    /// Largest accepted divergence from the cached price, in basis points.
+    pub max_divergence_bps: f64,
+}
+
+impl QuorumCheck {
+    fn divergence_bps(&self, price: f64) -> f64 {
+        (price - self.cached_price).abs() / self.cached_price * 10_000.0
+    }
+
+    /// Feeds whose price diverges from the cached one beyond the limit.
+    pub fn disagreeing(&self) -> Vec<PriceSource> {
+        self.sources
+            .iter()
+            .filter(|(_, p)| self.divergence_bps(*p) > self.max_divergence_bps)
+            .map(|(s, _)| *s)
+            .collect()
+    }
+
+    /// Enough feeds agree and none disagrees.
+    pub fn confirmed(&self) -> bool {
+        self.disagreeing().is_empty() && self.sources.len() >= MIN_AGREEING_SOURCES
+    }
+
+    pub fn describe(&self) -> String {
+        let sources: Vec<String> = self
+            .sources
+            .iter()
+            .map(|(s, p)| format!("{} {p:.6} ({:.0} bps)", s.as_str(), self.divergence_bps(*p)))
+            .collect();
+        match sources.is_empty() {
+            true => format!("cached {:.6}, no fresh feeds", self.cached_price),
+            false => format!("cached {:.6}, {}", self.cached_price, sources.join(", ")),
+        }
+    }
+}
+
+/// Before liquidations seizing more than `min_seized_lamports`, requires the reserves' Scope
+/// and Pyth feeds to agree with the cached prices the candidate was sized on. Candidates that
+/// fail are skipped and alerted for an operator to look at.
+pub struct PriceQuorum {
+    pub min_seized_lamports: u64,
+    pub max_divergence_bps: f64,
+    alerted: Mutex<HashMap<Pubkey, Instant>>,
+}
+
+impl PriceQuorum {
+    pub fn new(min_seized_lamports: u64, max_divergence_bps: f64) -> Self {
+        Self { min_seized_lamports, max_divergence_bps, alerted: Mutex::new(HashMap::new()) }
+    }
+
+    /// Whether the candidate is large enough to need the quorum. Unknown sizes count as large.
+    pub fn applies_to(&self, cand: &LiquidationCandidate) -> bool {
+        cand.expected_seized_lamports.is_none_or(|v| v >= self.min_seized_lamports)
+    }
+
+    pub async fn check(&self, rpc: &Rpc, cand: &LiquidationCandidate) -> Result<QuorumCheck> {
+        let reserves = fetch_reserves(rpc, &[cand.repay_reserve, cand.withdraw_reserve]).await?;
+        let repay = reserves.get(&cand.repay_reserve).context("Repay reserve missing")?;
+        let withdraw = reserves.get(&cand.withdraw_reserve).context("Withdraw reserve missing")?;
+        let repay_price = token_price_usd(repay);
+        ensure!(repay_price > 0.0, "Repay reserve has no cached price");
+        let cached_price = token_price_usd(withdraw) / repay_price;
+
+        let feeds = [repay, withdraw].map(|r| {
+            let info = &r.config.token_info;
+            [info.scope_configuration.price_feed, info.pyth_configuration.price]
+        });
+        let keys: Vec<Pubkey> = feeds.iter().flatten().copied().collect();
+        rpc.throttle(RequestClass::Candidate).await;
+        let accounts = rpc.get_multiple_accounts(&keys).context("Failed to fetch price feeds")?;
+        // Unset feeds hold the default key
+        let data = |i: usize| match keys[i] == Pubkey::default() {
+            true => None,
+            false => accounts[i].as_ref().map(|a| a.data.as_slice()),
+        };
+
+        let now_secs = now_millis() / 1_000;
+        let scope = |r: &types::Reserve, i| {
+            data(i).and_then(|d| scope_price(d, &r.config.token_info.scope_configuration.price_chain, now_secs))
+        };
+        let pyth = |i| data(i).and_then(|d| pyth_price(d, now_secs));
+        let mut sources = Vec::new();
+        // Feed indices follow `keys`: repay Scope, repay Pyth, withdraw Scope, withdraw Pyth
+        if let (Some(r), Some(w)) = (scope(repay, 0), scope(withdraw, 2)) {
+            sources.push((PriceSource::Scope, w / r));
+        }
+        if let (Some(r), Some(w)) = (pyth(1), pyth(3)) {
+            sources.push((PriceSource::Pyth, w / r));
+        }
+        let check = QuorumCheck { cached_price, sources, max_divergence_bps: self.max_divergence_bps };
+        let result = match check.confirmed() {
+            true => "confirmed",
+            false => "rejected",
+        };
+        metrics().inc_labeled("price_quorum_checks_total", &[("result", result)]);
+        Ok(check)
+    }
+
+    /// Ask an operator to review a rejected candidate, at most once per cooldown.
+    pub async fn alert(&self, alerter: &Alerter, cand: &LiquidationCandidate, check: &QuorumCheck) {
+        {
+            let mut alerted = self.alerted.lock().unwrap();
+            alerted.retain(|_, at| at.elapsed() < ALERT_COOLDOWN);
+            if alerted.insert(cand.obligation, Instant::now()).is_some() {
+                return;
+            }
+        }
+        let reason = match check.disagreeing().is_empty() {
+            true => format!("fewer than {MIN_AGREEING_SOURCES} fresh price feeds"),
+            false => "price feeds disagree".to_string(),
+        };
+        alerter
+            .send(&format!(
+                "Held back liquidation of {} ({} lamports seized) for review: {reason}; {}",
+                cand.obligation,
+                cand.expected_seized_lamports.map_or("unknown".to_string(), |v| v.to_string()),
+                check.describe(),
+            ))
+            .await;
+    }
+}
+
+/// USD price from a Pyth `PriceUpdateV2` account, if fresh.
+pub fn pyth_price(data: &[u8], now_secs: u64) -> Option<f64> {
+    // A `Partial` verification level (tag 0) carries the number of signatures checked
+    let offset = match data.get(40)? {
+        0 => PYTH_MESSAGE_OFFSET + 1,
+        _ => PYTH_MESSAGE_OFFSET,
+    };
+    // Feed id, then price, confidence, exponent and publish time
+    let field = |at: usize, len: usize| data.get(offset + 32 + at..offset + 32 + at + len);
+    let price = i64::from_le_bytes(field(0, 8)?.try_into().ok()?);
+    let exponent = i32::from_le_bytes(field(16, 4)?.try_into().ok()?);
+    let published = i64::from_le_bytes(field(20, 8)?.try_into().ok()?);
+    let fresh = now_secs.saturating_sub(published.max(0) as u64) <= MAX_FEED_AGE_SECS;
+    (fresh && price > 0).then(|| price as f64 * 10f64.powi(exponent))
+}
+
+/// USD price from a Scope `OraclePrices` account, multiplying along the reserve's price
+/// chain, if every link is fresh.
+pub fn scope_price(data: &[u8], chain: &[u16], now_secs: u64) -> Option<f64> {
+    let mut price = 1.0;
+    let mut links = 0;
+    for &index in chain.iter().take_while(|i| **i != SCOPE_CHAIN_END) {
+        let at = SCOPE_PRICES_OFFSET + index as usize * SCOPE_PRICE_LEN;
+        let entry = data.get(at..at + SCOPE_PRICE_LEN)?;
+        let value = u64::from_le_bytes(entry[0..8].try_into().ok()?);
+        let exp = u64::from_le_bytes(entry[8..16].try_into().ok()?);
+        let updated = u64::from_le_bytes(entry[24..32].try_into().ok()?);
+        if value == 0 || now_secs.saturating_sub(updated) > MAX_FEED_AGE_SECS {
+            return None;
+        }
+        price *= value as f64 / 10f64.powi(exp as i32);
+        links += 1;
+    }
+    (links > 0).then_some(price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    /// `PriceUpdateV2` bytes with a `Full` verification level, or `Partial` when `partial`.
+    fn pyth_account(price: i64, exponent: i32, published: i64, partial: bool) -> Vec<u8> {
+        let mut data = vec![0u8; 40];
+        match partial {
+            true => data.extend([0, 3]),
+            false => data.push(1),
+        }
+        data.extend([7u8; 32]);
+        data.extend(price.to_le_bytes());
+        data.extend(5u64.to_le_bytes());
+        data.extend(exponent.to_le_bytes());
+        data.extend(published.to_le_bytes());
+        data
+    }
+
+    /// `OraclePrices` bytes holding `(value, exp, unix_timestamp)` entries.
+    fn scope_account(entries: &[(u64, u64, u64)]) -> Vec<u8> {
+        let mut data = vec![0u8; SCOPE_PRICES_OFFSET];
+        for (value, exp, updated) in entries {
+            data.extend(value.to_le_bytes());
+            data.extend(exp.to_le_bytes());
+            data.extend(0u64.to_le_bytes());
+            data.extend(updated.to_le_bytes());
+            data.extend([0u8; 24]);
+        }
+        data
+    }
+
+    fn close(a: Option<f64>, b: f64) -> bool {
+        a.is_some_and(|a| (a - b).abs() < 1e-9)
+    }
+
+    #[test]
+    fn pyth_reads_both_verification_levels() {
+        assert!(close(pyth_price(&pyth_account(15_000_000_000, -8, NOW as i64, false), NOW), 150.0));
+        assert!(close(pyth_price(&pyth_account(15_000_000_000, -8, NOW as i64, true), NOW), 150.0));
+    }
+
+    #[test]
+    fn pyth_rejects_stale_non_positive_and_short_accounts() {
+        let stale = NOW as i64 - MAX_FEED_AGE_SECS as i64 - 1;
+        assert_eq!(pyth_price(&pyth_account(15_000_000_000, -8, stale, false), NOW), None);
+        assert!(pyth_price(&pyth_account(15_000_000_000, -8, stale + 1, false), NOW).is_some());
+        assert_eq!(pyth_price(&pyth_account(0, -8, NOW as i64, false), NOW), None);
+        assert_eq!(pyth_price(&pyth_account(-1, -8, NOW as i64, false), NOW), None);
+        let full = pyth_account(15_000_000_000, -8, NOW as i64, false);
+        assert_eq!(pyth_price(&full[..full.len() - 1], NOW), None);
+        assert_eq!(pyth_price(&[], NOW), None);
+    }
+
+    #[test]
+    fn scope_multiplies_along_the_chain() {
+        let data = scope_account(&[(2_000_000, 6, NOW), (15_000, 2, NOW)]);
+        assert!(close(scope_price(&data, &[0, SCOPE_CHAIN_END, SCOPE_CHAIN_END, SCOPE_CHAIN_END], NOW), 2.0));
+        assert!(close(scope_price(&data, &[0, 1, SCOPE_CHAIN_END, SCOPE_CHAIN_END], NOW), 300.0));
+    }
+
+    #[test]
+    fn scope_rejects_stale_zero_missing_and_empty_chains() {
+        let stale = NOW - MAX_FEED_AGE_SECS - 1;
+        let data = scope_account(&[(2_000_000, 6, NOW), (15_000, 2, stale), (0, 0, NOW)]);
+        assert_eq!(scope_price(&data, &[0, 1, SCOPE_CHAIN_END, SCOPE_CHAIN_END], NOW), None);
+        assert_eq!(scope_price(&data, &[2, SCOPE_CHAIN_END, SCOPE_CHAIN_END, SCOPE_CHAIN_END], NOW), None);
+        assert_eq!(scope_price(&data, &[3, SCOPE_CHAIN_END, SCOPE_CHAIN_END, SCOPE_CHAIN_END], NOW), None);
+        assert_eq!(scope_price(&data, &[SCOPE_CHAIN_END; 4], NOW), None);
+        assert_eq!(scope_price(&data, &[], NOW), None);
+    }
+}
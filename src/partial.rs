@@ -0,0 +1,109 @@
+use solana_sdk::pubkey::Pubkey;
+
+/// Anchor discriminator of the Kamino `Obligation` account.
+pub const OBLIGATION_DISCRIMINATOR: [u8; 8] = [0xa8, 0xce, 0x8d, 0x6a, 0x58, 0x4c, 0xac, 0xa7];
+
+/// Serialized size of an `Obligation` account including the discriminator.
+pub const OBLIGATION_SIZE: usize = 3344;
+
+/// Anchor discriminator of the Kamino `Reserve` account.
+pub const RESERVE_DISCRIMINATOR: [u8; 8] = [0x2b, 0xf2, 0xcc, 0xca, 0x1a, 0xf7, 0x3b, 0x7f];
+
+/// Serialized size of a `Reserve` account including the discriminator.
+pub const RESERVE_SIZE: usize = 8624;
+
+/// Offset of `lending_market` in a raw `Reserve` account.
+pub const RESERVE_LENDING_MARKET_OFFSET: usize = 32;
+
+// Absolute byte offsets into the raw obligation account, discriminator included
+pub const OBLIGATION_TAG_OFFSET: usize = 8;
+pub const OBLIGATION_LENDING_MARKET_OFFSET: usize = 32;
+pub const OBLIGATION_OWNER_OFFSET: usize = 64;
+const DEPOSITS_OFFSET: usize = 96;
+const DEPOSIT_LEN: usize = 136;
+const MAX_DEPOSITS: usize = 8;
+const BORROWS_OFFSET: usize = 1208;
+const BORROW_LEN: usize = 200;
+const MAX_BORROWS: usize = 5;
+
+// Field offsets inside a single deposit / borrow slot
+const DEPOSIT_AMOUNT_OFFSET: usize = 32;
+const BORROW_AMOUNT_SF_OFFSET: usize = 88;
+
+/// Scaled-fraction shift used by Kamino for `*_sf` fields.
+const SF_SHIFT: u32 = 60;
+
+/// Non-empty deposit or borrow slot read straight from raw bytes.
+#[derive(Clone, Copy, Debug)]
+pub struct PositionSlot {
+    pub reserve: Pubkey,
+    pub amount: u64,
+}
+
+/// Zero-copy view over a raw obligation account for cheap pre-filtering.
+pub struct ObligationView<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ObligationView<'a> {
+    /// Wrap raw account data, returning None unless it looks like an obligation.
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        if data.len() != OBLIGATION_SIZE || data[..8] != OBLIGATION_DISCRIMINATOR {
+            return None;
+        }
+        Some(Self { data })
+    }
+
+    /// Product tag: vanilla, multiply, lending or leverage.
+    pub fn tag(&self) -> u64 {
+        self.u64_at(OBLIGATION_TAG_OFFSET)
+    }
+
+    pub fn lending_market(&self) -> Pubkey {
+        self.pubkey_at(OBLIGATION_LENDING_MARKET_OFFSET)
+    }
+
+    pub fn owner(&self) -> Pubkey {
+        self.pubkey_at(OBLIGATION_OWNER_OFFSET)
+    }
+
+    /// Non-empty collateral deposits, amounts in collateral token units.
+    pub fn deposits(&self) -> impl Iterator<Item = PositionSlot> + '_ {
+        (0..MAX_DEPOSITS).filter_map(move |i| {
+            let base = DEPOSITS_OFFSET + i * DEPOSIT_LEN;
+            let reserve = self.pubkey_at(base);
+            let amount = self.u64_at(base + DEPOSIT_AMOUNT_OFFSET);
+            (reserve != Pubkey::default() && amount > 0).then_some(PositionSlot { reserve, amount })
+        })
+    }
+
+    /// Non-empty borrows, amounts in liquidity token units (scaled fraction truncated).
+    pub fn borrows(&self) -> impl Iterator<Item = PositionSlot> + '_ {
+        (0..MAX_BORROWS).filter_map(move |i| {
+            let base = BORROWS_OFFSET + i * BORROW_LEN;
+            let reserve = self.pubkey_at(base);
+            let amount = (self.u128_at(base + BORROW_AMOUNT_SF_OFFSET) >> SF_SHIFT) as u64;
+            (reserve != Pubkey::default() && amount > 0).then_some(PositionSlot { reserve, amount })
+        })
+    }
+
+    pub fn deposit_count(&self) -> usize {
+        self.deposits().count()
+    }
+
+    pub fn borrow_count(&self) -> usize {
+        self.borrows().count()
+    }
+
+    fn pubkey_at(&self, offset: usize) -> Pubkey {
+        Pubkey::new_from_array(self.data[offset..offset + 32].try_into().unwrap())
+    }
+
+    fn u64_at(&self, offset: usize) -> u64 {
+        u64::from_le_bytes(self.data[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn u128_at(&self, offset: usize) -> u128 {
+        u128::from_le_bytes(self.data[offset..offset + 16].try_into().unwrap())
+    }
+}
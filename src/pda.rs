@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use carbon_kamino_lending_decoder::{types, KaminoLendingDecoder, PROGRAM_ID};
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account_client::address::get_associated_token_address_with_program_id;
+
+use crate::keeper::fetch_reserves;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+
+/// Seed prefix of a referrer's per-reserve fee account.
+pub const REFERRER_TOKEN_STATE_SEED: &[u8] = b"referrer_acc";
+
+/// Seed prefix of a market's lending market authority.
+pub const LENDING_MARKET_AUTHORITY_SEED: &[u8] = b"lma";
+
+/// PDA that owns a market's reserve vaults and signs transfers out of them.
+pub fn lending_market_authority(market: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[LENDING_MARKET_AUTHORITY_SEED, market.as_ref()], &PROGRAM_ID).0
+}
+
+/// Referrer token state PDA that accrues the referrer's share of fees on `reserve`.
+pub fn referrer_token_state(referrer: &Pubkey, reserve: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[REFERRER_TOKEN_STATE_SEED, referrer.as_ref(), reserve.as_ref()], &PROGRAM_ID).0
+}
+
+/// Market-level accounts some liquidation paths require, resolved once at startup.
+#[derive(Clone, Debug)]
+pub struct MarketAccounts {
+    pub market: Pubkey,
+    /// The market's lending market authority PDA.
+    pub authority: Pubkey,
+    /// Risk council authority configured on the market, if any.
+    pub risk_council: Option<Pubkey>,
+    /// Whether the market lets liquidators deleverage positions in reserves over their caps.
+    pub autodeleverage_enabled: bool,
+}
+
+impl MarketAccounts {
+    /// Fetch the lending market account and pull its authority accounts.
+    pub async fn resolve(rpc: &Rpc, market: Pubkey) -> Result<Self> {
+        rpc.throttle(RequestClass::Candidate).await;
+        let acc = rpc.get_account(&market).context("Failed to fetch lending market")?;
+        let lending_market = KaminoLendingDecoder::default()
+            .decode_lending_market(&acc.data)
+            .context("Failed to decode lending market")?;
+        let risk_council = Some(lending_market.risk_council).filter(|pk| *pk != Pubkey::default());
+        Ok(Self {
+            market,
+            authority: lending_market_authority(&market),
+            risk_council,
+            autodeleverage_enabled: lending_market.autodeleverage_enabled != 0,
+        })
+    }
+
+    /// Referrer token state for an obligation's referrer, or none when it has no referrer.
+    pub fn referrer_token_state(&self, referrer: &Pubkey, reserve: &Pubkey) -> Option<Pubkey> {
+        (*referrer != Pubkey::default()).then(|| referrer_token_state(referrer, reserve))
+    }
+}
+
+/// Mints, vaults and token programs of the two reserves a liquidation moves funds between.
+#[derive(Clone, Debug)]
+pub struct ReserveVaults {
+    pub repay_liquidity_mint: Pubkey,
+    /// Where the repaid liquidity goes.
+    pub repay_liquidity_supply: Pubkey,
+    pub repay_token_program: Pubkey,
+    pub withdraw_liquidity_mint: Pubkey,
+    pub withdraw_collateral_mint: Pubkey,
+    /// Holds the obligation's collateral tokens that get seized.
+    pub withdraw_collateral_supply: Pubkey,
+    /// Pays out the redeemed liquidity.
+    pub withdraw_liquidity_supply: Pubkey,
+    /// Receives the protocol's share of the liquidation.
+    pub withdraw_fee_receiver: Pubkey,
+    pub withdraw_token_program: Pubkey,
+}
+
+impl ReserveVaults {
+    pub fn from_reserves(repay: &types::Reserve, withdraw: &types::Reserve) -> Self {
+        Self {
+            repay_liquidity_mint: repay.liquidity.mint_pubkey,
+            repay_liquidity_supply: repay.liquidity.supply_vault,
+            repay_token_program: repay.liquidity.token_program,
+            withdraw_liquidity_mint: withdraw.liquidity.mint_pubkey,
+            withdraw_collateral_mint: withdraw.collateral.mint_pubkey,
+            withdraw_collateral_supply: withdraw.collateral.supply_vault,
+            withdraw_liquidity_supply: withdraw.liquidity.supply_vault,
+            withdraw_fee_receiver: withdraw.liquidity.fee_vault,
+            withdraw_token_program: withdraw.liquidity.token_program,
+        }
+    }
+
+    /// Fetch both reserves and resolve their vaults.
+    pub async fn fetch(rpc: &Rpc, repay_reserve: &Pubkey, withdraw_reserve: &Pubkey) -> Result<Self> {
+        let reserves = fetch_reserves(rpc, &[*repay_reserve, *withdraw_reserve]).await?;
+        let repay = reserves.get(repay_reserve).context("Repay reserve missing")?;
+        let withdraw = reserves.get(withdraw_reserve).context("Withdraw reserve missing")?;
+        Ok(Self::from_reserves(repay, withdraw))
+    }
+}
+
+/// Wallet whose token accounts fund repayments and receive seized collateral.
+#[derive(Clone, Debug)]
+pub struct LiquidatorAccounts {
+    /// Signs liquidations and owns the token accounts.
+    pub owner: Pubkey,
+    /// Token accounts used instead of the owner's associated token account, keyed by mint.
+    pub overrides: HashMap<Pubkey, Pubkey>,
+}
+
+impl LiquidatorAccounts {
+    pub fn new(owner: Pubkey) -> Self {
+        Self { owner, overrides: HashMap::new() }
+    }
+
+    /// The configured account for `mint`, or the owner's associated token account.
+    pub fn token_account(&self, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+        match self.overrides.get(mint) {
+            Some(account) => *account,
+            None => get_associated_token_address_with_program_id(&self.owner, mint, token_program),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lending_market_authority_matches_mainnet() {
+        let main_market: Pubkey = "7u3HeHxYDLhnCoErrtycNokbQYbWGzLs6JSDqGAv5PfF".parse().unwrap();
+        let expected: Pubkey = "9DrvZvyWh1HuAoZxvYWMvkf2XCzryCpGgHqrMjyDWpmo".parse().unwrap();
+        assert_eq!(lending_market_authority(&main_market), expected);
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use carbon_kamino_lending_decoder::types;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::pubkey::Pubkey;
+
+/// Kamino scaled-fraction values carry 60 fractional bits.
+const SF_ONE: f64 = (1u128 << 60) as f64;
+
+/// Convert a Kamino scaled-fraction value to f64.
+pub fn sf_to_f64(sf: u128) -> f64 {
+    sf as f64 / SF_ONE
+}
+
+/// Reserve's cached oracle price in USD per whole token.
+pub fn token_price_usd(reserve: &types::Reserve) -> f64 {
+    sf_to_f64(reserve.liquidity.market_price_sf)
+}
+
+/// USD value of `amount` base units of the reserve's liquidity token.
+pub fn value_usd(reserve: &types::Reserve, amount: u64) -> f64 {
+    amount as f64 / 10f64.powi(reserve.liquidity.mint_decimals as i32) * token_price_usd(reserve)
+}
+
+/// SOL price taken from the market's wrapped-SOL reserve, if it has one.
+pub fn sol_price_usd(reserves: &HashMap<Pubkey, types::Reserve>) -> Option<f64> {
+    reserves
+        .values()
+        .find(|r| r.liquidity.mint_pubkey == spl_token::native_mint::ID)
+        .map(token_price_usd)
+        .filter(|p| *p > 0.0)
+}
+
+/// Convert a USD amount to lamports at the given SOL price.
+pub fn usd_to_lamports(usd: f64, sol_price: f64) -> u64 {
+    (usd / sol_price * LAMPORTS_PER_SOL as f64).max(0.0) as u64
+}
+
+/// Share of the liquidation bonus the withdraw reserve pays to the protocol instead of the
+/// liquidator.
+pub fn protocol_fee_rate(withdraw: &types::Reserve) -> f64 {
+    f64::from(withdraw.config.protocol_liquidation_fee_pct.min(100)) / 100.0
+}
+
+/// Bonus the liquidator keeps, as a fraction of the repaid value: the withdraw reserve's
+/// minimum bonus, so the estimate errs low, less the protocol's cut.
+pub fn net_bonus_rate(withdraw: &types::Reserve) -> f64 {
+    f64::from(withdraw.config.min_liquidation_bonus_bps) / 10_000.0 * (1.0 - protocol_fee_rate(withdraw))
+}
+
+/// Liquidation bonus in USD received for repaying `repay_amount` of the repay reserve, after
+/// the protocol's cut.
+pub fn estimate_bonus_usd(repay: &types::Reserve, withdraw: &types::Reserve, repay_amount: u64) -> f64 {
+    value_usd(repay, repay_amount) * net_bonus_rate(withdraw)
+}
+
+/// Expected withdraw-reserve liquidity received for repaying `repay_amount`, including the
+/// bonus net of the protocol's cut.
+pub fn estimate_withdraw_amount(
+    reserves: &HashMap<Pubkey, types::Reserve>,
+    repay_reserve: &Pubkey,
+    withdraw_reserve: &Pubkey,
+    repay_amount: u64,
+) -> Option<u64> {
+    let repay = reserves.get(repay_reserve)?;
+    let withdraw = reserves.get(withdraw_reserve)?;
+    let price = token_price_usd(withdraw);
+    if price <= 0.0 {
+        return None;
+    }
+    let bonus = net_bonus_rate(withdraw);
+    let seized_usd = value_usd(repay, repay_amount) * (1.0 + bonus);
+    Some((seized_usd / price * 10f64.powi(withdraw.liquidity.mint_decimals as i32)) as u64)
+}
+
+/// Expected value of the collateral received in lamports, net bonus included.
+pub fn estimate_seized_lamports(
+    reserves: &HashMap<Pubkey, types::Reserve>,
+    repay_reserve: &Pubkey,
+    withdraw_reserve: &Pubkey,
+    repay_amount: u64,
+) -> Option<u64> {
+    let repay = reserves.get(repay_reserve)?;
+    let withdraw = reserves.get(withdraw_reserve)?;
+    let sol_price = sol_price_usd(reserves)?;
+    let bonus = net_bonus_rate(withdraw);
+    Some(usd_to_lamports(value_usd(repay, repay_amount) * (1.0 + bonus), sol_price))
+}
+
+/// Expected liquidation profit in lamports, before tips and fees.
+pub fn estimate_profit_lamports(
+    reserves: &HashMap<Pubkey, types::Reserve>,
+    repay_reserve: &Pubkey,
+    withdraw_reserve: &Pubkey,
+    repay_amount: u64,
+) -> Option<u64> {
+    let repay = reserves.get(repay_reserve)?;
+    let withdraw = reserves.get(withdraw_reserve)?;
+    let sol_price = sol_price_usd(reserves)?;
+    Some(usd_to_lamports(estimate_bonus_usd(repay, withdraw, repay_amount), sol_price))
+}
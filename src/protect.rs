@@ -0,0 +1,174 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Context, Result};
+use carbon_kamino_lending_decoder::PROGRAM_ID;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::VersionedTransaction;
+use tracing::{info, warn};
+
+use crate::kamino::{fetch_obligation, LiquidationCandidate};
+use crate::keeper::{fetch_reserves, refresh_ixs};
+use crate::metrics::metrics;
+use crate::pda::{LiquidatorAccounts, MarketAccounts};
+use crate::profit::value_usd;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::util::{fetch_latest_blockhash, TxBuilder};
+
+/// Anchor discriminator of RepayObligationLiquidity.
+const REPAY_OBLIGATION_LIQUIDITY_DISCRIMINATOR: [u8; 8] = [0x91, 0xb2, 0x0d, 0xe1, 0x4c, 0xf0, 0x93, 0x48];
+
+/// Which wallets to protect and how hard.
+#[derive(Clone, Debug)]
+pub struct ProtectConfig {
+    /// Obligation owners whose positions are repaid instead of liquidated.
+    pub owners: HashSet<Pubkey>,
+    /// Health below which a protected obligation gets a repayment.
+    pub trigger_health: f64,
+    /// Share of the largest borrow by USD value repaid per intervention, in basis points.
+    pub repay_bps: u16,
+    /// Minimum time between repayments on the same obligation, so one lands before the next.
+    pub cooldown: Duration,
+}
+
+/// Keeps protected wallets' obligations clear of liquidation by repaying part of their debt from
+/// the liquidator's own funds.
+pub struct Protector {
+    cfg: ProtectConfig,
+    last_repaid: HashMap<Pubkey, Instant>,
+}
+
+impl Protector {
+    pub fn new(cfg: ProtectConfig) -> Self {
+        Self { cfg, last_repaid: HashMap::new() }
+    }
+
+    /// Whether the candidate belongs to a protected wallet and must not be liquidated.
+    pub fn is_protected(&self, cand: &LiquidationCandidate) -> bool {
+        self.cfg.owners.contains(&cand.owner)
+    }
+
+    /// Repay part of the debt of each protected candidate below the trigger health. Repayments
+    /// are sent in the background; returns how many were started.
+    pub async fn tick(
+        &mut self,
+        rpc: &Arc<Rpc>,
+        builder: &TxBuilder<'_>,
+        market: &MarketAccounts,
+        liquidator: &LiquidatorAccounts,
+        candidates: &[&LiquidationCandidate],
+        dry_run: bool,
+    ) -> usize {
+        let due: Vec<&LiquidationCandidate> = candidates
+            .iter()
+            .copied()
+            .filter(|c| self.is_protected(c) && c.health < self.cfg.trigger_health)
+            .filter(|c| !self.last_repaid.get(&c.obligation).is_some_and(|t| t.elapsed() < self.cfg.cooldown))
+            .collect();
+        let mut sent = 0;
+        for cand in due {
+            if dry_run {
+                info!(
+                    obligation = %cand.obligation,
+                    owner = %cand.owner,
+                    health = cand.health,
+                    "Dry-run: would repay protected obligation"
+                );
+                continue;
+            }
+            match self.repay(rpc, builder, market, liquidator, cand).await {
+                Ok((reserve, amount, tx)) => {
+                    self.last_repaid.insert(cand.obligation, Instant::now());
+                    sent += 1;
+                    let (rpc, obligation, owner, health) = (Arc::clone(rpc), cand.obligation, cand.owner, cand.health);
+                    tokio::spawn(async move {
+                        rpc.throttle(RequestClass::Candidate).await;
+                        match rpc.send_transaction(&tx) {
+                            Ok(sig) => {
+                                info!(
+                                    obligation = %obligation,
+                                    owner = %owner,
+                                    health,
+                                    reserve = %reserve,
+                                    amount,
+                                    signature = %sig,
+                                    "Repaid protected obligation"
+                                );
+                                metrics().inc_labeled("protect_repayments_total", &[("result", "sent")]);
+                            }
+                            Err(e) => {
+                                warn!(obligation = %obligation, error = %e, "Failed to send repayment");
+                                metrics().inc_labeled("protect_repayments_total", &[("result", "failed")]);
+                            }
+                        }
+                    });
+                }
+                Err(e) => {
+                    warn!(obligation = %cand.obligation, error = %e, "Failed to repay protected obligation");
+                    metrics().inc_labeled("protect_repayments_total", &[("result", "failed")]);
+                }
+            }
+        }
+        sent
+    }
+
+    /// Build the signed repayment of the obligation's largest borrow by USD value, returning the
+    /// repaid reserve and amount with it.
+    async fn repay(
+        &self,
+        rpc: &Rpc,
+        builder: &TxBuilder<'_>,
+        market: &MarketAccounts,
+        liquidator: &LiquidatorAccounts,
+        cand: &LiquidationCandidate,
+    ) -> Result<(Pubkey, u64, VersionedTransaction)> {
+        let obl = fetch_obligation(rpc, &cand.obligation).await?;
+        let keys: Vec<Pubkey> = obl
+            .deposits
+            .iter()
+            .map(|d| d.reserve)
+            .chain(obl.borrows.iter().map(|b| b.reserve))
+            .filter(|pk| *pk != Pubkey::default())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let reserves = fetch_reserves(rpc, &keys).await?;
+
+        // The liquidation candidate's repay reserve follows the strategy; relief comes from the biggest debt
+        let value = |reserve: &Pubkey, amount: u64| reserves.get(reserve).map_or(0.0, |r| value_usd(r, amount));
+        let borrow = obl
+            .borrows
+            .iter()
+            .filter(|b| b.amount > 0)
+            .max_by(|a, b| value(&a.reserve, a.amount).total_cmp(&value(&b.reserve, b.amount)))
+            .context("Obligation has no borrows left")?;
+        let repay_reserve = borrow.reserve;
+        let amount = (borrow.amount as u128 * self.cfg.repay_bps as u128 / 10_000) as u64;
+        ensure!(amount > 0, "Repayment rounds to zero");
+        let reserve = reserves.get(&repay_reserve).context("Repay reserve missing")?;
+
+        // The program only accepts a repayment against a freshly refreshed obligation
+        let mut ixs = refresh_ixs(market, &cand.obligation, &obl, &reserves)?;
+        let source = liquidator.token_account(&reserve.liquidity.mint_pubkey, &reserve.liquidity.token_program);
+        let accounts = vec![
+            AccountMeta::new_readonly(liquidator.owner, true),
+            AccountMeta::new(cand.obligation, false),
+            AccountMeta::new_readonly(market.market, false),
+            AccountMeta::new(repay_reserve, false),
+            AccountMeta::new_readonly(reserve.liquidity.mint_pubkey, false),
+            AccountMeta::new(reserve.liquidity.supply_vault, false),
+            AccountMeta::new(source, false),
+            AccountMeta::new_readonly(reserve.liquidity.token_program, false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::instructions::ID, false),
+        ];
+        let mut data = REPAY_OBLIGATION_LIQUIDITY_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&amount.to_le_bytes());
+        ixs.push(Instruction { program_id: PROGRAM_ID, accounts, data });
+
+        let blockhash = fetch_latest_blockhash(rpc).await?;
+        Ok((repay_reserve, amount, builder.tx(blockhash, ixs)?))
+    }
+}
@@ -0,0 +1,146 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream};
+
+/// Longest HTTP CONNECT response header accepted from a proxy.
+const MAX_CONNECT_RESPONSE: usize = 8 * 1024;
+
+/// Outbound proxy for long-lived connections.
+#[derive(Clone, Debug)]
+pub struct Proxy {
+    kind: ProxyKind,
+    addr: String,
+    credentials: Option<(String, String)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ProxyKind {
+    HttpConnect,
+    Socks5,
+}
+
+impl Proxy {
+    /// Parse `http://[user:pass@]host:port` or `socks5://[user:pass@]host:port`.
+    pub fn parse(url: &str) -> Result<Self> {
+        let (scheme, rest) = url.split_once("://").context("Proxy URL needs a scheme")?;
+        let kind = match scheme {
+            "http" => ProxyKind::HttpConnect,
+            "socks5" | "socks5h" => ProxyKind::Socks5,
+            other => bail!("Unsupported proxy scheme {other}"),
+        };
+        let rest = rest.trim_end_matches('/');
+        let (credentials, addr) = match rest.rsplit_once('@') {
+            Some((auth, addr)) => {
+                let (user, pass) = auth.split_once(':').unwrap_or((auth, ""));
+                (Some((user.to_string(), pass.to_string())), addr)
+            }
+            None => (None, rest),
+        };
+        if addr.is_empty() {
+            bail!("Proxy URL is missing a host");
+        }
+        Ok(Self { kind, addr: addr.to_string(), credentials })
+    }
+
+    /// Open a TCP tunnel to `host:port` through the proxy.
+    pub async fn tunnel(&self, host: &str, port: u16, local_address: Option<IpAddr>) -> io::Result<TcpStream> {
+        let mut stream = connect_tcp(&self.addr, local_address).await?;
+        match self.kind {
+            ProxyKind::HttpConnect => self.http_connect(&mut stream, host, port).await?,
+            ProxyKind::Socks5 => self.socks5_connect(&mut stream, host, port).await?,
+        }
+        Ok(stream)
+    }
+
+    async fn http_connect(&self, stream: &mut TcpStream, host: &str, port: u16) -> io::Result<()> {
+        let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+        if let Some((user, pass)) = &self.credentials {
+            let token = base64::engine::general_purpose::STANDARD.encode(format!("{user}:{pass}"));
+            request.push_str(&format!("Proxy-Authorization: Basic {token}\r\n"));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // Byte at a time so nothing after the header is consumed
+        let mut response = Vec::new();
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAX_CONNECT_RESPONSE {
+                return Err(proxy_error("CONNECT response too long"));
+            }
+            response.push(stream.read_u8().await?);
+        }
+        let status = String::from_utf8_lossy(&response);
+        let status_line = status.lines().next().unwrap_or_default();
+        match status_line.split_whitespace().nth(1) {
+            Some("200") => Ok(()),
+            _ => Err(proxy_error(&format!("CONNECT refused: {status_line}"))),
+        }
+    }
+
+    async fn socks5_connect(&self, stream: &mut TcpStream, host: &str, port: u16) -> io::Result<()> {
+        let methods: &[u8] = if self.credentials.is_some() { &[0x00, 0x02] } else { &[0x00] };
+        stream.write_all(&[0x05, methods.len() as u8]).await?;
+        stream.write_all(methods).await?;
+        let mut choice = [0u8; 2];
+        stream.read_exact(&mut choice).await?;
+        match (choice[1], &self.credentials) {
+            (0x00, _) => {}
+            (0x02, Some((user, pass))) => {
+                let mut auth = vec![0x01, user.len() as u8];
+                auth.extend_from_slice(user.as_bytes());
+                auth.push(pass.len() as u8);
+                auth.extend_from_slice(pass.as_bytes());
+                stream.write_all(&auth).await?;
+                let mut status = [0u8; 2];
+                stream.read_exact(&mut status).await?;
+                if status[1] != 0x00 {
+                    return Err(proxy_error("SOCKS5 authentication failed"));
+                }
+            }
+            _ => return Err(proxy_error("SOCKS5 proxy offered no acceptable auth method")),
+        }
+
+        // Domain-name address type leaves resolution to the proxy
+        let mut request = vec![0x05, 0x01, 0x00, 0x03, host.len() as u8];
+        request.extend_from_slice(host.as_bytes());
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[1] != 0x00 {
+            return Err(proxy_error(&format!("SOCKS5 connect failed with code {}", reply[1])));
+        }
+        let bound_len = match reply[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => stream.read_u8().await? as usize,
+            other => return Err(proxy_error(&format!("SOCKS5 reply has unknown address type {other}"))),
+        };
+        let mut bound = vec![0u8; bound_len + 2];
+        stream.read_exact(&mut bound).await?;
+        Ok(())
+    }
+}
+
+/// Connect to `addr`, optionally from a specific local address (which also picks IPv4 or IPv6).
+pub async fn connect_tcp(addr: &str, local_address: Option<IpAddr>) -> io::Result<TcpStream> {
+    let Some(local) = local_address else {
+        return TcpStream::connect(addr).await;
+    };
+    let remote = tokio::net::lookup_host(addr)
+        .await?
+        .find(|a| a.is_ipv6() == local.is_ipv6())
+        .ok_or_else(|| proxy_error(&format!("{addr} has no address of the local address family")))?;
+    let socket = if local.is_ipv6() { TcpSocket::new_v6()? } else { TcpSocket::new_v4()? };
+    socket.bind(SocketAddr::new(local, 0))?;
+    socket.connect(remote).await
+}
+
+fn proxy_error(message: &str) -> io::Error {
+    io::Error::other(message.to_string())
+}
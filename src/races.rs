@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use anyhow::Result;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+
+use crate::opportunity::{OpportunityRecord, SkipReason, OPPORTUNITIES};
+use crate::store::Store;
+use crate::tracker::{SubmissionRecord, SUBMISSIONS};
+
+/// A competitor landing within this many slots of our first sighting beat us to detection.
+const LATE_DETECTION_SLOTS: u64 = 1;
+
+/// Why a liquidation window ended the way it did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RaceCause {
+    Won,
+    /// A competitor landed before we had anything on the wire.
+    DetectedTooLate,
+    /// Our submission was outbid: dropped or landed after the competitor's.
+    TipTooLow,
+    SimulationGate,
+    RpcError,
+    /// Held back by the in-flight bundle cap.
+    InFlightCap,
+    /// Seen but never sent, e.g. in dry-run or below the profit floor.
+    NotSubmitted,
+    /// No closing transaction found; the position likely recovered on its own.
+    Unattributed,
+}
+
+impl RaceCause {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RaceCause::Won => "won",
+            RaceCause::DetectedTooLate => "detected too late",
+            RaceCause::TipTooLow => "tip too low",
+            RaceCause::SimulationGate => "simulation gate",
+            RaceCause::RpcError => "rpc error",
+            RaceCause::InFlightCap => "in-flight cap",
+            RaceCause::NotSubmitted => "not submitted",
+            RaceCause::Unattributed => "unattributed",
+        }
+    }
+}
+
+/// Attribute one closed window, given our tracked submissions for its obligation.
+pub fn attribute(window: &OpportunityRecord, submissions: &[&SubmissionRecord]) -> RaceCause {
+    if window.ours {
+        return RaceCause::Won;
+    }
+    let Some(closing_slot) = window.closing_slot.filter(|_| window.closed_by.is_some()) else {
+        return RaceCause::Unattributed;
+    };
+    let first = submissions
+        .iter()
+        .filter(|s| (window.opened_at_ms..=window.closed_at_ms).contains(&s.submitted_at_ms))
+        .filter_map(|s| s.submitted_slot)
+        .min();
+    match (window.submitted, first) {
+        (true, Some(first)) if closing_slot <= first => RaceCause::DetectedTooLate,
+        // Anything we sent in time either dropped or landed behind the competitor
+        (true, _) => RaceCause::TipTooLow,
+        (false, _) => match window.skipped {
+            Some(SkipReason::SimulationGate) => RaceCause::SimulationGate,
+            Some(SkipReason::RpcError) => RaceCause::RpcError,
+            Some(SkipReason::InFlightCap) => RaceCause::InFlightCap,
+            None if closing_slot <= window.opened_slot + LATE_DETECTION_SLOTS => RaceCause::DetectedTooLate,
+            None => RaceCause::NotSubmitted,
+        },
+    }
+}
+
+/// Windows and their expected profit per cause.
+#[derive(Debug, Default)]
+pub struct RaceReport {
+    pub rows: BTreeMap<RaceCause, (usize, u64)>,
+}
+
+impl RaceReport {
+    /// Attribute every window closed at or after `since_ms`.
+    pub fn build(store: &Store, since_ms: u64) -> Result<Self> {
+        let windows: Vec<OpportunityRecord> = store.read_all(OPPORTUNITIES)?;
+        let submissions: Vec<SubmissionRecord> = store.read_all(SUBMISSIONS)?;
+        let mut by_obligation: BTreeMap<&str, Vec<&SubmissionRecord>> = BTreeMap::new();
+        for s in &submissions {
+            by_obligation.entry(s.obligation.as_str()).or_default().push(s);
+        }
+
+        let mut report = Self::default();
+        for window in windows.iter().filter(|w| w.closed_at_ms >= since_ms) {
+            let ours = by_obligation.get(window.obligation.as_str()).map_or(&[][..], Vec::as_slice);
+            let row = report.rows.entry(attribute(window, ours)).or_default();
+            row.0 += 1;
+            row.1 += window.expected_profit_lamports.unwrap_or(0);
+        }
+        Ok(report)
+    }
+
+    /// Table of causes with their share of windows and expected profit.
+    pub fn render(&self) -> String {
+        let total: usize = self.rows.values().map(|(n, _)| n).sum();
+        let mut out = String::new();
+        let _ = writeln!(out, "{:<20} {:>8} {:>7} {:>16}", "cause", "windows", "share", "expected SOL");
+        for (cause, (count, profit)) in &self.rows {
+            let share = *count as f64 / total.max(1) as f64 * 100.0;
+            let sol = *profit as f64 / LAMPORTS_PER_SOL as f64;
+            let _ = writeln!(out, "{:<20} {count:>8} {share:>6.1}% {sol:>16.6}", cause.as_str());
+        }
+        let _ = writeln!(out, "{:<20} {total:>8}", "total");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracker::TxOutcome;
+
+    /// A window seen at slot 100 and 1s, closed by a competitor at slot 105 and 5s.
+    fn window() -> OpportunityRecord {
+        OpportunityRecord {
+            obligation: "obligation".to_string(),
+            repay_reserve: "repay".to_string(),
+            withdraw_reserve: "withdraw".to_string(),
+            opened_at_ms: 1_000,
+            closed_at_ms: 5_000,
+            duration_ms: 4_000,
+            opened_slot: 100,
+            closed_slot: 110,
+            min_health: 0.95,
+            expected_profit_lamports: Some(1_000_000),
+            submitted: false,
+            closed_by: Some("competitor".to_string()),
+            closing_signature: Some("signature".to_string()),
+            closing_slot: Some(105),
+            ours: false,
+            skipped: None,
+        }
+    }
+
+    fn submission(submitted_at_ms: u64, submitted_slot: Option<u64>) -> SubmissionRecord {
+        SubmissionRecord {
+            obligation: "obligation".to_string(),
+            signature: "ours".to_string(),
+            bundle_id: "bundle".to_string(),
+            submitted_slot,
+            landed_slot: None,
+            slot_latency: None,
+            elapsed_ms: 0,
+            outcome: TxOutcome::Dropped,
+            error: None,
+            submitted_at_ms,
+        }
+    }
+
+    #[test]
+    fn our_close_is_a_win_and_an_unknown_closer_is_unattributed() {
+        assert_eq!(attribute(&OpportunityRecord { ours: true, ..window() }, &[]), RaceCause::Won);
+        assert_eq!(attribute(&OpportunityRecord { closed_by: None, ..window() }, &[]), RaceCause::Unattributed);
+        assert_eq!(attribute(&OpportunityRecord { closing_slot: None, ..window() }, &[]), RaceCause::Unattributed);
+    }
+
+    #[test]
+    fn submission_after_the_competitor_landed_was_too_late() {
+        let w = OpportunityRecord { submitted: true, ..window() };
+        assert_eq!(attribute(&w, &[&submission(2_000, Some(105))]), RaceCause::DetectedTooLate);
+        assert_eq!(attribute(&w, &[&submission(2_000, Some(104))]), RaceCause::TipTooLow);
+        // The earliest submission in the window counts
+        let (early, late) = (submission(2_000, Some(102)), submission(4_000, Some(107)));
+        assert_eq!(attribute(&w, &[&late, &early]), RaceCause::TipTooLow);
+    }
+
+    #[test]
+    fn submissions_outside_the_window_or_without_a_slot_are_ignored() {
+        let w = OpportunityRecord { submitted: true, ..window() };
+        assert_eq!(attribute(&w, &[&submission(500, Some(90))]), RaceCause::TipTooLow);
+        assert_eq!(attribute(&w, &[&submission(6_000, Some(120))]), RaceCause::TipTooLow);
+        assert_eq!(attribute(&w, &[&submission(2_000, None)]), RaceCause::TipTooLow);
+    }
+
+    #[test]
+    fn unsubmitted_windows_report_why_they_were_held_back() {
+        for (skipped, cause) in [
+            (SkipReason::SimulationGate, RaceCause::SimulationGate),
+            (SkipReason::RpcError, RaceCause::RpcError),
+            (SkipReason::InFlightCap, RaceCause::InFlightCap),
+        ] {
+            assert_eq!(attribute(&OpportunityRecord { skipped: Some(skipped), ..window() }, &[]), cause);
+        }
+        assert_eq!(attribute(&window(), &[]), RaceCause::NotSubmitted);
+        let quick = OpportunityRecord { closing_slot: Some(100 + LATE_DETECTION_SLOTS), ..window() };
+        assert_eq!(attribute(&quick, &[]), RaceCause::DetectedTooLate);
+    }
+}
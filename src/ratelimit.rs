@@ -0,0 +1,113 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Priority class of an RPC request. Higher-priority classes may dip into
+/// capacity that lower-priority classes must leave untouched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RequestClass {
+    /// Blockhash fetches needed to sign anything at all.
+    Blockhash,
+    /// Per-candidate account fetches on the hot path.
+    Candidate,
+    /// Bulk scans such as getProgramAccounts.
+    Scan,
+}
+
+impl RequestClass {
+    /// Fraction of bucket capacity that must remain after this class consumes tokens.
+    fn reserve_fraction(self) -> f64 {
+        match self {
+            RequestClass::Blockhash => 0.0,
+            RequestClass::Candidate => 0.25,
+            RequestClass::Scan => 0.5,
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token-bucket limiter for a single RPC endpoint.
+pub struct RateLimiter {
+    rps: f64,
+    capacity: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// Create a limiter refilling `rps` credits per second up to `burst` credits.
+    pub fn new(rps: f64, burst: u32) -> Self {
+        let capacity = f64::from(burst.max(1));
+        Self {
+            rps: rps.max(0.001),
+            capacity,
+            bucket: Mutex::new(Bucket { tokens: capacity, last_refill: Instant::now() }),
+        }
+    }
+
+    /// Wait until `cost` credits are available for the given class and consume them.
+    pub async fn acquire(&self, class: RequestClass, cost: u32) {
+        loop {
+            match self.try_acquire(class, cost) {
+                Ok(()) => return,
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    /// Consume credits if available, otherwise return how long to wait before retrying.
+    pub fn try_acquire(&self, class: RequestClass, cost: u32) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rps).min(self.capacity);
+        bucket.last_refill = now;
+
+        // Requests larger than the bucket still go through once it is full
+        let cost = f64::from(cost);
+        let required = (cost + self.capacity * class.reserve_fraction()).min(self.capacity);
+        if bucket.tokens >= required {
+            bucket.tokens -= cost;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((required - bucket.tokens) / self.rps))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lower_classes_leave_their_reserve() {
+        let limiter = RateLimiter::new(0.001, 4);
+        assert!(limiter.try_acquire(RequestClass::Scan, 2).is_ok());
+        // Scan must leave half the bucket, Candidate a quarter, Blockhash nothing
+        assert!(limiter.try_acquire(RequestClass::Scan, 1).is_err());
+        assert!(limiter.try_acquire(RequestClass::Candidate, 1).is_ok());
+        assert!(limiter.try_acquire(RequestClass::Candidate, 1).is_err());
+        assert!(limiter.try_acquire(RequestClass::Blockhash, 1).is_ok());
+        assert!(limiter.try_acquire(RequestClass::Blockhash, 1).is_err());
+    }
+
+    #[test]
+    fn wait_covers_the_missing_credits() {
+        let limiter = RateLimiter::new(10.0, 1);
+        assert!(limiter.try_acquire(RequestClass::Blockhash, 1).is_ok());
+        let wait = limiter.try_acquire(RequestClass::Blockhash, 1).unwrap_err();
+        assert!(wait > Duration::from_millis(90) && wait <= Duration::from_millis(100), "{wait:?}");
+    }
+
+    #[test]
+    fn oversized_request_goes_through_once_the_bucket_is_full() {
+        let limiter = RateLimiter::new(10.0, 2);
+        assert!(limiter.try_acquire(RequestClass::Scan, 5).is_ok());
+        // The overdraft is paid back before anything else goes through
+        let wait = limiter.try_acquire(RequestClass::Blockhash, 1).unwrap_err();
+        assert!(wait > Duration::from_millis(390), "{wait:?}");
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, warn};
+
+use crate::alert::Alerter;
+use crate::inventory::owned_token_accounts;
+use crate::kamino::LiquidationCandidate;
+use crate::keeper::fetch_reserves;
+use crate::metrics::metrics;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::store::Store;
+use crate::util::{landed_token_deltas, now_millis};
+
+/// Store collection holding one record per balance discrepancy found.
+pub const RECONCILIATIONS: &str = "reconciliations";
+
+/// An expected change that still has no on-chain status after this long never landed.
+const EXPECTATION_TTL: Duration = Duration::from_secs(300);
+
+/// Most signatures one `getSignatureStatuses` call accepts.
+const MAX_STATUS_BATCH: usize = 256;
+
+/// What moved the balances.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Liquidation,
+    Swap,
+    TopUp,
+    Sweep,
+}
+
+/// What a balance is held in. Reserves stand for their liquidity mint until it is resolved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Asset {
+    /// Native lamports, not wrapped SOL.
+    Sol,
+    Mint(Pubkey),
+    Reserve(Pubkey),
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Asset::Sol => write!(f, "SOL"),
+            Asset::Mint(mint) => write!(f, "{mint}"),
+            Asset::Reserve(reserve) => write!(f, "reserve {reserve}"),
+        }
+    }
+}
+
+impl Asset {
+    /// Side of a Jupiter swap, which wraps and unwraps SOL itself.
+    pub fn swapped(mint: Pubkey) -> Self {
+        match mint == spl_token::native_mint::ID {
+            true => Asset::Sol,
+            false => Asset::Mint(mint),
+        }
+    }
+}
+
+struct Expectation {
+    kind: ChangeKind,
+    deltas: Vec<(Asset, i128)>,
+    /// Paid even if the transaction fails on chain.
+    fee_lamports: u64,
+    /// A liquidation's withdraw reserve, whose seized amount is read back from the landed
+    /// transaction instead of estimated.
+    withdraw_reserve: Option<Pubkey>,
+    registered: Instant,
+}
+
+/// Balance changes our own transactions should cause, keyed by signature until
+/// reconciliation sees them land.
+#[derive(Default)]
+pub struct BalanceLedger {
+    pending: Mutex<HashMap<Signature, Expectation>>,
+}
+
+impl BalanceLedger {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Register what `signature` should change once it lands, fees included in `deltas`.
+    pub fn expect(&self, signature: Signature, kind: ChangeKind, deltas: Vec<(Asset, i128)>, fee_lamports: u64) {
+        self.insert(
+            signature,
+            Expectation { kind, deltas, fee_lamports, withdraw_reserve: None, registered: Instant::now() },
+        );
+    }
+
+    /// A liquidation repays `repay_amount`, receives withdraw liquidity and pays the tip and
+    /// fees. Klend pays anywhere from the reserve's minimum to its maximum bonus, so the
+    /// minimum-bonus estimate only stands in until the landed transaction shows the seizure.
+    pub fn expect_liquidation(&self, signature: Signature, cand: &LiquidationCandidate, tip_lamports: u64, fee: u64) {
+        let deltas = vec![
+            (Asset::Reserve(cand.repay_reserve), -i128::from(cand.repay_amount)),
+            (Asset::Reserve(cand.withdraw_reserve), i128::from(cand.expected_withdraw_amount.unwrap_or(0))),
+            (Asset::Sol, -i128::from(tip_lamports + fee)),
+        ];
+        let expectation = Expectation {
+            kind: ChangeKind::Liquidation,
+            deltas,
+            fee_lamports: fee,
+            withdraw_reserve: Some(cand.withdraw_reserve),
+            registered: Instant::now(),
+        };
+        self.insert(signature, expectation);
+    }
+
+    fn insert(&self, signature: Signature, expectation: Expectation) {
+        self.pending.lock().unwrap().insert(signature, expectation);
+    }
+}
+
+/// What a discrepancy most likely is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Finding {
+    /// Within tolerance; recorded but not alerted.
+    Dust,
+    /// An asset our transactions moved changed by a different amount, e.g. a missed or partial
+    /// fill or slippage beyond the quote.
+    FillMismatch,
+    /// An asset changed that none of our transactions touched.
+    UnexpectedTransfer,
+}
+
+impl Finding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Finding::Dust => "dust",
+            Finding::FillMismatch => "fill_mismatch",
+            Finding::UnexpectedTransfer => "unexpected_transfer",
+        }
+    }
+}
+
+/// Persisted discrepancy for one asset over one reconciliation window.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReconciliationRecord {
+    pub checked_at_ms: u64,
+    pub from_slot: u64,
+    pub to_slot: u64,
+    /// `SOL` or the mint address.
+    pub asset: String,
+    pub expected: i128,
+    pub actual: i128,
+    pub residual: i128,
+    pub finding: Finding,
+    /// Our transactions that landed in the window and moved this asset.
+    pub signatures: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReconcileConfig {
+    /// Wallets whose SOL and token balances are summed, e.g. the payer and liquidity owner.
+    pub wallets: Vec<Pubkey>,
+    pub interval: Duration,
+    /// Residual accepted relative to the expected change, in basis points.
+    pub tolerance_bps: u16,
+    /// Residual in lamports always accepted; covers fees of transactions that register no
+    /// expectation, such as cranks.
+    pub sol_dust_lamports: u64,
+    /// Residual in token base units always accepted.
+    pub token_dust: u64,
+}
+
+struct Balances {
+    slot: u64,
+    amounts: BTreeMap<Asset, i128>,
+}
+
+/// Compares how the wallets' balances actually moved between checks with what our landed
+/// transactions should have moved them by, and records and alerts on the difference.
+pub struct Reconciler {
+    cfg: ReconcileConfig,
+    ledger: Arc<BalanceLedger>,
+    store: Arc<Store>,
+    /// Balances at the previous check; the first check only takes them.
+    last: Mutex<Option<Balances>>,
+}
+
+impl Reconciler {
+    pub fn new(cfg: ReconcileConfig, ledger: Arc<BalanceLedger>, store: Arc<Store>) -> Self {
+        Self { cfg, ledger, store, last: Mutex::new(None) }
+    }
+
+    /// Reconcile every interval, off the scan loop; failures are logged and retried next interval.
+    /// Returns only to satisfy the supervisor, which restarts it after a panic.
+    pub async fn run(self: Arc<Self>, rpc: Arc<Rpc>, alerter: Arc<Alerter>) -> Result<()> {
+        let mut interval = tokio::time::interval(self.cfg.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.reconcile(&rpc, &alerter).await {
+                warn!(error = %e, "Balance reconciliation failed");
+                metrics().inc_labeled("reconciliations_total", &[("result", "error")]);
+            }
+        }
+    }
+
+    async fn reconcile(&self, rpc: &Rpc, alerter: &Alerter) -> Result<()> {
+        // Balances first, so anything landing after them waits for the next window
+        let now = self.balances(rpc).await?;
+        let last = self.last.lock().unwrap().replace(Balances { slot: now.slot, amounts: now.amounts.clone() });
+        let Some(prev) = last else {
+            debug!(slot = now.slot, "Took initial balances for reconciliation");
+            return Ok(());
+        };
+
+        let mut landed = self.landed_between(rpc, prev.slot, now.slot).await?;
+        let reserves: Vec<Pubkey> = landed
+            .iter()
+            .flat_map(|(_, e)| &e.deltas)
+            .filter_map(|(asset, _)| match asset {
+                Asset::Reserve(reserve) => Some(*reserve),
+                _ => None,
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let mints: HashMap<Pubkey, Pubkey> = match reserves.is_empty() {
+            true => HashMap::new(),
+            false => fetch_reserves(rpc, &reserves)
+                .await?
+                .into_iter()
+                .map(|(pk, reserve)| (pk, reserve.liquidity.mint_pubkey))
+                .collect(),
+        };
+        for (signature, expectation) in landed.iter_mut() {
+            let Some(reserve) = expectation.withdraw_reserve else { continue };
+            let Some(mint) = mints.get(&reserve) else { continue };
+            match landed_token_deltas(rpc, signature, &self.cfg.wallets).await {
+                Ok(changes) => {
+                    // Other legs in the same mint, e.g. a repay of the same token, are already booked
+                    let other_legs: i128 = expectation
+                        .deltas
+                        .iter()
+                        .filter(|(asset, _)| matches!(asset, Asset::Reserve(r) if *r != reserve && mints.get(r) == Some(mint)))
+                        .map(|(_, delta)| delta)
+                        .sum();
+                    let seized = changes.get(mint).copied().unwrap_or(0) - other_legs;
+                    for (asset, delta) in expectation.deltas.iter_mut() {
+                        if *asset == Asset::Reserve(reserve) {
+                            *delta = seized;
+                        }
+                    }
+                }
+                Err(e) => {
+                    debug!(signature = %signature, error = %e, "Seized amount unavailable, reconciling against the estimate")
+                }
+            }
+        }
+
+        let mut expected: BTreeMap<Asset, i128> = BTreeMap::new();
+        let mut movers: BTreeMap<Asset, Vec<String>> = BTreeMap::new();
+        for (signature, expectation) in &landed {
+            for (asset, delta) in &expectation.deltas {
+                let asset = match asset {
+                    // Liquidations move token accounts, wrapped SOL included
+                    Asset::Reserve(reserve) => {
+                        Asset::Mint(*mints.get(reserve).with_context(|| format!("Reserve {reserve} not found"))?)
+                    }
+                    asset => *asset,
+                };
+                *expected.entry(asset).or_default() += delta;
+                movers.entry(asset).or_default().push(signature.to_string());
+            }
+        }
+
+        let assets: BTreeSet<Asset> =
+            prev.amounts.keys().chain(now.amounts.keys()).chain(expected.keys()).copied().collect();
+        let mut flagged = Vec::new();
+        for asset in assets {
+            let actual = now.amounts.get(&asset).copied().unwrap_or(0) - prev.amounts.get(&asset).copied().unwrap_or(0);
+            let expected_change = expected.get(&asset).copied().unwrap_or(0);
+            let residual = actual - expected_change;
+            if residual == 0 {
+                continue;
+            }
+            let dust = match asset {
+                Asset::Sol => self.cfg.sol_dust_lamports,
+                _ => self.cfg.token_dust,
+            };
+            let tolerance = i128::from(dust).max(expected_change.abs() * i128::from(self.cfg.tolerance_bps) / 10_000);
+            let finding = match (residual.abs() <= tolerance, expected_change != 0) {
+                (true, _) => Finding::Dust,
+                (false, true) => Finding::FillMismatch,
+                (false, false) => Finding::UnexpectedTransfer,
+            };
+            metrics().inc_labeled("balance_discrepancies_total", &[("finding", finding.as_str())]);
+            let record = ReconciliationRecord {
+                checked_at_ms: now_millis(),
+                from_slot: prev.slot,
+                to_slot: now.slot,
+                asset: asset.to_string(),
+                expected: expected_change,
+                actual,
+                residual,
+                finding,
+                signatures: movers.remove(&asset).unwrap_or_default(),
+            };
+            if let Err(e) = self.store.append(RECONCILIATIONS, &record) {
+                warn!(error = %e, "Failed to persist reconciliation record");
+            }
+            if finding != Finding::Dust {
+                warn!(
+                    asset = %record.asset,
+                    expected = record.expected,
+                    actual = record.actual,
+                    residual = record.residual,
+                    finding = finding.as_str(),
+                    "Balance discrepancy"
+                );
+                flagged.push(format!(
+                    "{} {}: expected {}, actual {actual}",
+                    finding.as_str(),
+                    record.asset,
+                    record.expected
+                ));
+            }
+        }
+
+        let (landed, flagged_count) = (landed.len(), flagged.len());
+        info!(from_slot = prev.slot, to_slot = now.slot, landed, flagged = flagged_count, "Reconciled balances");
+        metrics()
+            .inc_labeled("reconciliations_total", &[("result", if flagged.is_empty() { "clean" } else { "flagged" })]);
+        if !flagged.is_empty() {
+            let message = format!(
+                "Balance reconciliation for slots {}..{} found discrepancies:\n{}",
+                prev.slot,
+                now.slot,
+                flagged.join("\n")
+            );
+            alerter.send(&message).await;
+        }
+        Ok(())
+    }
+
+    /// SOL and token balances summed over the wallets, at the newest slot they were read at.
+    async fn balances(&self, rpc: &Rpc) -> Result<Balances> {
+        let mut slot = 0;
+        let mut amounts = BTreeMap::new();
+        for wallet in &self.cfg.wallets {
+            rpc.throttle(RequestClass::Candidate).await;
+            let sol = rpc
+                .get_balance_with_commitment(wallet, CommitmentConfig::confirmed())
+                .with_context(|| format!("Failed to fetch SOL balance of {wallet}"))?;
+            slot = slot.max(sol.context.slot);
+            *amounts.entry(Asset::Sol).or_default() += i128::from(sol.value);
+
+            let (at, accounts) = owned_token_accounts(rpc, wallet).await?;
+            slot = slot.max(at);
+            for account in accounts {
+                *amounts.entry(Asset::Mint(account.mint)).or_default() += i128::from(account.amount);
+            }
+        }
+        Ok(Balances { slot, amounts })
+    }
+
+    /// Take the expectations whose transactions landed in `(from, to]`. Failed transactions
+    /// only count their fee; ones never seen past the TTL are dropped.
+    async fn landed_between(&self, rpc: &Rpc, from: u64, to: u64) -> Result<Vec<(Signature, Expectation)>> {
+        let signatures: Vec<Signature> = self.ledger.pending.lock().unwrap().keys().copied().collect();
+        let mut statuses = Vec::with_capacity(signatures.len());
+        for chunk in signatures.chunks(MAX_STATUS_BATCH) {
+            rpc.throttle(RequestClass::Candidate).await;
+            let batch = rpc.get_signature_statuses_with_history(chunk).context("Failed to fetch signature statuses")?;
+            statuses.extend(batch.value);
+        }
+
+        let mut pending = self.ledger.pending.lock().unwrap();
+        let mut landed = Vec::new();
+        for (signature, status) in signatures.into_iter().zip(statuses) {
+            match status {
+                // Landed after the balances were read
+                Some(status) if status.slot > to => {}
+                Some(status) => {
+                    let Some(mut expectation) = pending.remove(&signature) else { continue };
+                    if status.slot <= from {
+                        debug!(signature = %signature, slot = status.slot, "Expectation landed before the window, skipped");
+                        continue;
+                    }
+                    let failed = status.err.is_some();
+                    if failed {
+                        expectation.deltas = vec![(Asset::Sol, -i128::from(expectation.fee_lamports))];
+                        expectation.withdraw_reserve = None;
+                    }
+                    debug!(signature = %signature, kind = ?expectation.kind, failed, "Expectation landed");
+                    landed.push((signature, expectation));
+                }
+                None => {
+                    if pending.get(&signature).is_some_and(|e| e.registered.elapsed() > EXPECTATION_TTL) {
+                        pending.remove(&signature);
+                    }
+                }
+            }
+        }
+        Ok(landed)
+    }
+}
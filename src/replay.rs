@@ -0,0 +1,293 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{bail, Context, Result};
+use carbon_kamino_lending_decoder::{types, KaminoLendingDecoder, PROGRAM_ID};
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcTransactionConfig};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{UiTransactionEncoding, UiTransactionTokenBalance};
+use tracing::info;
+
+use crate::history::{SnapshotStore, StateSource};
+use crate::kamino::{select_candidates, DecodedAccounts, LiquidationLimits};
+use crate::profit::value_usd;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::strategy::StrategyProfile;
+
+/// Anchor discriminator of LiquidateObligationAndRedeemReserveCollateral.
+pub const LIQUIDATE_DISCRIMINATOR: [u8; 8] = [0xb1, 0x47, 0x9a, 0xbc, 0xe2, 0x85, 0x4a, 0x37];
+
+/// Positions of the accounts read from the liquidation instruction.
+const LIQUIDATOR_INDEX: usize = 0;
+pub const OBLIGATION_INDEX: usize = 1;
+const REPAY_RESERVE_INDEX: usize = 4;
+const WITHDRAW_RESERVE_INDEX: usize = 7;
+const SOURCE_LIQUIDITY_INDEX: usize = 13;
+const DESTINATION_LIQUIDITY_INDEX: usize = 15;
+
+/// What the landed liquidation did, from its instruction and token balance changes.
+#[derive(Debug, Serialize)]
+pub struct ActualLiquidation {
+    pub liquidator: String,
+    pub repay_reserve: String,
+    pub withdraw_reserve: String,
+    /// Requested repay amount from the instruction.
+    pub liquidity_amount: u64,
+    pub min_out: u64,
+    /// Liquidity actually taken from the liquidator.
+    pub repaid: u64,
+    /// Withdraw-reserve liquidity actually received.
+    pub received: u64,
+    pub profit_usd: Option<f64>,
+}
+
+/// What our estimators decide for the same obligation.
+#[derive(Debug, Serialize)]
+pub struct OurDecision {
+    pub health: f64,
+    pub liquidatable: bool,
+    pub repay_reserve: String,
+    pub withdraw_reserve: String,
+    pub repay_amount: u64,
+    pub expected_withdraw_amount: Option<u64>,
+    pub expected_profit_lamports: Option<u64>,
+}
+
+/// Our decision diffed against a historical liquidation.
+#[derive(Debug, Serialize)]
+pub struct ReplayReport {
+    pub signature: String,
+    pub slot: u64,
+    pub obligation: String,
+    /// Slot of the account state the estimators ran against, always before `slot`.
+    pub state_slot: u64,
+    pub state_source: StateSource,
+    pub actual: ActualLiquidation,
+    /// Unset when our selection skips the obligation entirely.
+    pub ours: Option<OurDecision>,
+    pub differences: Vec<String>,
+}
+
+/// Fetch a landed liquidation, run our health and profit estimators on the obligation it hit
+/// as it was before the liquidation, and report where our decision differs.
+///
+/// Standard RPC only serves current state, which already reflects the liquidation. `rpc` is used
+/// for the pre-state only when the node serves state from before the transaction's slot, e.g. a
+/// node started from an older snapshot; otherwise the newest snapshot in `snapshots` before that
+/// slot is used. Without either the replay is refused.
+pub async fn replay_tx(
+    rpc: &Rpc,
+    snapshots: Option<&SnapshotStore>,
+    signature: &Signature,
+    strategy: &StrategyProfile,
+) -> Result<ReplayReport> {
+    rpc.throttle(RequestClass::Candidate).await;
+    let fetched = rpc
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .context("Failed to fetch transaction")?;
+    let tx = fetched.transaction.transaction.decode().context("Failed to decode transaction")?;
+    let meta = fetched.transaction.meta.context("Transaction has no status metadata")?;
+
+    // Static keys, then lookup-table writable and readonly keys, as the runtime orders them
+    let mut keys: Vec<Pubkey> = tx.message.static_account_keys().to_vec();
+    if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+        for key in loaded.writable.iter().chain(&loaded.readonly) {
+            keys.push(key.parse().context("Invalid loaded address")?);
+        }
+    }
+
+    let ix = tx
+        .message
+        .instructions()
+        .iter()
+        .find(|ix| {
+            keys.get(ix.program_id_index as usize) == Some(&PROGRAM_ID) && ix.data.starts_with(&LIQUIDATE_DISCRIMINATOR)
+        })
+        .context("Transaction has no Kamino liquidation instruction")?;
+    let account = |i: usize| -> Result<(usize, Pubkey)> {
+        let index = *ix.accounts.get(i).context("Liquidation instruction is missing accounts")? as usize;
+        Ok((index, *keys.get(index).context("Account index out of range")?))
+    };
+    let arg = |i: usize| -> Result<u64> {
+        let start = 8 + i * 8;
+        let bytes = ix.data.get(start..start + 8).context("Liquidation instruction data too short")?;
+        Ok(u64::from_le_bytes(bytes.try_into()?))
+    };
+
+    let (_, liquidator) = account(LIQUIDATOR_INDEX)?;
+    let (_, obligation) = account(OBLIGATION_INDEX)?;
+    let (_, repay_reserve) = account(REPAY_RESERVE_INDEX)?;
+    let (_, withdraw_reserve) = account(WITHDRAW_RESERVE_INDEX)?;
+    let (source_index, _) = account(SOURCE_LIQUIDITY_INDEX)?;
+    let (destination_index, _) = account(DESTINATION_LIQUIDITY_INDEX)?;
+
+    let pre: Option<Vec<UiTransactionTokenBalance>> = meta.pre_token_balances.into();
+    let post: Option<Vec<UiTransactionTokenBalance>> = meta.post_token_balances.into();
+    let balance = |balances: &Option<Vec<UiTransactionTokenBalance>>, index: usize| -> u64 {
+        balances
+            .iter()
+            .flatten()
+            .find(|b| b.account_index as usize == index)
+            .and_then(|b| b.ui_token_amount.amount.parse().ok())
+            .unwrap_or(0)
+    };
+    let repaid = balance(&pre, source_index).saturating_sub(balance(&post, source_index));
+    let received = balance(&post, destination_index).saturating_sub(balance(&pre, destination_index));
+
+    // Re-run our selection on the obligation and every reserve it touches, as they were before
+    let (state_slot, state_source, obl, reserves) =
+        pre_state(rpc, snapshots, fetched.slot, &obligation, &[repay_reserve, withdraw_reserve]).await?;
+    let profit_usd = match (reserves.get(&repay_reserve), reserves.get(&withdraw_reserve)) {
+        (Some(repay), Some(withdraw)) => Some(value_usd(withdraw, received) - value_usd(repay, repaid)),
+        _ => None,
+    };
+
+    let market = obl.lending_market;
+    // Market limits are only read as they are now; they change rarely
+    let limits = LiquidationLimits::fetch(rpc, &market).await.ok();
+    let decoded = DecodedAccounts { reserves, obligations: vec![(obligation, obl)], limits };
+    // Selection takes a client but must read only the pre-state given to it
+    let offline = RpcClient::new_mock("fails".to_string());
+    let ours = select_candidates(&decoded, market, &offline, f64::INFINITY, strategy).into_iter().next().map(|c| {
+        OurDecision {
+            health: c.health,
+            liquidatable: c.is_liquidatable(),
+            repay_reserve: c.repay_reserve.to_string(),
+            withdraw_reserve: c.withdraw_reserve.to_string(),
+            repay_amount: c.repay_amount,
+            expected_withdraw_amount: c.expected_withdraw_amount,
+            expected_profit_lamports: c.expected_profit_lamports,
+        }
+    });
+
+    let actual = ActualLiquidation {
+        liquidator: liquidator.to_string(),
+        repay_reserve: repay_reserve.to_string(),
+        withdraw_reserve: withdraw_reserve.to_string(),
+        liquidity_amount: arg(0)?,
+        min_out: arg(1)?,
+        repaid,
+        received,
+        profit_usd,
+    };
+    let differences = differences(&actual, ours.as_ref(), fetched.slot, state_slot);
+    Ok(ReplayReport {
+        signature: signature.to_string(),
+        slot: fetched.slot,
+        obligation: obligation.to_string(),
+        state_slot,
+        state_source,
+        actual,
+        ours,
+        differences,
+    })
+}
+
+/// The obligation and the reserves it and `extra` touch, from before `slot`.
+async fn pre_state(
+    rpc: &Rpc,
+    snapshots: Option<&SnapshotStore>,
+    slot: u64,
+    obligation: &Pubkey,
+    extra: &[Pubkey],
+) -> Result<(u64, StateSource, types::Obligation, HashMap<Pubkey, types::Reserve>)> {
+    let decoder = KaminoLendingDecoder::default();
+    let reserve_keys = |obl: &types::Obligation| -> Vec<Pubkey> {
+        obl.deposits
+            .iter()
+            .map(|d| d.reserve)
+            .chain(obl.borrows.iter().map(|b| b.reserve))
+            .chain(extra.iter().copied())
+            .filter(|pk| *pk != Pubkey::default())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    };
+
+    // A node still behind the liquidation serves exact pre-state
+    let config = RpcAccountInfoConfig { commitment: Some(CommitmentConfig::confirmed()), ..Default::default() };
+    rpc.throttle(RequestClass::Candidate).await;
+    let response =
+        rpc.get_multiple_accounts_with_config(&[*obligation], config.clone()).context("Failed to fetch obligation")?;
+    if response.context.slot < slot {
+        let data = response.value.into_iter().next().flatten().context("Obligation not found")?.data;
+        let obl = decoder.decode_obligation(&data).context("Failed to decode obligation")?;
+        let keys = reserve_keys(&obl);
+        rpc.throttle(RequestClass::Candidate).await;
+        let response = rpc.get_multiple_accounts_with_config(&keys, config).context("Failed to fetch reserves")?;
+        if response.context.slot < slot {
+            let reserves = keys
+                .iter()
+                .zip(response.value)
+                .filter_map(|(pk, acc)| Some((*pk, decoder.decode_reserve(&acc?.data).ok()?)))
+                .collect();
+            return Ok((response.context.slot, StateSource::ArchiveRpc, obl, reserves));
+        }
+    }
+    info!(node_slot = response.context.slot, slot, "RPC state is not from before the liquidation, using snapshots");
+
+    let Some(store) = snapshots else {
+        bail!("No state from before slot {slot}: the RPC is past it and no snapshot dir is set");
+    };
+    let Some((at, accs)) = store.load_at(slot.saturating_sub(1))? else {
+        bail!("No snapshot before slot {slot}");
+    };
+    let (_, account) =
+        accs.iter().find(|(pk, _)| pk == obligation).with_context(|| format!("Snapshot {at} lacks {obligation}"))?;
+    let data = &account.data;
+    let obl = decoder.decode_obligation(data).context("Failed to decode obligation")?;
+    let keys = reserve_keys(&obl);
+    let reserves = accs
+        .iter()
+        .filter(|(pk, _)| keys.contains(pk))
+        .filter_map(|(pk, acc)| Some((*pk, decoder.decode_reserve(&acc.data).ok()?)))
+        .collect();
+    Ok((at, StateSource::SnapshotStore, obl, reserves))
+}
+
+fn differences(actual: &ActualLiquidation, ours: Option<&OurDecision>, slot: u64, state_slot: u64) -> Vec<String> {
+    let mut out = Vec::new();
+    if state_slot + 1 < slot {
+        out.push(format!("estimates use state {} slots before the liquidation", slot - state_slot));
+    }
+    let Some(ours) = ours else {
+        out.push("we would not consider this obligation (no eligible borrow/collateral pair)".to_string());
+        return out;
+    };
+    if !ours.liquidatable {
+        out.push(format!("we estimate health {:.4}, not liquidatable", ours.health));
+    }
+    let pairs = [
+        ("repay reserve", &ours.repay_reserve, &actual.repay_reserve),
+        ("withdraw reserve", &ours.withdraw_reserve, &actual.withdraw_reserve),
+    ];
+    for (label, mine, theirs) in pairs {
+        if mine != theirs {
+            out.push(format!("{label}: we pick {mine}, they used {theirs}"));
+        }
+    }
+    if ours.repay_amount != actual.repaid {
+        out.push(format!("repay amount: we size {}, they repaid {}", ours.repay_amount, actual.repaid));
+    }
+    if let Some(expected) = ours.expected_withdraw_amount.filter(|_| actual.repaid > 0) {
+        // Compare per unit repaid so a different repay size does not show up as estimator error
+        let scaled = expected as f64 * actual.repaid as f64 / ours.repay_amount.max(1) as f64;
+        if actual.received > 0 {
+            let error_bps = (scaled - actual.received as f64) / actual.received as f64 * 10_000.0;
+            out.push(format!("withdraw estimate error {error_bps:+.0} bps per unit repaid"));
+        }
+    }
+    out
+}
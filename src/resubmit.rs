@@ -0,0 +1,155 @@
+use std::time::Instant;
+
+use anyhow::Result;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+use tracing::{debug, info, warn};
+
+use crate::jito::{JitoSender, NextLeader};
+use crate::metrics::metrics;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::util::BLOCK_TIME;
+
+/// A submitted bundle waiting to land in its target leader's slot.
+struct PendingBundle {
+    obligation: Pubkey,
+    txs: Vec<VersionedTransaction>,
+    tracked: Signature,
+    /// Slot of the Jito leader the last submission was aimed at.
+    target_slot: u64,
+    /// Resubmissions so far, not counting the original.
+    attempts: u32,
+}
+
+/// Resends bundles that miss their targeted Jito leader to the following ones, so a dropped
+/// bundle gets another chance before the next scan rediscovers the candidate.
+///
+/// Bundles are resent byte for byte, keeping their signatures, so the signature tracker and
+/// bundle book still follow the original submission. Once the blockhash expires a bundle is
+/// dropped here and left to the next scan to rebuild.
+pub struct BundleResubmitter {
+    max_attempts: u32,
+    pending: Vec<PendingBundle>,
+    /// Last answer from the block engine and when it came.
+    leader: Option<(NextLeader, Instant)>,
+}
+
+impl BundleResubmitter {
+    pub fn new(max_attempts: u32) -> Self {
+        Self { max_attempts, pending: Vec::new(), leader: None }
+    }
+
+    /// The next Jito leader, asking the block engine only once that leader's slot has passed.
+    /// In between, the current slot is advanced from the cached answer by the nominal block time.
+    async fn next_leader(&mut self, jito: &mut JitoSender) -> Result<NextLeader> {
+        if let Some((leader, at)) = self.leader {
+            let current_slot = leader.current_slot + (at.elapsed().as_millis() / BLOCK_TIME.as_millis()) as u64;
+            if current_slot < leader.leader_slot {
+                return Ok(NextLeader { current_slot, ..leader });
+            }
+        }
+        let leader = jito.next_leader().await?;
+        self.leader = Some((leader, Instant::now()));
+        Ok(leader)
+    }
+
+    /// Watch a bundle just accepted by the block engine, aimed at the next Jito leader.
+    pub async fn track(
+        &mut self,
+        jito: &mut JitoSender,
+        obligation: Pubkey,
+        txs: &[VersionedTransaction],
+        tracked: Signature,
+    ) {
+        if self.max_attempts == 0 {
+            return;
+        }
+        match self.next_leader(jito).await {
+            Ok(leader) => self.pending.push(PendingBundle {
+                obligation,
+                txs: txs.to_vec(),
+                tracked,
+                target_slot: leader.leader_slot,
+                attempts: 0,
+            }),
+            Err(e) => {
+                debug!(obligation = %obligation, error = %e, "No leader schedule, bundle will not be resubmitted")
+            }
+        }
+    }
+
+    /// Resend every pending bundle whose target slot passed without it landing. Returns how many
+    /// were resent.
+    pub async fn tick(&mut self, rpc: &Rpc, jito: &mut JitoSender) -> usize {
+        if self.pending.is_empty() {
+            return 0;
+        }
+        // Any status, success or not, means the bundle executed and there is nothing to retry
+        rpc.throttle(RequestClass::Candidate).await;
+        let sigs: Vec<Signature> = self.pending.iter().map(|p| p.tracked).collect();
+        match rpc.get_signature_statuses(&sigs) {
+            Ok(statuses) => {
+                let mut statuses = statuses.value.into_iter();
+                self.pending.retain(|_| statuses.next().flatten().is_none());
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to check pending bundles");
+                return 0;
+            }
+        }
+        let leader = match self.next_leader(jito).await {
+            Ok(leader) => leader,
+            Err(e) => {
+                warn!(error = %e, "Failed to fetch next Jito leader");
+                return 0;
+            }
+        };
+
+        let mut resent = 0;
+        let mut kept = Vec::with_capacity(self.pending.len());
+        for mut bundle in std::mem::take(&mut self.pending) {
+            if leader.current_slot <= bundle.target_slot {
+                kept.push(bundle);
+                continue;
+            }
+            if bundle.attempts >= self.max_attempts {
+                info!(obligation = %bundle.obligation, attempts = bundle.attempts, "Bundle did not land, resubmissions exhausted");
+                metrics().inc_labeled("bundle_resubmissions_total", &[("result", "exhausted")]);
+                continue;
+            }
+            rpc.throttle(RequestClass::Blockhash).await;
+            let blockhash = bundle.txs[0].message.recent_blockhash();
+            if !rpc.is_blockhash_valid(blockhash, CommitmentConfig::processed()).unwrap_or(false) {
+                info!(obligation = %bundle.obligation, attempts = bundle.attempts, "Bundle blockhash expired, leaving it to the next scan");
+                metrics().inc_labeled("bundle_resubmissions_total", &[("result", "expired")]);
+                continue;
+            }
+
+            bundle.attempts += 1;
+            bundle.target_slot = leader.leader_slot;
+            match jito.send(&bundle.txs).await {
+                Ok(uuid) => {
+                    info!(
+                        obligation = %bundle.obligation,
+                        attempt = bundle.attempts,
+                        target_slot = bundle.target_slot,
+                        jito_uuid = %uuid,
+                        "Resubmitted bundle to the next Jito leader"
+                    );
+                    metrics().inc_labeled("bundle_resubmissions_total", &[("result", "sent")]);
+                    resent += 1;
+                }
+                Err(e) => {
+                    warn!(obligation = %bundle.obligation, attempt = bundle.attempts, error = %e, "Bundle resubmission failed");
+                    metrics().inc_labeled("bundle_resubmissions_total", &[("result", "failed")]);
+                }
+            }
+            kept.push(bundle);
+        }
+        self.pending = kept;
+        resent
+    }
+}
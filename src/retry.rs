@@ -0,0 +1,133 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use rand::Rng;
+use tracing::warn;
+
+/// Exponential backoff parameters for retrying a fallible operation.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_attempts: 4, base_delay: Duration::from_millis(100), max_delay: Duration::from_secs(2) }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the given retry (1-based), with up to 25% random jitter.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exp = self.base_delay.saturating_mul(1u32 << attempt.saturating_sub(1).min(16));
+        let capped = exp.min(self.max_delay);
+        let jitter = rand::thread_rng().gen_range(0.0..0.25);
+        capped.mul_f64(1.0 + jitter)
+    }
+}
+
+/// Run `op` until it succeeds or the policy's attempts are exhausted.
+pub async fn retry<T, F, Fut>(label: &str, policy: &RetryPolicy, mut op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Ok(v) => return Ok(v),
+            Err(e) if attempt >= policy.max_attempts => return Err(e),
+            Err(e) => {
+                let delay = policy.delay(attempt);
+                warn!(op = label, attempt, delay_ms = delay.as_millis() as u64, error = %e, "Retrying after error");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Circuit breaker that opens after a run of consecutive failures.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Open after `threshold` consecutive failures and stay open for `cooldown`.
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self { threshold: threshold.max(1), cooldown, consecutive_failures: 0, open_until: None }
+    }
+
+    /// Remaining time the breaker stays open, if it is open.
+    pub fn open_for(&self) -> Option<Duration> {
+        self.open_until.and_then(|until| until.checked_duration_since(Instant::now())).filter(|d| !d.is_zero())
+    }
+
+    /// Reset after a successful operation.
+    pub fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.open_until = None;
+    }
+
+    /// Record a failure; returns true if this failure tripped the breaker open.
+    pub fn record_failure(&mut self) -> bool {
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= self.threshold {
+            self.consecutive_failures = 0;
+            self.open_until = Some(Instant::now() + self.cooldown);
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaker_trips_after_the_threshold_and_resets_on_success() {
+        let mut breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        assert!(!breaker.record_failure());
+        assert!(!breaker.record_failure());
+        assert!(breaker.open_for().is_none());
+        assert!(breaker.record_failure());
+        assert!(breaker.open_for().is_some_and(|d| d <= Duration::from_secs(60)));
+
+        breaker.record_success();
+        assert!(breaker.open_for().is_none());
+        // The count restarts, so two more failures do not trip it again
+        assert!(!breaker.record_failure());
+        assert!(!breaker.record_failure());
+    }
+
+    #[test]
+    fn breaker_closes_once_the_cooldown_passes() {
+        let mut breaker = CircuitBreaker::new(1, Duration::ZERO);
+        assert!(breaker.record_failure());
+        assert!(breaker.open_for().is_none());
+    }
+
+    #[test]
+    fn zero_threshold_trips_on_the_first_failure() {
+        let mut breaker = CircuitBreaker::new(0, Duration::from_secs(1));
+        assert!(breaker.record_failure());
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_cap_with_bounded_jitter() {
+        let policy =
+            RetryPolicy { max_attempts: 10, base_delay: Duration::from_millis(100), max_delay: Duration::from_secs(1) };
+        for (attempt, base) in [(1, 100), (2, 200), (3, 400), (5, 1_000), (40, 1_000)] {
+            let delay = policy.delay(attempt);
+            let base = Duration::from_millis(base);
+            assert!(delay >= base && delay <= base.mul_f64(1.25), "attempt {attempt}: {delay:?}");
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+use solana_rpc_client::http_sender::HttpSender;
+use tracing::warn;
+
+use crate::metrics::metrics;
+use crate::ratelimit::{RateLimiter, RequestClass};
+
+/// Timeout of a single RPC request on endpoints built with custom headers.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a fetched block height is reused, about one slot.
+const BLOCK_HEIGHT_TTL: Duration = Duration::from_millis(400);
+
+/// Request budget for a single RPC endpoint.
+#[derive(Clone, Copy, Debug)]
+pub struct RpcLimits {
+    /// Sustained credits per second.
+    pub rps: f64,
+    /// Maximum credits that can be spent in a burst.
+    pub burst: u32,
+    /// Credits charged for a getProgramAccounts call.
+    pub gpa_cost: u32,
+}
+
+/// One RPC endpoint with the headers its provider requires.
+#[derive(Clone, Debug, Default)]
+pub struct RpcEndpoint {
+    pub url: String,
+    /// Extra HTTP headers sent with every request, e.g. an auth token.
+    pub headers: HashMap<String, String>,
+    /// Lower is preferred; failover walks endpoints in this order.
+    pub priority: u32,
+}
+
+struct Endpoint {
+    url: String,
+    client: RpcClient,
+}
+
+/// RPC client paired with the rate limiter guarding its endpoint. With several endpoints it
+/// serves the preferred one and fails over down the priority list.
+pub struct Rpc {
+    endpoints: Vec<Endpoint>,
+    active: AtomicUsize,
+    limiter: RateLimiter,
+    limits: RpcLimits,
+    /// Last block height fetched and when.
+    block_height: Mutex<Option<(u64, Instant)>>,
+}
+
+impl Rpc {
+    /// Create a rate-limited client for the given endpoint.
+    pub fn new(url: String, limits: RpcLimits) -> Self {
+        Self::from_endpoints(vec![Endpoint { client: RpcClient::new(url.clone()), url }], limits)
+    }
+
+    /// Create a client over several endpoints, sending each one's custom headers.
+    pub fn with_endpoints(mut endpoints: Vec<RpcEndpoint>, limits: RpcLimits) -> Result<Self> {
+        anyhow::ensure!(!endpoints.is_empty(), "At least one RPC endpoint is required");
+        endpoints.sort_by_key(|e| e.priority);
+        let built = endpoints
+            .into_iter()
+            .map(|e| Ok(Endpoint { client: client_with_headers(&e)?, url: e.url }))
+            .collect::<Result<_>>()?;
+        Ok(Self::from_endpoints(built, limits))
+    }
+
+    fn from_endpoints(endpoints: Vec<Endpoint>, limits: RpcLimits) -> Self {
+        Self {
+            endpoints,
+            active: AtomicUsize::new(0),
+            limiter: RateLimiter::new(limits.rps, limits.burst),
+            limits,
+            block_height: Mutex::new(None),
+        }
+    }
+
+    /// URL of the endpoint currently serving requests.
+    pub fn url(&self) -> &str {
+        &self.endpoints[self.active.load(Ordering::Relaxed)].url
+    }
+
+    /// Switch to the next endpoint in priority order, wrapping back to the preferred one.
+    /// Returns false when there is nothing to fail over to.
+    pub fn fail_over(&self) -> bool {
+        if self.endpoints.len() < 2 {
+            return false;
+        }
+        let from = self.active.load(Ordering::Relaxed);
+        let to = (from + 1) % self.endpoints.len();
+        self.active.store(to, Ordering::Relaxed);
+        warn!(from = %self.endpoints[from].url, to = %self.endpoints[to].url, "Failing over RPC endpoint");
+        metrics().inc("rpc_failovers_total");
+        true
+    }
+
+    /// Wait for budget for a single-credit request of the given class.
+    pub async fn throttle(&self, class: RequestClass) {
+        self.limiter.acquire(class, 1).await;
+    }
+
+    /// Current block height, fetched at most once per slot however many callers ask.
+    pub async fn block_height(&self) -> Result<u64> {
+        if let Some((height, at)) = *self.block_height.lock().unwrap() {
+            if at.elapsed() < BLOCK_HEIGHT_TTL {
+                return Ok(height);
+            }
+        }
+        self.throttle(RequestClass::Blockhash).await;
+        let height = self.get_block_height().context("Failed to fetch block height")?;
+        *self.block_height.lock().unwrap() = Some((height, Instant::now()));
+        Ok(height)
+    }
+
+    /// Wait for budget for a getProgramAccounts call.
+    pub async fn throttle_gpa(&self) {
+        self.limiter.acquire(RequestClass::Scan, self.limits.gpa_cost).await;
+    }
+}
+
+impl Deref for Rpc {
+    type Target = RpcClient;
+
+    fn deref(&self) -> &RpcClient {
+        &self.endpoints[self.active.load(Ordering::Relaxed)].client
+    }
+}
+
+fn client_with_headers(endpoint: &RpcEndpoint) -> Result<RpcClient> {
+    if endpoint.headers.is_empty() {
+        return Ok(RpcClient::new(endpoint.url.clone()));
+    }
+    let mut headers = HeaderMap::new();
+    for (name, value) in &endpoint.headers {
+        let name =
+            HeaderName::from_bytes(name.as_bytes()).with_context(|| format!("Invalid RPC header name {name}"))?;
+        let mut value = HeaderValue::from_str(value).with_context(|| format!("Invalid value for RPC header {name}"))?;
+        value.set_sensitive(true);
+        headers.insert(name, value);
+    }
+    let http = reqwest::Client::builder()
+        .default_headers(headers)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("Failed to build RPC HTTP client")?;
+    let sender = HttpSender::new_with_client(endpoint.url.clone(), http);
+    Ok(RpcClient::new_sender(sender, RpcClientConfig::default()))
+}
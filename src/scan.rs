@@ -0,0 +1,535 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use carbon_kamino_lending_decoder::PROGRAM_ID;
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use tracing::{debug, info, warn};
+
+use crate::discovery::ObligationDiscovery;
+use crate::history::{record_if_due, SnapshotStore};
+use crate::kamino::fetch_program_accounts;
+use crate::metrics::metrics;
+use crate::partial::{
+    ObligationView, OBLIGATION_LENDING_MARKET_OFFSET, OBLIGATION_OWNER_OFFSET, OBLIGATION_SIZE, OBLIGATION_TAG_OFFSET,
+    RESERVE_LENDING_MARKET_OFFSET, RESERVE_SIZE,
+};
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+
+/// How program accounts are fetched from RPC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScanStrategy {
+    /// A single unfiltered getProgramAccounts call.
+    Full,
+    /// Many small filtered calls partitioned by account type and owner prefix.
+    Chunked,
+    /// Try a full scan and fall back to chunked on failure, retrying a full scan every
+    /// `FULL_SCAN_RETRY`.
+    Auto,
+}
+
+impl FromStr for ScanStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "full" => Ok(Self::Full),
+            "chunked" => Ok(Self::Chunked),
+            "auto" => Ok(Self::Auto),
+            other => Err(anyhow!("Unknown scan strategy: {other}")),
+        }
+    }
+}
+
+/// Obligation product type, stored as the obligation's tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObligationType {
+    Vanilla,
+    Multiply,
+    Lending,
+    Leverage,
+}
+
+impl ObligationType {
+    pub fn tag(self) -> u64 {
+        match self {
+            ObligationType::Vanilla => 0,
+            ObligationType::Multiply => 1,
+            ObligationType::Lending => 2,
+            ObligationType::Leverage => 3,
+        }
+    }
+}
+
+impl FromStr for ObligationType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "vanilla" => Ok(Self::Vanilla),
+            "multiply" => Ok(Self::Multiply),
+            "lending" => Ok(Self::Lending),
+            "leverage" => Ok(Self::Leverage),
+            other => Err(anyhow!("Unknown obligation type: {other}")),
+        }
+    }
+}
+
+/// How long auto mode scans in chunks after a failed full scan, and a chunked scan fetches
+/// obligations by owner prefix after a failed market-wide fetch, before trying the larger
+/// request again.
+const FULL_SCAN_RETRY: Duration = Duration::from_secs(600);
+
+/// One filtered getProgramAccounts request of a chunked scan.
+#[derive(Clone, Copy, Debug)]
+enum ScanChunk {
+    Reserves,
+    /// Every obligation of the market in one request.
+    AllObligations,
+    /// Obligations whose owner pubkey starts with this byte, once `AllObligations` is too large
+    /// for the node.
+    Obligations(u8),
+}
+
+impl ScanChunk {
+    /// `tag` narrows obligation chunks to a single product type server-side.
+    fn filters(self, market: &Pubkey, tag: Option<u64>) -> Vec<RpcFilterType> {
+        match self {
+            ScanChunk::Reserves => vec![
+                RpcFilterType::DataSize(RESERVE_SIZE as u64),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(RESERVE_LENDING_MARKET_OFFSET, market.as_ref())),
+            ],
+            ScanChunk::AllObligations | ScanChunk::Obligations(_) => {
+                let mut filters = vec![
+                    RpcFilterType::DataSize(OBLIGATION_SIZE as u64),
+                    RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                        OBLIGATION_LENDING_MARKET_OFFSET,
+                        market.as_ref(),
+                    )),
+                ];
+                if let ScanChunk::Obligations(prefix) = self {
+                    filters.push(RpcFilterType::Memcmp(Memcmp::new_base58_encoded(OBLIGATION_OWNER_OFFSET, &[prefix])));
+                }
+                if let Some(tag) = tag {
+                    filters.push(RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                        OBLIGATION_TAG_OFFSET,
+                        &tag.to_le_bytes(),
+                    )));
+                }
+                filters
+            }
+        }
+    }
+}
+
+/// Progress of an in-flight chunked scan, kept across failures so a retry resumes.
+#[derive(Default)]
+struct ChunkProgress {
+    pending: VecDeque<ScanChunk>,
+    fetched: Vec<(Pubkey, Account)>,
+    /// Oldest context slot among the fetched chunks, which the pass as a whole is stamped with.
+    slot: Option<u64>,
+}
+
+/// Most accounts one getMultipleAccounts call returns.
+const MULTIPLE_ACCOUNTS_PAGE: usize = 100;
+
+/// How long an obligation may sit without borrows before the cache drops it, by default.
+pub const DEFAULT_IDLE_TTL: Duration = Duration::from_secs(600);
+
+/// Timeout for downloading a peer's scan cache, which can run to hundreds of megabytes.
+const PEER_CACHE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The incremental scan cache as exported to, and imported from, a peer instance.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheSnapshot {
+    pub market: Pubkey,
+    /// Newest market transaction the accounts reflect; the importer catches up from there.
+    pub newest_signature: Option<Signature>,
+    /// Age of the exporter's last full scan, so the importer keeps its rescan schedule.
+    pub scanned_ms_ago: u64,
+    /// Tags of the obligation types the exporter scans; empty for all.
+    pub obligation_tags: Vec<u64>,
+    /// Shared with the exporter's cache, so exporting copies no account data under its lock.
+    pub accounts: Vec<(Pubkey, Arc<Account>)>,
+}
+
+/// Accounts from the last full scan, kept current from market transaction history.
+struct AccountCache {
+    accounts: HashMap<Pubkey, Arc<Account>>,
+    /// When each cached obligation was first seen without borrows.
+    idle_since: HashMap<Pubkey, Instant>,
+    discovery: ObligationDiscovery,
+    scanned_at: Instant,
+    /// Context slot of the last pass; zero for a cache imported from a peer until it catches up.
+    slot: u64,
+}
+
+impl AccountCache {
+    fn new(accounts: Vec<(Pubkey, Arc<Account>)>, discovery: ObligationDiscovery, slot: u64) -> Self {
+        let mut cache =
+            Self { accounts: HashMap::new(), idle_since: HashMap::new(), discovery, scanned_at: Instant::now(), slot };
+        for (pk, acc) in accounts {
+            cache.insert(pk, acc);
+        }
+        cache
+    }
+
+    fn insert(&mut self, pk: Pubkey, acc: Arc<Account>) {
+        match ObligationView::new(&acc.data).map(|view| view.borrow_count()) {
+            Some(0) => {
+                self.idle_since.entry(pk).or_insert_with(Instant::now);
+            }
+            _ => {
+                self.idle_since.remove(&pk);
+            }
+        }
+        self.accounts.insert(pk, acc);
+    }
+
+    fn remove(&mut self, pk: &Pubkey) {
+        self.idle_since.remove(pk);
+        if self.accounts.remove(pk).is_some() {
+            metrics().inc_labeled("scan_cache_evictions_total", &[("reason", "closed")]);
+        }
+    }
+
+    /// Drop obligations that have had no borrows for longer than `ttl`. A later borrow touches
+    /// the obligation in a transaction and the next full scan picks it up again.
+    fn evict_idle(&mut self, ttl: Duration) {
+        let expired: Vec<Pubkey> =
+            self.idle_since.iter().filter(|(_, since)| since.elapsed() >= ttl).map(|(pk, _)| *pk).collect();
+        for pk in &expired {
+            self.idle_since.remove(pk);
+            self.accounts.remove(pk);
+        }
+        metrics().add("scan_cache_evictions_total", &[("reason", "idle")], expired.len() as u64);
+        metrics().set_gauge("scan_cache_accounts", &[], self.accounts.len() as f64);
+        metrics().set_gauge("scan_cache_idle_obligations", &[], self.idle_since.len() as f64);
+    }
+}
+
+/// Fetches Kamino program accounts using the configured strategy.
+pub struct ProgramScanner {
+    strategy: ScanStrategy,
+    market: Pubkey,
+    /// Obligation types kept by the scan; empty keeps all.
+    obligation_types: Vec<ObligationType>,
+    /// When auto mode last fell back from a full scan to chunks.
+    fell_back: Mutex<Option<Instant>>,
+    /// When a market-wide obligation fetch last failed and chunks split by owner prefix.
+    split: Mutex<Option<Instant>>,
+    progress: Mutex<ChunkProgress>,
+    /// Full scans are only repeated this often when set; passes in between catch up from history.
+    rescan_interval: Option<Duration>,
+    /// How long the cache keeps an obligation without borrows.
+    idle_ttl: Duration,
+    cache: Mutex<Option<AccountCache>>,
+    /// Records the market's state at intervals for `--at-slot` post-mortems.
+    snapshots: Option<Arc<SnapshotStore>>,
+}
+
+impl ProgramScanner {
+    pub fn new(strategy: ScanStrategy, market: Pubkey) -> Self {
+        Self {
+            strategy,
+            market,
+            obligation_types: Vec::new(),
+            fell_back: Mutex::new(None),
+            split: Mutex::new(None),
+            progress: Mutex::new(ChunkProgress::default()),
+            rescan_interval: None,
+            idle_ttl: DEFAULT_IDLE_TTL,
+            cache: Mutex::new(None),
+            snapshots: None,
+        }
+    }
+
+    /// Only return obligations of the given product types.
+    pub fn with_obligation_types(mut self, types: Vec<ObligationType>) -> Self {
+        self.obligation_types = types;
+        self
+    }
+
+    /// Save the market's accounts to `store` whenever a snapshot is due.
+    pub fn with_snapshots(mut self, store: Arc<SnapshotStore>) -> Self {
+        self.snapshots = Some(store);
+        self
+    }
+
+    /// Only run a full scan every `interval`. Passes in between refresh reserves, re-fetch
+    /// obligations touched by recent market transactions and add newly created ones.
+    pub fn with_rescan_interval(mut self, interval: Duration) -> Self {
+        self.rescan_interval = Some(interval).filter(|i| !i.is_zero());
+        self
+    }
+
+    /// Evict repaid obligations from the incremental cache once they have had no borrows for `ttl`.
+    pub fn with_idle_ttl(mut self, ttl: Duration) -> Self {
+        self.idle_ttl = ttl;
+        self
+    }
+
+    /// The incremental cache for a peer instance, sharing its accounts; serialize it after this
+    /// returns, outside the lock. None before the first full scan, in full-scan mode, and while
+    /// a pass holds the cache.
+    pub fn export(&self) -> Option<CacheSnapshot> {
+        let cache = self.cache.lock().unwrap();
+        let cache = cache.as_ref()?;
+        Some(CacheSnapshot {
+            market: self.market,
+            newest_signature: cache.discovery.newest(),
+            scanned_ms_ago: cache.scanned_at.elapsed().as_millis() as u64,
+            obligation_tags: self.obligation_types.iter().map(|t| t.tag()).collect(),
+            accounts: cache.accounts.iter().map(|(pk, acc)| (*pk, Arc::clone(acc))).collect(),
+        })
+    }
+
+    /// Seed the incremental cache from a peer's export, so the first pass catches up from the
+    /// peer's history cursor instead of running a full scan. Fails when the peer scans fewer
+    /// obligation types than this instance; obligations of types not scanned here are dropped.
+    /// Returns the number of accounts kept.
+    pub fn import(&self, mut snapshot: CacheSnapshot) -> Result<usize> {
+        ensure!(self.rescan_interval.is_some(), "Warm start needs incremental scanning (--rescan-secs)");
+        ensure!(snapshot.market == self.market, "Peer cache is for market {}", snapshot.market);
+        let peer_tags = &snapshot.obligation_tags;
+        let covered = peer_tags.is_empty()
+            || (!self.obligation_types.is_empty()
+                && self.obligation_types.iter().all(|t| peer_tags.contains(&t.tag())));
+        ensure!(covered, "Peer cache only holds obligation types with tags {peer_tags:?}");
+        if !self.obligation_types.is_empty() {
+            snapshot.accounts.retain(|(_, acc)| {
+                ObligationView::new(&acc.data)
+                    .is_none_or(|view| self.obligation_types.iter().any(|t| t.tag() == view.tag()))
+            });
+        }
+        let count = snapshot.accounts.len();
+        let discovery = ObligationDiscovery::resume(self.market, snapshot.newest_signature);
+        let mut cache = AccountCache::new(snapshot.accounts, discovery, 0);
+        let age = Duration::from_millis(snapshot.scanned_ms_ago);
+        cache.scanned_at = Instant::now().checked_sub(age).unwrap_or(cache.scanned_at);
+        *self.cache.lock().unwrap() = Some(cache);
+        metrics().inc_labeled("scan_passes_total", &[("kind", "warm_start")]);
+        Ok(count)
+    }
+
+    /// Fetch program accounts. The chunked strategy only returns the scanner's market with its reserves and obligations.
+    pub async fn fetch(&self, rpc: &Rpc) -> Result<Vec<(Pubkey, Account)>> {
+        let (slot, mut accs) = match self.rescan_interval {
+            Some(interval) => self.fetch_incremental(rpc, interval).await?,
+            None => self.fetch_unfiltered(rpc).await?,
+        };
+        if let Some(store) = self.snapshots.as_ref() {
+            record_if_due(store, slot, &self.market, &accs);
+        }
+        if !self.obligation_types.is_empty() {
+            accs.retain(|(_, acc)| {
+                ObligationView::new(&acc.data)
+                    .is_none_or(|view| self.obligation_types.iter().any(|t| t.tag() == view.tag()))
+            });
+        }
+        Ok(accs)
+    }
+
+    /// The accounts with the context slot they were read at.
+    async fn fetch_unfiltered(&self, rpc: &Rpc) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        match self.strategy {
+            ScanStrategy::Full => fetch_program_accounts(rpc, None, None).await,
+            ScanStrategy::Chunked => self.fetch_chunked(rpc).await,
+            ScanStrategy::Auto if recently(&self.fell_back) => self.fetch_chunked(rpc).await,
+            ScanStrategy::Auto => match fetch_program_accounts(rpc, None, None).await {
+                Ok(scanned) => {
+                    if self.fell_back.lock().unwrap().take().is_some() {
+                        info!("Full program scan succeeded again, leaving chunked scanning");
+                        *self.progress.lock().unwrap() = ChunkProgress::default();
+                    }
+                    Ok(scanned)
+                }
+                Err(e) => {
+                    warn!(
+                        error = %e,
+                        retry_secs = FULL_SCAN_RETRY.as_secs(),
+                        "Full program scan failed, switching to chunked scanning"
+                    );
+                    *self.fell_back.lock().unwrap() = Some(Instant::now());
+                    metrics().inc_labeled("scan_fallbacks_total", &[("to", "chunked")]);
+                    self.fetch_chunked(rpc).await
+                }
+            },
+        }
+    }
+
+    async fn fetch_incremental(&self, rpc: &Rpc, interval: Duration) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        let cached = self.cache.lock().unwrap().take().filter(|c| c.scanned_at.elapsed() < interval);
+        let mut cache = match cached {
+            Some(mut cache) => match self.catch_up(rpc, &mut cache).await {
+                Ok(()) => cache,
+                Err(e) => {
+                    warn!(error = %e, "Incremental scan failed, running a full scan");
+                    self.rescan(rpc).await?
+                }
+            },
+            None => self.rescan(rpc).await?,
+        };
+        cache.evict_idle(self.idle_ttl);
+        let accs = cache.accounts.iter().map(|(pk, acc)| (*pk, Account::clone(acc))).collect();
+        let slot = cache.slot;
+        *self.cache.lock().unwrap() = Some(cache);
+        Ok((slot, accs))
+    }
+
+    async fn rescan(&self, rpc: &Rpc) -> Result<AccountCache> {
+        // Mark before scanning so transactions landing mid-scan are replayed, not missed
+        let mut discovery = ObligationDiscovery::new(self.market);
+        discovery.mark(rpc).await?;
+        let (slot, accounts) = self.fetch_unfiltered(rpc).await?;
+        metrics().inc_labeled("scan_passes_total", &[("kind", "full")]);
+        Ok(AccountCache::new(accounts.into_iter().map(|(pk, acc)| (pk, Arc::new(acc))).collect(), discovery, slot))
+    }
+
+    async fn catch_up(&self, rpc: &Rpc, cache: &mut AccountCache) -> Result<()> {
+        let activity = cache.discovery.poll(rpc).await?;
+
+        // Reserves are few and carry the prices, so always take them fresh, with the market's limits.
+        // The pass is stamped with their slot; obligations re-fetched after are no older.
+        let (slot, reserves) = self.fetch_chunk(rpc, ScanChunk::Reserves, None).await?;
+        for (pk, acc) in reserves {
+            cache.insert(pk, Arc::new(acc));
+        }
+        cache.slot = slot;
+        let (pk, market) = self.fetch_market(rpc).await?;
+        cache.insert(pk, Arc::new(market));
+
+        let is_obligation =
+            |pk: &Pubkey| cache.accounts.get(pk).is_some_and(|acc| ObligationView::new(&acc.data).is_some());
+        let stale: BTreeSet<Pubkey> =
+            activity.touched.iter().filter(|pk| is_obligation(pk)).chain(&activity.created).copied().collect();
+        let stale: Vec<Pubkey> = stale.into_iter().collect();
+        for keys in stale.chunks(MULTIPLE_ACCOUNTS_PAGE) {
+            rpc.throttle(RequestClass::Scan).await;
+            let accounts = rpc.get_multiple_accounts(keys).context("Failed to re-fetch obligations")?;
+            for (pk, acc) in keys.iter().zip(accounts) {
+                match acc.filter(|acc| acc.owner == PROGRAM_ID) {
+                    Some(acc) => cache.insert(*pk, Arc::new(acc)),
+                    // Closed since the last pass
+                    None => cache.remove(pk),
+                }
+            }
+        }
+        metrics().inc_labeled("scan_passes_total", &[("kind", "incremental")]);
+        metrics().add("scan_incremental_refetched_total", &[], stale.len() as u64);
+        debug!(created = activity.created.len(), refetched = stale.len(), "Caught up from market history");
+        Ok(())
+    }
+
+    async fn fetch_chunk(
+        &self,
+        rpc: &Rpc,
+        chunk: ScanChunk,
+        tag: Option<u64>,
+    ) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        fetch_program_accounts(rpc, Some(chunk.filters(&self.market, tag)), None)
+            .await
+            .with_context(|| format!("Failed to fetch scan chunk {chunk:?}"))
+    }
+
+    async fn fetch_chunked(&self, rpc: &Rpc) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        {
+            let mut progress = self.progress.lock().unwrap();
+            if progress.pending.is_empty() {
+                progress.pending.push_back(ScanChunk::Reserves);
+                match recently(&self.split) {
+                    true => progress.pending.extend((0..=u8::MAX).map(ScanChunk::Obligations)),
+                    false => progress.pending.push_back(ScanChunk::AllObligations),
+                }
+                progress.fetched.clear();
+                progress.slot = None;
+            } else {
+                info!(remaining = progress.pending.len(), "Resuming chunked program scan");
+            }
+        }
+
+        let single_tag = match self.obligation_types.as_slice() {
+            [only] => Some(only.tag()),
+            _ => None,
+        };
+        loop {
+            let Some(chunk) = self.progress.lock().unwrap().pending.front().copied() else { break };
+            let (slot, accs) = match self.fetch_chunk(rpc, chunk, single_tag).await {
+                Ok(fetched) => fetched,
+                // Too many obligations for one response: split by owner prefix in its place
+                Err(e) if matches!(chunk, ScanChunk::AllObligations) => {
+                    warn!(
+                        error = %e,
+                        retry_secs = FULL_SCAN_RETRY.as_secs(),
+                        "Market-wide obligation fetch failed, splitting by owner"
+                    );
+                    *self.split.lock().unwrap() = Some(Instant::now());
+                    metrics().inc_labeled("scan_fallbacks_total", &[("to", "owner_prefix")]);
+                    let mut progress = self.progress.lock().unwrap();
+                    progress.pending.pop_front();
+                    for prefix in (0..=u8::MAX).rev() {
+                        progress.pending.push_front(ScanChunk::Obligations(prefix));
+                    }
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            // Only mark the chunk done once its accounts are safely recorded
+            let mut progress = self.progress.lock().unwrap();
+            progress.fetched.extend(accs);
+            progress.slot = Some(progress.slot.map_or(slot, |oldest| oldest.min(slot)));
+            progress.pending.pop_front();
+        }
+
+        let (slot, mut accs) = {
+            let mut progress = self.progress.lock().unwrap();
+            (progress.slot.take().unwrap_or_default(), std::mem::take(&mut progress.fetched))
+        };
+        accs.push(self.fetch_market(rpc).await?);
+        Ok((slot, accs))
+    }
+
+    /// The lending market account, which filtered chunks leave out but carries the market's
+    /// liquidation limits.
+    async fn fetch_market(&self, rpc: &Rpc) -> Result<(Pubkey, Account)> {
+        rpc.throttle(RequestClass::Scan).await;
+        let market = rpc.get_account(&self.market).context("Failed to fetch lending market")?;
+        Ok((self.market, market))
+    }
+}
+
+/// Whether `since` was set within the last `FULL_SCAN_RETRY`.
+fn recently(since: &Mutex<Option<Instant>>) -> bool {
+    since.lock().unwrap().is_some_and(|at| at.elapsed() < FULL_SCAN_RETRY)
+}
+
+/// Download a running instance's scan cache from its status API at `url`, with `token` as the
+/// bearer when set.
+pub async fn fetch_peer_cache(url: &str, token: Option<&str>) -> Result<CacheSnapshot> {
+    let url = format!("{}/cache", url.trim_end_matches('/'));
+    let client =
+        reqwest::Client::builder().timeout(PEER_CACHE_TIMEOUT).build().context("Failed to build HTTP client")?;
+    let mut request = client.get(&url);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let bytes = request
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach {url}"))?
+        .error_for_status()
+        .context("Peer refused the cache export")?
+        .bytes()
+        .await
+        .context("Failed to download peer cache")?;
+    bincode::deserialize(&bytes).context("Invalid peer cache")
+}
@@ -0,0 +1,127 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use tracing::info;
+
+use crate::kamino::{decode_accounts, select_candidates};
+use crate::rpc::Rpc;
+use crate::scan::ProgramScanner;
+use crate::strategy::StrategyProfile;
+
+/// Where the benchmark takes its program accounts from.
+pub enum ScanSource<'a> {
+    /// Fetch through the scanner on every iteration.
+    Live(&'a ProgramScanner),
+    /// Load once from a snapshot file and skip the fetch stage.
+    Snapshot(&'a Path),
+}
+
+/// `ScanSource` with a snapshot loaded up front.
+enum Accounts<'a> {
+    Live(&'a ProgramScanner),
+    Loaded(Vec<(Pubkey, Account)>),
+}
+
+#[derive(Default)]
+struct StageTimings {
+    fetch: Vec<Duration>,
+    decode: Vec<Duration>,
+    health: Vec<Duration>,
+}
+
+/// Run the fetch+decode+health pipeline `iterations` times and print per-stage latency.
+pub async fn bench_scan(
+    rpc: &Rpc,
+    market: Pubkey,
+    iterations: usize,
+    source: ScanSource<'_>,
+    record: Option<&Path>,
+) -> Result<()> {
+    let source = match source {
+        ScanSource::Live(scanner) => Accounts::Live(scanner),
+        ScanSource::Snapshot(path) => Accounts::Loaded(load_snapshot(path)?),
+    };
+
+    let mut timings = StageTimings::default();
+    for i in 0..iterations {
+        let started = Instant::now();
+        let live;
+        let accs = match &source {
+            Accounts::Live(scanner) => {
+                live = scanner.fetch(rpc).await?;
+                timings.fetch.push(started.elapsed());
+                &live
+            }
+            Accounts::Loaded(accs) => accs,
+        };
+
+        if i == 0 {
+            if let Some(path) = record {
+                save_snapshot(path, accs)?;
+                info!(path = %path.display(), accounts = accs.len(), "Recorded scan snapshot");
+            }
+        }
+
+        let started = Instant::now();
+        let decoded = decode_accounts(accs, &market);
+        timings.decode.push(started.elapsed());
+
+        let started = Instant::now();
+        let candidates = select_candidates(&decoded, market, rpc, 1.0, &StrategyProfile::default());
+        timings.health.push(started.elapsed());
+
+        info!(
+            iteration = i + 1,
+            accounts = accs.len(),
+            obligations = decoded.obligations.len(),
+            candidates = candidates.len(),
+            "Scan iteration complete"
+        );
+    }
+
+    println!("{:<8} {:>8} {:>10} {:>10} {:>10}", "stage", "samples", "p50_ms", "p95_ms", "max_ms");
+    for (name, samples) in
+        [("fetch", &mut timings.fetch), ("decode", &mut timings.decode), ("health", &mut timings.health)]
+    {
+        if samples.is_empty() {
+            continue;
+        }
+        samples.sort();
+        println!(
+            "{:<8} {:>8} {:>10.2} {:>10.2} {:>10.2}",
+            name,
+            samples.len(),
+            ms(percentile(samples, 0.50)),
+            ms(percentile(samples, 0.95)),
+            ms(*samples.last().unwrap()),
+        );
+    }
+    Ok(())
+}
+
+/// Nearest-rank percentile over sorted samples.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = ((sorted.len() as f64 * p).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn load_snapshot(path: &Path) -> Result<Vec<(Pubkey, Account)>> {
+    let file = File::open(path).with_context(|| format!("Failed to open snapshot {}", path.display()))?;
+    serde_json::from_reader(BufReader::new(file))
+        .with_context(|| format!("Failed to parse snapshot {}", path.display()))
+}
+
+fn save_snapshot(path: &Path, accs: &[(Pubkey, Account)]) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create snapshot {}", path.display()))?;
+    serde_json::to_writer(BufWriter::new(file), accs).context("Failed to write snapshot")?;
+    Ok(())
+}
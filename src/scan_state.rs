@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use solana_sdk::pubkey::Pubkey;
+use tracing::info;
+
+use crate::kamino::LiquidationCandidate;
+
+/// What one scan saw of an obligation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Seen {
+    health: f64,
+    liquidatable: bool,
+}
+
+/// Obligations returned by the previous scan, so each scan can be reported as a change.
+#[derive(Debug, Default)]
+pub struct ScanState {
+    seen: HashMap<Pubkey, Seen>,
+}
+
+/// Changes between two consecutive scans.
+#[derive(Debug, Default)]
+pub struct ScanDiff {
+    /// Newly below the watch health, with their health.
+    pub appeared: Vec<(Pubkey, f64)>,
+    /// Back above the watch health, repaid or liquidated.
+    pub disappeared: Vec<Pubkey>,
+    /// Turned liquidatable, with old and new health.
+    pub became_liquidatable: Vec<(Pubkey, f64, f64)>,
+    /// No longer liquidatable while still watched, with old and new health.
+    pub recovered: Vec<(Pubkey, f64, f64)>,
+    /// Obligations in the latest scan.
+    pub watched: usize,
+    pub liquidatable: usize,
+}
+
+impl ScanState {
+    /// Replace the state with `scanned` and return what changed.
+    pub fn update(&mut self, scanned: &[LiquidationCandidate]) -> ScanDiff {
+        let current: HashMap<Pubkey, Seen> = scanned
+            .iter()
+            .map(|c| (c.obligation, Seen { health: c.health, liquidatable: c.is_liquidatable() }))
+            .collect();
+
+        let mut diff = ScanDiff {
+            watched: current.len(),
+            liquidatable: current.values().filter(|s| s.liquidatable).count(),
+            ..Default::default()
+        };
+        for (pk, now) in &current {
+            match self.seen.get(pk) {
+                None => diff.appeared.push((*pk, now.health)),
+                Some(before) if !before.liquidatable && now.liquidatable => {
+                    diff.became_liquidatable.push((*pk, before.health, now.health))
+                }
+                Some(before) if before.liquidatable && !now.liquidatable => {
+                    diff.recovered.push((*pk, before.health, now.health))
+                }
+                Some(_) => {}
+            }
+        }
+        diff.disappeared = self.seen.keys().filter(|pk| !current.contains_key(pk)).copied().collect();
+        self.seen = current;
+        diff
+    }
+}
+
+impl ScanDiff {
+    pub fn is_empty(&self) -> bool {
+        self.appeared.is_empty()
+            && self.disappeared.is_empty()
+            && self.became_liquidatable.is_empty()
+            && self.recovered.is_empty()
+    }
+
+    /// One line per change; nothing when the scan matches the previous one.
+    pub fn log(&self) {
+        for (pk, health) in &self.appeared {
+            info!(obligation = %pk, health, "Obligation entered the watchlist");
+        }
+        for (pk, from, to) in &self.became_liquidatable {
+            info!(obligation = %pk, from, to, "Obligation became liquidatable");
+        }
+        for (pk, from, to) in &self.recovered {
+            info!(obligation = %pk, from, to, "Obligation no longer liquidatable");
+        }
+        for pk in &self.disappeared {
+            info!(obligation = %pk, "Obligation left the watchlist");
+        }
+        if !self.is_empty() {
+            info!(watched = self.watched, liquidatable = self.liquidatable, "Scan changed");
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{bail, ensure, Context, Error, Result};
+
+use crate::metrics::metrics;
+use crate::util::now_millis;
+
+/// Day names, matched by any prefix of at least three letters.
+const DAYS: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+/// Days and a UTC time range during which the bot may submit, written `Mon-Fri 13:30-20:00`,
+/// `Sat, Sun 00:00-06:00` or `* 22:00-02:00`. A range ending before it starts runs past midnight
+/// into the next day.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Window {
+    /// Indexed from Monday.
+    days: [bool; 7],
+    start_minute: u32,
+    end_minute: u32,
+}
+
+impl Window {
+    /// Whether the window covers `minute` of `weekday` (0 = Monday).
+    fn contains(&self, weekday: usize, minute: u32) -> bool {
+        match self.start_minute < self.end_minute {
+            true => self.days[weekday] && (self.start_minute..self.end_minute).contains(&minute),
+            // Overnight: the evening part of a listed day, or the morning after it
+            false => {
+                (self.days[weekday] && minute >= self.start_minute)
+                    || (self.days[(weekday + 6) % 7] && minute < self.end_minute)
+            }
+        }
+    }
+}
+
+impl FromStr for Window {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // The range is the last word, so day lists may contain spaces
+        let (days, range) =
+            s.trim().rsplit_once(' ').with_context(|| format!("Window {s:?} is not `<days> <HH:MM>-<HH:MM>`"))?;
+        let (start, end) = range.trim().split_once('-').with_context(|| format!("Window {s:?} has no time range"))?;
+        let (start_minute, end_minute) = (parse_time(start)?, parse_time(end)?);
+        ensure!(start_minute < 24 * 60, "Window {s:?} starts at 24:00, use 00:00 of the next day");
+        ensure!(start_minute != end_minute, "Window {s:?} is empty");
+
+        let mut listed = [false; 7];
+        for part in days.split(',') {
+            let part = part.trim();
+            if part == "*" {
+                listed = [true; 7];
+                continue;
+            }
+            let (first, last) = part.split_once('-').unwrap_or((part, part));
+            let (first, last) = (parse_day(first)?, parse_day(last)?);
+            // Ranges may wrap the weekend, e.g. Sat-Mon
+            let mut day = first;
+            loop {
+                listed[day] = true;
+                if day == last {
+                    break;
+                }
+                day = (day + 1) % 7;
+            }
+        }
+        Ok(Self { days: listed, start_minute, end_minute })
+    }
+}
+
+fn parse_day(s: &str) -> Result<usize> {
+    let lower = s.trim().to_ascii_lowercase();
+    DAYS.iter().position(|d| lower.len() >= 3 && d.starts_with(&lower)).with_context(|| format!("Unknown day {s:?}"))
+}
+
+fn parse_time(s: &str) -> Result<u32> {
+    let (h, m) = s.trim().split_once(':').with_context(|| format!("Time {s:?} is not HH:MM"))?;
+    let (h, m): (u32, u32) = (h.parse()?, m.parse()?);
+    // 24:00 closes a window at midnight
+    if h > 24 || m > 59 || (h == 24 && m > 0) {
+        bail!("Time {s:?} is out of range");
+    }
+    Ok(h * 60 + m)
+}
+
+/// Windows during which the bot is active; active at all times when empty.
+#[derive(Clone, Debug, Default)]
+pub struct Schedule {
+    windows: Vec<Window>,
+}
+
+impl Schedule {
+    pub fn parse(windows: &[String]) -> Result<Self> {
+        let windows = windows.iter().map(|w| w.parse()).collect::<Result<_>>()?;
+        Ok(Self { windows })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Whether a window covers the given Unix time.
+    pub fn is_active_at(&self, unix_secs: u64) -> bool {
+        // 1970-01-01 was a Thursday
+        let weekday = ((unix_secs / 86_400 + 3) % 7) as usize;
+        let minute = (unix_secs % 86_400 / 60) as u32;
+        self.windows.is_empty() || self.windows.iter().any(|w| w.contains(weekday, minute))
+    }
+}
+
+/// Whether submissions are paused, by the schedule, an operator's maintenance toggle, or a
+/// stale view of the cluster. Scanning and cache upkeep continue either way.
+#[derive(Debug, Default)]
+pub struct Activity {
+    schedule: Schedule,
+    maintenance: AtomicBool,
+    degraded: AtomicBool,
+}
+
+impl Activity {
+    pub fn new(schedule: Schedule) -> Self {
+        Self { schedule, maintenance: AtomicBool::new(false), degraded: AtomicBool::new(false) }
+    }
+
+    pub fn maintenance(&self) -> bool {
+        self.maintenance.load(Ordering::Relaxed)
+    }
+
+    pub fn set_maintenance(&self, enabled: bool) {
+        self.maintenance.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn degraded(&self) -> bool {
+        self.degraded.load(Ordering::Relaxed)
+    }
+
+    /// Pause submissions while the clock or slot view is off, see `SkewMonitor`.
+    pub fn set_degraded(&self, degraded: bool) {
+        self.degraded.store(degraded, Ordering::Relaxed);
+    }
+
+    /// Why submissions are paused right now, or None when the bot is active.
+    pub fn paused_reason(&self) -> Option<&'static str> {
+        let reason = match (self.maintenance(), self.degraded(), self.schedule.is_active_at(now_millis() / 1_000)) {
+            (true, _, _) => Some("maintenance"),
+            (false, true, _) => Some("cluster skew"),
+            (false, false, false) => Some("outside schedule"),
+            (false, false, true) => None,
+        };
+        metrics().set_gauge("submissions_paused", &[], f64::from(u8::from(reason.is_some())));
+        reason
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(s: &str) -> Window {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn day_lists_and_ranges_parse() {
+        assert_eq!(window("Mon-Fri 13:30-20:00").days, [true, true, true, true, true, false, false]);
+        assert_eq!(window("Sat, Sun 00:00-06:00").days, [false, false, false, false, false, true, true]);
+        assert_eq!(window("Sat-Mon 00:00-06:00").days, [true, false, false, false, false, true, true]);
+        assert_eq!(window("tuesday,Thurs 00:00-06:00").days, [false, true, false, true, false, false, false]);
+        assert_eq!(window("* 00:00-24:00").days, [true; 7]);
+    }
+
+    #[test]
+    fn malformed_windows_are_rejected() {
+        for s in ["Mon 10:00-10:00", "Mon 24:00-02:00", "Mon 10:00-25:00", "Mon 10:60-11:00", "Mo 10:00-11:00"] {
+            assert!(s.parse::<Window>().is_err(), "{s} parsed");
+        }
+        assert!("Monkey 10:00-11:00".parse::<Window>().is_err());
+        assert!("Mon 10:00".parse::<Window>().is_err());
+    }
+
+    #[test]
+    fn daytime_window_is_half_open() {
+        let w = window("Mon-Fri 13:30-20:00");
+        assert!(w.contains(0, 13 * 60 + 30));
+        assert!(w.contains(4, 20 * 60 - 1));
+        assert!(!w.contains(4, 20 * 60));
+        assert!(!w.contains(5, 14 * 60));
+    }
+
+    #[test]
+    fn overnight_window_runs_into_the_next_day() {
+        let w = window("Fri 22:00-02:00");
+        assert!(w.contains(4, 23 * 60));
+        assert!(w.contains(5, 60));
+        assert!(!w.contains(5, 2 * 60));
+        // The morning of a listed day belongs to the night before it
+        assert!(!w.contains(4, 60));
+    }
+
+    #[test]
+    fn overnight_window_wraps_past_the_end_of_the_week() {
+        let w = window("Sun 22:00-02:00");
+        assert!(w.contains(6, 22 * 60));
+        assert!(w.contains(0, 0));
+        assert!(!w.contains(6, 60));
+    }
+
+    #[test]
+    fn schedule_reads_weekday_and_minute_in_utc() {
+        let schedule = Schedule::parse(&["Thu 00:00-00:01".to_string()]).unwrap();
+        // 1970-01-01 00:00 UTC was a Thursday
+        assert!(schedule.is_active_at(59));
+        assert!(!schedule.is_active_at(60));
+        assert!(!schedule.is_active_at(7 * 86_400 - 1));
+        assert!(schedule.is_active_at(7 * 86_400));
+        assert!(Schedule::default().is_active_at(12_345));
+    }
+}
@@ -0,0 +1,165 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use serde_json::{json, Value};
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::transaction::VersionedTransaction;
+
+use crate::jito::JitoSender;
+use crate::kamino::LiquidationCandidate;
+use crate::latency::Route;
+use crate::retry::{retry, RetryPolicy};
+use crate::rpc::Rpc;
+
+/// Default Jito block-engine transaction endpoint.
+pub const DEFAULT_JITO_TX_URL: &str = "https://mainnet.block-engine.jito.wtf/api/v1/transactions";
+
+/// Backend used to submit a signed liquidation transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SenderKind {
+    /// Jito gRPC bundle containing the single transaction.
+    Bundle,
+    /// Jito sendTransaction with `bundleOnly` revert protection.
+    JitoTx,
+    /// Plain RPC sendTransaction (no revert protection).
+    Rpc,
+}
+
+impl SenderKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SenderKind::Bundle => "bundle",
+            SenderKind::JitoTx => "jito-tx",
+            SenderKind::Rpc => "rpc",
+        }
+    }
+}
+
+/// How the sender is chosen for each candidate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SenderPolicy {
+    /// Always use the given backend.
+    Fixed(SenderKind),
+    /// Bundles for contended (high-value) candidates, revert-protected sendTransaction otherwise.
+    Auto,
+}
+
+impl FromStr for SenderPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "bundle" => Ok(Self::Fixed(SenderKind::Bundle)),
+            "jito-tx" => Ok(Self::Fixed(SenderKind::JitoTx)),
+            "rpc" => Ok(Self::Fixed(SenderKind::Rpc)),
+            other => Err(anyhow!("Unknown sender: {other}")),
+        }
+    }
+}
+
+impl SenderPolicy {
+    /// Pick a backend for the candidate. Expected profit is used as the contention proxy:
+    /// valuable positions attract competing searchers, so they go out as full bundles.
+    pub fn choose(self, cand: &LiquidationCandidate, contention_profit_lamports: u64) -> SenderKind {
+        match self {
+            SenderPolicy::Fixed(kind) => kind,
+            SenderPolicy::Auto => match cand.expected_profit_lamports {
+                Some(p) if p >= contention_profit_lamports => SenderKind::Bundle,
+                _ => SenderKind::JitoTx,
+            },
+        }
+    }
+}
+
+/// Client for Jito's JSON-RPC sendTransaction endpoint.
+pub struct JitoTxSender {
+    url: String,
+    http: reqwest::Client,
+}
+
+impl JitoTxSender {
+    pub fn new(url: String) -> Self {
+        Self { url, http: reqwest::Client::new() }
+    }
+
+    /// Send with revert protection; returns the signature reported by the block engine.
+    pub async fn send(&self, tx: &VersionedTransaction) -> Result<String> {
+        let bytes = bincode::serialize(tx).context("Failed to serialize transaction")?;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "sendTransaction",
+            "params": [encoded, { "encoding": "base64" }],
+        });
+
+        let resp: Value = self
+            .http
+            .post(&self.url)
+            .query(&[("bundleOnly", "true")])
+            .json(&body)
+            .send()
+            .await
+            .context("Jito sendTransaction request failed")?
+            .json()
+            .await
+            .context("Invalid Jito sendTransaction response")?;
+
+        if let Some(err) = resp.get("error") {
+            bail!("Jito sendTransaction error: {err}");
+        }
+        resp.get("result")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .context("Jito sendTransaction response missing result")
+    }
+}
+
+/// All submission backends behind one entry point.
+pub struct Senders {
+    pub bundle: JitoSender,
+    pub jito_tx: JitoTxSender,
+}
+
+impl Senders {
+    /// Route a submission through `kind` takes; only bundles go through a probed region.
+    pub fn route(&self, kind: SenderKind) -> Route {
+        let region = match kind {
+            SenderKind::Bundle => self.bundle.region().unwrap_or("custom"),
+            SenderKind::JitoTx | SenderKind::Rpc => "default",
+        };
+        Route { sender: kind, region }
+    }
+
+    /// Submit through the chosen backend; returns the bundle UUID or transaction signature.
+    /// Multi-transaction bundles are only accepted by the bundle backend.
+    pub async fn send(&self, kind: SenderKind, rpc: &Rpc, txs: &[VersionedTransaction]) -> Result<String> {
+        if kind == SenderKind::Bundle {
+            return self.bundle.send(txs).await;
+        }
+        let [tx] = txs else {
+            bail!("{} sender cannot submit {} transactions at once", kind.as_str(), txs.len());
+        };
+        match kind {
+            SenderKind::JitoTx => self.jito_tx.send(tx).await,
+            _ => {
+                let config = RpcSendTransactionConfig { skip_preflight: true, ..Default::default() };
+                let sig = rpc.send_transaction_with_config(tx, config).context("RPC sendTransaction failed")?;
+                Ok(sig.to_string())
+            }
+        }
+    }
+
+    /// Submit with retries on transport errors, per the strategy's retry policy.
+    pub async fn send_with_retry(
+        &self,
+        kind: SenderKind,
+        rpc: &Rpc,
+        txs: &[VersionedTransaction],
+        policy: &RetryPolicy,
+    ) -> Result<String> {
+        retry(&format!("send_{}", kind.as_str()), policy, || self.send(kind, rpc, txs)).await
+    }
+}
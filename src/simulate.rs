@@ -0,0 +1,169 @@
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::VersionedTransaction;
+
+use crate::kamino::LiquidationCandidate;
+use crate::keeper::fetch_reserves;
+use crate::metrics::metrics;
+use crate::pda::LiquidatorAccounts;
+use crate::profit::{estimate_bonus_usd, usd_to_lamports, value_usd};
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::util::{now_millis, LiquidationTxs};
+
+/// Store collection holding one record per simulated liquidation.
+pub const SIMULATIONS: &str = "simulations";
+
+/// Byte range of the amount in an SPL token account.
+const TOKEN_AMOUNT_RANGE: std::ops::Range<usize> = 64..72;
+
+/// Persisted outcome of simulating a liquidation instead of sending it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SimulationRecord {
+    pub obligation: String,
+    pub slot: u64,
+    pub success: bool,
+    pub error: Option<String>,
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+    /// Repay-reserve liquidity spent by the payer.
+    pub repay_spent: u64,
+    /// Withdraw-reserve liquidity received by the payer.
+    pub withdraw_received: u64,
+    /// Payer SOL change including fees and tip (negative when paying).
+    pub sol_delta_lamports: i64,
+    /// Value received minus value spent, before SOL costs.
+    pub profit_usd: Option<f64>,
+    pub expected_profit_lamports: Option<u64>,
+    /// The candidate's expected profit re-valued at simulation-time reserve prices.
+    #[serde(default)]
+    pub expected_profit_usd: Option<f64>,
+    /// SOL price the candidate was valued at, from the market's wrapped-SOL reserve.
+    #[serde(default)]
+    pub sol_price_usd: Option<f64>,
+    pub simulated_at_ms: u64,
+}
+
+impl SimulationRecord {
+    pub fn would_profit(&self) -> bool {
+        self.success && self.profit_usd.is_some_and(|p| p > 0.0)
+    }
+
+    /// Simulated profit less `cost_lamports`, converted at the market's SOL price. Unset when
+    /// the market has no SOL price.
+    pub fn net_profit_lamports(&self, cost_lamports: u64) -> Option<i64> {
+        let profit_usd = self.profit_usd?;
+        let profit = usd_to_lamports(profit_usd.abs(), self.sol_price_usd?) as i64;
+        let profit = match profit_usd < 0.0 {
+            true => -profit,
+            false => profit,
+        };
+        Some(profit - cost_lamports as i64)
+    }
+}
+
+/// Simulate the liquidation transaction and measure the payer's SOL and the liquidator's token
+/// balance changes.
+pub async fn simulate_liquidation(
+    rpc: &Rpc,
+    payer: &Pubkey,
+    liquidator: &LiquidatorAccounts,
+    cand: &LiquidationCandidate,
+    tx: &VersionedTransaction,
+) -> Result<SimulationRecord> {
+    let reserves = fetch_reserves(rpc, &[cand.repay_reserve, cand.withdraw_reserve]).await?;
+    let repay = reserves.get(&cand.repay_reserve).context("Repay reserve missing")?;
+    let withdraw = reserves.get(&cand.withdraw_reserve).context("Withdraw reserve missing")?;
+    let repay_ata = liquidator.token_account(&repay.liquidity.mint_pubkey, &repay.liquidity.token_program);
+    let withdraw_ata = liquidator.token_account(&withdraw.liquidity.mint_pubkey, &withdraw.liquidity.token_program);
+    let watched = [*payer, repay_ata, withdraw_ata];
+
+    rpc.throttle(RequestClass::Candidate).await;
+    let before = rpc.get_multiple_accounts(&watched).context("Failed to fetch pre-simulation balances")?;
+    let pre_lamports = before[0].as_ref().map_or(0, |a| a.lamports);
+    let pre_repay = before[1].as_ref().map_or(0, |a| token_amount(&a.data));
+    let pre_withdraw = before[2].as_ref().map_or(0, |a| token_amount(&a.data));
+
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(CommitmentConfig::processed()),
+        accounts: Some(RpcSimulateTransactionAccountsConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            addresses: watched.iter().map(Pubkey::to_string).collect(),
+        }),
+        ..Default::default()
+    };
+    rpc.throttle(RequestClass::Candidate).await;
+    let result = rpc.simulate_transaction_with_config(tx, config).context("Simulation request failed")?;
+    let slot = result.context.slot;
+    let sim = result.value;
+
+    let after: Vec<Option<solana_sdk::account::Account>> =
+        sim.accounts.unwrap_or_default().into_iter().map(|a| a.and_then(|a| a.decode())).collect();
+    let post = |i: usize| after.get(i).and_then(Option::as_ref);
+    let post_lamports = post(0).map_or(pre_lamports, |a| a.lamports);
+    let post_repay = post(1).map_or(pre_repay, |a| token_amount(&a.data));
+    let post_withdraw = post(2).map_or(pre_withdraw, |a| token_amount(&a.data));
+
+    let success = sim.err.is_none();
+    let repay_spent = pre_repay.saturating_sub(post_repay);
+    let withdraw_received = post_withdraw.saturating_sub(pre_withdraw);
+    let profit_usd = success.then(|| value_usd(withdraw, withdraw_received) - value_usd(repay, repay_spent));
+
+    Ok(SimulationRecord {
+        obligation: cand.obligation.to_string(),
+        slot,
+        success,
+        error: sim.err.map(|e| e.to_string()),
+        units_consumed: sim.units_consumed,
+        logs: sim.logs.unwrap_or_default(),
+        repay_spent,
+        withdraw_received,
+        sol_delta_lamports: post_lamports as i64 - pre_lamports as i64,
+        profit_usd,
+        expected_profit_lamports: cand.expected_profit_lamports,
+        expected_profit_usd: Some(estimate_bonus_usd(repay, withdraw, cand.repay_amount)),
+        sol_price_usd: cand.sol_price_usd,
+        simulated_at_ms: now_millis(),
+    })
+}
+
+/// Simulate the liquidation and fail unless its token balance changes pay for `cost_lamports`
+/// of tip and fees. Returns the simulated net profit.
+pub async fn confirm_profit(
+    rpc: &Rpc,
+    payer: &Pubkey,
+    liquidator: &LiquidatorAccounts,
+    cand: &LiquidationCandidate,
+    built: &LiquidationTxs,
+    cost_lamports: u64,
+) -> Result<i64> {
+    let tx =
+        built.txs.iter().find(|tx| tx.signatures[0] == built.signature).context("Liquidation transaction missing")?;
+    let record = simulate_liquidation(rpc, payer, liquidator, cand, tx).await?;
+    let checked = check_profit(&record, cost_lamports);
+    let result = match checked.is_ok() {
+        true => "passed",
+        false => "rejected",
+    };
+    metrics().inc_labeled("strict_sim_checks_total", &[("result", result)]);
+    checked
+}
+
+fn check_profit(record: &SimulationRecord, cost_lamports: u64) -> Result<i64> {
+    if !record.success {
+        bail!("Simulation failed: {}", record.error.as_deref().unwrap_or("unknown error"));
+    }
+    let net = record.net_profit_lamports(cost_lamports).context("No price estimate to value the simulated profit")?;
+    ensure!(net > 0, "Simulated profit does not cover {cost_lamports} lamports of tip and fees (net {net})");
+    Ok(net)
+}
+
+fn token_amount(data: &[u8]) -> u64 {
+    data.get(TOKEN_AMOUNT_RANGE).and_then(|b| b.try_into().ok()).map(u64::from_le_bytes).unwrap_or(0)
+}
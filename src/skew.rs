@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use tracing::{debug, info};
+
+use crate::alert::Alerter;
+use crate::metrics::metrics;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::schedule::Activity;
+use crate::util::{now_millis, BLOCK_TIME};
+
+/// Longest silence on the slot subscription before the websocket view counts as stale.
+const MAX_WS_SILENCE: Duration = Duration::from_secs(10);
+
+/// Clock skew samples the check takes the median of, so one late block time cannot pause
+/// submissions.
+const SKEW_SAMPLES: usize = 5;
+
+/// Allowance on top of the configured clock skew for block times' whole-second precision and
+/// the spread of validator vote timestamps behind them.
+const SKEW_TOLERANCE: Duration = Duration::from_millis(1_000);
+
+/// Thresholds beyond which the bot's view of the cluster is too stale to act on.
+#[derive(Clone, Debug)]
+pub struct SkewConfig {
+    pub interval: Duration,
+    /// Largest gap between the local clock and the cluster's block time.
+    pub max_clock_skew: Option<Duration>,
+    /// Largest slot gap between the RPC and websocket views, or between the RPC's progress and
+    /// the progress elapsed time implies.
+    pub max_slot_lag: Option<u64>,
+}
+
+/// Compares the local clock, RPC slot progression and websocket slot notifications, pausing
+/// submissions through `Activity` while they disagree. A stale slot means a stale health view,
+/// so acting on it risks liquidating on prices that no longer hold.
+pub struct SkewMonitor {
+    cfg: SkewConfig,
+    rpc: Arc<Rpc>,
+    activity: Arc<Activity>,
+    alerter: Alerter,
+    /// Latest slot notified on the websocket and when it arrived.
+    ws_slot: Mutex<Option<(u64, Instant)>>,
+}
+
+impl SkewMonitor {
+    pub fn new(cfg: SkewConfig, rpc: Arc<Rpc>, activity: Arc<Activity>, alerter: Alerter) -> Arc<Self> {
+        Arc::new(Self { cfg, rpc, activity, alerter, ws_slot: Mutex::new(None) })
+    }
+
+    /// Record slot notifications until the subscription ends.
+    pub async fn watch_slots(self: Arc<Self>, ws_url: String) -> Result<()> {
+        let client = PubsubClient::new(&ws_url).await.context("Failed to connect websocket")?;
+        let (mut stream, unsubscribe) = client.slot_subscribe().await.context("slotSubscribe failed")?;
+        while let Some(info) = stream.next().await {
+            *self.ws_slot.lock().unwrap() = Some((info.slot, Instant::now()));
+        }
+        drop(stream);
+        unsubscribe().await;
+        anyhow::bail!("Slot subscription closed")
+    }
+
+    /// Check every interval, degrading on the first failed check and recovering on the first
+    /// clean one.
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        let mut previous: Option<(u64, Instant)> = None;
+        let mut skews = VecDeque::with_capacity(SKEW_SAMPLES);
+        loop {
+            tokio::time::sleep(self.cfg.interval).await;
+            let problems = match self.check(&mut previous, &mut skews).await {
+                Ok(problems) => problems,
+                Err(e) => {
+                    debug!(error = %e, "Skew check failed");
+                    continue;
+                }
+            };
+            match (problems.is_empty(), self.activity.degraded()) {
+                (false, false) => {
+                    self.activity.set_degraded(true);
+                    self.alerter
+                        .send(&format!("Cluster view is stale, pausing submissions: {}", problems.join("; ")))
+                        .await;
+                }
+                (true, true) => {
+                    self.activity.set_degraded(false);
+                    info!("Cluster view is current again, resuming submissions");
+                    self.alerter.send("Cluster clock and slots back within thresholds, resuming submissions").await;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// `skews` holds the latest clock skew samples in milliseconds.
+    async fn check(&self, previous: &mut Option<(u64, Instant)>, skews: &mut VecDeque<i64>) -> Result<Vec<String>> {
+        let mut problems = Vec::new();
+        self.rpc.throttle(RequestClass::Candidate).await;
+        let slot = self.rpc.get_slot_with_commitment(CommitmentConfig::processed()).context("Failed to fetch slot")?;
+        let now = Instant::now();
+
+        if let Some(max_lag) = self.cfg.max_slot_lag {
+            // The RPC should advance about one slot per block time
+            if let Some((prev_slot, prev_at)) = previous.replace((slot, now)) {
+                let expected = ((now - prev_at).as_millis() / BLOCK_TIME.as_millis()) as u64;
+                let advanced = slot.saturating_sub(prev_slot);
+                let behind = expected.saturating_sub(advanced);
+                metrics().set_gauge("skew_rpc_slots_behind", &[], behind as f64);
+                if behind > max_lag {
+                    problems.push(format!("RPC advanced {advanced} slots where {expected} were expected"));
+                }
+            }
+            let ws = *self.ws_slot.lock().unwrap();
+            match ws {
+                Some((_, at)) if at.elapsed() > MAX_WS_SILENCE => {
+                    problems.push(format!("no websocket slot notification for {}s", at.elapsed().as_secs()));
+                }
+                Some((ws_slot, at)) => {
+                    // Age the notified slot to now before comparing
+                    let ws_now = ws_slot + (at.elapsed().as_millis() / BLOCK_TIME.as_millis()) as u64;
+                    let lag = ws_now.abs_diff(slot);
+                    metrics().set_gauge("skew_ws_rpc_slot_lag", &[], lag as f64);
+                    if lag > max_lag {
+                        problems.push(format!("websocket at slot {ws_now} but RPC at {slot}"));
+                    }
+                }
+                None => {}
+            }
+        }
+
+        if let Some(max_skew) = self.cfg.max_clock_skew {
+            // A confirmed block was produced about a block time per slot it trails the tip by
+            self.rpc.throttle(RequestClass::Candidate).await;
+            let confirmed =
+                self.rpc.get_slot_with_commitment(CommitmentConfig::confirmed()).context("Failed to fetch slot")?;
+            self.rpc.throttle(RequestClass::Candidate).await;
+            let block_time = self.rpc.get_block_time(confirmed).context("Failed to fetch block time")?;
+            let block_age_ms = (slot.saturating_sub(confirmed) as u128 * BLOCK_TIME.as_millis()) as i64;
+            if skews.len() == SKEW_SAMPLES {
+                skews.pop_front();
+            }
+            skews.push_back(now_millis() as i64 - block_time * 1_000 - block_age_ms);
+
+            let mut sorted: Vec<i64> = skews.iter().copied().collect();
+            sorted.sort_unstable();
+            let skew_ms = sorted[sorted.len() / 2];
+            metrics().set_gauge("skew_clock_ms", &[], skew_ms as f64);
+            if skew_ms.unsigned_abs() > (max_skew + SKEW_TOLERANCE).as_millis() as u64 {
+                let samples = skews.len();
+                problems.push(format!("local clock is {skew_ms}ms off the cluster's block time (median of {samples})"));
+            }
+        }
+        Ok(problems)
+    }
+}
@@ -0,0 +1,355 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info};
+
+use crate::blink::{ActionRequest, Blinks};
+use crate::bundles::BundleBook;
+use crate::control::RuntimeControls;
+use crate::metrics::metrics;
+use crate::scan::ProgramScanner;
+
+/// Bundles returned by `GET /bundles`.
+const RECENT_BUNDLES: usize = 100;
+
+/// Largest request we read, head and body.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// How long a client may take to send its whole request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Headers wallets and blink clients require on every Solana Actions response.
+const ACTION_HEADERS: &str = "Access-Control-Allow-Origin: *\r\n\
+    Access-Control-Allow-Methods: GET,POST,PUT,OPTIONS\r\n\
+    Access-Control-Allow-Headers: Content-Type, Authorization, Content-Encoding, Accept-Encoding\r\n\
+    X-Action-Version: 2.1.3\r\n\
+    X-Blockchain-Ids: solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp\r\n";
+
+/// Minimal HTTP API for operators: `GET /metrics`, bearer-authenticated `GET /bundles` and
+/// `GET /bundles/{uuid}`, plus Solana Actions under `/actions/liquidate/{obligation}` when blinks are enabled,
+/// bearer-authenticated runtime controls under `/control` when a control token is set, and the scan
+/// cache as bincode on bearer-authenticated `GET /cache` for warm-starting peers.
+pub struct StatusServer {
+    bundles: Arc<BundleBook>,
+    blinks: Option<Arc<Blinks>>,
+    control: Option<Control>,
+    scanner: Option<Arc<ProgramScanner>>,
+    /// Bearer token for the bundle and cache routes, besides the control token.
+    read_token: Option<String>,
+}
+
+/// Runtime state operators may change, and the token that authorizes it.
+struct Control {
+    token: String,
+    controls: Arc<RuntimeControls>,
+}
+
+/// `POST /control/maintenance` request body.
+#[derive(Debug, Deserialize)]
+struct MaintenanceRequest {
+    enabled: bool,
+}
+
+/// `POST /control` request body, a command in the Telegram form such as `dryrun on`.
+#[derive(Debug, Deserialize)]
+struct CommandRequest {
+    command: String,
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+    /// Carries the CORS and version headers of the Actions spec.
+    action: bool,
+}
+
+impl Response {
+    fn json<T: Serialize>(value: &T) -> Self {
+        match serde_json::to_string(value) {
+            Ok(body) => {
+                Self { status: "200 OK", content_type: "application/json", body: body.into_bytes(), action: false }
+            }
+            Err(e) => Self::error("500 Internal Server Error", &e.to_string()),
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        let body = serde_json::json!({ "error": message }).to_string().into_bytes();
+        Self { status, content_type: "application/json", body, action: false }
+    }
+
+    /// Actions clients read errors from a `message` field.
+    fn action_error(status: &'static str, message: &str) -> Self {
+        let body = serde_json::json!({ "message": message }).to_string().into_bytes();
+        Self { status, content_type: "application/json", body, action: true }
+    }
+
+    fn action(mut self) -> Self {
+        self.action = true;
+        self
+    }
+}
+
+impl StatusServer {
+    pub fn new(bundles: Arc<BundleBook>) -> Self {
+        Self { bundles, blinks: None, control: None, scanner: None, read_token: None }
+    }
+
+    /// Serve Solana Actions for the candidates `blinks` publishes.
+    pub fn with_blinks(mut self, blinks: Arc<Blinks>) -> Self {
+        self.blinks = Some(blinks);
+        self
+    }
+
+    /// Accept `/control` requests carrying `Authorization: Bearer {token}`.
+    pub fn with_control(mut self, token: String, controls: Arc<RuntimeControls>) -> Self {
+        self.control = Some(Control { token, controls });
+        self
+    }
+
+    /// Accept bundle lookups and cache exports carrying `Authorization: Bearer {token}`, besides
+    /// the control token.
+    pub fn with_read_token(mut self, token: String) -> Self {
+        self.read_token = Some(token);
+        self
+    }
+
+    /// Export `scanner`'s incremental cache on `GET /cache`.
+    pub fn with_cache(mut self, scanner: Arc<ProgramScanner>) -> Self {
+        self.scanner = Some(scanner);
+        self
+    }
+
+    /// Bind `addr` and serve requests until the listener fails.
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr) -> Result<()> {
+        let listener = TcpListener::bind(addr).await.with_context(|| format!("Failed to bind status API on {addr}"))?;
+        info!(addr = %addr, "Status API listening");
+        loop {
+            let (stream, peer) = listener.accept().await.context("Status API accept failed")?;
+            let server = Arc::clone(&self);
+            tokio::spawn(async move {
+                if let Err(e) = server.handle(stream).await {
+                    debug!(peer = %peer, error = %e, "Status request failed");
+                }
+            });
+        }
+    }
+
+    async fn handle(&self, mut stream: TcpStream) -> Result<()> {
+        let buf = tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream))
+            .await
+            .context("Timed out reading request")??;
+        let head = String::from_utf8_lossy(&buf);
+        let mut parts = head.lines().next().unwrap_or_default().split_whitespace();
+        let method = parts.next().unwrap_or_default();
+        let path = parts.next().unwrap_or_default();
+        let openmetrics = head
+            .lines()
+            .any(|l| l.to_ascii_lowercase().starts_with("accept:") && l.contains("application/openmetrics-text"));
+        let bearer = head.lines().find_map(|l| {
+            let (name, value) = l.split_once(':')?;
+            let value = value.trim();
+            match name.eq_ignore_ascii_case("authorization") {
+                true => value.strip_prefix("Bearer ").map(str::trim),
+                false => None,
+            }
+        });
+
+        let body = head.split_once("\r\n\r\n").map_or("", |(_, body)| body);
+
+        let resp = match path {
+            p if p.starts_with("/actions") => self.route_action(method, path, body).await,
+            p if p.starts_with("/control") => self.route_control(method, path, body, bearer),
+            _ => self.route(method, path, openmetrics, bearer).await,
+        };
+        let head = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+            resp.status,
+            resp.content_type,
+            resp.body.len(),
+            if resp.action { ACTION_HEADERS } else { "" },
+        );
+        stream.write_all(head.as_bytes()).await.context("Failed to write response")?;
+        stream.write_all(&resp.body).await.context("Failed to write response")?;
+        Ok(())
+    }
+
+    async fn route(&self, method: &str, path: &str, openmetrics: bool, bearer: Option<&str>) -> Response {
+        if method != "GET" {
+            return Response::error("405 Method Not Allowed", "Only GET is supported");
+        }
+        let path = path.split('?').next().unwrap_or_default().trim_end_matches('/');
+        match path.split('/').skip(1).collect::<Vec<_>>().as_slice() {
+            // Exemplars are only part of the OpenMetrics format, so Prometheus must ask for it
+            ["metrics"] if openmetrics => Response {
+                status: "200 OK",
+                content_type: "application/openmetrics-text; version=1.0.0; charset=utf-8",
+                body: metrics().render_openmetrics().into_bytes(),
+                action: false,
+            },
+            ["metrics"] => Response {
+                status: "200 OK",
+                content_type: "text/plain; version=0.0.4",
+                body: metrics().render().into_bytes(),
+                action: false,
+            },
+            // Bundle history reveals our strategy, so it needs a token like the controls do
+            ["bundles", ..] if !self.read_authorized(bearer) => {
+                Response::error("401 Unauthorized", "Missing or invalid status token")
+            }
+            ["bundles"] => Response::json(&self.bundles.recent(RECENT_BUNDLES)),
+            ["bundles", id] => match self.bundles.get(id) {
+                Some(record) => Response::json(&record),
+                None => Response::error("404 Not Found", "Unknown bundle"),
+            },
+            // The cache holds every market account we track, so it is guarded the same way
+            ["cache"] if !self.read_authorized(bearer) => {
+                Response::error("401 Unauthorized", "Missing or invalid status token")
+            }
+            ["cache"] => self.cache().await,
+            _ => Response::error("404 Not Found", "Unknown route"),
+        }
+    }
+
+    /// Whether `bearer` is the read or control token; always false when neither is set.
+    fn read_authorized(&self, bearer: Option<&str>) -> bool {
+        let control = self.control.as_ref().map(|c| c.token.as_str());
+        bearer.is_some_and(|b| self.read_token.as_deref() == Some(b) || control == Some(b))
+    }
+
+    /// The scan cache for a peer's warm start. A pass briefly holds the cache, so peers retry
+    /// on 503. Encoding runs on the blocking pool, since a large cache takes a while.
+    async fn cache(&self) -> Response {
+        let Some(scanner) = self.scanner.as_ref() else {
+            return Response::error("404 Not Found", "Cache export is disabled");
+        };
+        let Some(snapshot) = scanner.export() else {
+            return Response::error("503 Service Unavailable", "Scan cache is not ready");
+        };
+        let accounts = snapshot.accounts.len();
+        match tokio::task::spawn_blocking(move || bincode::serialize(&snapshot)).await {
+            Ok(Ok(body)) => {
+                metrics().inc("cache_exports_total");
+                info!(accounts, bytes = body.len(), "Exported scan cache to a peer");
+                Response { status: "200 OK", content_type: "application/octet-stream", body, action: false }
+            }
+            Ok(Err(e)) => Response::error("500 Internal Server Error", &e.to_string()),
+            Err(e) => Response::error("500 Internal Server Error", &e.to_string()),
+        }
+    }
+
+    /// Operator controls; every request, reads included, must carry the control token.
+    fn route_control(&self, method: &str, path: &str, body: &str, bearer: Option<&str>) -> Response {
+        let Some(control) = self.control.as_ref() else {
+            return Response::error("404 Not Found", "Controls are disabled");
+        };
+        if bearer != Some(control.token.as_str()) {
+            metrics().inc_labeled("control_requests_total", &[("result", "unauthorized")]);
+            return Response::error("401 Unauthorized", "Missing or invalid control token");
+        }
+        let path = path.split('?').next().unwrap_or_default().trim_end_matches('/');
+        match (method, path.split('/').skip(1).collect::<Vec<_>>().as_slice()) {
+            ("GET", ["control"]) => Response::json(&control.controls.status()),
+            ("POST", ["control"]) => {
+                let request: CommandRequest = match serde_json::from_str(body) {
+                    Ok(request) => request,
+                    Err(e) => return Response::error("400 Bad Request", &format!("Invalid request body: {e}")),
+                };
+                match request.command.parse().and_then(|command| control.controls.apply(command, "http")) {
+                    Ok(message) => {
+                        metrics().inc_labeled("control_requests_total", &[("result", "ok")]);
+                        Response::json(&serde_json::json!({ "message": message, "status": control.controls.status() }))
+                    }
+                    Err(e) => Response::error("400 Bad Request", &format!("{e:#}")),
+                }
+            }
+            ("GET", ["control", "maintenance"]) => Response::json(&serde_json::json!({
+                "enabled": control.controls.activity().maintenance(),
+                "paused": control.controls.activity().paused_reason(),
+            })),
+            ("POST", ["control", "maintenance"]) => {
+                let request: MaintenanceRequest = match serde_json::from_str(body) {
+                    Ok(request) => request,
+                    Err(e) => return Response::error("400 Bad Request", &format!("Invalid request body: {e}")),
+                };
+                control.controls.activity().set_maintenance(request.enabled);
+                info!(enabled = request.enabled, "Maintenance mode changed through the control API");
+                metrics().inc_labeled("control_requests_total", &[("result", "ok")]);
+                Response::json(&serde_json::json!({ "enabled": request.enabled }))
+            }
+            _ => Response::error("404 Not Found", "Unknown control"),
+        }
+    }
+
+    /// Solana Actions: the `actions.json` rules, metadata on GET and the transaction on POST.
+    async fn route_action(&self, method: &str, path: &str, body: &str) -> Response {
+        let Some(blinks) = self.blinks.as_ref() else {
+            return Response::error("404 Not Found", "Blinks are disabled");
+        };
+        let path = path.split('?').next().unwrap_or_default().trim_end_matches('/');
+        match (method, path.split('/').skip(1).collect::<Vec<_>>().as_slice()) {
+            ("OPTIONS", _) => Response { status: "200 OK", content_type: "text/plain", body: Vec::new(), action: true },
+            ("GET", ["actions.json"]) => {
+                let rules =
+                    serde_json::json!({ "rules": [{ "pathPattern": "/actions/**", "apiPath": "/actions/**" }] });
+                Response::json(&rules).action()
+            }
+            ("GET", ["actions", "liquidate", obligation]) => match blinks.metadata(obligation) {
+                Some(metadata) => Response::json(&metadata).action(),
+                None => Response::action_error("404 Not Found", "Obligation is not currently liquidatable"),
+            },
+            ("POST", ["actions", "liquidate", obligation]) => {
+                if !blinks.admit() {
+                    return Response::action_error("429 Too Many Requests", "Too many requests, retry shortly");
+                }
+                let request: ActionRequest = match serde_json::from_str(body) {
+                    Ok(request) => request,
+                    Err(e) => return Response::action_error("400 Bad Request", &format!("Invalid request body: {e}")),
+                };
+                match blinks.transaction(obligation, &request).await {
+                    Ok(tx) => Response::json(&tx).action(),
+                    Err(e) => Response::action_error("422 Unprocessable Entity", &format!("{e:#}")),
+                }
+            }
+            _ => Response::action_error("404 Not Found", "Unknown action"),
+        }
+    }
+}
+
+/// Read the request head and as much body as its Content-Length announces, up to
+/// `MAX_REQUEST_BYTES`.
+async fn read_request(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    loop {
+        let n = stream.read(&mut chunk).await.context("Failed to read request")?;
+        if n == 0 {
+            return Ok(buf);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        ensure!(buf.len() <= MAX_REQUEST_BYTES, "Request is over {MAX_REQUEST_BYTES} bytes");
+        let Some(head_len) = buf.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4) else {
+            continue;
+        };
+        let length = String::from_utf8_lossy(&buf[..head_len])
+            .lines()
+            .find_map(|l| {
+                let (name, value) = l.split_once(':')?;
+                match name.eq_ignore_ascii_case("content-length") {
+                    true => value.trim().parse::<usize>().ok(),
+                    false => None,
+                }
+            })
+            .unwrap_or(0);
+        ensure!(head_len + length <= MAX_REQUEST_BYTES, "Request body of {length} bytes is too large");
+        if buf.len() >= head_len + length {
+            return Ok(buf);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Append-only JSON-lines persistence, one file per record kind under a data directory.
+pub struct Store {
+    dir: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+impl Store {
+    /// Open (and create if needed) a store rooted at `dir`.
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create data dir {}", dir.display()))?;
+        Ok(Self { dir: dir.to_path_buf(), write_lock: Mutex::new(()) })
+    }
+
+    fn path(&self, kind: &str) -> PathBuf {
+        self.dir.join(format!("{kind}.jsonl"))
+    }
+
+    /// Append one record to the `kind` collection.
+    pub fn append<T: Serialize>(&self, kind: &str, record: &T) -> Result<()> {
+        let mut line = serde_json::to_string(record).context("Failed to serialize record")?;
+        line.push('\n');
+
+        let _guard = self.write_lock.lock().unwrap();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(kind))
+            .with_context(|| format!("Failed to open {kind} store"))?;
+        file.write_all(line.as_bytes()).with_context(|| format!("Failed to append to {kind} store"))?;
+        Ok(())
+    }
+
+    /// Read every record of the `kind` collection, skipping lines that fail to parse.
+    pub fn read_all<T: DeserializeOwned>(&self, kind: &str) -> Result<Vec<T>> {
+        let path = self.path(kind);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let file = File::open(&path).with_context(|| format!("Failed to open {kind} store"))?;
+        let mut out = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.with_context(|| format!("Failed to read {kind} store"))?;
+            if let Ok(record) = serde_json::from_str(&line) {
+                out.push(record);
+            }
+        }
+        Ok(out)
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::retry::RetryPolicy;
+
+/// Mints treated as stablecoins unless a reserve override says otherwise: USDC, USDT, PYUSD, USDS.
+pub const STABLECOIN_MINTS: [&str; 4] = [
+    "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+    "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB",
+    "2b1kV6DkPAnxd5ixfnxCpjxmKwqjjaYmCZfHsFu24GXo",
+    "USDSwr9ApdHk5bvJKMjzff41FfuX8bSxdKcR81vTwcA",
+];
+
+/// Names of the built-in profiles.
+pub const PRESETS: [&str; 2] = ["conservative", "aggressive"];
+
+/// How the debt to repay is chosen when an obligation has several borrows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RepaySelection {
+    /// Numerically largest borrow.
+    #[default]
+    Largest,
+    /// Stablecoin debt first (predictable cost), seizing volatile collateral first (bonus upside).
+    PreferStable,
+}
+
+/// Rule ranking an obligation's borrows ahead of value when choosing which to repay.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RepayPreference {
+    /// Debt in a mint the liquidator already holds enough of to repay, with no swap first.
+    Held,
+    /// Stablecoin debt.
+    Stable,
+}
+
+/// What happens to the compute unit price while the fee market is quiet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuietCuPrice {
+    /// Leave out the set_compute_unit_price instruction.
+    Omit,
+    /// Keep the instruction with a price of zero.
+    Zero,
+}
+
+/// Tunable liquidation behaviour, selected per market.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrategyProfile {
+    /// Fraction of the chosen borrow repaid per liquidation.
+    pub repay_fraction: f64,
+    /// Accepted shortfall of received collateral versus the estimate, in basis points.
+    pub min_out_tolerance_bps: u16,
+    /// Share of expected profit paid as the Jito tip.
+    pub tip_profit_share: f64,
+    /// Share of expected seized collateral value paid as the tip; replaces the profit share when set.
+    pub tip_seized_share: Option<f64>,
+    /// Smallest tip in lamports (defaults to the CLI tip).
+    pub tip_floor_lamports: Option<u64>,
+    /// Largest tip in lamports.
+    pub tip_ceiling_lamports: Option<u64>,
+    /// Submission attempts per candidate.
+    pub retry_attempts: u32,
+    /// Initial delay between submission attempts.
+    pub retry_base_delay_ms: u64,
+    /// Maximum delay between submission attempts.
+    pub retry_max_delay_ms: u64,
+    /// Largest price impact accepted when selling seized collateral, in basis points.
+    pub max_price_impact_bps: u32,
+    /// Which borrow to repay and collateral to seize on multi-asset obligations.
+    pub repay_selection: RepaySelection,
+    /// Rules applied in order ahead of `repay_selection`, e.g. `["held", "stable"]`. When set,
+    /// the borrow matching the earliest rules wins, ties going to the larger USD value.
+    pub repay_preference: Vec<RepayPreference>,
+    /// Stop paying priority fees while the fee market is quiet, relying on the Jito tip alone.
+    pub quiet_cu_price: Option<QuietCuPrice>,
+    /// Median recent priority fee on our reserves, in micro-lamports per compute unit, at or below
+    /// which the fee market counts as quiet.
+    pub quiet_fee_threshold: u64,
+    /// Per-reserve overrides, filled in from the config file's `[reserves]` section.
+    #[serde(skip)]
+    pub reserves: HashMap<Pubkey, ReserveOverride>,
+    /// Liquidator token balances by mint, filled in at runtime for the `held` preference.
+    #[serde(skip)]
+    pub held: HashMap<Pubkey, u64>,
+}
+
+/// Settings for one reserve, overriding the profile for long-tail assets.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReserveOverride {
+    /// Min-out tolerance when this reserve is the collateral withdrawn, in basis points.
+    pub min_out_tolerance_bps: Option<u16>,
+    /// Largest repay per liquidation when this reserve is the debt, in liquidity base units.
+    pub max_repay_amount: Option<u64>,
+    /// Never repay or withdraw this reserve.
+    pub blacklisted: bool,
+    /// Preferred swap route label for unwinding this asset, passed to Jupiter as its DEX filter.
+    pub swap_route: Option<String>,
+    /// Price impact limit when selling this asset, in basis points.
+    pub max_price_impact_bps: Option<u32>,
+    /// Treat the asset as a stablecoin (or not), overriding the built-in mint list.
+    pub stable: Option<bool>,
+}
+
+impl Default for StrategyProfile {
+    fn default() -> Self {
+        Self::conservative()
+    }
+}
+
+impl StrategyProfile {
+    /// Small repay, tight min-out, modest tips.
+    pub fn conservative() -> Self {
+        Self {
+            repay_fraction: 0.2,
+            min_out_tolerance_bps: 100,
+            tip_profit_share: 0.2,
+            tip_seized_share: None,
+            tip_floor_lamports: None,
+            tip_ceiling_lamports: None,
+            retry_attempts: 1,
+            retry_base_delay_ms: 100,
+            retry_max_delay_ms: 400,
+            max_price_impact_bps: 100,
+            repay_selection: RepaySelection::Largest,
+            repay_preference: Vec::new(),
+            quiet_cu_price: None,
+            quiet_fee_threshold: 0,
+            reserves: HashMap::new(),
+            held: HashMap::new(),
+        }
+    }
+
+    /// Full close-factor repay, loose min-out, large tips and retries.
+    pub fn aggressive() -> Self {
+        Self {
+            repay_fraction: 0.5,
+            min_out_tolerance_bps: 500,
+            tip_profit_share: 0.6,
+            tip_seized_share: None,
+            tip_floor_lamports: None,
+            tip_ceiling_lamports: None,
+            retry_attempts: 3,
+            retry_base_delay_ms: 50,
+            retry_max_delay_ms: 200,
+            max_price_impact_bps: 300,
+            repay_selection: RepaySelection::Largest,
+            repay_preference: Vec::new(),
+            quiet_cu_price: None,
+            quiet_fee_threshold: 0,
+            reserves: HashMap::new(),
+            held: HashMap::new(),
+        }
+    }
+
+    /// Built-in profile by name, one of `PRESETS`.
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "conservative" => Some(Self::conservative()),
+            "aggressive" => Some(Self::aggressive()),
+            _ => None,
+        }
+    }
+
+    /// Whether the reserve is excluded from liquidations.
+    pub fn is_blacklisted(&self, reserve: &Pubkey) -> bool {
+        self.reserves.get(reserve).is_some_and(|r| r.blacklisted)
+    }
+
+    /// Preferred swap route for the reserve's asset, if configured.
+    pub fn swap_route(&self, reserve: &Pubkey) -> Option<&str> {
+        self.reserves.get(reserve).and_then(|r| r.swap_route.as_deref())
+    }
+
+    /// Whether the reserve's asset counts as a stablecoin for repay selection.
+    pub fn is_stable(&self, reserve: &Pubkey, mint: &Pubkey) -> bool {
+        match self.reserves.get(reserve).and_then(|r| r.stable) {
+            Some(stable) => stable,
+            None => STABLECOIN_MINTS.iter().any(|m| m.parse::<Pubkey>().is_ok_and(|m| m == *mint)),
+        }
+    }
+
+    /// Whether the liquidator holds enough of `mint` to repay `amount` without acquiring it.
+    pub fn holds(&self, mint: &Pubkey, amount: u64) -> bool {
+        self.held.get(mint).is_some_and(|held| *held >= amount)
+    }
+
+    /// Price impact limit for selling the reserve's asset, in basis points.
+    pub fn max_price_impact_bps(&self, reserve: &Pubkey) -> u32 {
+        self.reserves.get(reserve).and_then(|r| r.max_price_impact_bps).unwrap_or(self.max_price_impact_bps)
+    }
+
+    /// Amount of `borrow_amount` in `reserve` to repay under this profile.
+    pub fn repay_amount(&self, reserve: &Pubkey, borrow_amount: u64) -> u64 {
+        let amount = (borrow_amount as f64 * self.repay_fraction.clamp(0.0, 1.0)) as u64;
+        match self.reserves.get(reserve).and_then(|r| r.max_repay_amount) {
+            Some(max) => amount.min(max),
+            None => amount,
+        }
+    }
+
+    /// Minimum acceptable output of `withdraw_reserve` given an expected amount.
+    pub fn min_out(&self, withdraw_reserve: &Pubkey, expected_out: u64) -> u64 {
+        let bps = self
+            .reserves
+            .get(withdraw_reserve)
+            .and_then(|r| r.min_out_tolerance_bps)
+            .unwrap_or(self.min_out_tolerance_bps);
+        let tolerance = f64::from(bps.min(10_000)) / 10_000.0;
+        (expected_out as f64 * (1.0 - tolerance)) as u64
+    }
+
+    /// Tip for a candidate: a share of expected seized value or profit, scaled by `multiplier`
+    /// and clamped to the profile's floor (or `default_floor`) and ceiling.
+    pub fn tip_lamports(
+        &self,
+        expected_profit: Option<u64>,
+        expected_seized: Option<u64>,
+        default_floor: u64,
+        multiplier: f64,
+    ) -> u64 {
+        let tip = match self.tip_seized_share {
+            Some(share) => expected_seized.map(|v| (v as f64 * share.clamp(0.0, 1.0) * multiplier) as u64),
+            None => expected_profit.map(|p| (p as f64 * self.tip_profit_share * multiplier) as u64),
+        };
+        let (floor, ceiling) = self.tip_bounds(default_floor);
+        tip.unwrap_or(0).clamp(floor, ceiling)
+    }
+
+    /// Floor and ceiling `tip_lamports` clamps to; the ceiling is `u64::MAX` when unset.
+    pub fn tip_bounds(&self, default_floor: u64) -> (u64, u64) {
+        let floor = self.tip_floor_lamports.unwrap_or(default_floor);
+        (floor, self.tip_ceiling_lamports.unwrap_or(u64::MAX).max(floor))
+    }
+
+    /// Retry policy for candidate submission.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.retry_attempts.max(1),
+            base_delay: Duration::from_millis(self.retry_base_delay_ms),
+            max_delay: Duration::from_millis(self.retry_max_delay_ms),
+        }
+    }
+}
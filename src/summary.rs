@@ -0,0 +1,138 @@
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use tracing::info;
+
+use crate::events::{self, Event};
+use crate::metrics::metrics;
+use crate::sender::SenderKind;
+use crate::tracker::TxOutcome;
+
+/// Count one scan pass and its duration.
+pub fn record_scan(elapsed: Duration, candidates: usize) {
+    metrics().inc("scans_total");
+    metrics().add("scan_duration_ms_total", &[], elapsed.as_millis() as u64);
+    metrics().add("candidates_seen_total", &[], candidates as u64);
+    events::record(Event::Scan { liquidatable: candidates as u32, duration_us: elapsed.as_micros() as u64 });
+}
+
+/// Count one submission with the tip it bids and the profit it expects.
+pub fn record_submission(sender: SenderKind, tip_lamports: u64, expected_profit_lamports: Option<u64>) {
+    metrics().inc_labeled("submissions_total", &[("sender", sender.as_str())]);
+    metrics().add("submitted_tip_lamports_total", &[], tip_lamports);
+    metrics().add("submitted_expected_profit_lamports_total", &[], expected_profit_lamports.unwrap_or(0));
+}
+
+/// Totals read from the metrics registry at one point in time.
+#[derive(Clone, Copy, Debug, Default)]
+struct Snapshot {
+    scans: u64,
+    scan_ms: u64,
+    candidates: u64,
+    submissions: u64,
+    landed: u64,
+    resolved: u64,
+    tips: u64,
+    expected_profit: u64,
+}
+
+impl Snapshot {
+    fn capture() -> Self {
+        let m = metrics();
+        let submissions = [SenderKind::Bundle, SenderKind::JitoTx, SenderKind::Rpc]
+            .iter()
+            .map(|s| m.counter("submissions_total", &[("sender", s.as_str())]))
+            .sum();
+        let outcome = |o: TxOutcome| m.counter("tx_outcomes_total", &[("outcome", o.as_str())]);
+        Self {
+            scans: m.counter("scans_total", &[]),
+            scan_ms: m.counter("scan_duration_ms_total", &[]),
+            candidates: m.counter("candidates_seen_total", &[]),
+            submissions,
+            landed: outcome(TxOutcome::Landed),
+            resolved: outcome(TxOutcome::Landed) + outcome(TxOutcome::Reverted) + outcome(TxOutcome::Dropped),
+            tips: m.counter("submitted_tip_lamports_total", &[]),
+            expected_profit: m.counter("submitted_expected_profit_lamports_total", &[]),
+        }
+    }
+
+    fn since(&self, earlier: &Self) -> Self {
+        Self {
+            scans: self.scans - earlier.scans,
+            scan_ms: self.scan_ms - earlier.scan_ms,
+            candidates: self.candidates - earlier.candidates,
+            submissions: self.submissions - earlier.submissions,
+            landed: self.landed - earlier.landed,
+            resolved: self.resolved - earlier.resolved,
+            tips: self.tips - earlier.tips,
+            expected_profit: self.expected_profit - earlier.expected_profit,
+        }
+    }
+
+    fn avg_scan_ms(&self) -> f64 {
+        self.scan_ms as f64 / self.scans.max(1) as f64
+    }
+
+    fn land_rate(&self) -> Option<f64> {
+        (self.resolved > 0).then(|| self.landed as f64 / self.resolved as f64)
+    }
+}
+
+/// Operator-facing summary of scans and submissions, logged periodically and printed on exit.
+pub struct Summary {
+    started: Instant,
+    interval: Duration,
+    last_report: Instant,
+    last: Snapshot,
+}
+
+impl Summary {
+    /// A zero interval disables the periodic report; the exit summary is always available.
+    pub fn new(interval: Duration) -> Self {
+        let now = Instant::now();
+        Self { started: now, interval, last_report: now, last: Snapshot::capture() }
+    }
+
+    /// Log the window since the previous report if the interval has elapsed.
+    pub fn tick(&mut self) {
+        if self.interval.is_zero() || self.last_report.elapsed() < self.interval {
+            return;
+        }
+        let now = Snapshot::capture();
+        let window = now.since(&self.last);
+        info!(
+            window_secs = self.last_report.elapsed().as_secs(),
+            scans = window.scans,
+            avg_scan_ms = format!("{:.0}", window.avg_scan_ms()),
+            candidates = window.candidates,
+            submissions = window.submissions,
+            landed = window.landed,
+            land_rate = ?window.land_rate(),
+            tips_lamports = window.tips,
+            expected_profit_lamports = window.expected_profit,
+            "Periodic summary"
+        );
+        self.last = now;
+        self.last_report = Instant::now();
+    }
+
+    /// Lifetime totals as a text block.
+    pub fn render(&self) -> String {
+        let s = Snapshot::capture();
+        let sol = |lamports: u64| lamports as f64 / LAMPORTS_PER_SOL as f64;
+        let mut out = String::new();
+        let _ = writeln!(out, "=== Summary ({}s uptime) ===", self.started.elapsed().as_secs());
+        let _ = writeln!(out, "scans:                    {}", s.scans);
+        let _ = writeln!(out, "avg scan latency:         {:.0} ms", s.avg_scan_ms());
+        let _ = writeln!(out, "candidates seen:          {}", s.candidates);
+        let _ = writeln!(out, "submissions:              {}", s.submissions);
+        let _ = match s.land_rate() {
+            Some(rate) => writeln!(out, "land rate:                {:.1}% ({}/{})", rate * 100.0, s.landed, s.resolved),
+            None => writeln!(out, "land rate:                n/a"),
+        };
+        let _ = writeln!(out, "tips bid:                 {:.6} SOL", sol(s.tips));
+        let _ = writeln!(out, "expected profit (sent):   {:.6} SOL", sol(s.expected_profit));
+        out
+    }
+}
@@ -0,0 +1,76 @@
+use std::any::Any;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::metrics::metrics;
+use crate::retry::RetryPolicy;
+
+/// A task that ran at least this long is considered healthy and its backoff resets.
+const HEALTHY_RUN: Duration = Duration::from_secs(60);
+
+/// Backoff between restarts of a failed component.
+pub fn restart_policy() -> RetryPolicy {
+    RetryPolicy { max_attempts: u32::MAX, base_delay: Duration::from_millis(500), max_delay: Duration::from_secs(30) }
+}
+
+/// Route panics through tracing so they reach the same sink as every other log line.
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let location = info.location().map(|l| format!("{}:{}", l.file(), l.line())).unwrap_or_default();
+        error!(location, panic = %panic_message(info.payload()), "Task panicked");
+    }));
+}
+
+/// Best-effort text of a panic payload.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string())
+}
+
+/// Count a component failure for the restart metric.
+pub fn record_restart(component: &str, reason: &str) {
+    metrics().inc_labeled("supervisor_restarts_total", &[("component", component), ("reason", reason)]);
+}
+
+/// Run `factory`'s task until it returns Ok, restarting it with backoff whenever it errors or
+/// panics.
+pub fn spawn_supervised<F, Fut>(component: &'static str, policy: RetryPolicy, factory: F) -> JoinHandle<()>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut attempt = 0;
+        loop {
+            let started = Instant::now();
+            let reason = match tokio::spawn(factory()).await {
+                Ok(Ok(())) => {
+                    info!(component, "Supervised task finished");
+                    return;
+                }
+                Ok(Err(e)) => {
+                    warn!(component, error = %e, "Supervised task failed");
+                    "error"
+                }
+                Err(e) if e.is_panic() => "panic",
+                Err(e) => {
+                    warn!(component, error = %e, "Supervised task cancelled");
+                    return;
+                }
+            };
+            record_restart(component, reason);
+
+            attempt = if started.elapsed() >= HEALTHY_RUN { 1 } else { attempt + 1 };
+            let delay = policy.delay(attempt);
+            warn!(component, reason, attempt, delay_ms = delay.as_millis() as u64, "Restarting supervised task");
+            tokio::time::sleep(delay).await;
+        }
+    })
+}
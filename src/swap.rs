@@ -0,0 +1,192 @@
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use solana_sdk::transaction::VersionedTransaction;
+use tracing::debug;
+
+/// Default Jupiter swap API base URL.
+pub const DEFAULT_JUPITER_URL: &str = "https://quote-api.jup.ag/v6";
+
+/// Whether the quoted amount is the input or the desired output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwapMode {
+    ExactIn,
+    ExactOut,
+}
+
+impl SwapMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            SwapMode::ExactIn => "ExactIn",
+            SwapMode::ExactOut => "ExactOut",
+        }
+    }
+}
+
+/// Score penalty per route hop beyond the first, in basis points of output.
+const HOP_PENALTY_BPS: u64 = 5;
+
+/// A Jupiter quote with the amounts parsed out; `raw` is passed back verbatim to build the swap.
+#[derive(Clone, Debug)]
+pub struct Quote {
+    pub in_amount: u64,
+    pub out_amount: u64,
+    /// Price impact reported by Jupiter, in basis points.
+    pub price_impact_bps: u32,
+    /// Number of legs in the route plan.
+    pub hops: usize,
+    raw: Value,
+}
+
+impl Quote {
+    /// Output discounted for each extra hop, since every leg adds execution risk.
+    pub fn score(&self) -> u64 {
+        let penalty_bps = HOP_PENALTY_BPS * self.hops.saturating_sub(1) as u64;
+        (self.out_amount as u128 * 10_000u128.saturating_sub(penalty_bps as u128) / 10_000) as u64
+    }
+}
+
+/// Restrictions applied when searching for a route.
+#[derive(Clone, Debug, Default)]
+pub struct RouteFilter {
+    /// Only single-hop routes.
+    pub only_direct: bool,
+    /// Comma-separated Jupiter DEX labels to route through, if restricted.
+    pub dexes: Option<String>,
+}
+
+/// Minimal client for Jupiter's quote and swap endpoints.
+pub struct JupiterClient {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl JupiterClient {
+    pub fn new(base_url: String) -> Self {
+        Self { base_url, http: reqwest::Client::new() }
+    }
+
+    /// Quote a swap of `amount` base units between two mints.
+    pub async fn quote(
+        &self,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+        amount: u64,
+        mode: SwapMode,
+        slippage_bps: u16,
+    ) -> Result<Quote> {
+        self.quote_filtered(input_mint, output_mint, amount, mode, slippage_bps, &RouteFilter::default()).await
+    }
+
+    /// Best ExactIn route whose price impact stays within `max_impact_bps`, scored by
+    /// hop-adjusted output. None when every candidate route is too thin.
+    pub async fn best_route(
+        &self,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+        amount: u64,
+        slippage_bps: u16,
+        max_impact_bps: u32,
+        dexes: Option<&str>,
+    ) -> Result<Option<Quote>> {
+        let filters = [false, true].map(|only_direct| RouteFilter { only_direct, dexes: dexes.map(str::to_string) });
+        let mut quotes = Vec::with_capacity(filters.len());
+        for filter in &filters {
+            match self.quote_filtered(input_mint, output_mint, amount, SwapMode::ExactIn, slippage_bps, filter).await {
+                Ok(q) => quotes.push(q),
+                // A direct route often does not exist; the other variant may still succeed
+                Err(e) if filter.only_direct => debug!(error = %e, "No direct route"),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(quotes.into_iter().filter(|q| q.price_impact_bps <= max_impact_bps).max_by_key(Quote::score))
+    }
+
+    async fn quote_filtered(
+        &self,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+        amount: u64,
+        mode: SwapMode,
+        slippage_bps: u16,
+        filter: &RouteFilter,
+    ) -> Result<Quote> {
+        let mut params = vec![
+            ("inputMint", input_mint.to_string()),
+            ("outputMint", output_mint.to_string()),
+            ("amount", amount.to_string()),
+            ("swapMode", mode.as_str().to_string()),
+            ("slippageBps", slippage_bps.to_string()),
+        ];
+        if filter.only_direct {
+            params.push(("onlyDirectRoutes", "true".to_string()));
+        }
+        if let Some(dexes) = &filter.dexes {
+            params.push(("dexes", dexes.clone()));
+        }
+        let raw: Value = self
+            .http
+            .get(format!("{}/quote", self.base_url))
+            .query(&params)
+            .send()
+            .await
+            .context("Jupiter quote request failed")?
+            .error_for_status()
+            .context("Jupiter quote rejected")?
+            .json()
+            .await
+            .context("Invalid Jupiter quote response")?;
+
+        let amount_field = |name: &str| -> Result<u64> {
+            raw.get(name)
+                .and_then(Value::as_str)
+                .and_then(|s| s.parse().ok())
+                .with_context(|| format!("Jupiter quote missing {name}"))
+        };
+        let price_impact_bps = raw
+            .get("priceImpactPct")
+            .and_then(Value::as_str)
+            .and_then(|s| s.parse::<f64>().ok())
+            .map_or(0, |pct| (pct * 10_000.0).round() as u32);
+        let hops = raw.get("routePlan").and_then(Value::as_array).map_or(1, Vec::len);
+        Ok(Quote {
+            in_amount: amount_field("inAmount")?,
+            out_amount: amount_field("outAmount")?,
+            price_impact_bps,
+            hops,
+            raw,
+        })
+    }
+
+    /// Build and sign the swap transaction for a quote.
+    pub async fn swap_tx(&self, quote: &Quote, payer: &Keypair) -> Result<VersionedTransaction> {
+        let body = json!({
+            "quoteResponse": quote.raw,
+            "userPublicKey": payer.pubkey().to_string(),
+            "wrapAndUnwrapSol": true,
+        });
+        let resp: Value = self
+            .http
+            .post(format!("{}/swap", self.base_url))
+            .json(&body)
+            .send()
+            .await
+            .context("Jupiter swap request failed")?
+            .json()
+            .await
+            .context("Invalid Jupiter swap response")?;
+        if let Some(err) = resp.get("error") {
+            bail!("Jupiter swap error: {err}");
+        }
+
+        let encoded =
+            resp.get("swapTransaction").and_then(Value::as_str).context("Jupiter swap response missing transaction")?;
+        let bytes =
+            base64::engine::general_purpose::STANDARD.decode(encoded).context("Invalid swap transaction encoding")?;
+        let unsigned: VersionedTransaction = bincode::deserialize(&bytes).context("Invalid swap transaction")?;
+        VersionedTransaction::try_new(unsigned.message, &[payer]).context("Failed to sign swap transaction")
+    }
+}
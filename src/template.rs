@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Result};
+use carbon_kamino_lending_decoder::types;
+use rand::seq::IteratorRandom;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, info, warn};
+
+use crate::kamino::{fetch_obligation, fetch_open_obligation, liquidation_ix_for, min_out_for, LiquidationCandidate};
+use crate::metrics::metrics;
+use crate::pda::{LiquidatorAccounts, MarketAccounts, ReserveVaults};
+use crate::rpc::Rpc;
+use crate::strategy::StrategyProfile;
+
+/// Byte ranges of `liquidity_amount` and `min_out` in the liquidation instruction data.
+const AMOUNT_RANGE: std::ops::Range<usize> = 8..16;
+const MIN_OUT_RANGE: std::ops::Range<usize> = 16..24;
+
+/// Liquidation instruction with every account pre-resolved; only amounts are filled in at fire time.
+pub struct TxTemplate {
+    repay_reserve: Pubkey,
+    withdraw_reserve: Pubkey,
+    /// Obligation fields baked into the instruction accounts.
+    owner: Pubkey,
+    referrer: Pubkey,
+    ix: Instruction,
+    built_at: Instant,
+}
+
+impl TxTemplate {
+    /// Resolve all accounts for the candidate and build a zero-amount instruction.
+    pub async fn prepare(
+        rpc: &Rpc,
+        market: &MarketAccounts,
+        liquidator: &LiquidatorAccounts,
+        cand: &LiquidationCandidate,
+    ) -> Result<Self> {
+        let (obl, vaults) = tokio::try_join!(
+            fetch_obligation(rpc, &cand.obligation),
+            ReserveVaults::fetch(rpc, &cand.repay_reserve, &cand.withdraw_reserve),
+        )?;
+        let ix = liquidation_ix_for(cand, &obl, market, liquidator, &vaults, 0, 0)?;
+        ensure!(ix.data.len() >= MIN_OUT_RANGE.end, "Unexpected liquidation instruction layout");
+        Ok(Self {
+            repay_reserve: cand.repay_reserve,
+            withdraw_reserve: cand.withdraw_reserve,
+            owner: obl.owner,
+            referrer: obl.referrer,
+            ix,
+            built_at: Instant::now(),
+        })
+    }
+
+    /// Whether the template still targets the candidate's chosen reserves.
+    pub fn matches(&self, cand: &LiquidationCandidate) -> bool {
+        self.repay_reserve == cand.repay_reserve && self.withdraw_reserve == cand.withdraw_reserve
+    }
+
+    /// Whether the on-chain obligation still agrees with what the template was built from.
+    pub fn agrees_with(&self, obl: &types::Obligation) -> bool {
+        self.owner == obl.owner
+            && self.referrer == obl.referrer
+            && obl.borrows.iter().any(|b| b.reserve == self.repay_reserve && b.amount > 0)
+            && obl.deposits.iter().any(|d| d.reserve == self.withdraw_reserve && d.amount > 0)
+    }
+
+    /// Instruction with the repay amount and minimum output patched in.
+    pub fn instruction(&self, repay_amount: u64, min_out: u64) -> Instruction {
+        let mut ix = self.ix.clone();
+        ix.data[AMOUNT_RANGE].copy_from_slice(&repay_amount.to_le_bytes());
+        ix.data[MIN_OUT_RANGE].copy_from_slice(&min_out.to_le_bytes());
+        ix
+    }
+}
+
+/// Templates for watchlist obligations, keyed by obligation.
+pub struct TemplateCache {
+    templates: HashMap<Pubkey, TxTemplate>,
+    max_age: Duration,
+}
+
+impl TemplateCache {
+    pub fn new(max_age: Duration) -> Self {
+        Self { templates: HashMap::new(), max_age }
+    }
+
+    /// Ready instruction for a candidate if a matching template is cached.
+    pub fn instruction_for(&self, cand: &LiquidationCandidate, strategy: &StrategyProfile) -> Option<Instruction> {
+        self.templates
+            .get(&cand.obligation)
+            .filter(|t| t.matches(cand))
+            .map(|t| t.instruction(cand.repay_amount, min_out_for(cand, cand.repay_amount, strategy)))
+    }
+
+    /// Build templates for new or stale watchlist entries and drop ones no longer watched.
+    pub async fn refresh(
+        &mut self,
+        rpc: &Rpc,
+        market: &MarketAccounts,
+        liquidator: &LiquidatorAccounts,
+        watchlist: &[&LiquidationCandidate],
+    ) {
+        self.templates.retain(|pk, _| watchlist.iter().any(|c| c.obligation == *pk));
+
+        for cand in watchlist {
+            let fresh = self
+                .templates
+                .get(&cand.obligation)
+                .is_some_and(|t| t.matches(cand) && t.built_at.elapsed() < self.max_age);
+            if fresh {
+                continue;
+            }
+            match TxTemplate::prepare(rpc, market, liquidator, cand).await {
+                Ok(t) => {
+                    debug!(obligation = %cand.obligation, health = cand.health, "Prepared liquidation template");
+                    self.templates.insert(cand.obligation, t);
+                }
+                Err(e) => warn!(obligation = %cand.obligation, error = %e, "Failed to prepare liquidation template"),
+            }
+        }
+    }
+
+    /// Re-fetch a random sample of cached templates from RPC and drop any that drifted from
+    /// on-chain state; the next refresh rebuilds them. Returns the number of divergent entries.
+    pub async fn reconcile(&mut self, rpc: &Rpc, sample: usize) -> usize {
+        let picked: Vec<Pubkey> = self.templates.keys().copied().choose_multiple(&mut rand::thread_rng(), sample);
+        let (mut checked, mut divergent) = (0, 0);
+
+        for obligation in &picked {
+            // Closed or undecodable obligations are drift too; a failed fetch proves nothing
+            let agrees = match fetch_open_obligation(rpc, obligation).await {
+                Ok(obl) => obl.is_some_and(|obl| self.templates.get(obligation).is_some_and(|t| t.agrees_with(&obl))),
+                Err(e) => {
+                    debug!(obligation = %obligation, error = %e, "Reconciliation fetch failed, keeping template");
+                    continue;
+                }
+            };
+            checked += 1;
+            if !agrees {
+                self.templates.remove(obligation);
+                divergent += 1;
+            }
+        }
+
+        metrics().add("template_cache_checked_total", &[], checked as u64);
+        metrics().add("template_cache_divergence_total", &[], divergent as u64);
+        if divergent > 0 {
+            info!(checked, divergent, "Repaired drifted liquidation templates");
+        }
+        divergent
+    }
+
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+}
@@ -0,0 +1,209 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::rpc_config::RpcSignatureSubscribeConfig;
+use solana_client::rpc_response::RpcSignatureResult;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use tracing::{info, warn};
+
+use crate::bundles::BundleBook;
+use crate::events::{self, Event};
+use crate::latency::{observe_stage, Route, Stage};
+use crate::metrics::metrics;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
+use crate::store::Store;
+use crate::util::now_millis;
+
+/// Store collection holding one record per tracked submission.
+pub const SUBMISSIONS: &str = "submissions";
+
+/// Final state of a submitted transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxOutcome {
+    /// Executed successfully on chain.
+    Landed,
+    /// Included in a block but failed execution.
+    Reverted,
+    /// Never observed on chain before the tracking deadline.
+    Dropped,
+}
+
+impl TxOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TxOutcome::Landed => "landed",
+            TxOutcome::Reverted => "reverted",
+            TxOutcome::Dropped => "dropped",
+        }
+    }
+}
+
+/// Persisted result of tracking one submitted transaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmissionRecord {
+    pub obligation: String,
+    pub signature: String,
+    pub bundle_id: String,
+    /// Unset when the slot could not be read at submission.
+    pub submitted_slot: Option<u64>,
+    pub landed_slot: Option<u64>,
+    /// Slots between submission and landing.
+    pub slot_latency: Option<u64>,
+    pub elapsed_ms: u64,
+    pub outcome: TxOutcome,
+    pub error: Option<String>,
+    pub submitted_at_ms: u64,
+}
+
+/// Result of tracking: outcome, landed slot and error.
+type Tracked = (TxOutcome, Option<u64>, Option<String>);
+
+/// Watches submitted signatures over websocket and records their outcome. Signature status
+/// history covers what the subscription misses, e.g. one that landed before it opened.
+pub struct SignatureTracker {
+    ws_url: String,
+    timeout: Duration,
+    rpc: Arc<Rpc>,
+    store: Arc<Store>,
+    bundles: Arc<BundleBook>,
+}
+
+impl SignatureTracker {
+    pub fn new(ws_url: String, timeout: Duration, rpc: Arc<Rpc>, store: Arc<Store>, bundles: Arc<BundleBook>) -> Self {
+        Self { ws_url, timeout, rpc, store, bundles }
+    }
+
+    /// Track a signature in the background until it lands, reverts, or times out. Landings are
+    /// timed under `route`.
+    pub fn spawn(
+        self: &Arc<Self>,
+        obligation: Pubkey,
+        signature: Signature,
+        bundle_id: String,
+        submitted_slot: Option<u64>,
+        route: Route,
+    ) {
+        events::record(Event::Submitted {
+            obligation,
+            signature,
+            sender: route.sender.as_str().to_string(),
+            region: route.region.to_string(),
+            slot: submitted_slot,
+        });
+        let tracker = Arc::clone(self);
+        tokio::spawn(async move {
+            let submitted_at_ms = now_millis();
+            let started = Instant::now();
+            let (outcome, landed_slot, error) = match tracker.wait_for_outcome(&signature).await {
+                Ok(result) => result,
+                Err(e) => {
+                    warn!(signature = %signature, error = %e, "Signature subscription failed");
+                    tokio::time::sleep(tracker.timeout).await;
+                    (TxOutcome::Dropped, None, Some(format!("{e:#}")))
+                }
+            };
+            // Never count a signature as dropped without asking the status history
+            let (outcome, landed_slot, error) = match outcome {
+                TxOutcome::Dropped => tracker.from_history(&signature).await.unwrap_or((outcome, landed_slot, error)),
+                _ => (outcome, landed_slot, error),
+            };
+            if outcome == TxOutcome::Landed {
+                observe_stage(Stage::SubmitToLand, route, started.elapsed(), &signature);
+            }
+
+            let record = SubmissionRecord {
+                obligation: obligation.to_string(),
+                signature: signature.to_string(),
+                bundle_id,
+                submitted_slot,
+                landed_slot,
+                slot_latency: landed_slot.zip(submitted_slot).map(|(landed, sent)| landed.saturating_sub(sent)),
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                outcome,
+                error,
+                submitted_at_ms,
+            };
+            events::record(Event::Outcome { signature, outcome, landed_slot, elapsed_ms: record.elapsed_ms });
+            tracker.record(&record);
+        });
+    }
+
+    async fn wait_for_outcome(&self, signature: &Signature) -> Result<Tracked> {
+        let client = PubsubClient::new(&self.ws_url).await.context("Failed to connect websocket")?;
+        let config = RpcSignatureSubscribeConfig {
+            commitment: Some(CommitmentConfig::confirmed()),
+            enable_received_notification: Some(false),
+        };
+        let (mut stream, unsubscribe) =
+            client.signature_subscribe(signature, Some(config)).await.context("signatureSubscribe failed")?;
+
+        // The send happened before subscribing, so it may have landed already
+        if let Some(result) = self.from_history(signature).await {
+            drop(stream);
+            unsubscribe().await;
+            return Ok(result);
+        }
+
+        let result = match tokio::time::timeout(self.timeout, stream.next()).await {
+            Ok(Some(resp)) => match resp.value {
+                RpcSignatureResult::ProcessedSignature(p) => match p.err {
+                    None => (TxOutcome::Landed, Some(resp.context.slot), None),
+                    Some(err) => (TxOutcome::Reverted, Some(resp.context.slot), Some(err.to_string())),
+                },
+                RpcSignatureResult::ReceivedSignature(_) => (TxOutcome::Dropped, None, None),
+            },
+            Ok(None) => (TxOutcome::Dropped, None, Some("subscription closed".to_string())),
+            Err(_) => (TxOutcome::Dropped, None, None),
+        };
+
+        drop(stream);
+        unsubscribe().await;
+        Ok(result)
+    }
+
+    /// Outcome from the signature's status history, once it is confirmed.
+    async fn from_history(&self, signature: &Signature) -> Option<Tracked> {
+        self.rpc.throttle(RequestClass::Candidate).await;
+        let statuses = match self.rpc.get_signature_statuses_with_history(&[*signature]) {
+            Ok(response) => response.value,
+            Err(e) => {
+                warn!(signature = %signature, error = %e, "Failed to read signature status history");
+                return None;
+            }
+        };
+        let status = statuses.into_iter().next().flatten()?;
+        if !status.satisfies_commitment(CommitmentConfig::confirmed()) {
+            return None;
+        }
+        Some(match status.err {
+            None => (TxOutcome::Landed, Some(status.slot), None),
+            Some(err) => (TxOutcome::Reverted, Some(status.slot), Some(err.to_string())),
+        })
+    }
+
+    fn record(&self, record: &SubmissionRecord) {
+        info!(
+            signature = %record.signature,
+            outcome = record.outcome.as_str(),
+            slot_latency = ?record.slot_latency,
+            elapsed_ms = record.elapsed_ms,
+            "Submission outcome"
+        );
+        metrics().inc_labeled("tx_outcomes_total", &[("outcome", record.outcome.as_str())]);
+        if let Some(latency) = record.slot_latency {
+            metrics().add("tx_landing_slots_total", &[], latency);
+        }
+        self.bundles.resolve(&record.bundle_id, record.outcome, record.landed_slot);
+        if let Err(e) = self.store.append(SUBMISSIONS, record) {
+            warn!(error = %e, "Failed to persist submission record");
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{ensure, Context, Result};
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::Transaction;
+use spl_associated_token_account_client::address::get_associated_token_address;
+use spl_associated_token_account_client::instruction::create_associated_token_account_idempotent;
+use tokio::time::MissedTickBehavior;
+use tracing::{info, warn};
+
+use crate::alert::Alerter;
+use crate::metrics::metrics;
+use crate::ratelimit::RequestClass;
+use crate::reconcile::{Asset, BalanceLedger, ChangeKind};
+use crate::rpc::Rpc;
+use crate::swap::{JupiterClient, SwapMode};
+use crate::util::LAMPORTS_PER_SIGNATURE;
+
+/// USDC mint, the default source for fee top-ups.
+pub const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+/// Slippage accepted on top-up swaps.
+const TOP_UP_SLIPPAGE_BPS: u16 = 50;
+
+/// When and how to refill the payer's SOL for fees and tips.
+#[derive(Clone, Debug)]
+pub struct TopUpConfig {
+    /// Refill once the payer balance drops below this.
+    pub min_lamports: u64,
+    /// Balance to refill up to.
+    pub target_lamports: u64,
+    /// Token swapped into SOL.
+    pub source_mint: Pubkey,
+    /// Swap automatically; when false only alert.
+    pub auto: bool,
+    pub check_interval: Duration,
+}
+
+/// Payer balance watchdog that swaps held profit into SOL before the bot stalls.
+pub struct Treasury {
+    cfg: TopUpConfig,
+    jupiter: JupiterClient,
+    /// Whether the current low-balance episode was already alerted.
+    alerted: AtomicBool,
+    ledger: Option<Arc<BalanceLedger>>,
+}
+
+impl Treasury {
+    pub fn new(cfg: TopUpConfig, jupiter: JupiterClient) -> Self {
+        Self { cfg, jupiter, alerted: AtomicBool::new(false), ledger: None }
+    }
+
+    /// Register each top-up's expected balance changes for reconciliation.
+    pub fn with_ledger(mut self, ledger: Arc<BalanceLedger>) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Check the payer balance every interval while `active` is set, off the scan loop since a
+    /// top-up waits for its swap to confirm. Returns only to satisfy the supervisor.
+    pub async fn run(
+        self: Arc<Self>,
+        rpc: Arc<Rpc>,
+        payer: Arc<Keypair>,
+        alerter: Arc<Alerter>,
+        active: Arc<AtomicBool>,
+    ) -> Result<()> {
+        let mut interval = tokio::time::interval(self.cfg.check_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if active.load(Ordering::Relaxed) {
+                self.check(&rpc, &payer, &alerter).await;
+            }
+        }
+    }
+
+    /// Top up or alert when the payer balance is low.
+    async fn check(&self, rpc: &Rpc, payer: &Keypair, alerter: &Alerter) {
+        rpc.throttle(RequestClass::Candidate).await;
+        let balance = match rpc.get_balance(&payer.pubkey()) {
+            Ok(b) => b,
+            Err(e) => {
+                warn!(error = %e, "Failed to fetch payer balance");
+                return;
+            }
+        };
+        metrics().set_gauge("payer_balance_lamports", &[], balance as f64);
+        if balance >= self.cfg.min_lamports {
+            self.alerted.store(false, Ordering::Relaxed);
+            return;
+        }
+
+        let sol = balance as f64 / LAMPORTS_PER_SOL as f64;
+        if !self.cfg.auto {
+            self.alert_once(alerter, &format!("Payer SOL balance low ({sol:.4} SOL); auto top-up disabled")).await;
+            return;
+        }
+
+        let needed = self.cfg.target_lamports.saturating_sub(balance);
+        match self.top_up(rpc, payer, needed).await {
+            Ok(sig) => {
+                info!(needed_lamports = needed, signature = %sig, "Topped up payer SOL");
+                metrics().inc("treasury_top_ups_total");
+                self.alerted.store(false, Ordering::Relaxed);
+            }
+            Err(e) => {
+                self.alert_once(alerter, &format!("Payer SOL balance low ({sol:.4} SOL) and top-up failed: {e:#}"))
+                    .await;
+            }
+        }
+    }
+
+    async fn alert_once(&self, alerter: &Alerter, message: &str) {
+        if !self.alerted.swap(true, Ordering::Relaxed) {
+            alerter.send(message).await;
+        }
+    }
+
+    /// Swap just enough of the source token to receive `lamports` SOL.
+    async fn top_up(&self, rpc: &Rpc, payer: &Keypair, lamports: u64) -> Result<String> {
+        let quote = self
+            .jupiter
+            .quote(
+                &self.cfg.source_mint,
+                &spl_token::native_mint::ID,
+                lamports,
+                SwapMode::ExactOut,
+                TOP_UP_SLIPPAGE_BPS,
+            )
+            .await?;
+
+        rpc.throttle(RequestClass::Candidate).await;
+        let source_ata = get_associated_token_address(&payer.pubkey(), &self.cfg.source_mint);
+        let held: u64 = rpc
+            .get_token_account_balance(&source_ata)
+            .context("Failed to fetch source token balance")?
+            .amount
+            .parse()
+            .context("Invalid token balance")?;
+        ensure!(held >= quote.in_amount, "Insufficient source token balance ({held} < {})", quote.in_amount);
+
+        let tx = self.jupiter.swap_tx(&quote, payer).await?;
+        rpc.throttle(RequestClass::Candidate).await;
+        let sig = rpc.send_and_confirm_transaction(&tx).context("Top-up swap failed")?;
+        if let Some(ledger) = &self.ledger {
+            let deltas = vec![
+                (Asset::Mint(self.cfg.source_mint), -i128::from(quote.in_amount)),
+                (Asset::Sol, i128::from(quote.out_amount) - i128::from(LAMPORTS_PER_SIGNATURE)),
+            ];
+            ledger.expect(sig, ChangeKind::TopUp, deltas, LAMPORTS_PER_SIGNATURE);
+        }
+        Ok(sig.to_string())
+    }
+}
+
+/// Where and how much realized profit to move off the hot payer key.
+#[derive(Clone, Debug)]
+pub struct SweepConfig {
+    /// Cold-storage owner receiving the sweeps.
+    pub cold_wallet: Pubkey,
+    /// SOL kept on the payer as working capital.
+    pub sol_float_lamports: u64,
+    /// Token swept alongside SOL, usually the top-up stablecoin.
+    pub token_mint: Pubkey,
+    /// Token base units kept on the payer (e.g. to fund SOL top-ups).
+    pub token_float: u64,
+    pub interval: Duration,
+}
+
+/// Periodically transfers balances above the float to the cold wallet.
+pub struct Sweeper {
+    cfg: SweepConfig,
+    ledger: Option<Arc<BalanceLedger>>,
+}
+
+impl Sweeper {
+    pub fn new(cfg: SweepConfig) -> Self {
+        Self { cfg, ledger: None }
+    }
+
+    /// Register each sweep's expected balance changes for reconciliation.
+    pub fn with_ledger(mut self, ledger: Arc<BalanceLedger>) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Sweep every interval while `active` is set, off the scan loop since a sweep waits for
+    /// confirmation; failures are logged and retried next interval. Returns only to satisfy the
+    /// supervisor.
+    pub async fn run(self: Arc<Self>, rpc: Arc<Rpc>, payer: Arc<Keypair>, active: Arc<AtomicBool>) -> Result<()> {
+        let mut interval = tokio::time::interval(self.cfg.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if !active.load(Ordering::Relaxed) {
+                continue;
+            }
+            if let Err(e) = self.sweep(&rpc, &payer).await {
+                warn!(error = %e, "Profit sweep failed");
+            }
+        }
+    }
+
+    async fn sweep(&self, rpc: &Rpc, payer: &Keypair) -> Result<()> {
+        let owner = payer.pubkey();
+        let mut ixs = Vec::new();
+
+        // Token profit above the float
+        rpc.throttle(RequestClass::Candidate).await;
+        let source = get_associated_token_address(&owner, &self.cfg.token_mint);
+        let token_swept = match rpc.get_token_account_balance(&source) {
+            Ok(balance) => {
+                let held: u64 = balance.amount.parse().context("Invalid token balance")?;
+                let amount = held.saturating_sub(self.cfg.token_float);
+                if amount > 0 {
+                    let dest = get_associated_token_address(&self.cfg.cold_wallet, &self.cfg.token_mint);
+                    ixs.push(create_associated_token_account_idempotent(
+                        &owner,
+                        &self.cfg.cold_wallet,
+                        &self.cfg.token_mint,
+                        &spl_token::ID,
+                    ));
+                    ixs.push(spl_token::instruction::transfer_checked(
+                        &spl_token::ID,
+                        &source,
+                        &self.cfg.token_mint,
+                        &dest,
+                        &owner,
+                        &[],
+                        amount,
+                        balance.decimals,
+                    )?);
+                }
+                amount
+            }
+            // No token account yet means nothing to sweep
+            Err(_) => 0,
+        };
+
+        // SOL above the float; the float also covers this transaction's fee and any ATA rent
+        rpc.throttle(RequestClass::Candidate).await;
+        let balance = rpc.get_balance(&owner).context("Failed to fetch payer balance")?;
+        let sol_swept = balance.saturating_sub(self.cfg.sol_float_lamports);
+        if sol_swept > 0 {
+            ixs.push(system_instruction::transfer(&owner, &self.cfg.cold_wallet, sol_swept));
+        }
+
+        if ixs.is_empty() {
+            return Ok(());
+        }
+        rpc.throttle(RequestClass::Blockhash).await;
+        let blockhash = rpc.get_latest_blockhash().context("Failed to fetch blockhash")?;
+        let tx = Transaction::new_signed_with_payer(&ixs, Some(&owner), &[payer], blockhash);
+        let sig = rpc.send_and_confirm_transaction(&tx).context("Sweep transaction failed")?;
+        if let Some(ledger) = &self.ledger {
+            let deltas = vec![
+                (Asset::Mint(self.cfg.token_mint), -i128::from(token_swept)),
+                (Asset::Sol, -i128::from(sol_swept + LAMPORTS_PER_SIGNATURE)),
+            ];
+            ledger.expect(sig, ChangeKind::Sweep, deltas, LAMPORTS_PER_SIGNATURE);
+        }
+
+        metrics().add("treasury_swept_lamports_total", &[], sol_swept);
+        info!(
+            cold_wallet = %self.cfg.cold_wallet,
+            sol_lamports = sol_swept,
+            token_amount = token_swept,
+            signature = %sig,
+            "Swept profit to cold wallet"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use carbon_kamino_lending_decoder::types;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use spl_associated_token_account_client::address::get_associated_token_address_with_program_id;
+use tracing::{debug, info, warn};
+
+use crate::alert::Alerter;
+use crate::keeper::fetch_reserves;
+use crate::metrics::metrics;
+use crate::ratelimit::RequestClass;
+use crate::reconcile::{Asset, BalanceLedger, ChangeKind};
+use crate::rpc::Rpc;
+use crate::strategy::StrategyProfile;
+use crate::swap::JupiterClient;
+use crate::util::{landed_token_deltas, LAMPORTS_PER_SIGNATURE};
+
+/// Where and how seized collateral is sold.
+#[derive(Clone, Debug)]
+pub struct UnwindConfig {
+    /// Token the collateral is sold into.
+    pub output_mint: Pubkey,
+    pub slippage_bps: u16,
+    pub interval: Duration,
+}
+
+/// Liquidations whose seizure is still unknown are dropped after this long without landing.
+const PENDING_TTL: Duration = Duration::from_secs(300);
+
+/// Sells collateral received from liquidations, holding it whenever no route stays within the
+/// strategy's price impact limit for that asset. Only what our liquidations actually seized is
+/// sold, read from their landed token balance changes, so inventory held in the same accounts
+/// (e.g. for repaying) is left alone.
+pub struct Unwinder {
+    cfg: UnwindConfig,
+    jupiter: JupiterClient,
+    /// Withdraw reserves we have seized collateral from.
+    reserves: BTreeSet<Pubkey>,
+    /// Submitted liquidations not yet seen landing, with their withdraw reserve.
+    pending: Vec<(Pubkey, Signature, Instant)>,
+    /// Seized collateral not yet sold, by mint.
+    seized: HashMap<Pubkey, u64>,
+    /// Mints already alerted as held, until a sale succeeds.
+    held: HashSet<Pubkey>,
+    last_run: Option<Instant>,
+    ledger: Option<Arc<BalanceLedger>>,
+}
+
+impl Unwinder {
+    pub fn new(cfg: UnwindConfig, jupiter: JupiterClient) -> Self {
+        Self {
+            cfg,
+            jupiter,
+            reserves: BTreeSet::new(),
+            pending: Vec::new(),
+            seized: HashMap::new(),
+            held: HashSet::new(),
+            last_run: None,
+            ledger: None,
+        }
+    }
+
+    /// Register each sale's expected balance changes for reconciliation.
+    pub fn with_ledger(mut self, ledger: Arc<BalanceLedger>) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Remember a submitted liquidation whose seized collateral should be sold once it lands.
+    pub fn track(&mut self, withdraw_reserve: Pubkey, signature: Signature) {
+        self.reserves.insert(withdraw_reserve);
+        self.pending.push((withdraw_reserve, signature, Instant::now()));
+    }
+
+    /// Sell seized collateral if the interval has elapsed.
+    pub async fn tick(&mut self, rpc: &Rpc, payer: &Keypair, strategy: &StrategyProfile, alerter: &Alerter) {
+        if self.reserves.is_empty() || self.last_run.is_some_and(|t| t.elapsed() < self.cfg.interval) {
+            return;
+        }
+        self.last_run = Some(Instant::now());
+
+        let keys: Vec<Pubkey> = self.reserves.iter().copied().collect();
+        let reserves = match fetch_reserves(rpc, &keys).await {
+            Ok(r) => r,
+            Err(e) => {
+                warn!(error = %e, "Failed to fetch reserves for unwinding");
+                return;
+            }
+        };
+        self.collect_seized(rpc, &payer.pubkey(), &reserves).await;
+
+        for (reserve_pk, reserve) in &reserves {
+            let mint = reserve.liquidity.mint_pubkey;
+            let Some(seized) = self.seized.get(&mint).copied().filter(|s| *s > 0) else { continue };
+            if mint == self.cfg.output_mint {
+                self.seized.remove(&mint);
+                continue;
+            }
+            let ata =
+                get_associated_token_address_with_program_id(&payer.pubkey(), &mint, &reserve.liquidity.token_program);
+            match self.unwind(rpc, payer, strategy, reserve_pk, &mint, &ata, seized).await {
+                Ok(Some((sig, sold))) => {
+                    info!(mint = %mint, signature = %sig, sold, "Sold seized collateral");
+                    metrics().inc_labeled("unwind_swaps_total", &[("result", "sold")]);
+                    self.held.remove(&mint);
+                    match seized.saturating_sub(sold) {
+                        0 => self.seized.remove(&mint),
+                        left => self.seized.insert(mint, left),
+                    };
+                }
+                Ok(None) => {
+                    self.seized.remove(&mint);
+                }
+                Err(e) => {
+                    metrics().inc_labeled("unwind_swaps_total", &[("result", "held")]);
+                    if self.held.insert(mint) {
+                        alerter.send(&format!("Holding seized collateral {mint}: {e:#}")).await;
+                    } else {
+                        warn!(mint = %mint, error = %e, "Still holding seized collateral");
+                    }
+                }
+            }
+        }
+
+        // Reserves with nothing pending or left to sell need no more fetching
+        let mints: HashMap<Pubkey, Pubkey> = reserves.iter().map(|(pk, r)| (*pk, r.liquidity.mint_pubkey)).collect();
+        self.reserves.retain(|reserve| {
+            self.pending.iter().any(|(r, _, _)| r == reserve)
+                || mints.get(reserve).is_some_and(|mint| self.seized.contains_key(mint))
+        });
+    }
+
+    /// Add what landed liquidations seized to the amounts to sell; ones not found yet are kept
+    /// until `PENDING_TTL`.
+    async fn collect_seized(&mut self, rpc: &Rpc, owner: &Pubkey, reserves: &HashMap<Pubkey, types::Reserve>) {
+        let mut still_pending = Vec::with_capacity(self.pending.len());
+        for (reserve, signature, submitted) in std::mem::take(&mut self.pending) {
+            let Some(mint) = reserves.get(&reserve).map(|r| r.liquidity.mint_pubkey) else { continue };
+            match landed_token_deltas(rpc, &signature, std::slice::from_ref(owner)).await {
+                Ok(deltas) => {
+                    let received = deltas.get(&mint).copied().unwrap_or(0).clamp(0, i128::from(u64::MAX)) as u64;
+                    if received > 0 {
+                        *self.seized.entry(mint).or_insert(0) += received;
+                    }
+                }
+                Err(_) if submitted.elapsed() < PENDING_TTL => still_pending.push((reserve, signature, submitted)),
+                Err(e) => debug!(signature = %signature, error = %e, "Liquidation never landed, nothing to unwind"),
+            }
+        }
+        self.pending = still_pending;
+    }
+
+    /// Sell up to `seized` of the balance; Ok(None) when the account holds nothing.
+    #[allow(clippy::too_many_arguments)]
+    async fn unwind(
+        &self,
+        rpc: &Rpc,
+        payer: &Keypair,
+        strategy: &StrategyProfile,
+        reserve: &Pubkey,
+        mint: &Pubkey,
+        ata: &Pubkey,
+        seized: u64,
+    ) -> Result<Option<(String, u64)>> {
+        rpc.throttle(RequestClass::Candidate).await;
+        let balance: u64 = match rpc.get_token_account_balance(ata) {
+            Ok(balance) => balance.amount.parse().context("Invalid token balance")?,
+            Err(_) => 0,
+        };
+        let held = balance.min(seized);
+        if held == 0 {
+            return Ok(None);
+        }
+
+        let max_impact_bps = strategy.max_price_impact_bps(reserve);
+        let quote = self
+            .jupiter
+            .best_route(
+                mint,
+                &self.cfg.output_mint,
+                held,
+                self.cfg.slippage_bps,
+                max_impact_bps,
+                strategy.swap_route(reserve),
+            )
+            .await?
+            .with_context(|| format!("No route within {max_impact_bps} bps price impact for {held} units"))?;
+
+        let tx = self.jupiter.swap_tx(&quote, payer).await?;
+        rpc.throttle(RequestClass::Candidate).await;
+        let sig = rpc.send_and_confirm_transaction(&tx).context("Collateral swap failed")?;
+        if let Some(ledger) = &self.ledger {
+            let deltas = vec![
+                (Asset::Mint(*mint), -i128::from(quote.in_amount)),
+                (Asset::swapped(self.cfg.output_mint), i128::from(quote.out_amount)),
+                (Asset::Sol, -i128::from(LAMPORTS_PER_SIGNATURE)),
+            ];
+            ledger.expect(sig, ChangeKind::Swap, deltas, LAMPORTS_PER_SIGNATURE);
+        }
+        info!(
+            mint = %mint,
+            in_amount = quote.in_amount,
+            out_amount = quote.out_amount,
+            price_impact_bps = quote.price_impact_bps,
+            hops = quote.hops,
+            "Collateral swap confirmed"
+        );
+        Ok(Some((sig.to_string(), quote.in_amount)))
+    }
+}
@@ -1,53 +1,328 @@
-use anyhow::{Context, Result};
-use solana_client::rpc_client::RpcClient;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{ensure, Context, Result};
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::compute_budget::ComputeBudgetInstruction;
 use solana_sdk::hash::Hash;
 use solana_sdk::instruction::Instruction;
-use solana_sdk::message::{Message, VersionedMessage};
-use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::message::{v0, AddressLookupTableAccount, VersionedMessage};
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
 use solana_sdk::system_instruction;
 use solana_sdk::transaction::VersionedTransaction;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{UiTransactionEncoding, UiTransactionTokenBalance};
+
+use tracing::debug;
+
+use crate::hooks::{run_hooks, HookContext, InstructionHook};
+use crate::metrics::metrics;
+use crate::ratelimit::RequestClass;
+use crate::rpc::Rpc;
 
 /// Fetch latest blockhash from RPC.
-pub fn fetch_latest_blockhash(rpc: &RpcClient) -> Result<Hash> {
+pub async fn fetch_latest_blockhash(rpc: &Rpc) -> Result<Hash> {
+    rpc.throttle(RequestClass::Blockhash).await;
     let bh = rpc.get_latest_blockhash().context("Failed to fetch blockhash")?;
     Ok(bh)
 }
 
-/// Build a versioned transaction with compute budget and a Jito tip transfer.
-pub fn build_tx_with_tip(
-    payer: &Keypair,
-    blockhash: Hash,
-    mut ixs: Vec<Instruction>,
-    cu_limit: u32,
-    cu_price: u64,
-    tip_account: solana_sdk::pubkey::Pubkey,
-    tip_lamports: u64,
-) -> Result<VersionedTransaction> {
-    // Compute budget tuning
-    let budget_ixs = vec![
-        ComputeBudgetInstruction::set_compute_unit_limit(cu_limit),
-        ComputeBudgetInstruction::set_compute_unit_price(cu_price),
-    ];
-
-    // Tip transfer to Jito account
-    let tip_ix = system_instruction::transfer(&payer.pubkey(), &tip_account, tip_lamports);
-
-    // Compose instructions
-    let mut full_ixs = Vec::with_capacity(2 + ixs.len() + 1);
-    full_ixs.extend(budget_ixs);
-    full_ixs.extend(ixs.drain(..));
-    full_ixs.push(tip_ix);
-
-    // Build and sign
-    let msg = Message::new(&full_ixs, Some(&payer.pubkey()));
-    let vmsg = VersionedMessage::V0(msg);
-    let tx = VersionedTransaction::try_new(vmsg, &[payer])
-        .context("Failed to sign versioned transaction")?;
-
-    // Update recent blockhash
-    let mut tx = tx;
-    tx.message.set_recent_blockhash(blockhash);
-    Ok(tx)
+/// Blocks a blockhash stays valid for after it is produced.
+pub const BLOCKHASH_VALID_BLOCKS: u64 = 150;
+
+/// Nominal block time, e.g. for estimating slot progress between RPC checks.
+pub const BLOCK_TIME: Duration = Duration::from_millis(400);
+
+/// A blockhash together with how long it can still be used.
+#[derive(Clone, Copy, Debug)]
+pub struct Blockhash {
+    pub hash: Hash,
+    pub last_valid_block_height: u64,
+}
+
+impl Blockhash {
+    /// Blocks left before expiry, against the cluster's current block height.
+    pub async fn remaining_blocks(&self, rpc: &Rpc) -> Result<u64> {
+        Ok(self.last_valid_block_height.saturating_sub(rpc.block_height().await?))
+    }
+
+    /// Whether a transaction built on it may expire before landing.
+    pub async fn is_expiring(&self, rpc: &Rpc, margin_blocks: u64) -> Result<bool> {
+        Ok(self.remaining_blocks(rpc).await? <= margin_blocks)
+    }
+
+    /// Swap in a fresh blockhash when fewer than `margin_blocks` remain. Returns whether it changed,
+    /// so callers know to rebuild transactions signed with the old one.
+    pub async fn refresh_if_expiring(&mut self, rpc: &Rpc, margin_blocks: u64) -> Result<bool> {
+        let remaining = self.remaining_blocks(rpc).await?;
+        if remaining > margin_blocks {
+            return Ok(false);
+        }
+        *self = fetch_blockhash(rpc).await?;
+        debug!(remaining_blocks = remaining, "Refreshed expiring blockhash");
+        metrics().inc("blockhash_refreshes_total");
+        Ok(true)
+    }
+}
+
+/// Fetch the latest blockhash and its last valid block height.
+pub async fn fetch_blockhash(rpc: &Rpc) -> Result<Blockhash> {
+    rpc.throttle(RequestClass::Blockhash).await;
+    let (hash, last_valid_block_height) =
+        rpc.get_latest_blockhash_with_commitment(CommitmentConfig::confirmed()).context("Failed to fetch blockhash")?;
+    Ok(Blockhash { hash, last_valid_block_height })
+}
+
+/// Fetch the current slot from RPC.
+pub async fn fetch_slot(rpc: &Rpc) -> Result<u64> {
+    rpc.throttle(RequestClass::Candidate).await;
+    let slot = rpc.get_slot().context("Failed to fetch slot")?;
+    Ok(slot)
+}
+
+/// Net change a confirmed transaction made to the token accounts `owners` hold, by mint; empty
+/// when it failed on chain. Errors while the transaction cannot be fetched, e.g. before it lands.
+pub async fn landed_token_deltas(rpc: &Rpc, signature: &Signature, owners: &[Pubkey]) -> Result<HashMap<Pubkey, i128>> {
+    rpc.throttle(RequestClass::Candidate).await;
+    let fetched = rpc
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .with_context(|| format!("Transaction {signature} not available"))?;
+    let meta = fetched.transaction.meta.context("Transaction has no status metadata")?;
+    let mut deltas = HashMap::new();
+    if meta.err.is_some() {
+        return Ok(deltas);
+    }
+    let owners: Vec<String> = owners.iter().map(Pubkey::to_string).collect();
+    let mut add = |balances: OptionSerializer<Vec<UiTransactionTokenBalance>>, sign: i128| {
+        let balances: Option<Vec<UiTransactionTokenBalance>> = balances.into();
+        for balance in balances.into_iter().flatten() {
+            let owned = matches!(&balance.owner, OptionSerializer::Some(o) if owners.contains(o));
+            let mint = balance.mint.parse::<Pubkey>().ok();
+            let amount = balance.ui_token_amount.amount.parse::<i128>().ok();
+            if let (true, Some(mint), Some(amount)) = (owned, mint, amount) {
+                *deltas.entry(mint).or_insert(0) += sign * amount;
+            }
+        }
+    };
+    add(meta.pre_token_balances, -1);
+    add(meta.post_token_balances, 1);
+    deltas.retain(|_, delta| *delta != 0);
+    Ok(deltas)
+}
+
+/// Milliseconds since the Unix epoch.
+pub fn now_millis() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// Base fee charged per transaction signature.
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// Smallest and largest heap frame the runtime accepts; sizes must be multiples of 1 KiB.
+pub const MIN_HEAP_FRAME_BYTES: u32 = 32 * 1024;
+pub const MAX_HEAP_FRAME_BYTES: u32 = 256 * 1024;
+
+/// Compute budget instructions prepended to every liquidation transaction.
+#[derive(Clone, Copy, Debug)]
+pub struct ComputeBudget {
+    pub cu_limit: u32,
+    /// Price in micro-lamports per compute unit; the instruction is left out when unset.
+    pub cu_price: Option<u64>,
+    /// Requested heap frame size in bytes; default heap when unset.
+    pub heap_frame_bytes: Option<u32>,
 }
 
+impl ComputeBudget {
+    /// Validate the heap frame size against runtime limits.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(bytes) = self.heap_frame_bytes {
+            ensure!(
+                (MIN_HEAP_FRAME_BYTES..=MAX_HEAP_FRAME_BYTES).contains(&bytes) && bytes % 1024 == 0,
+                "Heap frame size must be a multiple of 1024 between {MIN_HEAP_FRAME_BYTES} and {MAX_HEAP_FRAME_BYTES} bytes"
+            );
+        }
+        Ok(())
+    }
+
+    /// Base and priority fee of one transaction carrying these instructions.
+    pub fn fee_lamports(&self, signatures: usize) -> u64 {
+        let priority = self.cu_price.unwrap_or(0) as u128 * u128::from(self.cu_limit) / 1_000_000;
+        signatures as u64 * LAMPORTS_PER_SIGNATURE + priority as u64
+    }
+
+    pub fn instructions(&self) -> Vec<Instruction> {
+        let mut ixs = Vec::with_capacity(3);
+        if let Some(bytes) = self.heap_frame_bytes {
+            ixs.push(ComputeBudgetInstruction::request_heap_frame(bytes));
+        }
+        ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(self.cu_limit));
+        if let Some(price) = self.cu_price {
+            ixs.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+        }
+        ixs
+    }
+}
+
+/// Everything needed to turn liquidation instructions into signed transactions.
+pub struct TxBuilder<'a> {
+    /// Fee payer; also pays tips.
+    pub payer: &'a Keypair,
+    /// Second signer owning the liquidity, when it is not the payer.
+    pub owner: Option<&'a Keypair>,
+    pub budget: ComputeBudget,
+    /// Lookup tables used to compress account keys; may be empty.
+    pub lookup_tables: &'a [AddressLookupTableAccount],
+    pub tip_account: Pubkey,
+    /// Pay the tip from a final bundle transaction instead of inside the liquidation,
+    /// so nothing is tipped unless the whole bundle lands.
+    pub separate_tip: bool,
+    /// Add their instructions to every budgeted transaction, after its own and before the tip.
+    pub hooks: &'a [Box<dyn InstructionHook>],
+}
+
+/// Owned counterpart of `TxBuilder` for transactions built off the scan loop, which a
+/// borrowing builder cannot follow into a spawned task. Signs with the payer alone.
+#[derive(Clone)]
+pub struct BackgroundSigner {
+    pub payer: Arc<Keypair>,
+    pub lookup_tables: Arc<Vec<AddressLookupTableAccount>>,
+    pub hooks: Arc<Vec<Box<dyn InstructionHook>>>,
+    pub tip_account: Pubkey,
+}
+
+impl BackgroundSigner {
+    pub fn builder(&self, budget: ComputeBudget) -> TxBuilder<'_> {
+        TxBuilder {
+            payer: &self.payer,
+            owner: None,
+            budget,
+            lookup_tables: &self.lookup_tables,
+            tip_account: self.tip_account,
+            separate_tip: false,
+            hooks: &self.hooks,
+        }
+    }
+}
+
+/// Signed liquidation transactions plus the signature to track for the outcome.
+#[derive(Clone)]
+pub struct LiquidationTxs {
+    pub txs: Vec<VersionedTransaction>,
+    pub signature: Signature,
+}
+
+impl TxBuilder<'_> {
+    /// Build a versioned transaction with compute budget and a Jito tip transfer.
+    pub fn tx_with_tip(
+        &self,
+        blockhash: Hash,
+        ixs: Vec<Instruction>,
+        tip_lamports: u64,
+    ) -> Result<VersionedTransaction> {
+        self.budgeted_tx(blockhash, ixs, Some(tip_lamports))
+    }
+
+    /// Transaction with the compute budget and no tip, for non-bundle sends.
+    pub fn tx(&self, blockhash: Hash, ixs: Vec<Instruction>) -> Result<VersionedTransaction> {
+        self.budgeted_tx(blockhash, ixs, None)
+    }
+
+    /// Transaction whose only job is paying the tip; placed last in a bundle.
+    pub fn tip_tx(&self, blockhash: Hash, tip_lamports: u64) -> Result<VersionedTransaction> {
+        let tip_ix = system_instruction::transfer(&self.payer.pubkey(), &self.tip_account, tip_lamports);
+        self.sign(blockhash, &[tip_ix])
+    }
+
+    /// Build the liquidation as one transaction when it fits the packet limit, otherwise as a
+    /// two-transaction bundle: `prelude` (e.g. refreshes) first, then `ixs`. Both halves carry
+    /// the compute budget. The tip rides in the liquidation transaction, or in a trailing
+    /// transaction when `separate_tip` is set.
+    pub fn liquidation_txs(
+        &self,
+        blockhash: Hash,
+        prelude: Vec<Instruction>,
+        ixs: Vec<Instruction>,
+        tip_lamports: u64,
+    ) -> Result<LiquidationTxs> {
+        let inline_tip = (!self.separate_tip).then_some(tip_lamports);
+        let combined: Vec<Instruction> = prelude.iter().chain(&ixs).cloned().collect();
+
+        let mut txs = match self.budgeted_tx(blockhash, combined, inline_tip) {
+            Ok(tx) => vec![tx],
+            Err(e) if prelude.is_empty() => return Err(e),
+            Err(_) => {
+                let prelude_tx =
+                    self.budgeted_tx(blockhash, prelude, None).context("Prelude does not fit a transaction")?;
+                let liquidate_tx = self
+                    .budgeted_tx(blockhash, ixs, inline_tip)
+                    .context("Liquidation does not fit a transaction even after splitting")?;
+                vec![prelude_tx, liquidate_tx]
+            }
+        };
+        let signature = txs[txs.len() - 1].signatures[0];
+
+        if self.separate_tip {
+            txs.push(self.tip_tx(blockhash, tip_lamports)?);
+        }
+        Ok(LiquidationTxs { txs, signature })
+    }
+
+    /// Compute budget, then `ixs`, then hook instructions, then the tip transfer if any; rejects
+    /// oversized transactions.
+    fn budgeted_tx(
+        &self,
+        blockhash: Hash,
+        ixs: Vec<Instruction>,
+        tip_lamports: Option<u64>,
+    ) -> Result<VersionedTransaction> {
+        // Compute budget tuning
+        let budget_ixs = self.budget.instructions();
+
+        // Custom instructions from hooks
+        let hook_ixs = run_hooks(self.hooks, &HookContext { payer: self.payer.pubkey(), instructions: &ixs })?;
+
+        // Compose instructions
+        let mut full_ixs = Vec::with_capacity(budget_ixs.len() + ixs.len() + hook_ixs.len() + 1);
+        full_ixs.extend(budget_ixs);
+        full_ixs.extend(ixs);
+        full_ixs.extend(hook_ixs);
+
+        // Tip transfer to Jito account
+        if let Some(tip) = tip_lamports {
+            full_ixs.push(system_instruction::transfer(&self.payer.pubkey(), &self.tip_account, tip));
+        }
+
+        let tx = self.sign(blockhash, &full_ixs)?;
+        let size = tx_size(&tx);
+        ensure!(size <= PACKET_DATA_SIZE, "Transaction is {size} bytes, over the {PACKET_DATA_SIZE}-byte packet limit");
+        Ok(tx)
+    }
+
+    /// Compile a v0 message against the lookup tables and sign it with the payer, plus the owner
+    /// when the message requires its signature.
+    pub fn sign(&self, blockhash: Hash, ixs: &[Instruction]) -> Result<VersionedTransaction> {
+        let msg = v0::Message::try_compile(&self.payer.pubkey(), ixs, self.lookup_tables, blockhash)
+            .context("Failed to compile transaction message")?;
+        let required = &msg.account_keys[..msg.header.num_required_signatures as usize];
+        let mut signers = vec![self.payer];
+        signers.extend(self.owner.filter(|o| o.pubkey() != self.payer.pubkey() && required.contains(&o.pubkey())));
+        VersionedTransaction::try_new(VersionedMessage::V0(msg), &signers)
+            .context("Failed to sign versioned transaction")
+    }
+}
+
+/// Serialized wire size of a transaction in bytes.
+pub fn tx_size(tx: &VersionedTransaction) -> usize {
+    bincode::serialized_size(tx).map(|n| n as usize).unwrap_or(usize::MAX)
+}
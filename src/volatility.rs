@@ -0,0 +1,264 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use carbon_kamino_lending_decoder::{types, KaminoLendingDecoder, PROGRAM_ID};
+use futures::stream::{BoxStream, SelectAll};
+use futures::StreamExt;
+use serde::Deserialize;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, info, warn};
+
+use crate::kamino::LiquidationCandidate;
+use crate::metrics::metrics;
+use crate::oracle::{pyth_price, scope_price, PriceSource};
+use crate::partial::{RESERVE_DISCRIMINATOR, RESERVE_LENDING_MARKET_OFFSET};
+use crate::profit::token_price_usd;
+use crate::util::now_millis;
+
+/// Price samples kept per reserve; older ones are dropped even inside the window.
+const MAX_SAMPLES: usize = 4_096;
+
+/// How much an asset's price has moved recently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VolatilityClass {
+    Calm,
+    Normal,
+    Volatile,
+}
+
+impl VolatilityClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VolatilityClass::Calm => "calm",
+            VolatilityClass::Normal => "normal",
+            VolatilityClass::Volatile => "volatile",
+        }
+    }
+}
+
+/// How the bot reacts to candidates of one class.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClassPolicy {
+    /// Pause between scans while the most volatile watched collateral is in this class.
+    pub scan_delay_ms: u64,
+    /// Expected profit below which a candidate is skipped.
+    pub min_profit_lamports: u64,
+    /// Factor applied to the strategy's tip before its floor and ceiling.
+    pub tip_multiplier: f64,
+}
+
+impl ClassPolicy {
+    pub fn scan_delay(&self) -> Duration {
+        Duration::from_millis(self.scan_delay_ms)
+    }
+
+    /// Whether the candidate's expected profit, when known, clears this class's minimum.
+    pub fn clears_min_profit(&self, cand: &LiquidationCandidate) -> bool {
+        cand.expected_profit_lamports.is_none_or(|p| p >= self.min_profit_lamports)
+    }
+}
+
+/// Thresholds and per-class policies.
+#[derive(Clone, Debug)]
+pub struct VolatilityConfig {
+    /// Period the price range is measured over.
+    pub window: Duration,
+    /// Range at or below which an asset is calm, in basis points of its lowest price.
+    pub calm_bps: f64,
+    /// Range at or above which an asset is volatile, in basis points.
+    pub volatile_bps: f64,
+    pub calm: ClassPolicy,
+    pub normal: ClassPolicy,
+    pub volatile: ClassPolicy,
+}
+
+impl VolatilityConfig {
+    pub fn policy(&self, class: VolatilityClass) -> &ClassPolicy {
+        match class {
+            VolatilityClass::Calm => &self.calm,
+            VolatilityClass::Normal => &self.normal,
+            VolatilityClass::Volatile => &self.volatile,
+        }
+    }
+
+    fn classify(&self, range_bps: f64) -> VolatilityClass {
+        match range_bps {
+            r if r >= self.volatile_bps => VolatilityClass::Volatile,
+            r if r <= self.calm_bps => VolatilityClass::Calm,
+            _ => VolatilityClass::Normal,
+        }
+    }
+}
+
+/// Recent oracle prices of every reserve in a market, streamed from reserve account updates
+/// and from the reserves' Scope and Pyth feeds.
+///
+/// Reserves carry the price of their last refresh, which covers every oracle type but only moves
+/// when someone refreshes; the feeds themselves update every few slots, so a move shows up even
+/// while nobody touches the market.
+pub struct PriceWatch {
+    cfg: VolatilityConfig,
+    samples: Mutex<HashMap<Pubkey, VecDeque<(Instant, f64)>>>,
+}
+
+impl PriceWatch {
+    pub fn new(cfg: VolatilityConfig) -> Arc<Self> {
+        Arc::new(Self { cfg, samples: Mutex::new(HashMap::new()) })
+    }
+
+    pub fn config(&self) -> &VolatilityConfig {
+        &self.cfg
+    }
+
+    /// Subscribe to the market's reserve accounts, and to each reserve's Scope and Pyth feeds as
+    /// reserves show up, recording their prices until the reserve stream ends.
+    pub async fn watch(self: Arc<Self>, ws_url: String, market: Pubkey) -> Result<()> {
+        let client = PubsubClient::new(&ws_url).await.context("Failed to connect websocket")?;
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &RESERVE_DISCRIMINATOR)),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(RESERVE_LENDING_MARKET_OFFSET, market.as_ref())),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(CommitmentConfig::processed()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (mut stream, unsubscribe) =
+            client.program_subscribe(&PROGRAM_ID, Some(config)).await.context("programSubscribe failed")?;
+        info!(market = %market, "Watching reserve prices");
+
+        let decoder = KaminoLendingDecoder::default();
+        // Feed accounts subscribed to, each with the reserves it prices and their Scope chain
+        let mut feeds: HashMap<Pubkey, Vec<(Pubkey, PriceSource, Vec<u16>)>> = HashMap::new();
+        let mut oracles: SelectAll<BoxStream<'_, (Pubkey, Account)>> = SelectAll::new();
+        let mut oracle_unsubscribes = Vec::new();
+        loop {
+            tokio::select! {
+                update = stream.next() => {
+                    let Some(update) = update else { break };
+                    let Ok(pubkey) = update.value.pubkey.parse::<Pubkey>() else { continue };
+                    let Some(account) = update.value.account.decode::<Account>() else { continue };
+                    let reserve = match decoder.decode_reserve(&account.data) {
+                        Ok(reserve) => reserve,
+                        Err(e) => {
+                            debug!(reserve = %pubkey, error = %e, "Failed to decode streamed reserve");
+                            continue;
+                        }
+                    };
+                    self.record(pubkey, token_price_usd(&reserve));
+                    for (feed, source, chain) in reserve_feeds(&reserve) {
+                        if !feeds.contains_key(&feed) {
+                            match client.account_subscribe(&feed, Some(feed_config())).await {
+                                Ok((updates, unsubscribe)) => {
+                                    let updates =
+                                        updates.filter_map(move |u| async move { u.value.decode::<Account>().map(|a| (feed, a)) });
+                                    oracles.push(updates.boxed());
+                                    oracle_unsubscribes.push(unsubscribe);
+                                    metrics().set_gauge("volatility_feed_subscriptions", &[], (feeds.len() + 1) as f64);
+                                }
+                                Err(e) => {
+                                    warn!(feed = %feed, error = %e, "Failed to subscribe to price feed");
+                                    continue;
+                                }
+                            }
+                        }
+                        let priced = feeds.entry(feed).or_default();
+                        priced.retain(|(r, s, _)| !(*r == pubkey && *s == source));
+                        priced.push((pubkey, source, chain));
+                    }
+                }
+                Some((feed, account)) = oracles.next(), if !oracles.is_empty() => {
+                    let now_secs = now_millis() / 1_000;
+                    for (reserve, source, chain) in feeds.get(&feed).into_iter().flatten() {
+                        let price = match source {
+                            PriceSource::Scope => scope_price(&account.data, chain, now_secs),
+                            PriceSource::Pyth => pyth_price(&account.data, now_secs),
+                        };
+                        if let Some(price) = price {
+                            self.record(*reserve, price);
+                        }
+                    }
+                }
+            }
+        }
+
+        drop(oracles);
+        for unsubscribe in oracle_unsubscribes {
+            unsubscribe().await;
+        }
+        drop(stream);
+        unsubscribe().await;
+        anyhow::bail!("Reserve subscription closed")
+    }
+
+    fn record(&self, reserve: Pubkey, price: f64) {
+        if !price.is_finite() || price <= 0.0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap();
+        let series = samples.entry(reserve).or_default();
+        series.push_back((now, price));
+        while series.len() > MAX_SAMPLES || series.front().is_some_and(|(t, _)| now - *t > self.cfg.window) {
+            series.pop_front();
+        }
+    }
+
+    /// Price range of the reserve over the window in basis points of its lowest price, or None
+    /// without at least two samples.
+    pub fn range_bps(&self, reserve: &Pubkey) -> Option<f64> {
+        let samples = self.samples.lock().unwrap();
+        let series = samples.get(reserve)?;
+        let recent = series.iter().filter(|(t, _)| t.elapsed() <= self.cfg.window).map(|(_, p)| *p);
+        let (min, max, n) = recent.fold((f64::MAX, 0.0f64, 0), |(lo, hi, n), p| (lo.min(p), hi.max(p), n + 1));
+        (n >= 2).then(|| (max - min) / min * 10_000.0)
+    }
+
+    /// Class of the reserve's asset; Normal until enough prices have streamed in.
+    pub fn class(&self, reserve: &Pubkey) -> VolatilityClass {
+        self.range_bps(reserve).map_or(VolatilityClass::Normal, |r| self.cfg.classify(r))
+    }
+
+    /// The more volatile of the candidate's collateral and debt, since either moving shifts its
+    /// health.
+    pub fn candidate_class(&self, cand: &LiquidationCandidate) -> VolatilityClass {
+        self.class(&cand.withdraw_reserve).max(self.class(&cand.repay_reserve))
+    }
+
+    /// Pause before the next scan, set by the most volatile asset among the scanned candidates,
+    /// or the Normal class when nothing is near liquidation.
+    pub fn scan_delay(&self, scanned: &[LiquidationCandidate]) -> Duration {
+        let class = scanned.iter().map(|c| self.candidate_class(c)).max().unwrap_or(VolatilityClass::Normal);
+        metrics().set_gauge("scan_volatility_class", &[], class as u8 as f64);
+        self.cfg.policy(class).scan_delay()
+    }
+}
+
+/// Scope and Pyth feeds the reserve configures, skipping unset ones.
+fn reserve_feeds(reserve: &types::Reserve) -> Vec<(Pubkey, PriceSource, Vec<u16>)> {
+    let info = &reserve.config.token_info;
+    let scope_chain = info.scope_configuration.price_chain.to_vec();
+    let scope = (info.scope_configuration.price_feed, PriceSource::Scope, scope_chain);
+    let pyth = (info.pyth_configuration.price, PriceSource::Pyth, Vec::new());
+    [scope, pyth].into_iter().filter(|(feed, _, _)| *feed != Pubkey::default()).collect()
+}
+
+fn feed_config() -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(CommitmentConfig::processed()),
+        ..Default::default()
+    }
+}
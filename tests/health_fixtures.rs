@@ -0,0 +1,182 @@
+//! Health estimates against obligations recorded from mainnet.
+//!
+//! Each fixture under `tests/fixtures/health` holds an obligation, the reserves it touches and
+//! their oracle accounts, as raw account data at one slot, and the health factor Kamino's API
+//! reported for it then.
+//! The estimate must land within the fixture's tolerance of Kamino's figure, so changes to the
+//! health math are checked against what the program itself would compute.
+//!
+//! Record a fixture with
+//!
+//! ```text
+//! FIXTURE_RPC_URL=<mainnet endpoint> FIXTURE_OBLIGATION=<obligation> FIXTURE_NAME=<name> \
+//!     cargo test --test health_fixtures -- --ignored record_fixture
+//! ```
+//!
+//! and check them with `cargo test --test health_fixtures -- --ignored`; the check stays out of
+//! the default run until fixtures are committed.
+//!
+//! The owner is overwritten before the fixture is written and the file is named after
+//! `FIXTURE_NAME`, so neither identifies the borrower.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use carbon_kamino_lending_decoder::KaminoLendingDecoder;
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcAccountInfoConfig;
+use solana_liquidation::health::{estimate_health, estimate_health_coarse};
+use solana_liquidation::kamino_api::{KaminoApi, DEFAULT_KAMINO_API_URL};
+use solana_liquidation::partial::{ObligationView, OBLIGATION_OWNER_OFFSET};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+
+/// Relative difference from Kamino's health factor accepted for newly recorded fixtures.
+const DEFAULT_TOLERANCE: f64 = 0.01;
+
+/// Written over the owner of recorded obligations.
+const ANONYMOUS_OWNER: Pubkey = Pubkey::new_from_array([0x11; 32]);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HealthFixture {
+    /// Slot the accounts were read at.
+    slot: u64,
+    lending_market: String,
+    /// Base64 obligation account data, owner anonymized.
+    obligation: String,
+    /// Base64 reserve account data by reserve address.
+    reserves: BTreeMap<String, String>,
+    /// Base64 account data of the reserves' Pyth, Switchboard and Scope feeds by address.
+    oracles: BTreeMap<String, String>,
+    /// Health factor from Kamino's API at recording time.
+    kamino_health: f64,
+    /// Accepted relative difference between the estimate and `kamino_health`.
+    tolerance: f64,
+}
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/health")
+}
+
+fn load_fixtures() -> Vec<(String, HealthFixture)> {
+    let entries =
+        std::fs::read_dir(fixtures_dir()).unwrap_or_else(|e| panic!("Cannot read {}: {e}", fixtures_dir().display()));
+    let mut fixtures: Vec<_> = entries
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .map(|p| {
+            let name = p.file_stem().unwrap().to_string_lossy().into_owned();
+            let raw = std::fs::read_to_string(&p).unwrap();
+            let fixture = serde_json::from_str(&raw).unwrap_or_else(|e| panic!("Invalid fixture {name}: {e}"));
+            (name, fixture)
+        })
+        .collect();
+    fixtures.sort_by(|a, b| a.0.cmp(&b.0));
+    fixtures
+}
+
+#[test]
+#[ignore = "needs fixtures recorded with record_fixture"]
+fn estimates_match_recorded_health() {
+    let fixtures = load_fixtures();
+    // Passing without fixtures would check nothing
+    assert!(!fixtures.is_empty(), "No fixtures in {}, record some with record_fixture", fixtures_dir().display());
+    let decoder = KaminoLendingDecoder::default();
+    // The estimate reads the recorded accounts only; nothing may reach the network
+    let rpc = RpcClient::new_mock("fails".to_string());
+
+    let mut failures = Vec::new();
+    for (name, fixture) in &fixtures {
+        let data = STANDARD.decode(&fixture.obligation).unwrap();
+        let obligation = decoder.decode_obligation(&data).unwrap_or_else(|e| panic!("{name}: bad obligation: {e}"));
+        let reserves: HashMap<Pubkey, _> = fixture
+            .reserves
+            .iter()
+            .map(|(pk, data)| {
+                let reserve = decoder
+                    .decode_reserve(&STANDARD.decode(data).unwrap())
+                    .unwrap_or_else(|e| panic!("{name}: bad reserve {pk}: {e}"));
+                (pk.parse().unwrap(), reserve)
+            })
+            .collect();
+
+        let health = estimate_health(&obligation, &reserves, &rpc).unwrap();
+        let coarse = estimate_health_coarse(&ObligationView::new(&data).expect("Not an obligation"));
+        assert!(
+            coarse >= health * (1.0 - 1e-9),
+            "{name}: coarse pre-filter {coarse} is below the full estimate {health}, it would drop the obligation",
+        );
+
+        let diff = (health - fixture.kamino_health).abs() / fixture.kamino_health;
+        println!("{name}: estimate {health:.6}, kamino {:.6}, off by {:.4}%", fixture.kamino_health, diff * 100.0);
+        if diff > fixture.tolerance {
+            failures.push(format!("{name}: estimate {health:.6} vs kamino {:.6}", fixture.kamino_health));
+        }
+    }
+    assert!(failures.is_empty(), "Estimates outside tolerance:\n{}", failures.join("\n"));
+}
+
+/// Record `FIXTURE_OBLIGATION` as it is now, with Kamino's health factor for it.
+#[tokio::test]
+#[ignore = "needs FIXTURE_RPC_URL, FIXTURE_OBLIGATION and FIXTURE_NAME"]
+async fn record_fixture() {
+    let url = std::env::var("FIXTURE_RPC_URL").expect("FIXTURE_RPC_URL not set");
+    let obligation: Pubkey = std::env::var("FIXTURE_OBLIGATION").expect("FIXTURE_OBLIGATION not set").parse().unwrap();
+    let name = std::env::var("FIXTURE_NAME").expect("FIXTURE_NAME not set");
+    let rpc = RpcClient::new_with_commitment(url, CommitmentConfig::confirmed());
+
+    let current = rpc.get_account_data(&obligation).expect("Failed to fetch obligation");
+    let view = ObligationView::new(&current).expect("Not an obligation");
+    let market = view.lending_market();
+    let touched: BTreeSet<Pubkey> = view.deposits().chain(view.borrows()).map(|p| p.reserve).collect();
+
+    // Oracle addresses come from the reserves' configs, which do not change between reads
+    let decoder = KaminoLendingDecoder::default();
+    let mut oracles = BTreeSet::new();
+    let reserve_keys: Vec<Pubkey> = touched.iter().copied().collect();
+    for account in rpc.get_multiple_accounts(&reserve_keys).expect("Failed to fetch reserves") {
+        let reserve = decoder.decode_reserve(&account.expect("Reserve missing").data).expect("Not a reserve");
+        let info = &reserve.config.token_info;
+        oracles.extend([
+            info.pyth_configuration.price,
+            info.switchboard_configuration.price_aggregator,
+            info.switchboard_configuration.twap_aggregator,
+            info.scope_configuration.price_feed,
+        ]);
+    }
+    oracles.remove(&Pubkey::default());
+
+    // One read for every account, so the obligation, reserves and oracles come from the same slot
+    let keys: Vec<Pubkey> =
+        std::iter::once(obligation).chain(touched.iter().copied()).chain(oracles.iter().copied()).collect();
+    let config = RpcAccountInfoConfig { commitment: Some(CommitmentConfig::confirmed()), ..Default::default() };
+    let response = rpc.get_multiple_accounts_with_config(&keys, config).expect("Failed to fetch accounts");
+    let mut accounts = response.value.into_iter().map(|a| a.expect("Account missing").data);
+    let mut data = accounts.next().unwrap();
+    let mut encoded = |keys: &BTreeSet<Pubkey>| -> BTreeMap<String, String> {
+        keys.iter().zip(accounts.by_ref()).map(|(pk, data)| (pk.to_string(), STANDARD.encode(data))).collect()
+    };
+    let reserves = encoded(&touched);
+    let oracles = encoded(&oracles);
+
+    let api = KaminoApi::new(DEFAULT_KAMINO_API_URL.to_string()).unwrap();
+    let kamino_health = api.health(&market, &obligation).await.expect("Failed to fetch Kamino's health factor");
+    data[OBLIGATION_OWNER_OFFSET..OBLIGATION_OWNER_OFFSET + 32].copy_from_slice(ANONYMOUS_OWNER.as_ref());
+
+    let fixture = HealthFixture {
+        slot: response.context.slot,
+        lending_market: market.to_string(),
+        obligation: STANDARD.encode(&data),
+        reserves,
+        oracles,
+        kamino_health,
+        tolerance: DEFAULT_TOLERANCE,
+    };
+    std::fs::create_dir_all(fixtures_dir()).unwrap();
+    let path = fixtures_dir().join(format!("{name}.json"));
+    std::fs::write(&path, serde_json::to_string_pretty(&fixture).unwrap() + "\n").unwrap();
+    println!("recorded {} at slot {}, kamino health {kamino_health:.6}", path.display(), fixture.slot);
+}
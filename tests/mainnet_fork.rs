@@ -0,0 +1,148 @@
+//! End-to-end liquidation against a local validator forked from mainnet.
+//!
+//! Ignored by default since it needs the Solana CLI and a mainnet RPC. Run with
+//!
+//! ```text
+//! FORK_RPC_URL=<mainnet endpoint> FORK_OBLIGATION=<unhealthy obligation> \
+//!     cargo test --test mainnet_fork -- --ignored --nocapture
+//! ```
+//!
+//! The obligation must be liquidatable at the current mainnet oracle prices, since the fork
+//! clones them as they are. A decoder release that shifts an account layout shows up here as a
+//! failed scan, a rejected instruction or a transaction that does not land.
+
+mod support;
+
+use std::collections::BTreeSet;
+
+use solana_liquidation::kamino::{build_liquidation_ix, fetch_obligation, find_liquidation_candidates};
+use solana_liquidation::keeper::{fetch_reserves, refresh_ixs};
+use solana_liquidation::pda::{lending_market_authority, referrer_token_state, LiquidatorAccounts, MarketAccounts};
+use solana_liquidation::rpc::{Rpc, RpcLimits};
+use solana_liquidation::scan::{ProgramScanner, ScanStrategy};
+use solana_liquidation::strategy::StrategyProfile;
+use solana_liquidation::util::{ComputeBudget, TxBuilder};
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use spl_associated_token_account_client::address::get_associated_token_address_with_program_id;
+use spl_associated_token_account_client::instruction::create_associated_token_account_idempotent;
+use support::{ForkSpec, TestValidator, TokenAccountFixture};
+
+const LIMITS: RpcLimits = RpcLimits { rps: 20.0, burst: 40, gpa_cost: 10 };
+
+const RPC_PORT: u16 = 18_899;
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs solana-test-validator, FORK_RPC_URL and FORK_OBLIGATION"]
+async fn liquidates_unhealthy_obligation_on_fork() {
+    let source_url = std::env::var("FORK_RPC_URL").expect("FORK_RPC_URL not set");
+    let obligation: Pubkey = std::env::var("FORK_OBLIGATION").expect("FORK_OBLIGATION not set").parse().unwrap();
+
+    // Everything the obligation's refresh and liquidation read, as it is on mainnet now
+    let mainnet = Rpc::new(source_url.clone(), LIMITS);
+    let obl = fetch_obligation(&mainnet, &obligation).await.expect("Failed to fetch obligation");
+    let market = obl.lending_market;
+    let touched: Vec<Pubkey> = obl
+        .deposits
+        .iter()
+        .map(|d| d.reserve)
+        .chain(obl.borrows.iter().map(|b| b.reserve))
+        .filter(|r| *r != Pubkey::default())
+        .collect();
+    let reserves = fetch_reserves(&mainnet, &touched).await.expect("Failed to fetch reserves");
+
+    let payer = Keypair::new();
+    let mut accounts = BTreeSet::from([obligation, market, lending_market_authority(&market)]);
+    let mut token_accounts = Vec::new();
+    for (pk, reserve) in &reserves {
+        let info = &reserve.config.token_info;
+        accounts.extend([
+            *pk,
+            reserve.liquidity.mint_pubkey,
+            reserve.liquidity.supply_vault,
+            reserve.liquidity.fee_vault,
+            reserve.collateral.mint_pubkey,
+            reserve.collateral.supply_vault,
+            info.pyth_configuration.price,
+            info.switchboard_configuration.price_aggregator,
+            info.switchboard_configuration.twap_aggregator,
+            info.scope_configuration.price_feed,
+        ]);
+        // Fund the payer with every borrowed token so any repay choice can be paid
+        if obl.borrows.iter().any(|b| b.reserve == *pk) {
+            let program = reserve.liquidity.token_program;
+            let mint = reserve.liquidity.mint_pubkey;
+            token_accounts.push(TokenAccountFixture {
+                address: get_associated_token_address_with_program_id(&payer.pubkey(), &mint, &program),
+                mint,
+                owner: payer.pubkey(),
+                amount: u64::MAX / 2,
+                token_program: program,
+            });
+        }
+    }
+    // Referred obligations also need the referrer's per-reserve token states, where they exist
+    if obl.referrer != Pubkey::default() {
+        let borrowed = obl.borrows.iter().map(|b| b.reserve).filter(|r| *r != Pubkey::default());
+        accounts.extend(borrowed.map(|r| referrer_token_state(&obl.referrer, &r)));
+    }
+    accounts.remove(&Pubkey::default());
+
+    let validator = TestValidator::start(&ForkSpec { source_url, accounts, token_accounts, rpc_port: RPC_PORT })
+        .expect("Failed to start test validator");
+    let rpc = Rpc::new(validator.rpc_url.clone(), LIMITS);
+    let airdrop = rpc.request_airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+    rpc.poll_for_signature(&airdrop).unwrap();
+
+    // Scan
+    let strategy = StrategyProfile::default();
+    let scanner = ProgramScanner::new(ScanStrategy::Full, market);
+    let candidates = find_liquidation_candidates(&rpc, &scanner, &market.to_string(), 1.0, &strategy, None)
+        .await
+        .expect("Scan failed");
+    let cand = candidates
+        .iter()
+        .find(|c| c.obligation == obligation)
+        .expect("Obligation not found among candidates; is it still unhealthy on mainnet?");
+    assert!(cand.is_liquidatable(), "Obligation scanned but not liquidatable");
+
+    // Build
+    let market_accounts = MarketAccounts::resolve(&rpc, market).await.unwrap();
+    let liquidator = LiquidatorAccounts::new(payer.pubkey());
+    let ix = build_liquidation_ix(&rpc, &market_accounts, &liquidator, cand, &strategy).await.expect("Build failed");
+    let withdraw = &reserves[&cand.withdraw_reserve];
+    let withdraw_mint = withdraw.liquidity.mint_pubkey;
+    let withdraw_program = withdraw.liquidity.token_program;
+    let mut ixs = vec![
+        create_associated_token_account_idempotent(&payer.pubkey(), &payer.pubkey(), &withdraw_mint, &withdraw_program),
+        create_associated_token_account_idempotent(
+            &payer.pubkey(),
+            &payer.pubkey(),
+            &withdraw.collateral.mint_pubkey,
+            &spl_token::ID,
+        ),
+    ];
+    ixs.extend(refresh_ixs(&market_accounts, &obligation, &obl, &reserves).unwrap());
+    ixs.push(ix);
+
+    // Send via RPC
+    let builder = TxBuilder {
+        payer: &payer,
+        owner: None,
+        budget: ComputeBudget { cu_limit: 1_400_000, cu_price: None, heap_frame_bytes: None },
+        lookup_tables: &[],
+        tip_account: payer.pubkey(),
+        separate_tip: false,
+        hooks: &[],
+    };
+    let blockhash = rpc.get_latest_blockhash().unwrap();
+    let tx = builder.tx(blockhash, ixs).expect("Liquidation does not fit one transaction");
+    let signature = rpc.send_and_confirm_transaction(&tx).expect("Liquidation did not land");
+
+    let received = get_associated_token_address_with_program_id(&payer.pubkey(), &withdraw_mint, &withdraw_program);
+    let balance = rpc.get_token_account_balance(&received).unwrap();
+    println!("liquidated {obligation} in {signature}, received {} of {withdraw_mint}", balance.ui_amount_string);
+    assert!(balance.amount.parse::<u64>().unwrap() > 0, "Liquidation landed but seized nothing");
+}
@@ -0,0 +1,129 @@
+//! `solana-test-validator` helper for tests that run against a fork of mainnet state.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use carbon_kamino_lending_decoder::PROGRAM_ID;
+use serde_json::json;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use spl_token::solana_program::program_pack::Pack;
+
+/// Lamports given to fabricated token accounts, comfortably above rent exemption.
+const TOKEN_ACCOUNT_LAMPORTS: u64 = 2_039_280;
+
+/// How long to wait for the validator to start answering RPC.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A local validator that clones the Kamino program and selected accounts from a source cluster.
+/// The process is killed and its ledger removed on drop.
+pub struct TestValidator {
+    child: Child,
+    ledger: PathBuf,
+    pub rpc_url: String,
+}
+
+/// What to clone into the fork and which accounts to fabricate.
+pub struct ForkSpec {
+    /// Cluster the accounts are cloned from.
+    pub source_url: String,
+    pub accounts: BTreeSet<Pubkey>,
+    /// Token accounts written directly into genesis, e.g. to fund repayments.
+    pub token_accounts: Vec<TokenAccountFixture>,
+    pub rpc_port: u16,
+}
+
+/// An initialized token account holding `amount` of `mint`.
+pub struct TokenAccountFixture {
+    pub address: Pubkey,
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub token_program: Pubkey,
+}
+
+impl TestValidator {
+    /// Start the validator and wait until its RPC is healthy.
+    pub fn start(spec: &ForkSpec) -> Result<Self> {
+        let ledger = std::env::temp_dir().join(format!("klend-fork-{}-{}", std::process::id(), spec.rpc_port));
+        std::fs::create_dir_all(&ledger).context("Failed to create ledger directory")?;
+
+        let mut cmd = Command::new("solana-test-validator");
+        cmd.arg("--reset")
+            .arg("--quiet")
+            .arg("--ledger")
+            .arg(&ledger)
+            .arg("--rpc-port")
+            .arg(spec.rpc_port.to_string())
+            .arg("--url")
+            .arg(&spec.source_url)
+            .arg("--clone-upgradeable-program")
+            .arg(PROGRAM_ID.to_string());
+        for account in &spec.accounts {
+            cmd.arg("--maybe-clone").arg(account.to_string());
+        }
+        for (i, fixture) in spec.token_accounts.iter().enumerate() {
+            let path = ledger.join(format!("token-account-{i}.json"));
+            write_token_account(&path, fixture)?;
+            cmd.arg("--account").arg(fixture.address.to_string()).arg(&path);
+        }
+        let child = cmd
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .context("Failed to spawn solana-test-validator; is the Solana CLI installed?")?;
+
+        let validator = Self { child, ledger, rpc_url: format!("http://127.0.0.1:{}", spec.rpc_port) };
+        validator.wait_healthy()?;
+        Ok(validator)
+    }
+
+    fn wait_healthy(&self) -> Result<()> {
+        let client = RpcClient::new(self.rpc_url.clone());
+        let started = Instant::now();
+        while started.elapsed() < STARTUP_TIMEOUT {
+            if client.get_health().is_ok() {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        bail!("Test validator did not become healthy within {}s", STARTUP_TIMEOUT.as_secs())
+    }
+}
+
+impl Drop for TestValidator {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.ledger);
+    }
+}
+
+/// Write an initialized SPL token account in the JSON format `--account` accepts.
+fn write_token_account(path: &Path, fixture: &TokenAccountFixture) -> Result<()> {
+    let state = spl_token::state::Account {
+        mint: fixture.mint,
+        owner: fixture.owner,
+        amount: fixture.amount,
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    };
+    let mut data = vec![0u8; spl_token::state::Account::LEN];
+    spl_token::state::Account::pack(state, &mut data).context("Failed to pack token account")?;
+    let account = json!({
+        "pubkey": fixture.address.to_string(),
+        "account": {
+            "lamports": TOKEN_ACCOUNT_LAMPORTS,
+            "data": [base64::engine::general_purpose::STANDARD.encode(&data), "base64"],
+            "owner": fixture.token_program.to_string(),
+            "executable": false,
+            "rentEpoch": 0,
+            "space": data.len(),
+        },
+    });
+    std::fs::write(path, serde_json::to_vec_pretty(&account)?).context("Failed to write token account fixture")
+}